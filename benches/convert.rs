@@ -2,7 +2,10 @@
 // Copyright (c) 2025 Au-Zone Technologies. All Rights Reserved.
 
 use criterion::{criterion_group, criterion_main, Criterion};
-use edgefirst_camera::image::{self, Image, ImageManager, Rotation};
+use edgefirst_camera::{
+    cpu,
+    image::{self, Image, ImageManager, Rotation},
+};
 
 pub fn benchmark_resize(c: &mut Criterion) {
     let fmts = [image::RGBA, image::RGB3, image::YUYV, image::NV12];
@@ -31,5 +34,37 @@ pub fn benchmark_resize(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, benchmark_resize);
+/// Software YUYV/NV12→RGBA, comparing the scalar reference against the
+/// dispatching entry points (NEON on aarch64).
+pub fn benchmark_cpu(c: &mut Criterion) {
+    let dims = [(640, 480), (1280, 720), (1920, 1080)];
+
+    let mut group = c.benchmark_group("cpu/YUYV");
+    for (width, height) in dims {
+        let src = vec![128u8; width * 2 * height];
+        let mut dst = vec![0u8; width * 4 * height];
+        group.bench_function(format!("scalar/{}x{}", width, height), |b| {
+            b.iter(|| cpu::yuyv_to_rgba_scalar(&src, width * 2, &mut dst, width * 4, width, height))
+        });
+        group.bench_function(format!("simd/{}x{}", width, height), |b| {
+            b.iter(|| cpu::yuyv_to_rgba(&src, width * 2, &mut dst, width * 4, width, height))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("cpu/NV12");
+    for (width, height) in dims {
+        let src = vec![128u8; width * height * 3 / 2];
+        let mut dst = vec![0u8; width * 4 * height];
+        group.bench_function(format!("scalar/{}x{}", width, height), |b| {
+            b.iter(|| cpu::nv12_to_rgba_scalar(&src, width, &mut dst, width * 4, width, height))
+        });
+        group.bench_function(format!("simd/{}x{}", width, height), |b| {
+            b.iter(|| cpu::nv12_to_rgba(&src, width, &mut dst, width * 4, width, height))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_resize, benchmark_cpu);
 criterion_main!(benches);
@@ -5,7 +5,9 @@ use criterion::{criterion_group, criterion_main, Criterion};
 use edgefirst_camera::{
     image,
     image::{encode_jpeg, Image},
+    video::{VideoManager, H264_INPUT_FORMAT},
 };
+use videostream::fourcc::FourCC;
 
 fn benchmark_jpeg(img: &Image) {
     let dma = img.dmabuf();
@@ -32,5 +34,23 @@ pub fn benchmark_encode(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, benchmark_encode);
+/// H.264 encoding into a new `Vec` per frame against a reused buffer.
+pub fn benchmark_h264_output(c: &mut Criterion) {
+    let mut group = c.benchmark_group("h264_output");
+    for dim in [(640, 480), (1920, 1080)] {
+        let img = Image::new(dim.0, dim.1, H264_INPUT_FORMAT).unwrap();
+        let mut vidmgr = VideoManager::builder(FourCC(*b"H264"), dim.0 as i32, dim.1 as i32)
+            .build()
+            .unwrap();
+        group.bench_function(format!("new_vec/{}x{}", dim.0, dim.1), |b| {
+            b.iter(|| vidmgr.encode_direct(&img).unwrap())
+        });
+        let mut data = Vec::new();
+        group.bench_function(format!("reused/{}x{}", dim.0, dim.1), |b| {
+            b.iter(|| vidmgr.encode_direct_into(&img, &mut data).unwrap())
+        });
+    }
+}
+
+criterion_group!(benches, benchmark_encode, benchmark_h264_output);
 criterion_main!(benches);
@@ -2,43 +2,43 @@
 // Copyright (c) 2025 Au-Zone Technologies. All Rights Reserved.
 
 use clap::Parser;
+pub use edgefirst_camera::config::{
+    BayerPattern, Codec, DebugFrameId, DedupMode, ErrorFramePolicy, FrameFormat, H264Bitrate,
+    H264Framing, H264Stamp, H264Variant, JpegPlane, JpegSubsamp, MirrorSetting, RotationSetting,
+    TestPattern,
+};
+use edgefirst_camera::{
+    image::{Rotation, RGBA},
+    jpeg,
+    pipeline::ConvertTarget,
+    sps::SampleAspectRatio,
+};
 use serde_json::json;
-use std::path::PathBuf;
-use zenoh::config::{Config, WhatAmI};
-
-/// Camera image mirroring options.
-///
-/// Determines how the camera image should be flipped before processing.
-/// Useful for correcting camera orientation.
-#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
-pub enum MirrorSetting {
-    /// No mirroring
-    None,
-    /// Flip horizontally (left-right)
-    Horizontal,
-    /// Flip vertically (top-bottom)
-    Vertical,
-    /// Flip both horizontally and vertically (180-degree rotation)
-    Both,
-}
-
-/// H.264 encoding bitrate presets.
-///
-/// Controls the trade-off between video quality and file size.
-/// Higher bitrates produce better quality but larger files.
-#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
-pub enum H264Bitrate {
-    /// Automatic bitrate selection based on resolution
-    Auto,
-    /// 5 Mbps (suitable for 720p)
-    Mbps5,
-    /// 25 Mbps (suitable for 1080p)
-    Mbps25,
-    /// 50 Mbps (suitable for high-quality 1080p)
-    Mbps50,
-    /// 100 Mbps (suitable for 4K or very high quality)
-    Mbps100,
-}
+use std::{borrow::Cow, error::Error, path::PathBuf};
+use zenoh::{
+    config::{Config, WhatAmI},
+    key_expr::keyexpr,
+};
+
+use crate::{output::OutputPattern, transcode::FrameSource, v4l2out::V4l2OutputFormat};
+
+const DEFAULT_FRAME_TOPIC: &str = "camera/frame";
+const DEFAULT_INFO_TOPIC: &str = "rt/camera/info";
+const DEFAULT_CONNECTED_TOPIC: &str = "rt/camera/connected";
+const DEFAULT_JPEG_TOPIC: &str = "rt/camera/jpeg";
+const DEFAULT_HIRES_TOPIC: &str = "rt/camera/jpeg/hires";
+const DEFAULT_JPEG_REGION_TOPIC: &str = "rt/camera/jpeg/region";
+const DEFAULT_H264_TOPIC: &str = "rt/camera/h264";
+const DEFAULT_H264_META_TOPIC: &str = "camera/h264/meta";
+const DEFAULT_H264_INIT_TOPIC: &str = "rt/camera/h264/init";
+const DEFAULT_H264_KEYFRAME_TOPIC: &str = "rt/camera/h264/force_keyframe";
+const DEFAULT_H264_TILES_TOPICS: [&str; 4] = [
+    "rt/camera/h264/tl",
+    "rt/camera/h264/tr",
+    "rt/camera/h264/bl",
+    "rt/camera/h264/br",
+];
+const TF_STATIC_TOPIC: &str = "rt/tf_static";
 
 /// Command-line arguments for EdgeFirst Camera Node.
 ///
@@ -61,10 +61,31 @@ pub enum H264Bitrate {
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    /// Camera capture device path (e.g., /dev/video0)
+    /// Frame source: `camera` captures from `--camera`, `dma:<topic>`
+    /// transcodes the CameraFrame buffers another node publishes on
+    /// `<topic>` (e.g. `dma:camera/frame`)
+    #[arg(long, env = "SOURCE", default_value = "camera")]
+    pub source: FrameSource,
+
+    /// Encode a synthetic pattern at `--camera-size` and `--target-fps`
+    /// instead of capturing, to bring up displays and networks before a
+    /// camera is connected. No device is opened; needs --h264,
+    /// --h264-variant or --jpeg
+    #[arg(long, env = "TEST_PATTERN", value_enum)]
+    pub test_pattern: Option<TestPattern>,
+
+    /// Camera capture device path (e.g., /dev/video0), or a selector that
+    /// stays stable across reboots: by-id:<link in /dev/v4l/by-id>,
+    /// name:<card name>, bus:<bus info> or serial:<USB serial>
     #[arg(short, long, env = "CAMERA", default_value = "/dev/video3")]
     pub camera: String,
 
+    /// Keep retrying to find and open the camera for this many seconds at
+    /// startup, for devices that are not enumerated yet at boot (0 fails on
+    /// the first attempt)
+    #[arg(long, env = "CAMERA_OPEN_TIMEOUT", default_value = "30")]
+    pub camera_open_timeout: u64,
+
     /// Camera capture resolution in pixels (width height)
     #[arg(
         long,
@@ -75,40 +96,361 @@ pub struct Args {
     )]
     pub camera_size: Vec<u32>,
 
+    /// Capture raw Bayer frames with this colour filter layout instead of
+    /// YUYV and demosaic them to RGBA on the CPU before the outputs; the
+    /// driver picks the bit depth (e.g. `RGGB` or `RG10`)
+    #[arg(long, env = "BAYER", value_enum)]
+    pub bayer: Option<BayerPattern>,
+
+    /// Restart the capture when no frame arrives within this many
+    /// milliseconds (0 disables the watchdog)
+    #[arg(long, env = "CAPTURE_WATCHDOG_MS", default_value = "5000")]
+    pub capture_watchdog_ms: u64,
+
+    /// Look for camera frames that repeat the previous frame exactly, as
+    /// some sensors do under error conditions: `flag` publishes them and
+    /// counts them in the logs and metrics, `skip` also drops them before
+    /// they are published or encoded
+    #[arg(long, env = "DEDUP_FRAMES", default_value = "off", value_enum)]
+    pub dedup_frames: DedupMode,
+
+    /// What the H.264 and JPEG outputs emit when a camera frame is half a
+    /// frame interval late: `gap` emits nothing, `freeze` repeats the last
+    /// good frame and `black` a black frame, at `--target-fps` until frames
+    /// return
+    #[arg(long, env = "ERROR_FRAME_POLICY", default_value = "gap", value_enum)]
+    pub error_frame_policy: ErrorFramePolicy,
+
+    /// Frame rate assumed when the camera does not report its frame
+    /// interval
+    #[arg(
+        long,
+        env = "TARGET_FPS",
+        default_value = "30",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub target_fps: u32,
+
     /// Camera image mirroring setting
     #[arg(long, env = "MIRROR", default_value = "both", value_enum)]
     pub mirror: MirrorSetting,
 
+    /// Rotation applied to every converted output (degrees clockwise).
+    /// Per-output flags such as `--h264-rotation` take precedence.
+    #[arg(long, env = "ROTATION", default_value = "0", value_enum)]
+    pub rotation: RotationSetting,
+
+    /// Namespace prefixed to every topic left at its default (frame, info,
+    /// liveness, JPEG, H.264, tiles, metadata and `rt/tf_static`), for running several
+    /// nodes on one network. It goes after the `rt/` prefix, so `robot1`
+    /// turns `rt/camera/jpeg` into `rt/robot1/camera/jpeg` and
+    /// `camera/frame` into `robot1/camera/frame`. Topics set explicitly are
+    /// used as given
+    #[arg(long, env = "TOPIC_NAMESPACE")]
+    pub topic_namespace: Option<String>,
+
     /// Zenoh topic for multi-plane camera frame (edgefirst_msgs/CameraFrame).
     /// Supersedes `--dma-topic` from 2.6.x. The new topic drops the `rt/`
     /// prefix per the schemas 3.1 convention for newly introduced topics.
-    #[arg(long, default_value = "camera/frame")]
+    #[arg(long, default_value = DEFAULT_FRAME_TOPIC)]
     pub frame_topic: String,
 
+    /// Pixel format of the buffer shared on `--frame-topic`. When it
+    /// differs from the camera format each frame is converted into a
+    /// separate DMA buffer; unset shares the camera buffer zero-copy
+    #[arg(long, env = "DMA_FORMAT", value_enum)]
+    pub dma_format: Option<FrameFormat>,
+
+    /// Zenoh topic for JSON crop requests (`{"x","y","width","height"}` in
+    /// camera pixels) applied to the buffer shared on `--frame-topic`; an
+    /// empty or `null` request restores the full frame
+    #[arg(long, env = "DMA_CROP_TOPIC")]
+    pub dma_crop_topic: Option<String>,
+
+    /// Save the first published frame (after any `--dma-format`
+    /// conversion) to this path as a NumPy .npy array, for comparing
+    /// against a Python preprocessing pipeline
+    #[arg(long, env = "DUMP_NPY")]
+    pub dump_npy: Option<PathBuf>,
+
+    /// Directory that relative `--record` and `--dump-npy` paths are
+    /// written under; created if missing
+    #[arg(long, env = "OUTPUT_DIR")]
+    pub output_dir: Option<PathBuf>,
+
+    /// File name, without extension, for a `--record` or `--dump-npy` path
+    /// that names a directory (ends in `/` or exists). Placeholders:
+    /// `{timestamp}` (UTC, e.g. `20231114T221320.123456789Z`), `{seq}`
+    /// (frame sequence number, 0 for `--record`) and `{frame_id}`
+    /// (`--camera-frame-id`)
+    #[arg(long, env = "OUTPUT_PATTERN", default_value = "{frame_id}_{timestamp}")]
+    pub output_pattern: OutputPattern,
+
+    /// Attach the CRC-32 of each frame buffer to its `--frame-topic` sample
+    /// (as `crc32=<hex>`) so consumers can check for corruption; reads the
+    /// whole frame, so leave off outside debugging
+    #[arg(long, env = "DMA_CHECKSUM")]
+    pub dma_checksum: bool,
+
     /// Zenoh topic for camera calibration info (sensor_msgs/CameraInfo)
-    #[arg(long, default_value = "rt/camera/info")]
+    #[arg(long, default_value = DEFAULT_INFO_TOPIC)]
     pub info_topic: String,
 
+    /// Zenoh topic for the camera liveness flag (std_msgs/Bool): true while
+    /// frames flow, false before the first frame and while a stalled
+    /// capture is restarted. Republished every second
+    #[arg(long, default_value = DEFAULT_CONNECTED_TOPIC)]
+    pub connected_topic: String,
+
     /// Enable JPEG streaming output
     #[arg(long, env = "JPEG")]
     pub jpeg: bool,
 
     /// Zenoh topic for JPEG compressed images (sensor_msgs/CompressedImage)
-    #[arg(long, default_value = "rt/camera/jpeg")]
+    #[arg(long, default_value = DEFAULT_JPEG_TOPIC)]
     pub jpeg_topic: String,
 
+    /// Rotation applied to the JPEG output, defaults to `--rotation`
+    #[arg(long, env = "JPEG_ROTATION", value_enum)]
+    pub jpeg_rotation: Option<RotationSetting>,
+
+    /// Publish JPEG frames at most this often, skipping the conversion and
+    /// encode of the frames in between; unset publishes every frame
+    #[arg(long, env = "JPEG_FPS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub jpeg_fps: Option<u32>,
+
+    /// Every SECONDS, also encode the JPEG output's frame at the full
+    /// `--camera-size` and publish it on `--hires-topic`, next to the
+    /// `--stream-size` JPEG stream. Requires `--jpeg`
+    #[arg(
+        long,
+        env = "HIRES_INTERVAL",
+        value_name = "SECONDS",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub hires_interval: Option<u64>,
+
+    /// Zenoh topic for the `--hires-interval` JPEG images
+    /// (sensor_msgs/CompressedImage)
+    #[arg(long, default_value = DEFAULT_HIRES_TOPIC)]
+    pub hires_topic: String,
+
+    /// Part of the frame to encode as JPEG: `all` converts the full colour
+    /// image, `luma` publishes only the Y plane of the YUYV or NV12 frame as
+    /// a greyscale JPEG at the source size, skipping the G2D conversion
+    /// (no resize or rotation)
+    #[arg(long, env = "JPEG_PLANE", default_value = "all", value_enum)]
+    pub jpeg_plane: JpegPlane,
+
+    /// JPEG quality from 1 to 100; lower values shrink the frames for
+    /// constrained links
+    #[arg(
+        long,
+        env = "JPEG_QUALITY",
+        default_value_t = jpeg::DEFAULT_QUALITY,
+        value_parser = clap::value_parser!(u8).range(1..=100)
+    )]
+    pub jpeg_quality: u8,
+
+    /// Chroma subsampling of colour JPEGs: `444` keeps full-resolution
+    /// colour, `422` halves it horizontally and `420` in both directions
+    #[arg(long, env = "JPEG_SUBSAMP", default_value = "420", value_enum)]
+    pub jpeg_subsamp: JpegSubsamp,
+
+    /// Encode only the part of each JPEG frame that changed, for static
+    /// scenes, and publish where it goes on `--jpeg-region-topic`. Frames
+    /// without a change are not published, and a full frame is still sent
+    /// every `--jpeg-dirty-refresh` seconds. Requires `--jpeg-plane all`
+    #[arg(long, env = "JPEG_DIRTY_RECT")]
+    pub jpeg_dirty_rect: bool,
+
+    /// Luma change, out of 255, that marks a pixel as changed for
+    /// `--jpeg-dirty-rect`; keep it above the sensor noise
+    #[arg(long, env = "JPEG_DIRTY_THRESHOLD", default_value_t = 16)]
+    pub jpeg_dirty_threshold: u8,
+
+    /// Seconds between the full frames of `--jpeg-dirty-rect`, which
+    /// subscribers that join late composite the regions onto
+    #[arg(
+        long,
+        env = "JPEG_DIRTY_REFRESH",
+        value_name = "SECONDS",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub jpeg_dirty_refresh: u64,
+
+    /// Zenoh topic for where each `--jpeg-dirty-rect` JPEG goes in the full
+    /// frame (JSON), published with the same timestamp as the image
+    #[arg(long, default_value = DEFAULT_JPEG_REGION_TOPIC)]
+    pub jpeg_region_topic: String,
+
+    /// Convert each frame once in the capture loop and share it between the
+    /// JPEG and H.264 encoders when both want the same image (same rotation);
+    /// set to false to have each encoder convert on its own
+    #[arg(long, env = "SHARED_CONVERT", default_value_t = true, action = clap::ArgAction::Set)]
+    pub shared_convert: bool,
+
+    /// Frames buffered between the capture loop and each encoder (H.264,
+    /// JPEG, tiles) before new frames are dropped. Deeper queues ride out
+    /// encoder stalls but add up to one frame period of latency per queued
+    /// frame. Unset keeps 1 for H.264/JPEG and 3 for tiles
+    #[arg(long, env = "ENCODE_QUEUE_DEPTH", value_parser = clap::value_parser!(u32).range(1..))]
+    pub encode_queue_depth: Option<u32>,
+
+    /// Cap the G2D conversions per second across the whole process, for
+    /// passively cooled boards. The H.264 and camera-side conversions wait
+    /// for the budget; JPEG and V4L2 output frames are dropped when it is
+    /// spent. Unset runs G2D unthrottled
+    #[arg(long, env = "G2D_MAX_OPS_PER_SEC", value_parser = clap::value_parser!(u32).range(1..))]
+    pub g2d_max_ops_per_sec: Option<u32>,
+
+    /// Cap the frames queued for or held by all outputs together. Each is
+    /// a handle on a DMA buffer with its own file descriptor; frames past
+    /// the cap are dropped for the output, as for a full queue. Unset
+    /// leaves only the per-output --encode-queue-depth
+    #[arg(long, env = "MAX_IN_FLIGHT", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_in_flight: Option<u32>,
+
+    /// Watch /sys/class/thermal/thermal_zoneN/temp and shed the heaviest
+    /// outputs while it is above --thermal-threshold: the 4K tiles first,
+    /// then the --hires-interval JPEGs, then one H.264 bitrate preset, one
+    /// more every --thermal-step degrees. Unset never throttles
+    #[arg(long, env = "THERMAL_ZONE")]
+    pub thermal_zone: Option<u32>,
+
+    /// Temperature in °C at which --thermal-zone starts shedding outputs
+    #[arg(
+        long,
+        env = "THERMAL_THRESHOLD",
+        value_name = "CELSIUS",
+        default_value = "85",
+        value_parser = clap::value_parser!(u32).range(1..200)
+    )]
+    pub thermal_threshold: u32,
+
+    /// Degrees °C between the --thermal-zone levels
+    #[arg(
+        long,
+        env = "THERMAL_STEP",
+        value_name = "CELSIUS",
+        default_value = "5",
+        value_parser = clap::value_parser!(u32).range(1..50)
+    )]
+    pub thermal_step: u32,
+
+    /// Degrees °C below a --thermal-zone level's start before its outputs
+    /// are restored
+    #[arg(
+        long,
+        env = "THERMAL_HYSTERESIS",
+        value_name = "CELSIUS",
+        default_value = "5",
+        value_parser = clap::value_parser!(u32).range(0..50)
+    )]
+    pub thermal_hysteresis: u32,
+
+    /// Read the camera's temperature and error-counter controls (those
+    /// named like "Temperature" or "MIPI CRC Errors") once a second, warn
+    /// when an error counter rises and add the readings to each
+    /// --h264-meta-topic message as `sensor`
+    #[arg(long, env = "SENSOR_DIAGNOSTICS")]
+    pub sensor_diagnostics: bool,
+
     /// Enable H.264 video streaming output
     #[arg(long, env = "H264")]
     pub h264: bool,
 
     /// Zenoh topic for H.264 video stream (foxglove_msgs/CompressedVideo)
-    #[arg(long, default_value = "rt/camera/h264")]
+    #[arg(long, default_value = DEFAULT_H264_TOPIC)]
     pub h264_topic: String,
 
+    /// Zenoh topic for per-frame H.264 metadata (JSON: resolution,
+    /// keyframe flag, sequence), published with the same timestamp as the
+    /// matching `--h264-topic` sample
+    #[arg(long, default_value = DEFAULT_H264_META_TOPIC)]
+    pub h264_meta_topic: String,
+
+    /// Zenoh topic for the SPS and PPS of the H.264 stream (Annex-B),
+    /// published whenever they change and answered to queries, so a
+    /// subscriber joining mid-stream can prepend them to the first keyframe
+    #[arg(long, default_value = DEFAULT_H264_INIT_TOPIC)]
+    pub h264_init_topic: String,
+
+    /// Zenoh topic to query for an immediate H.264 keyframe, so a
+    /// subscriber joining mid-stream need not wait for the next one
+    #[arg(long, default_value = DEFAULT_H264_KEYFRAME_TOPIC)]
+    pub h264_keyframe_topic: String,
+
+    /// Force an H.264 keyframe at least every this many frames, shortening
+    /// the encoder's own keyframe interval. Each forced keyframe reopens the
+    /// encoder. 0 keeps the encoder's interval
+    #[arg(long, env = "H264_KEYFRAME_INTERVAL", default_value = "0")]
+    pub h264_keyframe_interval: u32,
+
+    /// Force an H.264 keyframe on the first frame captured after each
+    /// multiple of SECONDS (default 1) of wall-clock time, so cameras with
+    /// synchronised clocks start their GOPs together, instead of every
+    /// --h264-keyframe-interval frames
+    #[arg(
+        long,
+        env = "H264_KEYFRAME_ALIGN_WALLCLOCK",
+        value_name = "SECONDS",
+        default_missing_value = "1",
+        num_args = 0..=1,
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with = "h264_keyframe_interval"
+    )]
+    pub h264_keyframe_align_wallclock: Option<u64>,
+
+    /// Additional H.264 stream of the camera on its own topic, as
+    /// `topic=<key>,fps=<n>,bitrate=<preset>,size=<W>x<H>` with only `topic`
+    /// required. Repeat for more variants, separated by `;` in the
+    /// environment variable. Each runs its own encoder
+    #[arg(long, env = "H264_VARIANTS", value_delimiter = ';')]
+    pub h264_variant: Vec<H264Variant>,
+
     /// H.264 encoding bitrate preset
     #[arg(long, env = "H264_BITRATE", default_value = "auto")]
     pub h264_bitrate: H264Bitrate,
 
+    /// Codec of the `--h264`, `--h264-tiles` and `--h264-variant` streams.
+    /// `h265` roughly halves the bitrate at the same quality but cannot be
+    /// recorded, served on `--unix-socket` or given a `--pixel-aspect`, and
+    /// publishes no `--h264-init-topic` parameter sets
+    #[arg(long, env = "CODEC", default_value = "h264", value_enum)]
+    pub codec: Codec,
+
+    /// B-frames between reference frames in the H.264 stream. B-frames
+    /// save bits but make the decoder hold frames back for reordering;
+    /// the Hantro H1 encodes I and P frames only, so 0 (low latency, frames
+    /// in decode order) is the only supported value
+    #[arg(long, env = "H264_BFRAMES", default_value = "0")]
+    pub h264_bframes: u32,
+
+    /// Header stamp of the H.264 and H.264 metadata messages: the frame's
+    /// `capture` time, or the `encoder`'s presentation timestamp for
+    /// debugging A/V sync and encoder latency
+    #[arg(long, env = "H264_STAMP", default_value = "capture", value_enum)]
+    pub h264_stamp: H264Stamp,
+
+    /// NAL unit framing of the published H.264 stream, tiles and parameter
+    /// sets: `annexb` start codes, or `avcc` 4-byte length prefixes for MP4
+    /// muxers. Foxglove only decodes Annex-B, so `avcc` consumers read the
+    /// `framing` of the H.264 metadata. Recordings, the Unix socket and SRT
+    /// stay Annex-B
+    #[arg(long, env = "H264_FRAMING", default_value = "annexb", value_enum)]
+    pub h264_framing: H264Framing,
+
+    /// Sample (pixel) aspect ratio signalled in the H.264 stream as "W:H",
+    /// for anamorphic lenses or sensors with non-square pixels
+    #[arg(long, env = "PIXEL_ASPECT", default_value = "1:1")]
+    pub pixel_aspect: SampleAspectRatio,
+
+    /// Rotation applied to the H.264 stream, defaults to `--rotation`
+    #[arg(long, env = "H264_ROTATION", value_enum)]
+    pub h264_rotation: Option<RotationSetting>,
+
     /// Enable 4K tiling (splits 4K into 4x 1080p tiles for hardware encoding)
     #[arg(long, env = "H264_TILES")]
     pub h264_tiles: bool,
@@ -117,7 +459,7 @@ pub struct Args {
     /// bottom-right
     #[arg(
         long,
-        default_value = "rt/camera/h264/tl rt/camera/h264/tr rt/camera/h264/bl rt/camera/h264/br",
+        default_values = DEFAULT_H264_TILES_TOPICS,
         value_delimiter = ' ',
         num_args = 4
     )]
@@ -128,6 +470,29 @@ pub struct Args {
     #[arg(long, env = "H264_TILES_FPS", default_value = "15")]
     pub h264_tiles_fps: u32,
 
+    /// Write converted frames to a V4L2 output device (e.g. a v4l2loopback
+    /// node such as /dev/video10) so other V4L2 applications can consume the
+    /// stream. Frames are scaled to `--stream-size`.
+    #[arg(long, env = "V4L2_OUTPUT")]
+    pub v4l2_output: Option<PathBuf>,
+
+    /// Pixel format written to the V4L2 output device
+    #[arg(long, env = "V4L2_OUTPUT_FORMAT", default_value = "yuyv", value_enum)]
+    pub v4l2_output_format: V4l2OutputFormat,
+
+    /// Serve the encoded `--h264` and `--jpeg` frames to local clients on a
+    /// Unix domain socket at this path, each frame behind a 16-byte header
+    /// carrying its length, kind and capture time
+    #[arg(long, env = "UNIX_SOCKET")]
+    pub unix_socket: Option<PathBuf>,
+
+    /// Send the `--h264` stream to an SRT listener at this
+    /// `srt://host:port` URL, with optional `latency` (ms), `overhead` (%)
+    /// and `streamid` query options
+    #[cfg(feature = "srt")]
+    #[arg(long, env = "SRT")]
+    pub srt: Option<crate::srt::SrtConfig>,
+
     /// Record the live H.264 stream to this file (raw Annex-B `.h264`).
     ///
     /// A matching `<path>.json` sidecar is written alongside at startup
@@ -170,6 +535,11 @@ pub struct Args {
     )]
     pub stream_size: Vec<u32>,
 
+    /// Halve the stream resolution at startup until the buffers the enabled
+    /// outputs need fit in the free CMA pool, instead of only warning
+    #[arg(long, env = "CMA_ADAPT")]
+    pub cma_adapt: bool,
+
     /// Enable verbose debug logging
     #[arg(short, long)]
     pub verbose: bool,
@@ -178,6 +548,17 @@ pub struct Args {
     #[arg(long, env = "CAM_INFO_PATH", default_value = "")]
     pub cam_info_path: String,
 
+    /// Entry of the calibration JSON's `dewarpConfigArray` to read, for
+    /// ISP configurations with one dewarp config per view or channel
+    #[arg(long, env = "DEWARP_INDEX", default_value = "0")]
+    pub dewarp_index: usize,
+
+    /// Remove the lens distortion of the `--cam-info-path` calibration from
+    /// every frame on the CPU, for ISPs in bypass that leave it in. The
+    /// published CameraInfo then carries zero distortion
+    #[arg(long, env = "RECTIFY_OUTPUT")]
+    pub rectify_output: bool,
+
     /// Camera optical frame translation from base_link (x y z in meters)
     #[arg(
         long,
@@ -188,11 +569,12 @@ pub struct Args {
     )]
     pub cam_tf_vec: Vec<f64>,
 
-    /// Camera optical frame rotation quaternion from base_link (x y z w)
+    /// Camera optical frame rotation quaternion from base_link (x y z w),
+    /// normalized to unit length before publishing
     #[arg(
         long,
         env = "CAM_TF_QUAT",
-        default_value = "-1 1 -1 1",
+        default_value = "-0.5 0.5 -0.5 0.5",
         value_delimiter = ' ',
         num_args = 4
     )]
@@ -206,10 +588,60 @@ pub struct Args {
     #[arg(long, default_value = "camera_optical")]
     pub camera_frame_id: String,
 
+    /// Append the capture time (`stamp`), or the capture time and sequence
+    /// number (`stamp-seq`), to the frame ID of the published camera frame,
+    /// JPEG and H.264 messages, e.g. `camera_optical@1700000000.000000005#42`.
+    /// For reading timing off `ros2 topic echo`; the suffixed ID no longer
+    /// matches the TF tree
+    #[arg(
+        long,
+        env = "DEBUG_FRAME_ID",
+        default_value = "off",
+        default_missing_value = "stamp",
+        num_args = 0..=1,
+        value_enum
+    )]
+    pub debug_frame_id: DebugFrameId,
+
+    /// JSON file of extra static transforms published on tf_static next to
+    /// the camera transform, for cameras mounted through intermediate
+    /// links: an array of {base_frame_id, child_frame_id, translation,
+    /// rotation} objects
+    #[arg(long, env = "TF_CONFIG")]
+    pub tf_config: Option<PathBuf>,
+
+    /// Serve Prometheus metrics on this port at `/metrics`
+    #[cfg(feature = "metrics")]
+    #[arg(long, env = "METRICS_PORT")]
+    pub metrics_port: Option<u16>,
+
     /// Enable Tokio async runtime console for debugging
     #[arg(long, env = "TOKIO_CONSOLE")]
     pub tokio_console: bool,
 
+    /// Do not log to journald, e.g. in containers where its socket is
+    /// present but unusable
+    #[arg(long, env = "NO_JOURNALD")]
+    pub no_journald: bool,
+
+    /// Log the source and destination G2D surfaces (format code, planes,
+    /// rectangle, stride) before every blit, at trace level
+    #[arg(long, env = "G2D_TRACE")]
+    pub g2d_trace: bool,
+
+    /// Log the average and p99 duration of the camera_read, jpeg_convert,
+    /// jpeg_encode, h264_resize and h264_encode stages every SECONDS
+    /// (default 10), for profiling without Tracy
+    #[arg(
+        long,
+        env = "TIMING_LOG",
+        value_name = "SECONDS",
+        default_missing_value = "10",
+        num_args = 0..=1,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub timing_log: Option<u64>,
+
     /// Enable Tracy profiler for performance analysis
     #[arg(long, env = "TRACY")]
     pub tracy: bool,
@@ -231,6 +663,128 @@ pub struct Args {
     no_multicast_scouting: bool,
 }
 
+impl Args {
+    /// Rotation for the H.264 stream: `--h264-rotation` if set, otherwise
+    /// the global `--rotation`.
+    pub fn h264_output_rotation(&self) -> Rotation {
+        self.h264_rotation.unwrap_or(self.rotation).into()
+    }
+
+    /// Rotation for the JPEG output: `--jpeg-rotation` if set, otherwise
+    /// the global `--rotation`.
+    pub fn jpeg_output_rotation(&self) -> Rotation {
+        self.jpeg_rotation.unwrap_or(self.rotation).into()
+    }
+
+    /// Frame ID for an image message stamped `sec`.`nanosec` with sequence
+    /// number `seq`: `--camera-frame-id` with the `--debug-frame-id` suffix.
+    pub fn message_frame_id(&self, sec: i32, nanosec: u32, seq: u64) -> Cow<'_, str> {
+        let id = &self.camera_frame_id;
+        match self.debug_frame_id {
+            DebugFrameId::Off => Cow::Borrowed(id),
+            DebugFrameId::Stamp => Cow::Owned(format!("{id}@{sec}.{nanosec:09}")),
+            DebugFrameId::StampSeq => Cow::Owned(format!("{id}@{sec}.{nanosec:09}#{seq}")),
+        }
+    }
+
+    /// Capacity of an encoder's frame channel: `--encode-queue-depth` if
+    /// set, otherwise the output's `default`.
+    pub fn encode_queue_depth(&self, default: usize) -> usize {
+        self.encode_queue_depth
+            .map_or(default, |depth| depth as usize)
+    }
+
+    /// Prefix `--topic-namespace`, if set, onto every topic still at its
+    /// default. Fails when the namespace is not a valid Zenoh key
+    /// expression or contains wildcards.
+    pub fn apply_topic_namespace(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(ns) = self.topic_namespace.clone() else {
+            return Ok(());
+        };
+        match keyexpr::new(ns.as_str()) {
+            Ok(key) if !key.contains('*') => {}
+            Ok(_) => {
+                return Err(format!("--topic-namespace {ns:?} must not contain wildcards").into())
+            }
+            Err(_) => {
+                return Err(format!(
+                    "--topic-namespace {ns:?} is not a valid Zenoh key expression \
+                     (no empty or leading/trailing '/' chunks, no '#' or '?')"
+                )
+                .into())
+            }
+        }
+
+        for (topic, default) in [
+            (&mut self.frame_topic, DEFAULT_FRAME_TOPIC),
+            (&mut self.info_topic, DEFAULT_INFO_TOPIC),
+            (&mut self.connected_topic, DEFAULT_CONNECTED_TOPIC),
+            (&mut self.jpeg_topic, DEFAULT_JPEG_TOPIC),
+            (&mut self.hires_topic, DEFAULT_HIRES_TOPIC),
+            (&mut self.jpeg_region_topic, DEFAULT_JPEG_REGION_TOPIC),
+            (&mut self.h264_topic, DEFAULT_H264_TOPIC),
+            (&mut self.h264_meta_topic, DEFAULT_H264_META_TOPIC),
+            (&mut self.h264_init_topic, DEFAULT_H264_INIT_TOPIC),
+            (&mut self.h264_keyframe_topic, DEFAULT_H264_KEYFRAME_TOPIC),
+        ]
+        .into_iter()
+        .chain(
+            self.h264_tiles_topics
+                .iter_mut()
+                .zip(DEFAULT_H264_TILES_TOPICS),
+        ) {
+            if *topic == default {
+                *topic = namespaced(&ns, default);
+            }
+        }
+        Ok(())
+    }
+
+    /// Topic the static transforms are published on, `rt/tf_static` under
+    /// `--topic-namespace`.
+    pub fn tf_static_topic(&self) -> String {
+        match &self.topic_namespace {
+            Some(ns) => namespaced(ns, TF_STATIC_TOPIC),
+            None => TF_STATIC_TOPIC.to_string(),
+        }
+    }
+
+    /// The RGBA conversion the JPEG and H.264 encoders share, if both are
+    /// enabled with the same rotation, `--shared-convert` is on and
+    /// `--hires-interval` is off. When `None` each encoder converts the
+    /// frames it receives itself.
+    pub fn shared_convert_target(&self) -> Option<ConvertTarget> {
+        // The --hires-interval frames are converted from the camera frame.
+        if !(self.shared_convert && self.jpeg && self.h264)
+            || self.jpeg_plane != JpegPlane::All
+            || self.hires_interval.is_some()
+        {
+            return None;
+        }
+        let rotation = self.h264_output_rotation();
+        if rotation != self.jpeg_output_rotation() {
+            return None;
+        }
+        let (width, height) = rotation.rotated_size(self.stream_size[0], self.stream_size[1]);
+        Some(ConvertTarget {
+            format: RGBA,
+            width,
+            height,
+            rotation,
+            crop: None,
+        })
+    }
+}
+
+/// `topic` under namespace `ns`, keeping a leading `rt/` (the ROS 2 bridge
+/// prefix) in front.
+fn namespaced(ns: &str, topic: &str) -> String {
+    match topic.strip_prefix("rt/") {
+        Some(rest) => format!("rt/{ns}/{rest}"),
+        None => format!("{ns}/{topic}"),
+    }
+}
+
 impl From<Args> for Config {
     fn from(args: Args) -> Self {
         let mut config = Config::default();
@@ -253,16 +807,17 @@ impl From<Args> for Config {
                 .unwrap();
         }
 
+        // The interface only means something while multicast scouting runs.
         if args.no_multicast_scouting {
             config
                 .insert_json5("scouting/multicast/enabled", &json!(false).to_string())
                 .unwrap();
+        } else {
+            config
+                .insert_json5("scouting/multicast/interface", &json!("lo").to_string())
+                .unwrap();
         }
 
-        config
-            .insert_json5("scouting/multicast/interface", &json!("lo").to_string())
-            .unwrap();
-
         config
     }
 }
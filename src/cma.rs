@@ -0,0 +1,314 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Startup check of the buffers the enabled outputs need against the free
+//! CMA pool.
+//!
+//! Every [`Image`](edgefirst_camera::image::Image) comes from the CMA heap,
+//! and so do the camera and encoder buffers. On boards with a smaller pool
+//! than the node expects, the allocation that runs out fails deep inside
+//! one output's thread. Reading `CmaFree` from `/proc/meminfo` before any
+//! output starts lets the node name what would not fit and, with
+//! `--cma-adapt`, halve `--stream-size` until it does. The estimate counts
+//! the buffers the node allocates itself plus an approximation of the
+//! encoder's own frames, so it is a guide rather than an exact figure.
+
+use std::{fmt, fs, io};
+
+use edgefirst_camera::{
+    image::{plane_stride, I420, NV12, RGBA, YUYV},
+    pipeline::FormatConverter,
+    video::VideoManager,
+};
+use tracing::{debug, info, warn};
+use videostream::fourcc::FourCC;
+
+use crate::{
+    args::{Args, ErrorFramePolicy, FrameFormat, JpegPlane},
+    TilePosition,
+};
+
+/// Buffers the V4L2 capture queue holds.
+const CAMERA_BUFFERS: u64 = 4;
+
+/// Frames the H.264 encoder keeps for its input, reference and
+/// reconstructed pictures. Approximate; the VPU driver decides.
+const ENCODER_FRAMES: u64 = 3;
+
+/// `--cma-adapt` does not shrink the stream below this width.
+pub const MIN_STREAM_WIDTH: u32 = 320;
+
+/// Free CMA in bytes, or `None` when the kernel has no CMA pool.
+pub fn available() -> io::Result<Option<u64>> {
+    Ok(parse_cma_free(&fs::read_to_string("/proc/meminfo")?))
+}
+
+/// The `CmaFree` line of `/proc/meminfo` contents, in bytes.
+pub fn parse_cma_free(meminfo: &str) -> Option<u64> {
+    meminfo.lines().find_map(|line| {
+        let kb = line.strip_prefix("CmaFree:")?.trim().strip_suffix("kB")?;
+        kb.trim().parse::<u64>().ok().map(|kb| kb * 1024)
+    })
+}
+
+/// One group of buffers in an [`Estimate`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct BufferUse {
+    pub name: &'static str,
+    pub bytes: u64,
+}
+
+/// CMA the enabled outputs are expected to allocate.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Estimate {
+    pub uses: Vec<BufferUse>,
+}
+
+impl Estimate {
+    pub fn total(&self) -> u64 {
+        self.uses.iter().map(|u| u.bytes).sum()
+    }
+
+    fn add(&mut self, name: &'static str, count: u64, format: FourCC, width: u32, height: u32) {
+        if count > 0 {
+            self.uses.push(BufferUse {
+                name,
+                bytes: count * frame_bytes(format, width, height),
+            });
+        }
+    }
+}
+
+/// One line per buffer group, largest first, then the total.
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut uses = self.uses.clone();
+        uses.sort_by_key(|u| std::cmp::Reverse(u.bytes));
+        for u in &uses {
+            writeln!(f, "  {:<16} {:>6} KiB", u.name, u.bytes / 1024)?;
+        }
+        write!(f, "  {:<16} {:>6} KiB", "total", self.total() / 1024)
+    }
+}
+
+/// Bytes of one `width`x`height` frame in `format`, counting formats
+/// [`plane_stride`] does not know as four bytes per pixel.
+fn frame_bytes(format: FourCC, width: u32, height: u32) -> u64 {
+    let stride = plane_stride(format, width).unwrap_or(width as usize * 4) as u64;
+    match format {
+        NV12 | I420 => stride * height as u64 * 3 / 2,
+        _ => stride * height as u64,
+    }
+}
+
+/// Estimate the CMA the outputs enabled in `args` allocate, at its current
+/// `--camera-size` and `--stream-size`.
+pub fn estimate(args: &Args) -> Estimate {
+    let [cam_w, cam_h] = [args.camera_size[0], args.camera_size[1]];
+    let [stream_w, stream_h] = [args.stream_size[0], args.stream_size[1]];
+    let buffers = FormatConverter::BUFFERS as u64;
+    let shared = args.shared_convert_target();
+    let mut estimate = Estimate::default();
+
+    let camera_format = args.bayer.map_or(YUYV, |pattern| pattern.fourcc());
+    estimate.add("camera", CAMERA_BUFFERS, camera_format, cam_w, cam_h);
+    if args.bayer.is_some() {
+        estimate.add("debayer", buffers, RGBA, cam_w, cam_h);
+    }
+    if args.rectify_output {
+        let format = args.bayer.map_or(YUYV, |_| RGBA);
+        estimate.add("rectify", buffers, format, cam_w, cam_h);
+    }
+    if let Some(target) = shared {
+        estimate.add(
+            "shared convert",
+            buffers,
+            target.format,
+            target.width,
+            target.height,
+        );
+    }
+    if args.h264 {
+        let staging = shared.is_none() as u64;
+        estimate.add("h264 staging", staging, RGBA, stream_w, stream_h);
+        estimate.add("h264 encoder", ENCODER_FRAMES, NV12, stream_w, stream_h);
+    }
+    for variant in &args.h264_variant {
+        let (width, height) = variant.size.unwrap_or((stream_w, stream_h));
+        estimate.add("h264 variant", 1, RGBA, width, height);
+        estimate.add("h264 variant", ENCODER_FRAMES, NV12, width, height);
+    }
+    if args.jpeg {
+        let staging = (shared.is_none() && args.jpeg_plane == JpegPlane::All) as u64;
+        estimate.add("jpeg staging", staging, RGBA, stream_w, stream_h);
+        if args.hires_interval.is_some() {
+            let staging = (args.jpeg_plane == JpegPlane::All) as u64;
+            estimate.add("jpeg hires", staging, RGBA, cam_w, cam_h);
+        }
+    }
+    if args.error_frame_policy != ErrorFramePolicy::Gap {
+        // Each encoder keeps a copy of the last frame it received.
+        let encoders = args.h264 as u64 + args.jpeg as u64;
+        match shared {
+            Some(t) => estimate.add("error frames", encoders, t.format, t.width, t.height),
+            None => estimate.add("error frames", encoders, camera_format, cam_w, cam_h),
+        }
+    }
+    if args.v4l2_output.is_some() {
+        let format = args.v4l2_output_format.fourcc();
+        estimate.add("v4l2 output", 1, format, stream_w, stream_h);
+    }
+    let dma_format = match args.dma_format {
+        Some(format) if format.fourcc() != camera_format => Some(format),
+        _ if args.dma_crop_topic.is_some() => Some(args.dma_format.unwrap_or(FrameFormat::Yuyv)),
+        _ => None,
+    };
+    if let Some(format) = dma_format {
+        estimate.add("dma convert", buffers, format.fourcc(), cam_w, cam_h);
+    }
+    if args.h264_tiles {
+        let (tile_w, tile_h) = TilePosition::get_output_dimensions();
+        estimate.add("h264 tiles", 4 * ENCODER_FRAMES, NV12, tile_w, tile_h);
+    }
+    estimate
+}
+
+/// The largest `--stream-size` reached by halving the current one whose
+/// [`estimate`] fits in `budget` bytes, or `None` when no halving the
+/// encoder accepts, down to [`MIN_STREAM_WIDTH`], fits. Returns the current
+/// size when it already fits.
+pub fn fit_stream_size(args: &Args, budget: u64) -> Option<(u32, u32)> {
+    let mut args = args.clone();
+    loop {
+        let (width, height) = (args.stream_size[0], args.stream_size[1]);
+        if estimate(&args).total() <= budget {
+            return Some((width, height));
+        }
+        let (width, height) = (width / 2, height / 2);
+        let (enc_w, enc_h) = args.h264_output_rotation().rotated_size(width, height);
+        if width < MIN_STREAM_WIDTH || VideoManager::check_size(enc_w as i32, enc_h as i32).is_err()
+        {
+            return None;
+        }
+        args.stream_size = vec![width, height];
+    }
+}
+
+/// Compare the [`estimate`] for `args` with `free` bytes of CMA. When it
+/// does not fit, either halve `--stream-size` until it does (with
+/// `--cma-adapt`) or warn with the breakdown. Never fails: the estimate is
+/// approximate and the allocations may still succeed.
+pub fn check(args: &mut Args, free: u64) {
+    let estimate = estimate(args);
+    if estimate.total() <= free {
+        debug!(
+            "Estimated CMA use {} KiB of {} KiB free",
+            estimate.total() / 1024,
+            free / 1024
+        );
+        return;
+    }
+    let (width, height) = (args.stream_size[0], args.stream_size[1]);
+    if args.cma_adapt {
+        if let Some((fit_w, fit_h)) = fit_stream_size(args, free) {
+            info!(
+                "Reducing stream size from {width}x{height} to {fit_w}x{fit_h} to fit \
+                 {} KiB of free CMA (--cma-adapt)",
+                free / 1024
+            );
+            args.stream_size = vec![fit_w, fit_h];
+            return;
+        }
+    }
+    warn!(
+        "Enabled outputs are estimated to need {} KiB of CMA but only {} KiB is free; \
+         allocations may fail{}:\n{estimate}",
+        estimate.total() / 1024,
+        free / 1024,
+        if args.cma_adapt {
+            " even at the smallest stream size"
+        } else {
+            ", reduce --stream-size or pass --cma-adapt"
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser as _;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn args(extra: &[&str]) -> Args {
+        Args::parse_from(["edgefirst-camera"].iter().chain(extra))
+    }
+
+    #[test]
+    fn parses_cma_free() {
+        let meminfo = "MemTotal:        3902216 kB\n\
+                       CmaTotal:         655360 kB\n\
+                       CmaFree:          524288 kB\n";
+        assert_eq!(parse_cma_free(meminfo), Some(512 * MIB));
+        assert_eq!(parse_cma_free("MemTotal: 3902216 kB\n"), None);
+    }
+
+    #[test]
+    fn estimate_counts_enabled_outputs() {
+        let base = estimate(&args(&["--camera-size", "1920", "1080"])).total();
+        let camera = CAMERA_BUFFERS * 1920 * 1080 * 2;
+        assert_eq!(base, camera);
+
+        let with_jpeg = estimate(&args(&[
+            "--camera-size",
+            "1920",
+            "1080",
+            "--stream-size",
+            "1920",
+            "1080",
+            "--jpeg",
+        ]));
+        assert_eq!(with_jpeg.total(), camera + 1920 * 1080 * 4);
+        assert!(with_jpeg.to_string().contains("jpeg staging"));
+    }
+
+    #[test]
+    fn fit_halves_stream_size_until_within_budget() {
+        let args = args(&[
+            "--camera-size",
+            "1920",
+            "1080",
+            "--stream-size",
+            "1920",
+            "1080",
+            "--h264",
+            "--jpeg",
+        ]);
+        let total = estimate(&args).total();
+        assert_eq!(fit_stream_size(&args, total), Some((1920, 1080)));
+
+        // The camera buffers stay; only the stream-sized buffers shrink.
+        let mut half = args.clone();
+        half.stream_size = vec![960, 540];
+        let half_total = estimate(&half).total();
+        assert!(half_total < total);
+        assert_eq!(fit_stream_size(&args, total - 1), Some((960, 540)));
+        assert_eq!(fit_stream_size(&args, half_total), Some((960, 540)));
+        assert_eq!(fit_stream_size(&args, half_total - 1), Some((480, 270)));
+
+        // 240x135 is below the minimum and misaligned.
+        assert_eq!(fit_stream_size(&args, 16 * MIB), None);
+    }
+
+    #[test]
+    fn check_adapts_only_when_asked() {
+        let mut plain = args(&["--stream-size", "1920", "1080", "--h264"]);
+        let budget = estimate(&plain).total() - 1;
+        check(&mut plain, budget);
+        assert_eq!(plain.stream_size, [1920, 1080]);
+
+        let mut adapt = args(&["--stream-size", "1920", "1080", "--h264", "--cma-adapt"]);
+        check(&mut adapt, budget);
+        assert_eq!(adapt.stream_size, [960, 540]);
+    }
+}
@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Colour correction on the CPU.
+//!
+//! Sensors run without the ISP, such as raw Bayer sensors demosaiced by
+//! the node, deliver colours in the sensor's own primaries and linear
+//! light. A [`ColorCorrection`] maps them to the output colour space with a
+//! 3x3 colour correction matrix (CCM), then encodes each channel with its
+//! own gamma. Both come from the calibration file.
+//!
+//! The matrix is applied in fixed point and the gamma through a lookup
+//! table, so correcting a pixel costs nine multiplies and three lookups.
+//!
+//! ```
+//! use edgefirst_camera::color::ColorCorrection;
+//!
+//! // Swap red and blue, no gamma.
+//! let swap = [0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0];
+//! let ccm = ColorCorrection::new(swap, [1.0; 3]).unwrap();
+//! let mut px = [10, 20, 30, 255];
+//! ccm.apply_rgba(&[10, 20, 30, 255], 4, &mut px, 4, 1, 1);
+//! assert_eq!(px, [30, 20, 10, 255]);
+//! ```
+
+use std::error::Error;
+
+/// Fractional bits of the fixed-point matrix.
+const SHIFT: u32 = 12;
+
+/// Largest magnitude of a matrix coefficient. Real CCMs stay within a few
+/// units; the bound keeps the fixed-point sums within an `i32`.
+const MAX_COEFFICIENT: f64 = 64.0;
+
+/// The identity matrix, row-major.
+pub const IDENTITY: [f64; 9] = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+
+/// A colour correction matrix followed by a per-channel gamma.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorCorrection {
+    /// Row-major matrix scaled by `1 << SHIFT`.
+    matrix: [i32; 9],
+    /// Gamma lookup of each of R, G and B.
+    lut: [[u8; 256]; 3],
+}
+
+impl ColorCorrection {
+    /// Build the correction that maps each RGB pixel by the row-major
+    /// `matrix`, then encodes each of R, G and B as
+    /// `255 * (v / 255) ^ (1 / gamma)`. A gamma of 1 leaves the channel
+    /// linear; 2.2 approximates sRGB.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for a coefficient that is not finite or beyond
+    /// ±64, or a gamma that is not finite and positive.
+    pub fn new(matrix: [f64; 9], gamma: [f64; 3]) -> Result<Self, Box<dyn Error>> {
+        if let Some(c) = matrix
+            .iter()
+            .find(|c| !(c.is_finite() && c.abs() <= MAX_COEFFICIENT))
+        {
+            return Err(format!(
+                "colour correction coefficient {c} is not within ±{MAX_COEFFICIENT}"
+            )
+            .into());
+        }
+        if let Some(g) = gamma.iter().find(|g| !(g.is_finite() && **g > 0.0)) {
+            return Err(format!("colour correction gamma {g} is not positive").into());
+        }
+        let scale = (1 << SHIFT) as f64;
+        let lut = gamma.map(|g| {
+            let mut lut = [0; 256];
+            for (v, out) in lut.iter_mut().enumerate() {
+                *out = (255.0 * (v as f64 / 255.0).powf(1.0 / g)).round() as u8;
+            }
+            lut
+        });
+        Ok(Self {
+            matrix: matrix.map(|c| (c * scale).round() as i32),
+            lut,
+        })
+    }
+
+    /// Returns `true` if correcting leaves every pixel unchanged, as with
+    /// the identity matrix and a gamma of 1.
+    pub fn is_identity(&self) -> bool {
+        let identity = IDENTITY.map(|c| c as i32 * (1 << SHIFT));
+        self.matrix == identity
+            && self
+                .lut
+                .iter()
+                .all(|lut| lut.iter().enumerate().all(|(v, &out)| out as usize == v))
+    }
+
+    /// Correct a `width`x`height` RGBA frame from `src` into `dst`. Alpha is
+    /// copied unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either buffer is too small for the frame size.
+    pub fn apply_rgba(
+        &self,
+        src: &[u8],
+        src_stride: usize,
+        dst: &mut [u8],
+        dst_stride: usize,
+        width: usize,
+        height: usize,
+    ) {
+        let m = &self.matrix;
+        let round = 1 << (SHIFT - 1);
+        for row in 0..height {
+            let input = &src[row * src_stride..][..width * 4];
+            let out = &mut dst[row * dst_stride..][..width * 4];
+            for (px, inp) in out.chunks_exact_mut(4).zip(input.chunks_exact(4)) {
+                let (r, g, b) = (inp[0] as i32, inp[1] as i32, inp[2] as i32);
+                for c in 0..3 {
+                    let v = (m[c * 3] * r + m[c * 3 + 1] * g + m[c * 3 + 2] * b + round) >> SHIFT;
+                    px[c] = self.lut[c][v.clamp(0, 255) as usize];
+                }
+                px[3] = inp[3];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 16x8 RGBA frame whose pixels all differ.
+    fn rgba_pattern() -> Vec<u8> {
+        (0..16 * 8u32)
+            .flat_map(|i| [i as u8, (i * 3) as u8, (i * 7) as u8, (i * 5) as u8])
+            .collect()
+    }
+
+    #[test]
+    fn identity_is_a_no_op() {
+        let ccm = ColorCorrection::new(IDENTITY, [1.0; 3]).unwrap();
+        assert!(ccm.is_identity());
+
+        let src = rgba_pattern();
+        let mut dst = vec![0; src.len()];
+        ccm.apply_rgba(&src, 16 * 4, &mut dst, 16 * 4, 16, 8);
+        assert_eq!(dst, src);
+    }
+
+    #[test]
+    fn channel_swap_moves_red_to_blue() {
+        let swap = [0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0];
+        let ccm = ColorCorrection::new(swap, [1.0; 3]).unwrap();
+        assert!(!ccm.is_identity());
+
+        let src = rgba_pattern();
+        // A padded destination row is left alone past the frame.
+        let mut dst = vec![0xee; 8 * 20 * 4];
+        ccm.apply_rgba(&src, 16 * 4, &mut dst, 20 * 4, 16, 8);
+        for (row, out) in dst.chunks_exact(20 * 4).enumerate() {
+            let input = &src[row * 16 * 4..][..16 * 4];
+            for (px, inp) in out[..16 * 4].chunks_exact(4).zip(input.chunks_exact(4)) {
+                assert_eq!(px, [inp[2], inp[1], inp[0], inp[3]]);
+            }
+            assert!(out[16 * 4..].iter().all(|&v| v == 0xee));
+        }
+    }
+
+    #[test]
+    fn matrix_output_is_clamped_and_gamma_encoded() {
+        // Boost red by 2 and subtract it from green.
+        let ccm = ColorCorrection::new(
+            [2.0, 0.0, 0.0, -1.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            [1.0, 1.0, 2.0],
+        )
+        .unwrap();
+        let mut px = [0; 4];
+        ccm.apply_rgba(&[200, 100, 64, 7], 4, &mut px, 4, 1, 1);
+        // 255 * (64 / 255) ^ (1 / 2) = 127.75
+        assert_eq!(px, [255, 0, 128, 7]);
+
+        let gamma = ColorCorrection::new(IDENTITY, [2.2; 3]).unwrap();
+        assert!(!gamma.is_identity());
+        let mut px = [0; 4];
+        gamma.apply_rgba(&[0, 255, 128, 255], 4, &mut px, 4, 1, 1);
+        assert_eq!(px, [0, 255, 186, 255]);
+    }
+
+    #[test]
+    fn rejects_bad_coefficients_and_gamma() {
+        let mut matrix = IDENTITY;
+        matrix[4] = f64::NAN;
+        assert!(ColorCorrection::new(matrix, [1.0; 3]).is_err());
+        matrix[4] = 100.0;
+        assert!(ColorCorrection::new(matrix, [1.0; 3]).is_err());
+        assert!(ColorCorrection::new(IDENTITY, [1.0, 0.0, 1.0]).is_err());
+        assert!(ColorCorrection::new(IDENTITY, [1.0, 1.0, -2.2]).is_err());
+    }
+}
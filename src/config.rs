@@ -0,0 +1,519 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Encoder and capture settings shared by the library and the camera node's
+//! command line.
+//!
+//! ```
+//! use edgefirst_camera::config::{H264Bitrate, MirrorSetting};
+//! use videostream::camera::Mirror;
+//!
+//! let bitrate = H264Bitrate::Mbps25;
+//! assert_eq!(bitrate, H264Bitrate::Mbps25);
+//! assert!(matches!(Mirror::from(MirrorSetting::Both), Mirror::Both));
+//! ```
+
+use std::str::FromStr;
+
+use clap::ValueEnum as _;
+use videostream::{camera::Mirror, fourcc::FourCC};
+
+use crate::{
+    image::{
+        Rotation, NV12, RGBA, SBGGR10, SBGGR8, SGBRG10, SGBRG8, SGRBG10, SGRBG8, SRGGB10, SRGGB8,
+        YUYV,
+    },
+    nal,
+};
+
+/// H.264 encoding bitrate presets.
+///
+/// Controls the trade-off between video quality and file size.
+/// Higher bitrates produce better quality but larger files.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum H264Bitrate {
+    /// Automatic bitrate selection based on resolution
+    Auto,
+    /// 5 Mbps (suitable for 720p)
+    Mbps5,
+    /// 25 Mbps (suitable for 1080p)
+    Mbps25,
+    /// 50 Mbps (suitable for high-quality 1080p)
+    Mbps50,
+    /// 100 Mbps (suitable for 4K or very high quality)
+    Mbps100,
+}
+
+/// Codec of the hardware-encoded video streams.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum Codec {
+    /// H.264/AVC
+    H264,
+    /// H.265/HEVC, roughly half the bitrate of H.264 at the same quality
+    H265,
+}
+
+impl Codec {
+    /// Format the encoder is opened with.
+    pub fn fourcc(self) -> FourCC {
+        match self {
+            Codec::H264 => FourCC(*b"H264"),
+            Codec::H265 => FourCC(*b"HEVC"),
+        }
+    }
+
+    /// The codec encoding `format`, or `None` if the encoder has none.
+    pub fn from_fourcc(format: FourCC) -> Option<Codec> {
+        [Codec::H264, Codec::H265]
+            .into_iter()
+            .find(|codec| codec.fourcc() == format)
+    }
+
+    /// The `format` field of `foxglove_msgs/CompressedVideo` messages.
+    pub fn format(self) -> &'static str {
+        match self {
+            Codec::H264 => "h264",
+            Codec::H265 => "h265",
+        }
+    }
+}
+
+/// Camera image mirroring options.
+///
+/// Determines how the camera image should be flipped before processing.
+/// Useful for correcting camera orientation.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum MirrorSetting {
+    /// No mirroring
+    None,
+    /// Flip horizontally (left-right)
+    Horizontal,
+    /// Flip vertically (top-bottom)
+    Vertical,
+    /// Flip both horizontally and vertically (180-degree rotation)
+    Both,
+}
+
+impl From<MirrorSetting> for Mirror {
+    fn from(setting: MirrorSetting) -> Self {
+        match setting {
+            MirrorSetting::None => Mirror::None,
+            MirrorSetting::Horizontal => Mirror::Horizontal,
+            MirrorSetting::Vertical => Mirror::Vertical,
+            MirrorSetting::Both => Mirror::Both,
+        }
+    }
+}
+
+/// Output rotation options, in degrees clockwise.
+///
+/// Applied by G2D when a frame is converted for an output, so the camera
+/// frame itself is never rotated.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum RotationSetting {
+    /// No rotation
+    #[value(name = "0")]
+    Rotate0,
+    /// Rotate 90 degrees clockwise
+    #[value(name = "90")]
+    Rotate90,
+    /// Rotate 180 degrees
+    #[value(name = "180")]
+    Rotate180,
+    /// Rotate 270 degrees clockwise (90 degrees counter-clockwise)
+    #[value(name = "270")]
+    Rotate270,
+}
+
+impl From<RotationSetting> for Rotation {
+    fn from(setting: RotationSetting) -> Self {
+        match setting {
+            RotationSetting::Rotate0 => Rotation::Rotation0,
+            RotationSetting::Rotate90 => Rotation::Rotation90,
+            RotationSetting::Rotate180 => Rotation::Rotation180,
+            RotationSetting::Rotate270 => Rotation::Rotation270,
+        }
+    }
+}
+
+/// Which part of the frame the JPEG output encodes.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum JpegPlane {
+    /// The full colour image, converted to RGBA by G2D
+    All,
+    /// Only the luma (Y) plane as a greyscale JPEG, read straight from the
+    /// camera buffer without a G2D conversion
+    Luma,
+}
+
+/// Chroma subsampling of colour JPEGs. Greyscale JPEGs have no chroma.
+#[derive(clap::ValueEnum, Clone, Debug, Default, PartialEq, Eq, Copy)]
+pub enum JpegSubsamp {
+    /// Full-resolution chroma (4:4:4)
+    #[value(name = "444")]
+    Sub1x1,
+    /// Chroma halved horizontally (4:2:2)
+    #[value(name = "422")]
+    Sub2x1,
+    /// Chroma halved horizontally and vertically (4:2:0)
+    #[default]
+    #[value(name = "420")]
+    Sub2x2,
+}
+
+/// Synthetic image generated by `--test-pattern` instead of capturing.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Eq, Copy)]
+pub enum TestPattern {
+    /// Eight vertical 75% colour bars: white, yellow, cyan, green,
+    /// magenta, red, blue and black
+    Colorbars,
+    /// A horizontal grey ramp from black to white
+    Gradient,
+    /// A black and white checkerboard of 16 columns
+    Checker,
+}
+
+/// Handling of frames that repeat the previous frame exactly.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum DedupMode {
+    /// Do not look for repeated frames
+    Off,
+    /// Publish repeated frames but count and log them
+    Flag,
+    /// Drop repeated frames before they are published or encoded
+    Skip,
+}
+
+/// Where the header stamps of the H.264 messages come from.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum H264Stamp {
+    /// The capture time of the encoded frame
+    Capture,
+    /// The presentation timestamp the encoder assigned to the output frame,
+    /// or the capture time for frames the encoder reports none for
+    Encoder,
+}
+
+/// How the NAL units of the published H.264 stream are delimited.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Copy,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum H264Framing {
+    /// Start codes, as the encoder writes them, for streaming and raw
+    /// `.h264` players
+    #[default]
+    #[value(name = "annexb")]
+    AnnexB,
+    /// A 4-byte big-endian length before each NAL unit, as MP4 muxers
+    /// expect
+    Avcc,
+}
+
+impl H264Framing {
+    /// The Annex-B access unit `annex_b` in this framing. A reframed unit
+    /// is written into `scratch`, which callers keep across frames.
+    pub fn apply<'a>(self, annex_b: &'a [u8], scratch: &'a mut Vec<u8>) -> &'a [u8] {
+        match self {
+            Self::AnnexB => annex_b,
+            Self::Avcc => {
+                nal::to_avcc_into(annex_b, scratch);
+                scratch
+            }
+        }
+    }
+}
+
+/// What the encoder outputs emit for a frame that does not arrive on time.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum ErrorFramePolicy {
+    /// Emit nothing, leaving a gap in the stream
+    Gap,
+    /// Repeat the last good frame
+    Freeze,
+    /// Emit a black frame
+    Black,
+}
+
+/// An additional H.264 output of the same camera, parsed from comma-separated
+/// `key=value` pairs:
+///
+/// ```
+/// use edgefirst_camera::config::{H264Bitrate, H264Variant};
+///
+/// let variant: H264Variant = "topic=rt/camera/h264_low,fps=10,bitrate=mbps5,size=640x360"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(variant.topic, "rt/camera/h264_low");
+/// assert_eq!(variant.fps, Some(10));
+/// assert_eq!(variant.bitrate, H264Bitrate::Mbps5);
+/// assert_eq!(variant.size, Some((640, 360)));
+/// ```
+///
+/// Only `topic` is required.
+#[derive(Clone, Debug, PartialEq)]
+pub struct H264Variant {
+    pub topic: String,
+    /// Frame rate limit; unset encodes every camera frame.
+    pub fps: Option<u32>,
+    pub bitrate: H264Bitrate,
+    /// Encoded size before rotation; unset uses the main stream size.
+    pub size: Option<(u32, u32)>,
+}
+
+impl H264Variant {
+    /// Topic of the JSON metadata published next to the variant's video:
+    /// the video topic without the `rt/` bridge prefix, plus `/meta`, as
+    /// with the main H.264 stream.
+    pub fn meta_topic(&self) -> String {
+        let topic = self.topic.strip_prefix("rt/").unwrap_or(&self.topic);
+        format!("{topic}/meta")
+    }
+
+    /// Topic of the SPS and PPS of the variant's video: the video topic
+    /// plus `/init`, as with the main H.264 stream.
+    pub fn init_topic(&self) -> String {
+        format!("{}/init", self.topic)
+    }
+
+    /// Topic queried to force a keyframe in the variant's video: the video
+    /// topic plus `/force_keyframe`, as with the main H.264 stream.
+    pub fn keyframe_topic(&self) -> String {
+        format!("{}/force_keyframe", self.topic)
+    }
+}
+
+impl FromStr for H264Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut topic = None;
+        let mut variant = H264Variant {
+            topic: String::new(),
+            fps: None,
+            bitrate: H264Bitrate::Auto,
+            size: None,
+        };
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').ok_or_else(|| {
+                format!("expected key=value in H.264 variant '{s}', got '{pair}'")
+            })?;
+            let value = value.trim();
+            match key.trim() {
+                "topic" if value.is_empty() => {
+                    return Err(format!("empty topic in H.264 variant '{s}'"))
+                }
+                "topic" => topic = Some(value.to_string()),
+                "fps" => match value.parse::<u32>() {
+                    Ok(fps) if fps > 0 => variant.fps = Some(fps),
+                    _ => return Err(format!("invalid fps '{value}' in H.264 variant '{s}'")),
+                },
+                "bitrate" => {
+                    variant.bitrate = H264Bitrate::from_str(value, true)
+                        .map_err(|e| format!("invalid bitrate in H.264 variant '{s}': {e}"))?
+                }
+                "size" => {
+                    let size = value
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                        .filter(|&(w, h)| w > 0 && h > 0);
+                    variant.size = Some(size.ok_or_else(|| {
+                        format!("invalid size '{value}' in H.264 variant '{s}', expected WxH")
+                    })?);
+                }
+                other => return Err(format!("unknown key '{other}' in H.264 variant '{s}'")),
+            }
+        }
+        variant.topic = topic.ok_or_else(|| format!("H.264 variant '{s}' has no topic"))?;
+        Ok(variant)
+    }
+}
+
+/// Debugging suffix appended to the `frame_id` of published image messages.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum DebugFrameId {
+    /// Publish the plain frame ID
+    Off,
+    /// Append `@<sec>.<nanosec>` of the capture time
+    Stamp,
+    /// Append the capture time and `#<sequence>`
+    StampSeq,
+}
+
+/// Pixel formats the shared camera frame buffer can be converted to.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum FrameFormat {
+    /// YUYV 4:2:2 packed
+    Yuyv,
+    /// NV12 4:2:0, Y plane followed by interleaved UV
+    Nv12,
+    /// RGBA 8-bit per channel
+    Rgba,
+}
+
+impl FrameFormat {
+    pub fn fourcc(self) -> FourCC {
+        match self {
+            FrameFormat::Yuyv => YUYV,
+            FrameFormat::Nv12 => NV12,
+            FrameFormat::Rgba => RGBA,
+        }
+    }
+
+    /// Bytes per row of the first plane for a `width`-pixel image.
+    pub fn row_stride(self, width: u32) -> u32 {
+        match self {
+            FrameFormat::Yuyv => width * 2,
+            FrameFormat::Nv12 => width,
+            FrameFormat::Rgba => width * 4,
+        }
+    }
+}
+
+/// Colour filter arrangement of a raw Bayer sensor, named by the colours of
+/// the top-left 2x2 block read row by row.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum BayerPattern {
+    /// Red, green / green, blue
+    Rggb,
+    /// Blue, green / green, red
+    Bggr,
+    /// Green, blue / red, green
+    Gbrg,
+    /// Green, red / blue, green
+    Grbg,
+}
+
+impl BayerPattern {
+    /// The 8-bit V4L2 format carrying this pattern.
+    pub fn fourcc(self) -> FourCC {
+        match self {
+            BayerPattern::Rggb => SRGGB8,
+            BayerPattern::Bggr => SBGGR8,
+            BayerPattern::Gbrg => SGBRG8,
+            BayerPattern::Grbg => SGRBG8,
+        }
+    }
+
+    /// The pattern and bits per sample of a Bayer `format`, or `None` for
+    /// any other format.
+    pub fn from_fourcc(format: FourCC) -> Option<(BayerPattern, u32)> {
+        Some(match format {
+            SRGGB8 => (BayerPattern::Rggb, 8),
+            SBGGR8 => (BayerPattern::Bggr, 8),
+            SGBRG8 => (BayerPattern::Gbrg, 8),
+            SGRBG8 => (BayerPattern::Grbg, 8),
+            SRGGB10 => (BayerPattern::Rggb, 10),
+            SBGGR10 => (BayerPattern::Bggr, 10),
+            SGBRG10 => (BayerPattern::Gbrg, 10),
+            SGRBG10 => (BayerPattern::Grbg, 10),
+            _ => return None,
+        })
+    }
+
+    /// Position `(x, y)` of the red sample in each 2x2 block. Blue sits
+    /// diagonally opposite and green fills the other two.
+    pub fn red_position(self) -> (usize, usize) {
+        match self {
+            BayerPattern::Rggb => (0, 0),
+            BayerPattern::Bggr => (1, 1),
+            BayerPattern::Gbrg => (0, 1),
+            BayerPattern::Grbg => (1, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bayer_pattern_fourcc_round_trips() {
+        for pattern in [
+            BayerPattern::Rggb,
+            BayerPattern::Bggr,
+            BayerPattern::Gbrg,
+            BayerPattern::Grbg,
+        ] {
+            assert_eq!(
+                BayerPattern::from_fourcc(pattern.fourcc()),
+                Some((pattern, 8))
+            );
+        }
+        assert_eq!(
+            BayerPattern::from_fourcc(SRGGB10),
+            Some((BayerPattern::Rggb, 10))
+        );
+        assert_eq!(BayerPattern::from_fourcc(YUYV), None);
+    }
+
+    #[test]
+    fn h264_variant_parses_defaults_and_rejects_bad_input() {
+        let variant: H264Variant = "topic=rt/camera/h264_30".parse().unwrap();
+        assert_eq!(variant.fps, None);
+        assert_eq!(variant.bitrate, H264Bitrate::Auto);
+        assert_eq!(variant.size, None);
+        assert_eq!(variant.meta_topic(), "camera/h264_30/meta");
+
+        for bad in [
+            "fps=10",
+            "topic=",
+            "topic=a,fps=0",
+            "topic=a,fps=ten",
+            "topic=a,bitrate=mbps7",
+            "topic=a,size=640",
+            "topic=a,size=0x360",
+            "topic=a,gop=30",
+            "topic=a,fps",
+        ] {
+            assert!(bad.parse::<H264Variant>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn codec_fourcc_round_trips() {
+        for codec in [Codec::H264, Codec::H265] {
+            assert_eq!(Codec::from_fourcc(codec.fourcc()), Some(codec));
+        }
+        assert_eq!(Codec::H265.fourcc(), FourCC(*b"HEVC"));
+        assert_eq!(Codec::H265.format(), "h265");
+        assert_eq!(Codec::from_fourcc(YUYV), None);
+        assert_eq!(Codec::from_str("h265", true), Ok(Codec::H265));
+    }
+
+    #[test]
+    fn h264_framing_names_and_apply() {
+        assert_eq!(
+            H264Framing::from_str("annexb", true),
+            Ok(H264Framing::AnnexB)
+        );
+        assert_eq!(H264Framing::from_str("avcc", true), Ok(H264Framing::Avcc));
+
+        let au = [0, 0, 0, 1, 0x09, 0xf0, 0, 0, 1, 0x41, 0x9a];
+        let mut scratch = Vec::new();
+        assert_eq!(H264Framing::AnnexB.apply(&au, &mut scratch), au);
+        assert!(scratch.is_empty());
+        assert_eq!(
+            H264Framing::Avcc.apply(&au, &mut scratch),
+            [0, 0, 0, 2, 0x09, 0xf0, 0, 0, 0, 2, 0x41, 0x9a]
+        );
+    }
+
+    #[test]
+    fn frame_format_fourcc_and_stride() {
+        assert_eq!(FrameFormat::Rgba.fourcc(), RGBA);
+        assert_eq!(FrameFormat::Rgba.row_stride(1920), 7680);
+        assert_eq!(FrameFormat::Yuyv.fourcc(), YUYV);
+        assert_eq!(FrameFormat::Yuyv.row_stride(1920), 3840);
+        assert_eq!(FrameFormat::Nv12.fourcc(), NV12);
+        assert_eq!(FrameFormat::Nv12.row_stride(1920), 1920);
+    }
+}
@@ -0,0 +1,876 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! CPU pixel format conversion.
+//!
+//! These routines back conversions on platforms without G2D. The hottest
+//! paths (YUYV→RGBA and NV12→RGBA) have NEON inner loops that are selected
+//! at runtime on aarch64; every other target, and the tail of each row that
+//! does not fill a whole vector, runs the scalar code. Both produce
+//! bit-identical output: the NEON kernels evaluate the same fixed-point
+//! BT.601 limited-range equations as [`yuv_to_rgb`] in 32-bit lanes.
+//!
+//! The way back, RGBA to YUYV and NV12 for the encoders, and
+//! [`resize_rgba_nearest`] are scalar only. They serve the software
+//! [`ImageManager`](crate::image::ImageManager) when G2D is unavailable.
+//!
+//! Raw Bayer frames are demosaiced by [`bayer_to_rgba`], which is scalar
+//! only since G2D cannot read Bayer formats.
+//!
+//! ```
+//! use edgefirst_camera::cpu;
+//!
+//! // Two pixels of mid grey: Y0 U Y1 V.
+//! let yuyv = [126, 128, 126, 128];
+//! let mut rgba = [0u8; 8];
+//! cpu::yuyv_to_rgba(&yuyv, 4, &mut rgba, 8, 2, 1);
+//! assert_eq!(rgba, [128, 128, 128, 255, 128, 128, 128, 255]);
+//! ```
+
+use crate::config::BayerPattern;
+
+/// Convert one BT.601 limited-range YUV sample to RGB using 8-bit
+/// fixed-point coefficients.
+#[inline]
+pub fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let c = 298 * (y as i32 - 16);
+    let d = u as i32 - 128;
+    let e = v as i32 - 128;
+    [
+        clamp((c + 409 * e + 128) >> 8),
+        clamp((c - 100 * d - 208 * e + 128) >> 8),
+        clamp((c + 516 * d + 128) >> 8),
+    ]
+}
+
+#[inline]
+fn clamp(x: i32) -> u8 {
+    x.clamp(0, 255) as u8
+}
+
+/// Convert one RGB pixel to BT.601 limited-range YUV, the inverse of
+/// [`yuv_to_rgb`] up to rounding.
+#[inline]
+pub fn rgb_to_yuv(r: u8, g: u8, b: u8) -> [u8; 3] {
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    [
+        clamp(((66 * r + 129 * g + 25 * b + 128) >> 8) + 16),
+        clamp(((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128),
+        clamp(((112 * r - 94 * g - 18 * b + 128) >> 8) + 128),
+    ]
+}
+
+/// Average of the RGB of `pixels`, each four bytes of RGBA.
+#[inline]
+fn average_rgb<'a>(pixels: impl Iterator<Item = &'a [u8]>) -> [u8; 3] {
+    let mut sum = [0u32; 3];
+    let mut count = 0;
+    for px in pixels {
+        for (s, &c) in sum.iter_mut().zip(px) {
+            *s += c as u32;
+        }
+        count += 1;
+    }
+    sum.map(|s| ((s + count / 2) / count) as u8)
+}
+
+/// Convert a packed YUYV 4:2:2 frame to RGBA.
+///
+/// `src_stride` and `dst_stride` are bytes per row. Uses NEON when the CPU
+/// supports it.
+///
+/// # Panics
+///
+/// Panics if either buffer is too small for `width`x`height`.
+pub fn yuyv_to_rgba(
+    src: &[u8],
+    src_stride: usize,
+    dst: &mut [u8],
+    dst_stride: usize,
+    width: usize,
+    height: usize,
+) {
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        check_yuyv(src, src_stride, dst, dst_stride, width, height);
+        for row in 0..height {
+            let s = &src[row * src_stride..];
+            let d = &mut dst[row * dst_stride..];
+            // SAFETY: NEON support was detected above and the row bounds
+            // were checked by check_yuyv.
+            let done = unsafe { neon::yuyv_row(s, d, width) };
+            yuyv_row(&s[done * 2..], &mut d[done * 4..], width - done);
+        }
+        return;
+    }
+    yuyv_to_rgba_scalar(src, src_stride, dst, dst_stride, width, height);
+}
+
+/// Scalar reference implementation of [`yuyv_to_rgba`].
+pub fn yuyv_to_rgba_scalar(
+    src: &[u8],
+    src_stride: usize,
+    dst: &mut [u8],
+    dst_stride: usize,
+    width: usize,
+    height: usize,
+) {
+    check_yuyv(src, src_stride, dst, dst_stride, width, height);
+    for row in 0..height {
+        yuyv_row(
+            &src[row * src_stride..],
+            &mut dst[row * dst_stride..],
+            width,
+        );
+    }
+}
+
+/// Convert a two-plane NV12 frame to RGBA. The interleaved UV plane
+/// follows the Y plane at `src_stride * height` and shares its stride.
+///
+/// # Panics
+///
+/// Panics if either buffer is too small for `width`x`height`.
+pub fn nv12_to_rgba(
+    src: &[u8],
+    src_stride: usize,
+    dst: &mut [u8],
+    dst_stride: usize,
+    width: usize,
+    height: usize,
+) {
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        check_nv12(src, src_stride, dst, dst_stride, width, height);
+        let (y_plane, uv_plane) = src.split_at(src_stride * height);
+        for row in 0..height {
+            let y = &y_plane[row * src_stride..];
+            let uv = &uv_plane[row / 2 * src_stride..];
+            let d = &mut dst[row * dst_stride..];
+            // SAFETY: NEON support was detected above and the row bounds
+            // were checked by check_nv12.
+            let done = unsafe { neon::nv12_row(y, uv, d, width) };
+            nv12_row(&y[done..], &uv[done..], &mut d[done * 4..], width - done);
+        }
+        return;
+    }
+    nv12_to_rgba_scalar(src, src_stride, dst, dst_stride, width, height);
+}
+
+/// Scalar reference implementation of [`nv12_to_rgba`].
+pub fn nv12_to_rgba_scalar(
+    src: &[u8],
+    src_stride: usize,
+    dst: &mut [u8],
+    dst_stride: usize,
+    width: usize,
+    height: usize,
+) {
+    check_nv12(src, src_stride, dst, dst_stride, width, height);
+    let (y_plane, uv_plane) = src.split_at(src_stride * height);
+    for row in 0..height {
+        nv12_row(
+            &y_plane[row * src_stride..],
+            &uv_plane[row / 2 * src_stride..],
+            &mut dst[row * dst_stride..],
+            width,
+        );
+    }
+}
+
+fn check_yuyv(
+    src: &[u8],
+    src_stride: usize,
+    dst: &[u8],
+    dst_stride: usize,
+    width: usize,
+    height: usize,
+) {
+    if height == 0 {
+        return;
+    }
+    let row = width.div_ceil(2) * 4;
+    assert!(
+        src_stride >= row && src.len() >= src_stride * (height - 1) + row,
+        "YUYV source too small for {width}x{height}"
+    );
+    assert!(
+        dst_stride >= width * 4 && dst.len() >= dst_stride * (height - 1) + width * 4,
+        "RGBA destination too small for {width}x{height}"
+    );
+}
+
+fn check_nv12(
+    src: &[u8],
+    src_stride: usize,
+    dst: &[u8],
+    dst_stride: usize,
+    width: usize,
+    height: usize,
+) {
+    if height == 0 {
+        return;
+    }
+    let row = width.div_ceil(2) * 2;
+    assert!(
+        src_stride >= row
+            && src.len() >= src_stride * height + src_stride * ((height - 1) / 2) + row,
+        "NV12 source too small for {width}x{height}"
+    );
+    assert!(
+        dst_stride >= width * 4 && dst.len() >= dst_stride * (height - 1) + width * 4,
+        "RGBA destination too small for {width}x{height}"
+    );
+}
+
+/// Convert an RGBA frame to packed YUYV 4:2:2. Each pixel pair shares
+/// the chroma of its average colour; alpha is ignored.
+///
+/// # Panics
+///
+/// Panics if either buffer is too small for `width`x`height`.
+pub fn rgba_to_yuyv(
+    src: &[u8],
+    src_stride: usize,
+    dst: &mut [u8],
+    dst_stride: usize,
+    width: usize,
+    height: usize,
+) {
+    if height == 0 {
+        return;
+    }
+    let row = width.div_ceil(2) * 4;
+    assert!(
+        src_stride >= width * 4 && src.len() >= src_stride * (height - 1) + width * 4,
+        "RGBA source too small for {width}x{height}"
+    );
+    assert!(
+        dst_stride >= row && dst.len() >= dst_stride * (height - 1) + row,
+        "YUYV destination too small for {width}x{height}"
+    );
+    for y in 0..height {
+        let rgba = &src[y * src_stride..][..width * 4];
+        let yuyv = &mut dst[y * dst_stride..][..row];
+        for (pair, out) in rgba.chunks(8).zip(yuyv.chunks_exact_mut(4)) {
+            let [r, g, b] = average_rgb(pair.chunks_exact(4));
+            let [_, u, v] = rgb_to_yuv(r, g, b);
+            let luma = |px: &[u8]| rgb_to_yuv(px[0], px[1], px[2])[0];
+            let y0 = luma(&pair[..4]);
+            // An odd last pixel repeats to fill its pair.
+            let y1 = pair.get(4..8).map_or(y0, luma);
+            out.copy_from_slice(&[y0, u, y1, v]);
+        }
+    }
+}
+
+/// Convert an RGBA frame to NV12 4:2:0, writing the luma to `y` and the
+/// interleaved chroma to `uv`. Both planes are `dst_stride` bytes per row
+/// and each 2x2 block shares the chroma of its average colour; alpha is
+/// ignored. Taking the planes apart lets a region of a larger frame be
+/// written.
+///
+/// # Panics
+///
+/// Panics if any buffer is too small for `width`x`height`.
+pub fn rgba_to_nv12(
+    src: &[u8],
+    src_stride: usize,
+    y: &mut [u8],
+    uv: &mut [u8],
+    dst_stride: usize,
+    width: usize,
+    height: usize,
+) {
+    if height == 0 {
+        return;
+    }
+    let chroma_row = width.div_ceil(2) * 2;
+    let chroma_rows = height.div_ceil(2);
+    assert!(
+        src_stride >= width * 4 && src.len() >= src_stride * (height - 1) + width * 4,
+        "RGBA source too small for {width}x{height}"
+    );
+    assert!(
+        dst_stride >= chroma_row
+            && y.len() >= dst_stride * (height - 1) + width
+            && uv.len() >= dst_stride * (chroma_rows - 1) + chroma_row,
+        "NV12 destination too small for {width}x{height}"
+    );
+    for row in 0..height {
+        let rgba = &src[row * src_stride..][..width * 4];
+        let luma = &mut y[row * dst_stride..][..width];
+        for (px, out) in rgba.chunks_exact(4).zip(luma) {
+            *out = rgb_to_yuv(px[0], px[1], px[2])[0];
+        }
+    }
+    for row in 0..chroma_rows {
+        let top = &src[row * 2 * src_stride..][..width * 4];
+        let bottom = match row * 2 + 1 < height {
+            true => &src[(row * 2 + 1) * src_stride..][..width * 4],
+            false => top,
+        };
+        let chroma = &mut uv[row * dst_stride..][..chroma_row];
+        for (x, out) in chroma.chunks_exact_mut(2).enumerate() {
+            let block = top[x * 8..].chunks_exact(4).take(2);
+            let block = block.chain(bottom[x * 8..].chunks_exact(4).take(2));
+            let [r, g, b] = average_rgb(block);
+            let [_, u, v] = rgb_to_yuv(r, g, b);
+            out.copy_from_slice(&[u, v]);
+        }
+    }
+}
+
+/// Scale an RGBA frame to `dst_width`x`dst_height` with nearest-neighbour
+/// sampling: each destination pixel copies the source pixel under its
+/// centre.
+///
+/// # Panics
+///
+/// Panics if either buffer is too small for its size.
+#[allow(clippy::too_many_arguments)]
+pub fn resize_rgba_nearest(
+    src: &[u8],
+    src_stride: usize,
+    src_width: usize,
+    src_height: usize,
+    dst: &mut [u8],
+    dst_stride: usize,
+    dst_width: usize,
+    dst_height: usize,
+) {
+    if src_height == 0 || dst_height == 0 {
+        return;
+    }
+    assert!(
+        src_stride >= src_width * 4 && src.len() >= src_stride * (src_height - 1) + src_width * 4,
+        "RGBA source too small for {src_width}x{src_height}"
+    );
+    assert!(
+        dst_stride >= dst_width * 4 && dst.len() >= dst_stride * (dst_height - 1) + dst_width * 4,
+        "RGBA destination too small for {dst_width}x{dst_height}"
+    );
+    let nearest = |i: usize, from: usize, to: usize| (i * 2 + 1) * from / (to * 2);
+    let columns: Vec<usize> = (0..dst_width)
+        .map(|x| nearest(x, src_width, dst_width) * 4)
+        .collect();
+    for y in 0..dst_height {
+        let src_row = &src[nearest(y, src_height, dst_height) * src_stride..];
+        let dst_row = &mut dst[y * dst_stride..][..dst_width * 4];
+        for (out, &x) in dst_row.chunks_exact_mut(4).zip(&columns) {
+            out.copy_from_slice(&src_row[x..x + 4]);
+        }
+    }
+}
+
+/// Blend the packed RGBA `overlay` onto `base` in place, source over: each
+/// channel becomes `overlay * a + base * (1 - a)`, where `a` is the overlay
+/// pixel's alpha scaled by `global_alpha`. The overlay alpha is straight,
+/// not premultiplied, as G2D's `SRC_ALPHA` / `ONE_MINUS_SRC_ALPHA` blend
+/// takes it.
+///
+/// # Panics
+///
+/// Panics if the buffers differ in length.
+pub fn blend_rgba_over(overlay: &[u8], base: &mut [u8], global_alpha: u8) {
+    assert_eq!(
+        overlay.len(),
+        base.len(),
+        "RGBA overlay and base differ in size"
+    );
+    let scale = |v: u32, by: u32| (v * by + 127) / 255;
+    for (src, dst) in overlay.chunks_exact(4).zip(base.chunks_exact_mut(4)) {
+        let a = scale(src[3] as u32, global_alpha as u32);
+        for (d, &s) in dst.iter_mut().zip(src) {
+            *d = (scale(s as u32, a) + scale(*d as u32, 255 - a)) as u8;
+        }
+    }
+}
+
+/// Demosaic a raw Bayer frame to RGBA with bilinear interpolation.
+///
+/// Each pixel keeps its own sample and takes the missing colours from the
+/// average of its nearest neighbours of that colour. Samples are one byte
+/// when `bits` is 8 and a little-endian `u16` otherwise, scaled down to 8
+/// bits. At the frame border the neighbours are mirrored, which keeps the
+/// colour of each neighbour position unchanged. `src_stride` and
+/// `dst_stride` are bytes per row.
+///
+/// # Panics
+///
+/// Panics if either buffer is too small for `width`x`height`, if the frame
+/// is smaller than 2x2, or if `bits` is not in `8..=16`.
+#[allow(clippy::too_many_arguments)]
+pub fn bayer_to_rgba(
+    src: &[u8],
+    src_stride: usize,
+    dst: &mut [u8],
+    dst_stride: usize,
+    width: usize,
+    height: usize,
+    pattern: BayerPattern,
+    bits: u32,
+) {
+    check_bayer(src, src_stride, dst, dst_stride, width, height, bits);
+    let sample = |x: usize, y: usize| -> u32 {
+        let row = &src[y * src_stride..];
+        if bits == 8 {
+            row[x] as u32
+        } else {
+            u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]) as u32 >> (bits - 8)
+        }
+    };
+    let mirror = |i: usize, len: usize| -> (usize, usize) {
+        let before = if i == 0 { 1 } else { i - 1 };
+        let after = if i + 1 == len { len - 2 } else { i + 1 };
+        (before, after)
+    };
+    let (red_x, red_y) = pattern.red_position();
+
+    for y in 0..height {
+        let (up, down) = mirror(y, height);
+        let dst_row = &mut dst[y * dst_stride..];
+        for x in 0..width {
+            let (left, right) = mirror(x, width);
+            let own = sample(x, y);
+            let horizontal = (sample(left, y) + sample(right, y)).div_ceil(2);
+            let vertical = (sample(x, up) + sample(x, down)).div_ceil(2);
+            let cross =
+                (sample(left, y) + sample(right, y) + sample(x, up) + sample(x, down) + 2) / 4;
+            let diagonal = (sample(left, up)
+                + sample(right, up)
+                + sample(left, down)
+                + sample(right, down)
+                + 2)
+                / 4;
+            let on_red_row = y % 2 == red_y;
+            let on_red_column = x % 2 == red_x;
+            let rgb = match (on_red_row, on_red_column) {
+                (true, true) => [own, cross, diagonal],
+                (false, false) => [diagonal, cross, own],
+                // Green on a red row: red left and right, blue above and
+                // below.
+                (true, false) => [horizontal, own, vertical],
+                (false, true) => [vertical, own, horizontal],
+            };
+            let [r, g, b] = rgb.map(|c| c.min(255) as u8);
+            dst_row[x * 4..x * 4 + 4].copy_from_slice(&[r, g, b, 255]);
+        }
+    }
+}
+
+fn check_bayer(
+    src: &[u8],
+    src_stride: usize,
+    dst: &[u8],
+    dst_stride: usize,
+    width: usize,
+    height: usize,
+    bits: u32,
+) {
+    assert!((8..=16).contains(&bits), "unsupported Bayer depth {bits}");
+    assert!(
+        width >= 2 && height >= 2,
+        "Bayer frame {width}x{height} is smaller than 2x2"
+    );
+    let row = if bits == 8 { width } else { width * 2 };
+    assert!(
+        src_stride >= row && src.len() >= src_stride * (height - 1) + row,
+        "Bayer source too small for {width}x{height}"
+    );
+    assert!(
+        dst_stride >= width * 4 && dst.len() >= dst_stride * (height - 1) + width * 4,
+        "RGBA destination too small for {width}x{height}"
+    );
+}
+
+fn yuyv_row(src: &[u8], dst: &mut [u8], width: usize) {
+    for (x, (yuyv, rgba)) in src
+        .chunks_exact(4)
+        .zip(dst.chunks_mut(8))
+        .take(width.div_ceil(2))
+        .enumerate()
+    {
+        let (u, v) = (yuyv[1], yuyv[3]);
+        let [r, g, b] = yuv_to_rgb(yuyv[0], u, v);
+        rgba[..4].copy_from_slice(&[r, g, b, 255]);
+        if x * 2 + 1 < width {
+            let [r, g, b] = yuv_to_rgb(yuyv[2], u, v);
+            rgba[4..8].copy_from_slice(&[r, g, b, 255]);
+        }
+    }
+}
+
+fn nv12_row(y: &[u8], uv: &[u8], dst: &mut [u8], width: usize) {
+    for x in 0..width {
+        let (u, v) = (uv[x & !1], uv[x | 1]);
+        let [r, g, b] = yuv_to_rgb(y[x], u, v);
+        dst[x * 4..x * 4 + 4].copy_from_slice(&[r, g, b, 255]);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    /// Convert eight YUV samples with the fixed-point equations of
+    /// [`super::yuv_to_rgb`], widening to 32 bits so the result is exact.
+    #[inline]
+    #[target_feature(enable = "neon")]
+    unsafe fn yuv_to_rgb(y: uint8x8_t, u: uint8x8_t, v: uint8x8_t) -> [uint8x8_t; 3] {
+        let y = vreinterpretq_s16_u16(vsubl_u8(y, vdup_n_u8(16)));
+        let d = vreinterpretq_s16_u16(vsubl_u8(u, vdup_n_u8(128)));
+        let e = vreinterpretq_s16_u16(vsubl_u8(v, vdup_n_u8(128)));
+
+        let lo = yuv_to_rgb_half(vget_low_s16(y), vget_low_s16(d), vget_low_s16(e));
+        let hi = yuv_to_rgb_half(vget_high_s16(y), vget_high_s16(d), vget_high_s16(e));
+        [
+            vqmovn_u16(vcombine_u16(lo[0], hi[0])),
+            vqmovn_u16(vcombine_u16(lo[1], hi[1])),
+            vqmovn_u16(vcombine_u16(lo[2], hi[2])),
+        ]
+    }
+
+    /// Four lanes of [`yuv_to_rgb`] on offset samples, saturated to `0..`.
+    #[inline]
+    #[target_feature(enable = "neon")]
+    unsafe fn yuv_to_rgb_half(y: int16x4_t, d: int16x4_t, e: int16x4_t) -> [uint16x4_t; 3] {
+        let c = vaddq_s32(vmull_n_s16(y, 298), vdupq_n_s32(128));
+        let r = vmlal_n_s16(c, e, 409);
+        let g = vmlal_n_s16(vmlal_n_s16(c, d, -100), e, -208);
+        let b = vmlal_n_s16(c, d, 516);
+        [
+            vqmovun_s32(vshrq_n_s32::<8>(r)),
+            vqmovun_s32(vshrq_n_s32::<8>(g)),
+            vqmovun_s32(vshrq_n_s32::<8>(b)),
+        ]
+    }
+
+    /// Store sixteen pixels whose even and odd samples were converted
+    /// separately.
+    #[inline]
+    #[target_feature(enable = "neon")]
+    unsafe fn store_rgba(dst: *mut u8, even: [uint8x8_t; 3], odd: [uint8x8_t; 3]) {
+        let r = vzip_u8(even[0], odd[0]);
+        let g = vzip_u8(even[1], odd[1]);
+        let b = vzip_u8(even[2], odd[2]);
+        let a = vdup_n_u8(255);
+        vst4_u8(dst, uint8x8x4_t(r.0, g.0, b.0, a));
+        vst4_u8(dst.add(32), uint8x8x4_t(r.1, g.1, b.1, a));
+    }
+
+    /// Convert as many whole 16-pixel blocks of a YUYV row as fit in
+    /// `width`, returning the number of pixels written.
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn yuyv_row(src: &[u8], dst: &mut [u8], width: usize) -> usize {
+        let blocks = width / 16;
+        for i in 0..blocks {
+            // Deinterleave 16 pixels into Y0, U, Y1, V lanes.
+            let px = vld4_u8(src.as_ptr().add(i * 32));
+            let even = yuv_to_rgb(px.0, px.1, px.3);
+            let odd = yuv_to_rgb(px.2, px.1, px.3);
+            store_rgba(dst.as_mut_ptr().add(i * 64), even, odd);
+        }
+        blocks * 16
+    }
+
+    /// Convert as many whole 16-pixel blocks of an NV12 row as fit in
+    /// `width`, returning the number of pixels written.
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn nv12_row(y: &[u8], uv: &[u8], dst: &mut [u8], width: usize) -> usize {
+        let blocks = width / 16;
+        for i in 0..blocks {
+            let luma = vld2_u8(y.as_ptr().add(i * 16));
+            let chroma = vld2_u8(uv.as_ptr().add(i * 16));
+            let even = yuv_to_rgb(luma.0, chroma.0, chroma.1);
+            let odd = yuv_to_rgb(luma.1, chroma.0, chroma.1);
+            store_rgba(dst.as_mut_ptr().add(i * 64), even, odd);
+        }
+        blocks * 16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random bytes covering the full 0..=255 range.
+    fn pattern(len: usize, seed: u32) -> Vec<u8> {
+        let mut x = seed;
+        (0..len)
+            .map(|_| {
+                x = x.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (x >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn yuv_to_rgb_reference_colors() {
+        assert_eq!(yuv_to_rgb(16, 128, 128), [0, 0, 0]);
+        assert_eq!(yuv_to_rgb(235, 128, 128), [255, 255, 255]);
+        assert_eq!(yuv_to_rgb(81, 90, 240), [255, 0, 0]);
+        assert_eq!(yuv_to_rgb(145, 54, 34), [0, 255, 1]);
+        assert_eq!(yuv_to_rgb(41, 240, 110), [0, 0, 255]);
+        // Out-of-range inputs saturate rather than wrap.
+        assert_eq!(yuv_to_rgb(0, 0, 0), [0, 135, 0]);
+        assert_eq!(yuv_to_rgb(255, 255, 255), [255, 125, 255]);
+    }
+
+    #[test]
+    fn yuyv_shares_chroma_across_pixel_pair() {
+        let src = [81, 90, 41, 240];
+        let mut dst = [0u8; 8];
+        yuyv_to_rgba_scalar(&src, 4, &mut dst, 8, 2, 1);
+        assert_eq!(&dst[0..3], &yuv_to_rgb(81, 90, 240));
+        assert_eq!(&dst[4..7], &yuv_to_rgb(41, 90, 240));
+        assert_eq!((dst[3], dst[7]), (255, 255));
+    }
+
+    #[test]
+    fn nv12_uses_chroma_of_2x2_block() {
+        // 2x2 image: four luma samples, one UV pair.
+        let src = [16, 235, 81, 145, 128, 128];
+        let mut dst = [0u8; 16];
+        nv12_to_rgba_scalar(&src, 2, &mut dst, 8, 2, 2);
+        assert_eq!(&dst[0..4], &[0, 0, 0, 255]);
+        assert_eq!(&dst[4..8], &[255, 255, 255, 255]);
+        assert_eq!(&dst[8..11], &yuv_to_rgb(81, 128, 128));
+        assert_eq!(&dst[12..15], &yuv_to_rgb(145, 128, 128));
+    }
+
+    #[test]
+    fn padded_strides_leave_padding_untouched() {
+        let (width, height) = (6, 3);
+        let src = pattern(16 * height, 7);
+        let mut dst = vec![0xaa; 32 * height];
+        yuyv_to_rgba(&src, 16, &mut dst, 32, width, height);
+        for row in dst.chunks(32) {
+            assert!(row[width * 4..].iter().all(|&b| b == 0xaa));
+        }
+    }
+
+    #[test]
+    fn dispatch_matches_scalar_yuyv() {
+        // Widths exercise whole NEON blocks, partial tails, and odd sizes.
+        for (width, height) in [(16usize, 2usize), (33, 3), (64, 4), (130, 5), (1, 1)] {
+            let stride = width.div_ceil(2) * 4 + 8;
+            let src = pattern(stride * height, width as u32);
+            let mut fast = vec![0u8; width * 4 * height];
+            let mut slow = vec![0u8; width * 4 * height];
+            yuyv_to_rgba(&src, stride, &mut fast, width * 4, width, height);
+            yuyv_to_rgba_scalar(&src, stride, &mut slow, width * 4, width, height);
+            assert_eq!(fast, slow, "YUYV {width}x{height}");
+        }
+    }
+
+    #[test]
+    fn dispatch_matches_scalar_nv12() {
+        for (width, height) in [(16usize, 2usize), (34, 3), (64, 4), (130, 5), (2, 1)] {
+            let stride = width + 6;
+            let src = pattern(stride * (height + height.div_ceil(2)), width as u32);
+            let mut fast = vec![0u8; width * 4 * height];
+            let mut slow = vec![0u8; width * 4 * height];
+            nv12_to_rgba(&src, stride, &mut fast, width * 4, width, height);
+            nv12_to_rgba_scalar(&src, stride, &mut slow, width * 4, width, height);
+            assert_eq!(fast, slow, "NV12 {width}x{height}");
+        }
+    }
+
+    /// Sample `rgb` through a `pattern` colour filter, as the sensor would.
+    fn mosaic(rgb: [u8; 3], pattern: BayerPattern, width: usize, height: usize) -> Vec<u8> {
+        let (red_x, red_y) = pattern.red_position();
+        let mut raw = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                raw.push(match (y % 2 == red_y, x % 2 == red_x) {
+                    (true, true) => rgb[0],
+                    (false, false) => rgb[2],
+                    _ => rgb[1],
+                });
+            }
+        }
+        raw
+    }
+
+    #[test]
+    fn bayer_reconstructs_flat_colour() {
+        let (width, height) = (6, 4);
+        let rgb = [200, 100, 50];
+        for pattern in [
+            BayerPattern::Rggb,
+            BayerPattern::Bggr,
+            BayerPattern::Gbrg,
+            BayerPattern::Grbg,
+        ] {
+            let raw = mosaic(rgb, pattern, width, height);
+            let mut dst = vec![0u8; width * 4 * height];
+            bayer_to_rgba(&raw, width, &mut dst, width * 4, width, height, pattern, 8);
+            for pixel in dst.chunks_exact(4) {
+                assert_eq!(pixel, [200, 100, 50, 255], "{pattern:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn bayer_interpolates_missing_colours() {
+        // RGGB with red rising to the right and blue rising downwards.
+        #[rustfmt::skip]
+        let raw = [
+            10, 80, 30, 80,
+            80, 100, 80, 100,
+            10, 80, 30, 80,
+            80, 140, 80, 140,
+        ];
+        let mut dst = [0u8; 64];
+        bayer_to_rgba(&raw, 4, &mut dst, 16, 4, 4, BayerPattern::Rggb, 8);
+        let pixel = |x: usize, y: usize| &dst[(y * 4 + x) * 4..(y * 4 + x) * 4 + 4];
+        // Red site: own red, green from the cross, blue from the diagonals.
+        assert_eq!(pixel(2, 2), [30, 80, 120, 255]);
+        // Green on a red row: red from left and right, blue from above
+        // and below.
+        assert_eq!(pixel(1, 2), [20, 80, 120, 255]);
+        // Green on a blue row: blue from left and right, red from above
+        // and below.
+        assert_eq!(pixel(2, 1), [30, 80, 100, 255]);
+        // Blue site: red from the diagonals.
+        assert_eq!(pixel(1, 1), [20, 80, 100, 255]);
+        // The border mirrors its neighbours.
+        assert_eq!(pixel(0, 0), [10, 80, 100, 255]);
+    }
+
+    #[test]
+    fn bayer_scales_10_bit_samples() {
+        let (width, height) = (4, 2);
+        let raw: Vec<u8> = mosaic([255, 128, 4], BayerPattern::Rggb, width, height)
+            .into_iter()
+            .flat_map(|v| (v as u16 * 4).to_le_bytes())
+            .collect();
+        let mut dst = vec![0u8; width * 4 * height];
+        bayer_to_rgba(
+            &raw,
+            width * 2,
+            &mut dst,
+            width * 4,
+            width,
+            height,
+            BayerPattern::Rggb,
+            10,
+        );
+        for pixel in dst.chunks_exact(4) {
+            assert_eq!(pixel, [255, 128, 4, 255]);
+        }
+    }
+
+    #[test]
+    fn rgb_to_yuv_round_trips_reference_colors() {
+        assert_eq!(rgb_to_yuv(0, 0, 0), [16, 128, 128]);
+        assert_eq!(rgb_to_yuv(255, 255, 255), [235, 128, 128]);
+        assert_eq!(rgb_to_yuv(255, 0, 0), [82, 90, 240]);
+        for rgb in [[255, 0, 0], [0, 255, 0], [0, 0, 255], [90, 160, 30]] {
+            let [y, u, v] = rgb_to_yuv(rgb[0], rgb[1], rgb[2]);
+            let back = yuv_to_rgb(y, u, v);
+            for (a, b) in back.iter().zip(rgb) {
+                assert!(a.abs_diff(b) <= 3, "{rgb:?} came back as {back:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn rgba_to_yuyv_averages_chroma_of_pairs() {
+        // Red and blue share one chroma sample; the odd third pixel fills
+        // its pair alone.
+        let src = [255, 0, 0, 255, 0, 0, 255, 255, 0, 0, 0, 255];
+        let mut dst = [0xaa; 12];
+        rgba_to_yuyv(&src, 12, &mut dst, 8, 3, 1);
+        let [_, u, v] = rgb_to_yuv(128, 0, 128);
+        assert_eq!(dst[..4], [82, u, 41, v]);
+        assert_eq!(dst[4..8], [16, 128, 16, 128]);
+        assert!(dst[8..].iter().all(|&b| b == 0xaa));
+    }
+
+    #[test]
+    fn rgba_to_nv12_round_trips_flat_colour() {
+        let (width, height) = (6, 4);
+        let src: Vec<u8> = [90, 160, 30, 255].repeat(width * height);
+        let mut nv12 = vec![0; width * height * 3 / 2];
+        let (y, uv) = nv12.split_at_mut(width * height);
+        rgba_to_nv12(&src, width * 4, y, uv, width, width, height);
+        assert!(y.iter().all(|&b| b == y[0]));
+        let mut back = vec![0; width * height * 4];
+        nv12_to_rgba_scalar(&nv12, width, &mut back, width * 4, width, height);
+        assert_eq!(back[..3], yuv_to_rgb(nv12[0], nv12[24], nv12[25]));
+        for px in back.chunks_exact(4) {
+            assert!(
+                px.iter().zip(&src).all(|(a, b)| a.abs_diff(*b) <= 3),
+                "{px:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rgba_to_nv12_averages_chroma_of_blocks() {
+        // Left block red over blue, right block all white; odd height
+        // repeats the last row.
+        #[rustfmt::skip]
+        let src = [
+            255, 0, 0, 255,  255, 0, 0, 255,  255, 255, 255, 255,  255, 255, 255, 255,
+            0, 0, 255, 255,  0, 0, 255, 255,  255, 255, 255, 255,  255, 255, 255, 255,
+            0, 0, 0, 255,    0, 0, 0, 255,    0, 0, 0, 255,        0, 0, 0, 255,
+        ];
+        let mut y = [0; 4 * 3];
+        let mut uv = [0; 4 * 2];
+        rgba_to_nv12(&src, 16, &mut y, &mut uv, 4, 4, 3);
+        assert_eq!(y, [82, 82, 235, 235, 41, 41, 235, 235, 16, 16, 16, 16]);
+        let [_, u, v] = rgb_to_yuv(128, 0, 128);
+        assert_eq!(uv, [u, v, 128, 128, 128, 128, 128, 128]);
+    }
+
+    #[test]
+    fn resize_nearest_samples_pixel_centres() {
+        // A 4x2 frame of distinct pixels.
+        let src: Vec<u8> = (0..8u8).flat_map(|i| [i, i, i, 255]).collect();
+        let mut half = [0; 2 * 4];
+        resize_rgba_nearest(&src, 16, 4, 2, &mut half, 8, 2, 1);
+        assert_eq!(half, [5, 5, 5, 255, 7, 7, 7, 255]);
+
+        let mut double = vec![0; 8 * 4 * 4];
+        resize_rgba_nearest(&src, 16, 4, 2, &mut double, 32, 8, 4);
+        let grey: Vec<u8> = double.chunks_exact(4).map(|px| px[0]).collect();
+        assert_eq!(grey[..8], [0, 0, 1, 1, 2, 2, 3, 3]);
+        assert_eq!(grey[8..16], grey[..8]);
+        assert_eq!(grey[16..24], [4, 4, 5, 5, 6, 6, 7, 7]);
+
+        let mut same = vec![0; src.len()];
+        resize_rgba_nearest(&src, 16, 4, 2, &mut same, 16, 4, 2);
+        assert_eq!(same, src);
+    }
+
+    #[test]
+    fn blend_over_weights_by_pixel_and_global_alpha() {
+        let overlay = [
+            200, 100, 0, 255, // opaque
+            200, 100, 0, 0, // transparent
+            255, 255, 255, 128, // half
+        ];
+        let mut base = [10, 20, 30, 255, 10, 20, 30, 255, 0, 0, 0, 255];
+        let mut opaque = base;
+        blend_rgba_over(&overlay, &mut opaque, 255);
+        assert_eq!(opaque[..8], [200, 100, 0, 255, 10, 20, 30, 255]);
+        assert_eq!(opaque[8..11], [128, 128, 128]);
+
+        // Half the global alpha halves every pixel's weight.
+        blend_rgba_over(&overlay, &mut base, 128);
+        assert_eq!(base[..3], [105, 60, 15]);
+        assert_eq!(base[4..7], [10, 20, 30]);
+        assert_eq!(base[8..11], [64, 64, 64]);
+    }
+
+    #[test]
+    #[should_panic(expected = "too small")]
+    fn undersized_destination_panics() {
+        let src = [0u8; 8];
+        let mut dst = [0u8; 8];
+        yuyv_to_rgba(&src, 8, &mut dst, 16, 4, 1);
+    }
+}
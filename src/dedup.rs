@@ -0,0 +1,201 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Detection of repeated camera frames.
+//!
+//! Some sensors keep delivering the last good frame when they hit an error.
+//! Live sensor data always carries some noise, so a frame whose luma is
+//! bit-identical to the previous one is a repeat rather than a static scene.
+//! [`DuplicateDetector`] fingerprints the luma plane of each frame and
+//! compares it to the previous frame's.
+//!
+//! ```
+//! use edgefirst_camera::{
+//!     config::DedupMode,
+//!     dedup::{DedupAction, DuplicateDetector},
+//!     image::NV12,
+//! };
+//!
+//! let frame = vec![16u8; 64 * 48 * 3 / 2];
+//! let mut detector = DuplicateDetector::new(DedupMode::Skip);
+//! assert_eq!(detector.check(&frame, NV12, 64, 48), DedupAction::Publish);
+//! assert_eq!(detector.check(&frame, NV12, 64, 48), DedupAction::Skip);
+//! ```
+
+use std::error::Error;
+use videostream::fourcc::FourCC;
+
+use crate::{
+    config::DedupMode,
+    image::{Image, NV12, YUYV},
+};
+
+/// Only every `ROW_STEP`th luma row is hashed, which keeps the cost per
+/// 1080p frame well under a millisecond while any sensor noise still shows.
+const ROW_STEP: usize = 4;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a hash of every [`ROW_STEP`]th row of the luma plane, read in place
+/// without copying it out. Returns `None` for formats without a luma plane
+/// or when `pix` is too short for the frame.
+pub fn luma_fingerprint(pix: &[u8], format: FourCC, width: u32, height: u32) -> Option<u64> {
+    let (width, height) = (width as usize, height as usize);
+    // Row stride of the luma samples and the distance between two of them.
+    let (stride, step) = match format {
+        NV12 => (width, 1),
+        YUYV => (width * 2, 2),
+        _ => return None,
+    };
+    let luma = pix.get(..stride * height)?;
+    if stride == 0 {
+        return Some(FNV_OFFSET);
+    }
+
+    let mut hash = FNV_OFFSET;
+    for row in luma.chunks_exact(stride).step_by(ROW_STEP) {
+        for &y in row.iter().step_by(step) {
+            hash = (hash ^ y as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+    Some(hash)
+}
+
+/// What to do with a frame after comparing it to the previous one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DedupAction {
+    /// A new frame, or detection is off.
+    Publish,
+    /// A repeat of the previous frame that should still be published.
+    Flag,
+    /// A repeat of the previous frame that should be dropped.
+    Skip,
+}
+
+/// Compares each frame's [`luma_fingerprint`] with the previous frame's.
+#[derive(Debug)]
+pub struct DuplicateDetector {
+    mode: DedupMode,
+    last: Option<u64>,
+    repeats: u64,
+}
+
+impl DuplicateDetector {
+    pub fn new(mode: DedupMode) -> Self {
+        Self {
+            mode,
+            last: None,
+            repeats: 0,
+        }
+    }
+
+    /// Check the next frame's pixels. Frames in formats without a luma plane
+    /// are always published and reset the comparison.
+    pub fn check(&mut self, pix: &[u8], format: FourCC, width: u32, height: u32) -> DedupAction {
+        if self.mode == DedupMode::Off {
+            return DedupAction::Publish;
+        }
+
+        let fingerprint = luma_fingerprint(pix, format, width, height);
+        let repeat = fingerprint.is_some() && fingerprint == self.last;
+        self.last = fingerprint;
+        if !repeat {
+            self.repeats = 0;
+            return DedupAction::Publish;
+        }
+
+        self.repeats += 1;
+        match self.mode {
+            DedupMode::Off => DedupAction::Publish,
+            DedupMode::Flag => DedupAction::Flag,
+            DedupMode::Skip => DedupAction::Skip,
+        }
+    }
+
+    /// [`DuplicateDetector::check`] on a DMA image, mapping it for the read.
+    pub fn check_image(&mut self, image: &Image) -> Result<DedupAction, Box<dyn Error>> {
+        if self.mode == DedupMode::Off {
+            return Ok(DedupAction::Publish);
+        }
+        let mut image = image.try_clone()?;
+        let mapped = image.mmap()?;
+        Ok(self.check(
+            mapped.as_slice(),
+            image.format(),
+            image.width(),
+            image.height(),
+        ))
+    }
+
+    /// Consecutive repeats seen so far, 0 after a new frame.
+    pub fn repeats(&self) -> u64 {
+        self.repeats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::RGBA;
+
+    fn nv12_frame(seed: u8) -> Vec<u8> {
+        (0..16 * 8 * 3 / 2)
+            .map(|i| (i as u8).wrapping_add(seed))
+            .collect()
+    }
+
+    #[test]
+    fn second_identical_frame_is_flagged() {
+        let frame = nv12_frame(0);
+        let mut detector = DuplicateDetector::new(DedupMode::Flag);
+        assert_eq!(detector.check(&frame, NV12, 16, 8), DedupAction::Publish);
+        assert_eq!(detector.check(&frame, NV12, 16, 8), DedupAction::Flag);
+        assert_eq!(detector.repeats(), 1);
+
+        // A new frame resets the run.
+        assert_eq!(
+            detector.check(&nv12_frame(1), NV12, 16, 8),
+            DedupAction::Publish
+        );
+        assert_eq!(detector.repeats(), 0);
+    }
+
+    #[test]
+    fn second_identical_frame_is_skipped() {
+        let frame = nv12_frame(0);
+        let mut detector = DuplicateDetector::new(DedupMode::Skip);
+        assert_eq!(detector.check(&frame, NV12, 16, 8), DedupAction::Publish);
+        assert_eq!(detector.check(&frame, NV12, 16, 8), DedupAction::Skip);
+        assert_eq!(detector.check(&frame, NV12, 16, 8), DedupAction::Skip);
+        assert_eq!(detector.repeats(), 2);
+    }
+
+    #[test]
+    fn off_publishes_everything() {
+        let frame = nv12_frame(0);
+        let mut detector = DuplicateDetector::new(DedupMode::Off);
+        assert_eq!(detector.check(&frame, NV12, 16, 8), DedupAction::Publish);
+        assert_eq!(detector.check(&frame, NV12, 16, 8), DedupAction::Publish);
+    }
+
+    #[test]
+    fn fingerprint_ignores_chroma() {
+        // YUYV 2x1: Y0 U Y1 V. Only the Y bytes count.
+        let a = luma_fingerprint(&[10, 128, 20, 128], YUYV, 2, 1);
+        let b = luma_fingerprint(&[10, 90, 20, 200], YUYV, 2, 1);
+        let c = luma_fingerprint(&[11, 128, 20, 128], YUYV, 2, 1);
+        assert!(a.is_some());
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn frames_without_luma_are_never_repeats() {
+        let frame = [0u8; 16];
+        assert_eq!(luma_fingerprint(&frame, RGBA, 2, 2), None);
+        let mut detector = DuplicateDetector::new(DedupMode::Skip);
+        assert_eq!(detector.check(&frame, RGBA, 2, 2), DedupAction::Publish);
+        assert_eq!(detector.check(&frame, RGBA, 2, 2), DedupAction::Publish);
+    }
+}
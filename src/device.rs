@@ -0,0 +1,384 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Resolve `--camera` selectors to a V4L2 device node.
+//!
+//! `/dev/videoN` numbers depend on probe order, so they change across
+//! reboots and hotplug. Besides a device path, `--camera` takes a selector
+//! that is resolved once at startup:
+//!
+//! - `by-id:<name>`: the udev link `/dev/v4l/by-id/<name>`
+//! - `name:<card>`: the capture device whose `VIDIOC_QUERYCAP` card name is
+//!   `<card>`, as `v4l2-ctl --list-devices` shows it
+//! - `bus:<bus_info>`: the capture device on bus `<bus_info>`, such as
+//!   `usb-xhci-hcd.1.auto-1`
+//! - `serial:<serial>`: the capture device whose USB device reports
+//!   `<serial>`
+//!
+//! The selectors other than `by-id:` match only devices that capture video,
+//! since UVC cameras also expose a metadata node with the same names.
+
+use std::{
+    error::Error,
+    fmt, fs,
+    fs::OpenOptions,
+    io,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// `V4L2_CAP_VIDEO_CAPTURE`
+const V4L2_CAP_VIDEO_CAPTURE: u32 = 0x1;
+
+/// `V4L2_CAP_VIDEO_CAPTURE_MPLANE`
+const V4L2_CAP_VIDEO_CAPTURE_MPLANE: u32 = 0x1000;
+
+/// `V4L2_CAP_DEVICE_CAPS`
+const V4L2_CAP_DEVICE_CAPS: u32 = 0x8000_0000;
+
+/// `VIDIOC_QUERYCAP`, i.e. `_IOR('V', 0, struct v4l2_capability)`.
+const VIDIOC_QUERYCAP: u64 = 0x8068_5600;
+
+/// Directory of the udev `by-id` links.
+const BY_ID_DIR: &str = "/dev/v4l/by-id";
+
+/// Mirror of the kernel's `struct v4l2_capability`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct V4l2Capability {
+    driver: [u8; 16],
+    card: [u8; 32],
+    bus_info: [u8; 32],
+    version: u32,
+    capabilities: u32,
+    device_caps: u32,
+    reserved: [u32; 3],
+}
+
+/// What `--camera` names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CameraSelector {
+    Path(PathBuf),
+    ById(String),
+    Name(String),
+    BusInfo(String),
+    Serial(String),
+}
+
+impl FromStr for CameraSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((kind, value)) = s.split_once(':') else {
+            return Ok(Self::Path(PathBuf::from(s)));
+        };
+        let selector = match kind {
+            "by-id" => Self::ById(value.to_string()),
+            "name" => Self::Name(value.to_string()),
+            "bus" => Self::BusInfo(value.to_string()),
+            "serial" => Self::Serial(value.to_string()),
+            _ => return Ok(Self::Path(PathBuf::from(s))),
+        };
+        if value.is_empty() {
+            return Err(format!(
+                "camera selector {s:?} is missing a value after '{kind}:'"
+            ));
+        }
+        Ok(selector)
+    }
+}
+
+impl fmt::Display for CameraSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::ById(name) => write!(f, "by-id:{name}"),
+            Self::Name(card) => write!(f, "name:{card}"),
+            Self::BusInfo(bus) => write!(f, "bus:{bus}"),
+            Self::Serial(serial) => write!(f, "serial:{serial}"),
+        }
+    }
+}
+
+/// A V4L2 device node and the identity it reports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub path: PathBuf,
+    pub card: String,
+    pub bus_info: String,
+    /// Serial number of the USB device behind the node, if any.
+    pub serial: Option<String>,
+    /// Whether the node captures video, as opposed to metadata or output.
+    pub capture: bool,
+}
+
+impl CameraSelector {
+    /// Whether `device` is the one selected. Paths and `by-id:` links are
+    /// resolved without enumerating devices and match nothing.
+    fn matches(&self, device: &DeviceInfo) -> bool {
+        device.capture
+            && match self {
+                Self::Path(_) | Self::ById(_) => false,
+                Self::Name(card) => device.card == *card,
+                Self::BusInfo(bus) => device.bus_info == *bus,
+                Self::Serial(serial) => device.serial.as_deref() == Some(serial.as_str()),
+            }
+    }
+
+    /// Pick the one device of `devices` this selector matches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error listing the capture devices when none or more than
+    /// one match.
+    pub fn select<'a>(&self, devices: &'a [DeviceInfo]) -> Result<&'a DeviceInfo, String> {
+        let matching: Vec<&DeviceInfo> = devices.iter().filter(|d| self.matches(d)).collect();
+        let list = |devices: &mut dyn Iterator<Item = &DeviceInfo>| {
+            devices
+                .map(|d| {
+                    let serial = d.serial.as_deref().unwrap_or("none");
+                    format!(
+                        "{} (name: {}, bus: {}, serial: {serial})",
+                        d.path.display(),
+                        d.card,
+                        d.bus_info
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match matching[..] {
+            [device] => Ok(device),
+            [] => {
+                let mut capture = devices.iter().filter(|d| d.capture).peekable();
+                if capture.peek().is_none() {
+                    return Err(format!("no V4L2 capture device matches --camera {self}"));
+                }
+                Err(format!(
+                    "no V4L2 capture device matches --camera {self}; found {}",
+                    list(&mut capture)
+                ))
+            }
+            _ => Err(format!(
+                "--camera {self} matches several capture devices, use a more specific \
+                 selector such as serial: to pick one of {}",
+                list(&mut matching.iter().copied())
+            )),
+        }
+    }
+}
+
+/// Resolve `--camera` to the device node to open, returning plain paths
+/// unchanged.
+///
+/// # Errors
+///
+/// Returns an error if the selector is malformed, the `by-id` link does not
+/// exist, or no single capture device matches.
+pub fn resolve(camera: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let selector: CameraSelector = camera.parse()?;
+    match &selector {
+        CameraSelector::Path(path) => Ok(path.clone()),
+        CameraSelector::ById(name) => {
+            let link = Path::new(BY_ID_DIR).join(name);
+            fs::canonicalize(&link)
+                .map_err(|e| format!("--camera {selector}: {}: {e}", link.display()).into())
+        }
+        _ => Ok(selector.select(&enumerate()?)?.path.clone()),
+    }
+}
+
+/// Every `/dev/videoN` node in numeric order with the identity it reports.
+/// Nodes that cannot be queried, for example while another process holds
+/// them exclusively, are skipped.
+pub fn enumerate() -> io::Result<Vec<DeviceInfo>> {
+    let mut nodes: Vec<(u32, PathBuf)> = fs::read_dir("/dev")?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            let index = name.to_str()?.strip_prefix("video")?.parse().ok()?;
+            Some((index, entry.path()))
+        })
+        .collect();
+    nodes.sort();
+    Ok(nodes
+        .into_iter()
+        .filter_map(|(_, path)| query(&path).ok())
+        .collect())
+}
+
+/// Query the identity of the device node at `path`.
+pub fn query(path: &Path) -> io::Result<DeviceInfo> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut cap = V4l2Capability::default();
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), VIDIOC_QUERYCAP as _, &mut cap) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let caps = if cap.capabilities & V4L2_CAP_DEVICE_CAPS != 0 {
+        cap.device_caps
+    } else {
+        cap.capabilities
+    };
+    Ok(DeviceInfo {
+        path: path.to_path_buf(),
+        card: c_string(&cap.card),
+        bus_info: c_string(&cap.bus_info),
+        serial: path
+            .file_name()
+            .and_then(|name| usb_serial(Path::new(name))),
+        capture: caps & (V4L2_CAP_VIDEO_CAPTURE | V4L2_CAP_VIDEO_CAPTURE_MPLANE) != 0,
+    })
+}
+
+/// The text of a NUL-padded kernel string field.
+fn c_string(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).trim().to_string()
+}
+
+/// Serial number of the USB device the node `name` (such as `video0`)
+/// belongs to, if it is a USB device that reports one.
+fn usb_serial(name: &Path) -> Option<String> {
+    let device = Path::new("/sys/class/video4linux")
+        .join(name)
+        .join("device");
+    sysfs_serial(&fs::canonicalize(device).ok()?)
+}
+
+/// The `serial` attribute of the USB device `dir` is or sits under. The
+/// USB device is the closest ancestor with an `idVendor` attribute, so a
+/// camera without a serial does not pick up the one of its hub.
+fn sysfs_serial(dir: &Path) -> Option<String> {
+    let usb = dir.ancestors().find(|dir| dir.join("idVendor").is_file())?;
+    let serial = fs::read_to_string(usb.join("serial")).ok()?;
+    Some(serial.trim().to_string()).filter(|serial| !serial.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(
+        path: &str,
+        card: &str,
+        bus: &str,
+        serial: Option<&str>,
+        capture: bool,
+    ) -> DeviceInfo {
+        DeviceInfo {
+            path: PathBuf::from(path),
+            card: card.to_string(),
+            bus_info: bus.to_string(),
+            serial: serial.map(str::to_string),
+            capture,
+        }
+    }
+
+    #[test]
+    fn v4l2_capability_matches_kernel_layout() {
+        assert_eq!(std::mem::size_of::<V4l2Capability>(), 104);
+    }
+
+    #[test]
+    fn parses_selectors() {
+        let parse = |s: &str| s.parse::<CameraSelector>();
+        assert_eq!(
+            parse("/dev/video3"),
+            Ok(CameraSelector::Path("/dev/video3".into()))
+        );
+        assert_eq!(
+            parse("by-id:usb-Logitech_C920_ABC-video-index0"),
+            Ok(CameraSelector::ById(
+                "usb-Logitech_C920_ABC-video-index0".into()
+            ))
+        );
+        assert_eq!(
+            parse("name:HD Pro Webcam C920"),
+            Ok(CameraSelector::Name("HD Pro Webcam C920".into()))
+        );
+        assert_eq!(
+            parse("bus:usb-xhci-hcd.1.auto-1"),
+            Ok(CameraSelector::BusInfo("usb-xhci-hcd.1.auto-1".into()))
+        );
+        assert_eq!(
+            parse("serial:ABC123"),
+            Ok(CameraSelector::Serial("ABC123".into()))
+        );
+        // Paths may contain colons of their own.
+        assert_eq!(
+            parse("/dev/v4l/by-path/platform-usb:1.0-video-index0"),
+            Ok(CameraSelector::Path(
+                "/dev/v4l/by-path/platform-usb:1.0-video-index0".into()
+            ))
+        );
+        let err = parse("serial:").unwrap_err();
+        assert!(err.contains("serial:"), "{err}");
+        // Selectors print back as given.
+        for s in ["name:HD Pro Webcam C920", "bus:platform:isp", "/dev/video0"] {
+            assert_eq!(parse(s).unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn selects_the_one_matching_capture_device() {
+        let devices = [
+            device("/dev/video0", "C920", "usb-1", Some("A1"), true),
+            // The UVC metadata node of the same camera.
+            device("/dev/video1", "C920", "usb-1", Some("A1"), false),
+            device("/dev/video2", "C920", "usb-2", Some("B2"), true),
+            device("/dev/video3", "VIV", "platform:viv0", None, true),
+        ];
+        let select = |s: &str| {
+            s.parse::<CameraSelector>()
+                .unwrap()
+                .select(&devices)
+                .map(|d| d.path.clone())
+        };
+        assert_eq!(select("serial:B2"), Ok("/dev/video2".into()));
+        assert_eq!(select("bus:usb-1"), Ok("/dev/video0".into()));
+        assert_eq!(select("name:VIV"), Ok("/dev/video3".into()));
+
+        let err = select("name:C920").unwrap_err();
+        assert!(
+            err.contains("several") && err.contains("/dev/video2"),
+            "{err}"
+        );
+        let err = select("serial:Z9").unwrap_err();
+        assert!(err.contains("/dev/video3 (name: VIV"), "{err}");
+        assert!(!err.contains("/dev/video1"), "{err}");
+        assert!(CameraSelector::Name("C920".into()).select(&[]).is_err());
+    }
+
+    #[test]
+    fn reads_the_serial_of_the_closest_usb_device() {
+        let root = std::env::temp_dir().join(format!("camera-serial-{}", std::process::id()));
+        let interface = root.join("usb1/1-1/1-1:1.0");
+        fs::create_dir_all(&interface).unwrap();
+        assert_eq!(sysfs_serial(&interface), None);
+        fs::write(root.join("usb1/idVendor"), "1d6b\n").unwrap();
+        fs::write(root.join("usb1/serial"), "xhci-hcd.1.auto\n").unwrap();
+        // A camera without a serial of its own does not report its hub's.
+        fs::write(root.join("usb1/1-1/idVendor"), "046d\n").unwrap();
+        assert_eq!(sysfs_serial(&interface), None);
+        fs::write(root.join("usb1/1-1/serial"), "ABC123\n").unwrap();
+        assert_eq!(sysfs_serial(&interface).as_deref(), Some("ABC123"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn plain_paths_resolve_unchanged() {
+        assert_eq!(resolve("/dev/video3").unwrap(), Path::new("/dev/video3"));
+        assert!(resolve("by-id:no-such-camera").is_err());
+    }
+
+    #[test]
+    #[ignore = "requires a V4L2 capture device at /dev/video3"]
+    fn resolves_the_camera_by_name() {
+        let info = query(Path::new("/dev/video3")).unwrap();
+        println!("{info:?}");
+        let name = format!("name:{}", info.card);
+        assert_eq!(resolve(&name).unwrap(), Path::new("/dev/video3"));
+    }
+}
@@ -0,0 +1,297 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Dirty rectangles for `--jpeg-dirty-rect`.
+//!
+//! A camera pointed at a mostly static scene, such as a door, spends most of
+//! each JPEG re-encoding pixels that did not change. [`DirtyTracker`] keeps
+//! the luma of the image its consumers have composited so far and reports
+//! the smallest [`BLOCK`]-aligned rectangle that differs from it by more
+//! than a threshold, so only that region is encoded. Comparing against the
+//! composited image rather than the previous frame keeps slow changes, each
+//! below the threshold, from drifting away unnoticed.
+//!
+//! ```
+//! use edgefirst_camera::dirty::{Dirty, DirtyTracker};
+//!
+//! let mut tracker = DirtyTracker::new(16);
+//! let mut frame = vec![0u8; 64 * 64 * 4];
+//! assert_eq!(tracker.update_rgba(&frame, 64, 64, false), Dirty::Full);
+//! assert_eq!(tracker.update_rgba(&frame, 64, 64, false), Dirty::Unchanged);
+//!
+//! // One white pixel at (40, 10) dirties the 16x16 block around it.
+//! frame[(10 * 64 + 40) * 4..][..3].fill(255);
+//! let Dirty::Region(rect) = tracker.update_rgba(&frame, 64, 64, false) else {
+//!     panic!("expected a region");
+//! };
+//! assert_eq!((rect.x, rect.y, rect.width, rect.height), (32, 0, 16, 16));
+//! ```
+
+use crate::image::Rect;
+
+/// Dirty rectangles are grown to multiples of this many pixels: the
+/// 16x16 MCU of a 4:2:0 JPEG, so a region never splits one.
+pub const BLOCK: u32 = 16;
+
+/// BT.601 luma of each pixel of a tightly packed RGBA image, into `luma`.
+pub fn rgba_luma(pix: &[u8], luma: &mut Vec<u8>) {
+    luma.clear();
+    luma.extend(pix.chunks_exact(4).map(|px| {
+        let y = 77 * px[0] as u32 + 150 * px[1] as u32 + 29 * px[2] as u32;
+        (y >> 8) as u8
+    }));
+}
+
+/// The bounding box of the pixels whose luma differs between `prev` and
+/// `cur`, two `width`x`height` planes, by more than `threshold`, or `None`
+/// when no pixel does.
+pub fn luma_dirty_rect(
+    prev: &[u8],
+    cur: &[u8],
+    width: u32,
+    height: u32,
+    threshold: u8,
+) -> Option<Rect> {
+    let width = width as usize;
+    let len = width * height as usize;
+    if width == 0 || prev.len() < len || cur.len() < len {
+        return None;
+    }
+    // Left, top, right and bottom, exclusive.
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for (y, (prev, cur)) in prev[..len]
+        .chunks_exact(width)
+        .zip(cur[..len].chunks_exact(width))
+        .enumerate()
+    {
+        let changed = |(a, b): (&u8, &u8)| a.abs_diff(*b) > threshold;
+        let Some(left) = prev.iter().zip(cur).position(changed) else {
+            continue;
+        };
+        let right = width - prev.iter().zip(cur).rev().position(changed).unwrap_or(0);
+        bounds = Some(match bounds {
+            None => (left, y, right, y + 1),
+            Some((l, t, r, _)) => (l.min(left), t, r.max(right), y + 1),
+        });
+    }
+    let (left, top, right, bottom) = bounds?;
+    Some(Rect {
+        x: left as i32,
+        y: top as i32,
+        width: (right - left) as i32,
+        height: (bottom - top) as i32,
+    })
+}
+
+/// Grow `rect` outwards to the [`BLOCK`] grid, clamped to a `width`x`height`
+/// image. Edges of the image need not be on the grid.
+pub fn align_to_blocks(rect: Rect, width: u32, height: u32) -> Rect {
+    let block = BLOCK as i32;
+    let left = rect.x / block * block;
+    let top = rect.y / block * block;
+    let right = ((rect.x + rect.width + block - 1) / block * block).min(width as i32);
+    let bottom = ((rect.y + rect.height + block - 1) / block * block).min(height as i32);
+    Rect {
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+    }
+}
+
+/// What a frame must send for consumers to show it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dirty {
+    /// The whole frame: the first one, after a size change, or on request.
+    Full,
+    /// Only this region changed.
+    Region(Rect),
+    /// Nothing changed by more than the threshold.
+    Unchanged,
+}
+
+/// Tracks the image consumers have composited and what each new frame
+/// changes in it.
+#[derive(Debug)]
+pub struct DirtyTracker {
+    threshold: u8,
+    width: u32,
+    height: u32,
+    /// Luma of the composited image, empty before the first frame.
+    reference: Vec<u8>,
+    /// Luma of the frame being compared, kept to reuse its allocation.
+    current: Vec<u8>,
+}
+
+impl DirtyTracker {
+    /// A tracker ignoring luma changes of `threshold` or less, which should
+    /// be above the sensor noise.
+    pub fn new(threshold: u8) -> Self {
+        Self {
+            threshold,
+            width: 0,
+            height: 0,
+            reference: Vec::new(),
+            current: Vec::new(),
+        }
+    }
+
+    /// Compare a tightly packed `width`x`height` RGBA frame with the
+    /// composited image and update it with what the frame sends. `full`
+    /// asks for the whole frame, for consumers that joined since the last
+    /// full frame.
+    pub fn update_rgba(&mut self, pix: &[u8], width: u32, height: u32, full: bool) -> Dirty {
+        rgba_luma(pix, &mut self.current);
+        if full
+            || (width, height) != (self.width, self.height)
+            || self.reference.len() != self.current.len()
+        {
+            self.width = width;
+            self.height = height;
+            std::mem::swap(&mut self.reference, &mut self.current);
+            return Dirty::Full;
+        }
+
+        let Some(rect) = luma_dirty_rect(
+            &self.reference,
+            &self.current,
+            width,
+            height,
+            self.threshold,
+        ) else {
+            return Dirty::Unchanged;
+        };
+        let rect = align_to_blocks(rect, width, height);
+        let (x, w) = (rect.x as usize, rect.width as usize);
+        for y in rect.y as usize..(rect.y + rect.height) as usize {
+            let row = y * width as usize + x;
+            self.reference[row..row + w].copy_from_slice(&self.current[row..row + w]);
+        }
+        Dirty::Region(rect)
+    }
+}
+
+/// Copy `rect` out of a tightly packed `width`-pixel RGBA image, for
+/// encoding on its own.
+pub fn crop_rgba(pix: &[u8], width: u32, rect: Rect) -> Vec<u8> {
+    let stride = width as usize * 4;
+    let (x, w) = (rect.x as usize * 4, rect.width as usize * 4);
+    pix.chunks_exact(stride)
+        .skip(rect.y as usize)
+        .take(rect.height as usize)
+        .flat_map(|row| &row[x..x + w])
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: u32 = 128;
+    const H: u32 = 96;
+
+    /// A mid-grey luma plane with sensor-like noise of up to +-3 and a
+    /// bright `size`x`size` square at (`x`, `y`).
+    fn scene(x: u32, y: u32, size: u32, seed: u32) -> Vec<u8> {
+        (0..H)
+            .flat_map(|row| (0..W).map(move |col| (row, col)))
+            .map(|(row, col)| {
+                if (x..x + size).contains(&col) && (y..y + size).contains(&row) {
+                    220
+                } else {
+                    let noise = (row * 31 + col * 17 + seed * 13) % 7;
+                    (125 + noise) as u8
+                }
+            })
+            .collect()
+    }
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn moving_object_is_tightly_bounded() {
+        // A 10x10 object moves 6 pixels right and 3 down between frames.
+        let before = scene(30, 20, 10, 0);
+        let after = scene(36, 23, 10, 1);
+        let dirty = luma_dirty_rect(&before, &after, W, H, 16).unwrap();
+        // Exactly the union of where the object was and where it is now;
+        // the noise stays under the threshold.
+        assert_eq!(dirty, rect(30, 20, 16, 13));
+
+        // A static scene, noise and all, has nothing to send.
+        assert_eq!(
+            luma_dirty_rect(&before, &scene(30, 20, 10, 5), W, H, 16),
+            None
+        );
+    }
+
+    #[test]
+    fn regions_grow_to_whole_blocks() {
+        assert_eq!(
+            align_to_blocks(rect(30, 20, 16, 13), W, H),
+            rect(16, 16, 32, 32)
+        );
+        // Clamped to an image that is not a multiple of the block.
+        assert_eq!(
+            align_to_blocks(rect(95, 40, 5, 10), 100, 50),
+            rect(80, 32, 20, 18)
+        );
+    }
+
+    #[test]
+    fn tracker_sends_changes_against_the_composited_image() {
+        let rgba = |luma: &[u8]| -> Vec<u8> { luma.iter().flat_map(|&y| [y, y, y, 255]).collect() };
+        let mut tracker = DirtyTracker::new(16);
+        assert_eq!(
+            tracker.update_rgba(&rgba(&scene(30, 20, 10, 0)), W, H, false),
+            Dirty::Full
+        );
+        assert_eq!(
+            tracker.update_rgba(&rgba(&scene(36, 23, 10, 1)), W, H, false),
+            Dirty::Region(rect(16, 16, 32, 32))
+        );
+        assert_eq!(
+            tracker.update_rgba(&rgba(&scene(36, 23, 10, 2)), W, H, false),
+            Dirty::Unchanged
+        );
+
+        // A drift of 10 per frame is under the threshold each time, but is
+        // sent once it adds up.
+        let mut drifted = rgba(&scene(36, 23, 10, 0));
+        let mut sent = None;
+        for _ in 0..3 {
+            drifted
+                .chunks_exact_mut(4)
+                .take(W as usize)
+                .for_each(|px| px[..3].iter_mut().for_each(|c| *c += 10));
+            sent = Some(tracker.update_rgba(&drifted, W, H, false));
+            if sent != Some(Dirty::Unchanged) {
+                break;
+            }
+        }
+        assert_eq!(sent, Some(Dirty::Region(rect(0, 0, W as i32, 16))));
+
+        // A new size or an explicit request sends everything.
+        assert_eq!(tracker.update_rgba(&drifted, W, H, true), Dirty::Full);
+        assert_eq!(
+            tracker.update_rgba(&drifted[..(W * 48 * 4) as usize], W, 48, false),
+            Dirty::Full
+        );
+    }
+
+    #[test]
+    fn crops_rows_of_the_region() {
+        // 4x3 RGBA with each pixel's bytes holding its index.
+        let pix: Vec<u8> = (0..12u8).flat_map(|i| [i; 4]).collect();
+        let crop = crop_rgba(&pix, 4, rect(1, 1, 2, 2));
+        assert_eq!(crop, [[5; 4], [6; 4], [9; 4], [10; 4]].concat());
+    }
+}
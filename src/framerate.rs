@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Query the frame interval a V4L2 capture device negotiated.
+//!
+//! The node used to assume 30 fps everywhere. Reading the interval back
+//! with `VIDIOC_G_PARM` lets the encoder, the low-fps warning, and the
+//! recording sidecar follow 15 or 60 fps sensors. The query runs on a
+//! second handle to the device, which V4L2 allows alongside the streaming
+//! one.
+
+use std::{fmt, fs::OpenOptions, io, os::fd::AsRawFd, path::Path};
+
+/// `V4L2_BUF_TYPE_VIDEO_CAPTURE`
+const V4L2_BUF_TYPE_VIDEO_CAPTURE: u32 = 1;
+
+/// `V4L2_CAP_TIMEPERFRAME`
+const V4L2_CAP_TIMEPERFRAME: u32 = 0x1000;
+
+/// `VIDIOC_G_PARM`, i.e. `_IOWR('V', 21, struct v4l2_streamparm)`.
+const VIDIOC_G_PARM: u64 = 0xc0cc_5615;
+
+/// Mirror of the kernel's `struct v4l2_captureparm`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct V4l2CaptureParm {
+    capability: u32,
+    capturemode: u32,
+    timeperframe_numerator: u32,
+    timeperframe_denominator: u32,
+    extendedmode: u32,
+    readbuffers: u32,
+    reserved: [u32; 4],
+}
+
+/// The `parm` union of `struct v4l2_streamparm`. Only `capture` is used;
+/// `raw` pads the union to the kernel's 200 bytes.
+#[repr(C)]
+union V4l2StreamParmUnion {
+    capture: V4l2CaptureParm,
+    raw: [u8; 200],
+}
+
+/// Mirror of the kernel's `struct v4l2_streamparm`.
+#[repr(C)]
+struct V4l2StreamParm {
+    type_: u32,
+    parm: V4l2StreamParmUnion,
+}
+
+/// Time per frame in seconds, as the fraction `numerator / denominator`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameInterval {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl FrameInterval {
+    /// Frames per second, or `None` for a zero fraction, which drivers
+    /// report when the interval is unknown.
+    pub fn fps(&self) -> Option<f64> {
+        if self.numerator == 0 || self.denominator == 0 {
+            return None;
+        }
+        Some(self.denominator as f64 / self.numerator as f64)
+    }
+}
+
+impl fmt::Display for FrameInterval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}s", self.numerator, self.denominator)
+    }
+}
+
+/// Read the negotiated capture frame interval of `device`. Returns
+/// `Ok(None)` when the driver does not report one.
+pub fn query(device: &Path) -> io::Result<Option<FrameInterval>> {
+    let file = OpenOptions::new().read(true).write(true).open(device)?;
+
+    let mut parm = V4l2StreamParm {
+        type_: V4L2_BUF_TYPE_VIDEO_CAPTURE,
+        parm: V4l2StreamParmUnion { raw: [0; 200] },
+    };
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), VIDIOC_G_PARM as _, &mut parm) };
+    if ret < 0 {
+        let err = io::Error::last_os_error();
+        // ENOTTY/EINVAL: the driver does not implement G_PARM at all.
+        return match err.raw_os_error() {
+            Some(libc::ENOTTY) | Some(libc::EINVAL) => Ok(None),
+            _ => Err(err),
+        };
+    }
+
+    let capture = unsafe { parm.parm.capture };
+    if capture.capability & V4L2_CAP_TIMEPERFRAME == 0 {
+        return Ok(None);
+    }
+    Ok(Some(FrameInterval {
+        numerator: capture.timeperframe_numerator,
+        denominator: capture.timeperframe_denominator,
+    }))
+}
+
+/// Whole frames per second to run the node at: the camera's interval when
+/// it reports a usable one, otherwise `fallback` (`--target-fps`).
+pub fn derive_fps(interval: Option<FrameInterval>, fallback: u32) -> u32 {
+    match interval.and_then(|i| i.fps()) {
+        Some(fps) if fps >= 0.5 => fps.round() as u32,
+        _ => fallback,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(numerator: u32, denominator: u32) -> Option<FrameInterval> {
+        Some(FrameInterval {
+            numerator,
+            denominator,
+        })
+    }
+
+    #[test]
+    fn v4l2_streamparm_matches_kernel_layout() {
+        assert_eq!(std::mem::size_of::<V4l2CaptureParm>(), 40);
+        assert_eq!(std::mem::size_of::<V4l2StreamParm>(), 204);
+    }
+
+    #[test]
+    fn derive_fps_from_common_intervals() {
+        assert_eq!(derive_fps(interval(1, 30), 30), 30);
+        assert_eq!(derive_fps(interval(1, 15), 30), 15);
+        assert_eq!(derive_fps(interval(1, 60), 30), 60);
+        // NTSC-style 30000/1001 rounds to 30.
+        assert_eq!(derive_fps(interval(1001, 30000), 25), 30);
+        assert_eq!(derive_fps(interval(2, 15), 30), 8);
+    }
+
+    #[test]
+    fn derive_fps_falls_back_when_unreported() {
+        assert_eq!(derive_fps(None, 30), 30);
+        assert_eq!(derive_fps(interval(0, 0), 25), 25);
+        assert_eq!(derive_fps(interval(1, 0), 25), 25);
+        assert_eq!(derive_fps(interval(0, 30), 25), 25);
+        // Slower than one frame every two seconds is not a usable rate.
+        assert_eq!(derive_fps(interval(10, 1), 30), 30);
+    }
+
+    #[test]
+    #[ignore = "requires a V4L2 capture device at /dev/video3"]
+    fn query_camera_frame_interval() {
+        let interval = query(Path::new("/dev/video3")).unwrap();
+        println!("frame interval: {:?}", interval);
+        if let Some(interval) = interval {
+            assert!(interval.fps().is_some());
+        }
+    }
+}
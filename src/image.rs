@@ -5,29 +5,44 @@ use core::fmt;
 use dma_buf::DmaBuf;
 use dma_heap::{Heap, HeapKind};
 use g2d_sys::{
-    g2d_buf, g2d_format, g2d_format_G2D_NV12, g2d_format_G2D_RGB888, g2d_format_G2D_RGBA8888,
-    g2d_format_G2D_RGBX8888, g2d_format_G2D_YUYV, g2d_rotation_G2D_ROTATION_0,
-    g2d_rotation_G2D_ROTATION_180, g2d_rotation_G2D_ROTATION_270, g2d_rotation_G2D_ROTATION_90,
-    G2DPhysical, G2DSurface, G2D,
+    g2d_blend_func_G2D_ONE_MINUS_SRC_ALPHA, g2d_blend_func_G2D_SRC_ALPHA, g2d_buf, g2d_cap_mode,
+    g2d_cap_mode_G2D_BLEND, g2d_cap_mode_G2D_GLOBAL_ALPHA, g2d_format, g2d_format_G2D_I420,
+    g2d_format_G2D_NV12, g2d_format_G2D_RGB888, g2d_format_G2D_RGBA8888, g2d_format_G2D_RGBX8888,
+    g2d_format_G2D_YUYV, g2d_rotation_G2D_FLIP_H, g2d_rotation_G2D_FLIP_V,
+    g2d_rotation_G2D_ROTATION_0, g2d_rotation_G2D_ROTATION_180, g2d_rotation_G2D_ROTATION_270,
+    g2d_rotation_G2D_ROTATION_90, G2DPhysical, G2DSurface, Version, G2D,
 };
+use libc::{dup, mmap, munmap, MAP_FAILED, MAP_SHARED, PROT_READ, PROT_WRITE};
 use std::{
+    borrow::Cow,
+    collections::HashSet,
     error::Error,
     ffi::c_void,
     io,
+    mem::ManuallyDrop,
     os::{
         fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd},
         unix::io::OwnedFd,
     },
+    path::{Path, PathBuf},
     ptr::null_mut,
     slice::{from_raw_parts, from_raw_parts_mut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, Once, OnceLock, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
 };
-use tracing::{debug, warn};
-use turbojpeg::{
-    libc::{dup, mmap, munmap, MAP_SHARED, PROT_READ, PROT_WRITE},
-    OwnedBuf,
-};
+use tracing::{debug, trace, warn};
 use videostream::{camera::CameraBuffer, encoder::VSLRect, fourcc::FourCC, frame::Frame};
 
+use crate::{
+    config::{BayerPattern, JpegSubsamp},
+    cpu,
+    jpeg::{self, JpegBuf},
+};
+
 /// RGB 24-bit pixel format (8 bits per channel, no alpha)
 pub const RGB3: FourCC = FourCC(*b"RGB3");
 
@@ -37,16 +52,154 @@ pub const RGBX: FourCC = FourCC(*b"RGBX");
 /// RGBA 32-bit pixel format (8 bits per channel, with alpha)
 pub const RGBA: FourCC = FourCC(*b"RGBA");
 
+/// BGRA 32-bit pixel format (8 bits per channel, with alpha, blue first)
+pub const BGRA: FourCC = FourCC(*b"BGRA");
+
 /// YUYV 4:2:2 YUV packed format (common camera output format)
 pub const YUYV: FourCC = FourCC(*b"YUYV");
 
 /// NV12 4:2:0 YUV semi-planar format (efficient for video encoding)
 pub const NV12: FourCC = FourCC(*b"NV12");
 
+/// I420 4:2:0 YUV planar format: the Y plane, then the U and V planes at
+/// half resolution (`V4L2_PIX_FMT_YUV420`)
+pub const I420: FourCC = FourCC(*b"YU12");
+
+/// 8-bit greyscale, one byte per pixel (`V4L2_PIX_FMT_GREY`)
+pub const GREY: FourCC = FourCC(*b"GREY");
+
+/// 8-bit Bayer RGGB raw sensor format (`V4L2_PIX_FMT_SRGGB8`)
+pub const SRGGB8: FourCC = FourCC(*b"RGGB");
+
+/// 8-bit Bayer BGGR raw sensor format (`V4L2_PIX_FMT_SBGGR8`)
+pub const SBGGR8: FourCC = FourCC(*b"BA81");
+
+/// 8-bit Bayer GBRG raw sensor format (`V4L2_PIX_FMT_SGBRG8`)
+pub const SGBRG8: FourCC = FourCC(*b"GBRG");
+
+/// 8-bit Bayer GRBG raw sensor format (`V4L2_PIX_FMT_SGRBG8`)
+pub const SGRBG8: FourCC = FourCC(*b"GRBG");
+
+/// 10-bit Bayer RGGB, one little-endian `u16` per sample
+/// (`V4L2_PIX_FMT_SRGGB10`)
+pub const SRGGB10: FourCC = FourCC(*b"RG10");
+
+/// 10-bit Bayer BGGR, one little-endian `u16` per sample
+/// (`V4L2_PIX_FMT_SBGGR10`)
+pub const SBGGR10: FourCC = FourCC(*b"BG10");
+
+/// 10-bit Bayer GBRG, one little-endian `u16` per sample
+/// (`V4L2_PIX_FMT_SGBRG10`)
+pub const SGBRG10: FourCC = FourCC(*b"GB10");
+
+/// 10-bit Bayer GRBG, one little-endian `u16` per sample
+/// (`V4L2_PIX_FMT_SGRBG10`)
+pub const SGRBG10: FourCC = FourCC(*b"BA10");
+
+/// 16-bit greyscale, one little-endian `u16` per pixel, as depth cameras
+/// deliver (`V4L2_PIX_FMT_Y16`)
+pub const Y16: FourCC = FourCC(*b"Y16 ");
+
+/// Device node of the CMA heap [`Image::new`] allocates from.
+pub const CMA_HEAP_PATH: &str = "/dev/dma_heap/linux,cma";
+
+/// Errors with a known cause that callers may want to tell apart.
+#[derive(Debug)]
+pub enum CameraError {
+    /// The CMA DMA heap device node does not exist, usually because the
+    /// kernel was built without DMA heap support.
+    DmaHeapUnavailable { path: PathBuf },
+    /// An image was described with a zero dimension, or one too large for
+    /// G2D's signed sizes (a negative value cast to `u32`).
+    InvalidDimensions { width: u32, height: u32 },
+    /// An image was described in a pixel format whose layout [`Image`]
+    /// does not know.
+    UnsupportedFormat { format: FourCC },
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DmaHeapUnavailable { path } => write!(
+                f,
+                "DMA heap {} not found: the kernel needs CONFIG_DMABUF_HEAPS=y and \
+                 CONFIG_DMABUF_HEAPS_CMA=y with a CMA area reserved",
+                path.display()
+            ),
+            Self::InvalidDimensions { width, height } => write!(
+                f,
+                "invalid image dimensions {width}x{height}: width and height must be \
+                 between 1 and {}",
+                i32::MAX
+            ),
+            Self::UnsupportedFormat { format } => write!(
+                f,
+                "unsupported pixel format {format}: expected one of RGB3, RGBX, RGBA, \
+                 BGRA, YUYV, NV12, YU12, GREY, Y16 or 8/10-bit Bayer"
+            ),
+        }
+    }
+}
+
+impl Error for CameraError {}
+
+/// Checks that a `width`x`height` image can be allocated and handed to G2D,
+/// returning [`CameraError::InvalidDimensions`] if not.
+pub fn check_dimensions(width: u32, height: u32) -> Result<(), CameraError> {
+    let valid = 1..=i32::MAX as u32;
+    if valid.contains(&width) && valid.contains(&height) {
+        Ok(())
+    } else {
+        Err(CameraError::InvalidDimensions { width, height })
+    }
+}
+
+/// Checks that [`Image`] knows the layout of `format`, returning
+/// [`CameraError::UnsupportedFormat`] if not.
+pub fn check_format(format: FourCC) -> Result<(), CameraError> {
+    match plane_stride(format, 1) {
+        Some(_) => Ok(()),
+        None => Err(CameraError::UnsupportedFormat { format }),
+    }
+}
+
+/// Explain a failure to open the heap at `path` when the device node is
+/// missing; any other failure is returned unchanged.
+fn heap_open_error(path: &Path, err: Box<dyn Error>) -> Box<dyn Error> {
+    if path.exists() {
+        return err;
+    }
+    Box::new(CameraError::DmaHeapUnavailable {
+        path: path.to_path_buf(),
+    })
+}
+
+/// G2D library 2.3.0, which the i.MX BSP reports as driver version 6.4.11.
+///
+/// Releases at or above this version use the extended surface layout; older
+/// releases take the legacy path.
+pub const G2D_2_3_0: Version = Version {
+    major: 6,
+    minor: 4,
+    patch: 11,
+    num: 1049711,
+};
+
+/// Returns `true` when `version` is at or above `min`.
+///
+/// Compares `major`, then `minor`, then `patch`. The packed `num` build
+/// identifier is deliberately ignored: it does not track the semantic
+/// version, so including it (as a derived field-order comparison would) can
+/// place a library on the wrong side of a feature gate.
+pub fn g2d_version_at_least(version: &Version, min: &Version) -> bool {
+    (version.major, version.minor, version.patch) >= (min.major, min.minor, min.patch)
+}
+
 /// Rectangle specification for crop operations.
 ///
 /// Defines a rectangular region within an image for cropping,
 /// tiling, or region-of-interest operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Rect {
     /// X coordinate of top-left corner
     pub x: i32,
@@ -74,9 +227,10 @@ impl From<VSLRect> for Rect {
 /// The G2D hardware accelerator supports 90-degree rotations
 /// for efficient image transformation without CPU intervention.
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub enum Rotation {
     /// No rotation (0 degrees)
+    #[default]
     Rotation0 = g2d_rotation_G2D_ROTATION_0 as isize,
     /// Rotate 90 degrees clockwise
     Rotation90 = g2d_rotation_G2D_ROTATION_90 as isize,
@@ -85,6 +239,187 @@ pub enum Rotation {
     /// Rotate 270 degrees clockwise (90 degrees counter-clockwise)
     Rotation270 = g2d_rotation_G2D_ROTATION_270 as isize,
 }
+
+impl Rotation {
+    /// Returns `true` for the quarter turns that swap width and height.
+    pub fn is_transposed(self) -> bool {
+        matches!(self, Rotation::Rotation90 | Rotation::Rotation270)
+    }
+
+    /// Size of a `width`x`height` image after this rotation is applied.
+    pub fn rotated_size(self, width: u32, height: u32) -> (u32, u32) {
+        if self.is_transposed() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// This rotation followed by a half turn.
+    fn half_turned(self) -> Self {
+        match self {
+            Rotation::Rotation0 => Rotation::Rotation180,
+            Rotation::Rotation90 => Rotation::Rotation270,
+            Rotation::Rotation180 => Rotation::Rotation0,
+            Rotation::Rotation270 => Rotation::Rotation90,
+        }
+    }
+}
+
+/// Mirroring applied by [`ImageManager::convert_with`] after the rotation,
+/// so [`Flip::Horizontal`] swaps the left and right of the image as it
+/// comes out. It is independent of the camera's own mirror setting, so one
+/// capture can feed a mirrored and an unmirrored output.
+///
+/// G2D applies any flip without a rotation, and any flip with a half
+/// turn, in one blit. A [`Flip::Horizontal`] or [`Flip::Vertical`] with a
+/// quarter turn also flips the source surface, which G2D honours only from
+/// [`G2D_2_3_0`]; older releases reject it (see
+/// [`ImageManager::can_flip`]). The software manager handles every
+/// combination.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Flip {
+    /// No mirroring
+    #[default]
+    None,
+    /// Mirror left to right
+    Horizontal,
+    /// Mirror top to bottom
+    Vertical,
+    /// Mirror both ways, the same as a further half turn
+    Both,
+}
+
+/// The G2D `rot` values of the source and destination surfaces that apply
+/// `rotation` and then `flip`. The destination takes a rotation or a
+/// flip, not both; a flip after a quarter turn is the other flip before
+/// it, so that one goes on the source.
+fn g2d_orientation(rotation: Rotation, flip: Flip) -> (u32, u32) {
+    match (rotation, flip) {
+        (r, Flip::None) => (g2d_rotation_G2D_ROTATION_0, r as u32),
+        (r, Flip::Both) => (g2d_rotation_G2D_ROTATION_0, r.half_turned() as u32),
+        (Rotation::Rotation0, Flip::Horizontal) | (Rotation::Rotation180, Flip::Vertical) => {
+            (g2d_rotation_G2D_ROTATION_0, g2d_rotation_G2D_FLIP_H)
+        }
+        (Rotation::Rotation0, Flip::Vertical) | (Rotation::Rotation180, Flip::Horizontal) => {
+            (g2d_rotation_G2D_ROTATION_0, g2d_rotation_G2D_FLIP_V)
+        }
+        (r, Flip::Horizontal) => (g2d_rotation_G2D_FLIP_V, r as u32),
+        (r, Flip::Vertical) => (g2d_rotation_G2D_FLIP_H, r as u32),
+    }
+}
+
+/// Placement of the second source in [`ImageManager::composite`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompositeOptions {
+    /// Region of the destination the second source is scaled into. Position
+    /// and size must be even and the region must fit in the destination.
+    pub inset: Rect,
+}
+
+impl CompositeOptions {
+    /// An inset `1/scale` of the `width`x`height` destination in its
+    /// bottom-right corner, `margin` pixels in from both edges. The size and
+    /// position are rounded down to even values.
+    pub fn bottom_right(width: u32, height: u32, scale: u32, margin: u32) -> Self {
+        let even = |v: u32| (v & !1) as i32;
+        let (w, h) = (even(width / scale.max(1)), even(height / scale.max(1)));
+        Self {
+            inset: Rect {
+                x: even(width.saturating_sub(margin)) - w,
+                y: even(height.saturating_sub(margin)) - h,
+                width: w,
+                height: h,
+            },
+        }
+    }
+
+    /// Checks that the inset is a non-empty, even region inside a
+    /// `width`x`height` destination.
+    pub fn check(&self, width: u32, height: u32) -> Result<(), Box<dyn Error>> {
+        check_region("composite inset", self.inset, width, height)
+    }
+}
+
+/// Settings for [`ImageManager::convert_with`]. The default converts the
+/// whole source into the whole destination without rotation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConvertOptions {
+    /// Region of the source to read, the whole source when `None`.
+    pub crop: Option<Rect>,
+    pub rotation: Rotation,
+    /// Mirroring applied after `rotation`.
+    pub flip: Flip,
+    /// Region of the destination the source is scaled into, the whole
+    /// destination when `None`. Pixels outside it keep their contents, so
+    /// a source can be placed anywhere for letterboxing or compositing.
+    /// Position and size must be even and the region must fit in the
+    /// destination.
+    pub dst_rect: Option<Rect>,
+}
+
+impl ConvertOptions {
+    /// Checks that `dst_rect`, if set, is a non-empty, even region inside a
+    /// `width`x`height` destination.
+    pub fn check(&self, width: u32, height: u32) -> Result<(), Box<dyn Error>> {
+        match self.dst_rect {
+            Some(r) => check_region("destination rectangle", r, width, height),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Checks that `r` is a non-empty region with even position and size inside
+/// a `width`x`height` image, naming it `what` in the error.
+fn check_region(what: &str, r: Rect, width: u32, height: u32) -> Result<(), Box<dyn Error>> {
+    if r.width <= 0 || r.height <= 0 || r.x < 0 || r.y < 0 {
+        return Err(format!("{what} {r:?} is empty or off the frame").into());
+    }
+    if (r.x | r.y | r.width | r.height) % 2 != 0 {
+        return Err(format!("{what} {r:?} must have even position and size").into());
+    }
+    if r.x as i64 + r.width as i64 > width as i64 || r.y as i64 + r.height as i64 > height as i64 {
+        return Err(format!("{what} {r:?} does not fit in {width}x{height}").into());
+    }
+    Ok(())
+}
+
+/// Horizontal and vertical chroma subsampling of `format`, `None` when
+/// every pixel has its own chroma.
+fn chroma_alignment(format: FourCC) -> Option<(i32, i32)> {
+    match format {
+        YUYV => Some((2, 1)),
+        NV12 => Some((2, 2)),
+        _ => None,
+    }
+}
+
+/// `crop` snapped to whole chroma samples of `format`. A YUYV or NV12
+/// crop that starts or ends between two pixels sharing a chroma sample
+/// reads that sample for the wrong pixels and shifts the colours, so both
+/// edges are rounded down to the chroma alignment (even columns, and even
+/// rows for NV12), keeping at least one sample. Other formats, and crops
+/// that are already aligned, are returned unchanged.
+pub fn align_crop(crop: Rect, format: FourCC) -> Rect {
+    let Some((ax, ay)) = chroma_alignment(format) else {
+        return crop;
+    };
+    let snap = |start: i32, len: i32, align: i32| {
+        let aligned = start - start.rem_euclid(align);
+        let end = start + len;
+        let end = (end - end.rem_euclid(align)).max(aligned + align);
+        (aligned, end - aligned)
+    };
+    let (x, width) = snap(crop.x, crop.width, ax);
+    let (y, height) = snap(crop.y, crop.height, ay);
+    Rect {
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
 pub struct G2DBuffer<'a> {
     buf: *mut g2d_buf,
     imgmgr: &'a ImageManager,
@@ -138,7 +473,100 @@ impl Drop for G2DBuffer<'_> {
     }
 }
 
+/// The process's G2D handle and the buffers allocated on it, shared by
+/// every [`ImageManager`] while any is alive.
+struct G2DContext {
+    /// Held from an operation's first blit through `g2d_finish`, so the
+    /// operations of different threads never interleave on the handle.
+    g2d: Mutex<G2D>,
+    /// Addresses of the `g2d_buf`s handed out by [`ImageManager::alloc`]
+    /// and not yet freed. Anything left at drop is freed before the G2D
+    /// handle is closed.
+    allocations: Mutex<HashSet<usize>>,
+}
+
+/// Where an [`ImageManager`] runs its operations.
+enum Backend {
+    /// On the G2D engine, through the process's shared handle.
+    Hardware(Arc<G2DContext>),
+    /// On the CPU, for platforms without G2D; see [`ImageManager::software`].
+    Software,
+}
+
+// SAFETY: a libg2d handle is not tied to the thread that opened it, and
+// every call on this one is made with `g2d` locked.
+unsafe impl Send for G2DContext {}
+unsafe impl Sync for G2DContext {}
+
+/// The open [`G2DContext`], if any manager still holds it.
+static G2D_CONTEXT: Mutex<Weak<G2DContext>> = Mutex::new(Weak::new());
+
+impl G2DContext {
+    /// The process's G2D context, opening the device if no manager has it
+    /// open.
+    fn get() -> Result<Arc<Self>, Box<dyn Error>> {
+        let mut shared = G2D_CONTEXT.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(context) = shared.upgrade() {
+            return Ok(context);
+        }
+        let g2d = G2D::new("libg2d.so.2")?;
+        if !g2d_version_at_least(&g2d.version(), &G2D_2_3_0) {
+            debug!(
+                "G2D {} predates {}, using the legacy surface layout",
+                g2d.version(),
+                G2D_2_3_0
+            );
+        }
+        let context = Arc::new(Self {
+            g2d: Mutex::new(g2d),
+            allocations: Mutex::new(HashSet::new()),
+        });
+        *shared = Arc::downgrade(&context);
+        Ok(context)
+    }
+
+    /// Lock the handle. A thread that panicked holding it left no G2D
+    /// state half-changed that the next operation relies on.
+    fn lock(&self) -> MutexGuard<'_, G2D> {
+        self.g2d.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for G2DContext {
+    fn drop(&mut self) {
+        // Buffers normally cannot outlive their manager, but a leaked one
+        // (`mem::forget`, `Box::leak`) would otherwise be freed, if ever,
+        // against a closed G2D handle. Free them while it is still open.
+        let allocations = match self.allocations.get_mut() {
+            Ok(allocations) => allocations,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if allocations.is_empty() {
+            return;
+        }
+        warn!(
+            "ImageManager dropped with {} outstanding G2D buffer(s), freeing them",
+            allocations.len()
+        );
+        let g2d = match self.g2d.get_mut() {
+            Ok(g2d) => g2d,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        for buf in allocations.drain() {
+            unsafe {
+                g2d.lib.g2d_free(buf as *mut g2d_buf);
+            }
+        }
+    }
+}
+
 /// Map a V4L2/videostream FourCC to the corresponding G2D format constant.
+/// Whether G2D converts `format`, the formats every converting or encoding
+/// output can take from the camera.
+pub fn g2d_supports(format: FourCC) -> bool {
+    fourcc_to_g2d_format(format).is_ok()
+}
+
 fn fourcc_to_g2d_format(fourcc: FourCC) -> Result<g2d_format, io::Error> {
     match fourcc {
         RGB3 => Ok(g2d_format_G2D_RGB888),
@@ -146,6 +574,7 @@ fn fourcc_to_g2d_format(fourcc: FourCC) -> Result<g2d_format, io::Error> {
         RGBA => Ok(g2d_format_G2D_RGBA8888),
         YUYV => Ok(g2d_format_G2D_YUYV),
         NV12 => Ok(g2d_format_G2D_NV12),
+        I420 => Ok(g2d_format_G2D_I420),
         _ => Err(io::Error::new(
             io::ErrorKind::Unsupported,
             format!("unsupported G2D pixel format: {fourcc}"),
@@ -153,27 +582,84 @@ fn fourcc_to_g2d_format(fourcc: FourCC) -> Result<g2d_format, io::Error> {
     }
 }
 
-/// Build a [`G2DSurface`] from an [`Image`]'s DMA buffer and metadata.
-fn surface_from_image(img: &Image) -> Result<G2DSurface, Box<dyn Error>> {
-    let phys = G2DPhysical::new(img.fd.as_raw_fd())?;
-    let addr = phys.address();
-    let planes = match img.format {
-        NV12 => {
-            let y_size = img.width as u64 * img.height as u64;
-            [addr, addr + y_size, 0]
-        }
+/// Name of a G2D format constant, for logs.
+fn g2d_format_name(format: g2d_format) -> &'static str {
+    match format {
+        g2d_format_G2D_RGB888 => "RGB888",
+        g2d_format_G2D_RGBX8888 => "RGBX8888",
+        g2d_format_G2D_RGBA8888 => "RGBA8888",
+        g2d_format_G2D_YUYV => "YUYV",
+        g2d_format_G2D_NV12 => "NV12",
+        g2d_format_G2D_I420 => "I420",
+        _ => "unknown",
+    }
+}
+
+/// Every field of a [`G2DSurface`] that describes the pixels, as logged
+/// before each blit with `--g2d-trace`: the format name and numeric code,
+/// the three plane addresses, the `left`/`top`/`right`/`bottom` rectangle,
+/// the stride, size and rotation.
+pub struct SurfaceTrace<'a>(pub &'a G2DSurface);
+
+impl fmt::Display for SurfaceTrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = self.0;
+        write!(
+            f,
+            "format={}(code {}) planes=[{:#x}, {:#x}, {:#x}] rect=({},{})-({},{}) stride={} \
+             size={}x{} rot={}",
+            g2d_format_name(s.format),
+            s.format,
+            s.planes[0],
+            s.planes[1],
+            s.planes[2],
+            s.left,
+            s.top,
+            s.right,
+            s.bottom,
+            s.stride,
+            s.width,
+            s.height,
+            s.rot,
+        )
+    }
+}
+
+/// A full-frame [`G2DSurface`] for a `width`x`height` buffer in `format` at
+/// physical address `addr`, with rows `stride` bytes apart.
+fn surface_at(
+    format: FourCC,
+    width: u32,
+    height: u32,
+    stride: u32,
+    addr: u64,
+) -> Result<G2DSurface, Box<dyn Error>> {
+    let g2d_format = fourcc_to_g2d_format(format)?;
+    // G2D counts the stride in pixels of the first plane.
+    let bpp = packed_stride(format, 1).max(1);
+    if stride % bpp != 0 || stride < width * bpp {
+        return Err(
+            format!("{width}-pixel {format} rows cannot be {stride} bytes apart for G2D").into(),
+        );
+    }
+    // The chroma planes follow the luma rows: NV12's interleaved UV plane,
+    // or I420's U and V planes of half the stride and half the rows.
+    let y_size = stride as u64 * height as u64;
+    let planes = match format {
+        NV12 => [addr, addr + y_size, 0],
+        I420 => [addr, addr + y_size, addr + y_size + y_size / 4],
         _ => [addr, 0, 0],
     };
     Ok(G2DSurface {
         planes,
-        format: fourcc_to_g2d_format(img.format)?,
+        format: g2d_format,
         left: 0,
         top: 0,
-        right: img.width as i32,
-        bottom: img.height as i32,
-        stride: img.width as i32,
-        width: img.width as i32,
-        height: img.height as i32,
+        right: width as i32,
+        bottom: height as i32,
+        stride: (stride / bpp) as i32,
+        width: width as i32,
+        height: height as i32,
         blendfunc: 0,
         clrcolor: 0,
         rot: 0,
@@ -187,32 +673,104 @@ fn surface_from_frame(frame: &Frame) -> Result<G2DSurface, Box<dyn Error>> {
         Some(v) => G2DPhysical::from(v as u64),
         None => G2DPhysical::new(frame.handle()?)?,
     };
-    let fourcc = FourCC::from(frame.fourcc()?);
-    let width = frame.width()?;
-    let height = frame.height()?;
-    let addr = phys.address();
-    let planes = match fourcc {
-        NV12 => {
-            let y_size = width as u64 * height as u64;
-            [addr, addr + y_size, 0]
+    surface_at(
+        FourCC::from(frame.fourcc()?),
+        frame.width()? as u32,
+        frame.height()? as u32,
+        frame.stride()? as u32,
+        phys.address(),
+    )
+}
+
+/// How an [`ImageManager`] behaves when the process-wide blit budget set
+/// with [`set_max_blits_per_sec`] is spent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlitPriority {
+    /// Wait for the budget to refill, delaying the operation.
+    #[default]
+    High,
+    /// Fail the operation with [`BlitBudgetExceeded`] so the caller drops
+    /// the frame.
+    Low,
+}
+
+/// Error of a [`BlitPriority::Low`] operation skipped because the blit
+/// budget was spent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlitBudgetExceeded;
+
+impl fmt::Display for BlitBudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "G2D blit budget exceeded, operation skipped")
+    }
+}
+
+impl Error for BlitBudgetExceeded {}
+
+/// Token bucket capping the G2D blits per second of every
+/// [`ImageManager`] in the process, for boards that overheat when G2D runs
+/// flat out. It holds a tenth of a second of blits (at least two) so a
+/// frame's conversions can run back to back.
+#[derive(Debug)]
+pub struct BlitBudget {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl BlitBudget {
+    pub fn new(blits_per_sec: u32) -> Self {
+        let rate = blits_per_sec.max(1) as f64;
+        let capacity = (rate / 10.0).max(2.0);
+        Self {
+            rate,
+            capacity,
+            state: Mutex::new((capacity, Instant::now())),
         }
-        _ => [addr, 0, 0],
-    };
-    Ok(G2DSurface {
-        planes,
-        format: fourcc_to_g2d_format(fourcc)?,
-        left: 0,
-        top: 0,
-        right: width,
-        bottom: height,
-        stride: width,
-        width,
-        height,
-        blendfunc: 0,
-        clrcolor: 0,
-        rot: 0,
-        global_alpha: 0,
-    })
+    }
+
+    /// Take `blits` tokens at `now`, or return how long until they are
+    /// available without taking any.
+    pub fn try_take(&self, blits: u32, now: Instant) -> Result<(), Duration> {
+        let blits = (blits as f64).min(self.capacity);
+        let mut state = self.state.lock().unwrap();
+        let (tokens, last) = *state;
+        let tokens = (tokens + now.saturating_duration_since(last).as_secs_f64() * self.rate)
+            .min(self.capacity);
+        if tokens >= blits {
+            *state = (tokens - blits, now.max(last));
+            return Ok(());
+        }
+        *state = (tokens, now.max(last));
+        Err(Duration::from_secs_f64((blits - tokens) / self.rate))
+    }
+
+    /// Take `blits` tokens, sleeping until they are available for
+    /// [`BlitPriority::High`] or failing with [`BlitBudgetExceeded`] for
+    /// [`BlitPriority::Low`].
+    pub fn take(&self, blits: u32, priority: BlitPriority) -> Result<(), BlitBudgetExceeded> {
+        loop {
+            match self.try_take(blits, Instant::now()) {
+                Ok(()) => return Ok(()),
+                Err(_) if priority == BlitPriority::Low => return Err(BlitBudgetExceeded),
+                Err(wait) => thread::sleep(wait),
+            }
+        }
+    }
+}
+
+static BLIT_BUDGET: OnceLock<BlitBudget> = OnceLock::new();
+
+/// Cap the G2D blits of every [`ImageManager`] in the process at
+/// `blits_per_sec`. Can be set once, before or after managers are created.
+///
+/// # Errors
+///
+/// Returns an error if a cap was already set.
+pub fn set_max_blits_per_sec(blits_per_sec: u32) -> Result<(), Box<dyn Error>> {
+    BLIT_BUDGET
+        .set(BlitBudget::new(blits_per_sec))
+        .map_err(|_| "G2D blit budget is already set".into())
 }
 
 /// Manager for NXP G2D hardware accelerator operations.
@@ -221,10 +779,22 @@ fn surface_from_frame(frame: &Frame) -> Result<G2DSurface, Box<dyn Error>> {
 /// accelerator for efficient image processing operations including format
 /// conversion, scaling, cropping, and rotation.
 ///
+/// Where G2D cannot be opened, such as on a development workstation, the
+/// manager falls back to converting on the CPU; see
+/// [`ImageManager::software`].
+///
 /// # Thread Safety
 ///
-/// `ImageManager` is **not** thread-safe. Create separate instances for each
-/// thread, or use synchronization primitives to protect shared access.
+/// `ImageManager` is `Send` and `Sync`. Every manager in the process shares
+/// one G2D handle, opened by the first [`ImageManager::new`] and closed when
+/// the last manager is dropped, so threads may create their own managers,
+/// each with its own [`BlitPriority`], without opening the device again.
+///
+/// Each operation holds the handle from its first blit until G2D has
+/// finished, so operations from different threads run one after another.
+/// G2D is a single engine and would serialize them anyway, but a thread
+/// can wait for a large conversion of another thread, such as a 4K frame,
+/// before its own starts.
 ///
 /// # Example
 ///
@@ -242,29 +812,142 @@ fn surface_from_frame(frame: &Frame) -> Result<G2DSurface, Box<dyn Error>> {
 /// # }
 /// ```
 pub struct ImageManager {
-    g2d: G2D,
+    backend: Backend,
+    priority: BlitPriority,
+    /// Calls to [`ImageManager::convert_with`], see
+    /// [`ImageManager::conversion_count`].
+    conversions: AtomicU64,
 }
 
 impl ImageManager {
-    /// Creates a new ImageManager instance and opens the G2D hardware device.
+    /// Creates a new ImageManager, opening the G2D hardware device unless
+    /// another manager in the process already has it open.
+    ///
+    /// When the G2D library cannot be loaded (`libg2d.so.2`) or the device
+    /// cannot be opened (usually `/dev/galcore`), the manager converts on
+    /// the CPU instead, as [`ImageManager::software`] does, and the first
+    /// such manager logs a warning with the reason.
     ///
     /// # Errors
     ///
-    /// Returns an error if:
-    /// - The G2D library cannot be loaded (`libg2d.so.2`)
-    /// - The G2D device cannot be opened (usually `/dev/galcore`)
-    /// - Insufficient permissions to access the hardware
+    /// Does not currently fail; the `Result` is kept for callers written
+    /// against the hardware-only manager.
     ///
     /// # Platform Requirements
     ///
-    /// Requires NXP i.MX8M Plus with G2D hardware support.
+    /// Hardware acceleration requires NXP i.MX8M Plus with G2D support.
     pub fn new() -> Result<Self, Box<dyn Error>> {
-        let g2d = G2D::new("libg2d.so.2")?;
-        Ok(Self { g2d })
+        let backend = match G2DContext::get() {
+            Ok(g2d) => Backend::Hardware(g2d),
+            Err(e) => {
+                static WARNED: Once = Once::new();
+                WARNED.call_once(|| warn!("G2D unavailable, converting on the CPU: {e}"));
+                Backend::Software
+            }
+        };
+        Ok(Self::with_backend(backend))
+    }
+
+    /// Creates a manager that converts on the CPU even where G2D is
+    /// available.
+    ///
+    /// It handles [`convert`](Self::convert),
+    /// [`convert_with`](Self::convert_with), [`pyramid`](Self::pyramid),
+    /// [`composite`](Self::composite) and [`blend`](Self::blend) by mapping
+    /// the images: it reads YUYV, NV12, RGBA, RGBX, BGRA and RGB3, writes
+    /// the same formats, and resizes with nearest-neighbour sampling. G2D buffers
+    /// ([`alloc`](Self::alloc)) and V4L2 frames
+    /// ([`convert_phys`](Self::convert_phys)) need the hardware.
+    pub fn software() -> Self {
+        Self::with_backend(Backend::Software)
+    }
+
+    fn with_backend(backend: Backend) -> Self {
+        Self {
+            backend,
+            priority: BlitPriority::default(),
+            conversions: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether this manager runs on the G2D engine rather than the CPU.
+    pub fn is_hardware(&self) -> bool {
+        matches!(self.backend, Backend::Hardware(_))
+    }
+
+    /// The G2D context, or an error naming `what` needs it for a software
+    /// manager.
+    fn g2d(&self, what: &str) -> Result<&G2DContext, Box<dyn Error>> {
+        match &self.backend {
+            Backend::Hardware(g2d) => Ok(g2d),
+            Backend::Software => Err(format!("{what} needs G2D, which is unavailable").into()),
+        }
     }
 
+    /// Whether this manager's operations wait for, or are dropped by, the
+    /// blit budget from [`set_max_blits_per_sec`]. Defaults to
+    /// [`BlitPriority::High`].
+    pub fn set_priority(&mut self, priority: BlitPriority) {
+        self.priority = priority;
+    }
+
+    /// Take `blits` from the process-wide budget, if one is set, before an
+    /// operation issues any of them.
+    fn take_budget(&self, blits: u32) -> Result<(), BlitBudgetExceeded> {
+        match BLIT_BUDGET.get() {
+            Some(budget) => budget.take(blits, self.priority),
+            None => Ok(()),
+        }
+    }
+
+    /// The G2D library version, or all zeros for a software manager.
     pub fn version(&self) -> g2d_sys::Version {
-        self.g2d.version()
+        match &self.backend {
+            Backend::Hardware(g2d) => g2d.lock().version(),
+            Backend::Software => g2d_sys::Version {
+                major: 0,
+                minor: 0,
+                patch: 0,
+                num: 0,
+            },
+        }
+    }
+
+    /// Whether [`convert_with`](Self::convert_with) can apply `flip` after
+    /// `rotation`: always on the CPU, and on G2D unless a horizontal or
+    /// vertical flip follows a quarter turn on a release before
+    /// [`G2D_2_3_0`].
+    pub fn can_flip(&self, rotation: Rotation, flip: Flip) -> bool {
+        let (src_rot, _) = g2d_orientation(rotation, flip);
+        src_rot == g2d_rotation_G2D_ROTATION_0
+            || !self.is_hardware()
+            || g2d_version_at_least(&self.version(), &G2D_2_3_0)
+    }
+
+    /// Whether `self` and `other` share one G2D handle, as every hardware
+    /// manager alive at the same time does. Software managers have none.
+    pub fn shares_handle_with(&self, other: &ImageManager) -> bool {
+        match (&self.backend, &other.backend) {
+            (Backend::Hardware(a), Backend::Hardware(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Number of conversions ([`convert`](Self::convert) and
+    /// [`convert_with`](Self::convert_with) calls) requested from this
+    /// manager, including ones that failed.
+    pub fn conversion_count(&self) -> u64 {
+        self.conversions.load(Ordering::Relaxed)
+    }
+
+    /// Blit `src` to `dst`, first logging both surfaces at trace level so
+    /// `--g2d-trace` shows what the driver was handed. The `g2d-sys` crate
+    /// translates these to the legacy `g2d_surface` layout on G2D before
+    /// 2.3.0, with the same values.
+    fn blit(g2d: &G2D, src: &G2DSurface, dst: &G2DSurface) -> Result<(), Box<dyn Error>> {
+        trace!(src = %SurfaceTrace(src), dst = %SurfaceTrace(dst), "G2D blit");
+        g2d.blit(src, dst)?;
+        Ok(())
     }
 
     /// Allocates a G2D buffer for hardware-accelerated operations.
@@ -277,27 +960,47 @@ impl ImageManager {
     ///
     /// # Errors
     ///
-    /// Returns an error if the G2D driver fails to allocate the buffer.
+    /// Returns an error if the G2D driver fails to allocate the buffer or
+    /// the manager converts on the CPU.
     pub fn alloc(
         &self,
         width: i32,
         height: i32,
         channels: i32,
     ) -> Result<G2DBuffer<'_>, Box<dyn Error>> {
-        let g2d_buf = unsafe { self.g2d.lib.g2d_alloc(width * height * channels, 0) };
+        let g2d = self.g2d("allocating a G2D buffer")?;
+        let g2d_buf = unsafe { g2d.lock().lib.g2d_alloc(width * height * channels, 0) };
         if g2d_buf.is_null() {
             return Err(Box::new(io::Error::other("g2d_alloc failed")));
         }
         debug!("G2D Buffer alloc'd");
+        g2d.allocations.lock().unwrap().insert(g2d_buf as usize);
         Ok(G2DBuffer {
             buf: g2d_buf,
             imgmgr: self,
         })
     }
 
+    /// Frees a buffer from [`ImageManager::alloc`]. Freeing the same
+    /// buffer again, including from its own `Drop`, is a no-op.
     pub fn free(&self, buf: &mut G2DBuffer) {
+        let Backend::Hardware(g2d) = &self.backend else {
+            return;
+        };
+        if !g2d.allocations.lock().unwrap().remove(&(buf.buf as usize)) {
+            return;
+        }
         unsafe {
-            self.g2d.lib.g2d_free(buf.buf);
+            g2d.lock().lib.g2d_free(buf.buf);
+        }
+    }
+
+    /// Number of buffers from [`ImageManager::alloc`], by any manager, that
+    /// have not been freed yet. Always 0 for a software manager.
+    pub fn outstanding_allocations(&self) -> usize {
+        match &self.backend {
+            Backend::Hardware(g2d) => g2d.allocations.lock().unwrap().len(),
+            Backend::Software => 0,
         }
     }
 
@@ -307,7 +1010,8 @@ impl ImageManager {
     ///
     /// * `from` - Source image (must be DMA-backed)
     /// * `to` - Destination image (must be DMA-backed)
-    /// * `crop` - Optional cropping rectangle
+    /// * `crop` - Optional cropping rectangle, snapped to whole chroma samples
+    ///   for YUYV and NV12 sources (see [`align_crop`])
     /// * `rot` - Rotation angle (0, 90, 180, or 270 degrees)
     ///
     /// # Errors
@@ -324,50 +1028,645 @@ impl ImageManager {
         crop: Option<Rect>,
         rot: Rotation,
     ) -> Result<(), Box<dyn Error>> {
-        let mut src = surface_from_image(from)?;
+        let options = ConvertOptions {
+            crop,
+            rotation: rot,
+            ..Default::default()
+        };
+        self.convert_with(from, to, &options)
+    }
 
-        if let Some(r) = crop {
+    /// Like [`convert`](Self::convert), with the crop, rotation, flip and
+    /// the destination region taken from `options`. With `dst_rect` set
+    /// only that region of `to` is written. A crop of a YUYV or NV12 source
+    /// is first snapped to whole chroma samples with [`align_crop`].
+    ///
+    /// ```no_run
+    /// use edgefirst_camera::image::{ConvertOptions, Image, ImageManager, Rect, RGBA, YUYV};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let imgmgr = ImageManager::new()?;
+    /// let src = Image::new(1280, 720, YUYV)?;
+    /// let dst = Image::new(640, 640, RGBA)?;
+    /// imgmgr.fill(&dst, None, 0x000000ff)?;
+    /// // Letterbox 16:9 into a square: 640x360 centred vertically.
+    /// let options = ConvertOptions {
+    ///     dst_rect: Some(Rect { x: 0, y: 140, width: 640, height: 360 }),
+    ///     ..Default::default()
+    /// };
+    /// imgmgr.convert_with(&src, &dst, &options)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `dst_rect` is rejected by
+    /// [`ConvertOptions::check`], this G2D cannot apply the flip (see
+    /// [`can_flip`](Self::can_flip)) or the blit fails.
+    pub fn convert_with(
+        &self,
+        from: &Image,
+        to: &Image,
+        options: &ConvertOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        self.conversions.fetch_add(1, Ordering::Relaxed);
+        options.check(to.width(), to.height())?;
+        if !self.can_flip(options.rotation, options.flip) {
+            return Err(format!(
+                "G2D {} cannot flip {:?} after {:?}, which needs G2D {} or newer",
+                self.version(),
+                options.flip,
+                options.rotation,
+                G2D_2_3_0
+            )
+            .into());
+        }
+        self.take_budget(1)?;
+        let Backend::Hardware(g2d) = &self.backend else {
+            return software_convert(from, to, options);
+        };
+
+        let mut src = from.as_g2d_surface()?;
+
+        if let Some(crop) = options.crop {
+            let r = align_crop(crop, from.format());
+            if r != crop {
+                debug!(
+                    "crop {crop:?} snapped to {r:?} for {} chroma",
+                    from.format()
+                );
+            }
             src.left = r.x;
             src.top = r.y;
             src.right = r.x + r.width;
             src.bottom = r.y + r.height;
         }
 
-        let mut dst = surface_from_image(to)?;
-        dst.rot = rot as u32;
+        let (src_rot, dst_rot) = g2d_orientation(options.rotation, options.flip);
+        src.rot = src_rot;
+        let mut dst = to.as_g2d_surface()?;
+        dst.rot = dst_rot;
+        if let Some(r) = options.dst_rect {
+            dst.left = r.x;
+            dst.top = r.y;
+            dst.right = r.x + r.width;
+            dst.bottom = r.y + r.height;
+        }
 
-        self.g2d.blit(&src, &dst)?;
-        self.g2d.finish()?;
-        // FIXME: A cache invalidation is required here, currently missing!
+        let g2d = g2d.lock();
+        Self::blit(&g2d, &src, &dst)?;
+        g2d.finish()?;
+        drop(g2d);
+        // `Image::mmap` syncs again when it maps `to`; this also covers
+        // readers that map it some other way, such as `with_dma_buf`.
+        sync_after_blit(to);
 
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn convert_phys(
+    /// Downscales `src` into one image per entry of `scales`, each
+    /// `1/scale` of the source size in the source format.
+    ///
+    /// Scales must increase (for example `[1, 2, 4]` for full, half and
+    /// quarter size). Each level is blitted from the previous one rather
+    /// than from `src`, so the larger levels are read once instead of once
+    /// per level. See [`pyramid_sizes`] for the accepted scales.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a scale is rejected by [`pyramid_sizes`], or if
+    /// allocating or converting a level fails.
+    pub fn pyramid(&self, src: &Image, scales: &[u32]) -> Result<Vec<Image>, Box<dyn Error>> {
+        let sizes = pyramid_sizes(src.width(), src.height(), scales)?;
+        let mut levels: Vec<Image> = Vec::with_capacity(sizes.len());
+        for (width, height) in sizes {
+            let level = Image::new(width, height, src.format())?;
+            let from = levels.last().unwrap_or(src);
+            self.convert(from, &level, None, Rotation::Rotation0)?;
+            levels.push(level);
+        }
+        Ok(levels)
+    }
+
+    /// Picture-in-picture composition: scales `main` over the whole of `to`,
+    /// then `inset` into the `options.inset` region of `to` on top of it.
+    /// Both blits run on the G2D engine, or the CPU for a software manager,
+    /// and the inset fully covers the region beneath it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the inset region is rejected by
+    /// [`CompositeOptions::check`] or if either blit fails.
+    pub fn composite(
         &self,
-        from: &Frame,
+        main: &Image,
+        inset: &Image,
         to: &Image,
-        crop: &Option<Rect>,
+        options: &CompositeOptions,
     ) -> Result<(), Box<dyn Error>> {
-        let mut src = surface_from_frame(from)?;
+        options.check(to.width(), to.height())?;
+        self.take_budget(2)?;
+        let Backend::Hardware(g2d) = &self.backend else {
+            software_convert(main, to, &ConvertOptions::default())?;
+            let region = ConvertOptions {
+                dst_rect: Some(options.inset),
+                ..Default::default()
+            };
+            return software_convert(inset, to, &region);
+        };
 
-        if let Some(r) = crop {
-            src.left = r.x;
-            src.top = r.y;
-            src.right = r.x + r.width;
-            src.bottom = r.y + r.height;
-        }
+        let src = main.as_g2d_surface()?;
+        let dst = to.as_g2d_surface()?;
+        let g2d = g2d.lock();
+        Self::blit(&g2d, &src, &dst)?;
 
-        let dst = surface_from_image(to)?;
-
-        self.g2d.blit(&src, &dst)?;
-        self.g2d.finish()?;
-        // FIXME: A cache invalidation is required here, currently missing!
+        let r = options.inset;
+        let src = inset.as_g2d_surface()?;
+        let mut dst = to.as_g2d_surface()?;
+        dst.left = r.x;
+        dst.top = r.y;
+        dst.right = r.x + r.width;
+        dst.bottom = r.y + r.height;
+        Self::blit(&g2d, &src, &dst)?;
+        g2d.finish()?;
+        drop(g2d);
+        sync_after_blit(to);
 
         Ok(())
     }
-}
+
+    /// Alpha-blends `overlay` over `base` into `dst`: `base` is scaled over
+    /// the whole of `dst`, then `overlay` is scaled into `dst_rect` and
+    /// blended source over what lies beneath, each pixel weighted by its
+    /// own alpha times `global_alpha / 255`. Passing the same image as
+    /// `base` and `dst` burns the overlay into it in place, for example a
+    /// status or timestamp overlay before encoding.
+    ///
+    /// `overlay` must be RGBA with a valid, straight (not premultiplied)
+    /// alpha channel: G2D blends with `SRC_ALPHA` / `ONE_MINUS_SRC_ALPHA`,
+    /// so an overlay with zero alpha leaves `base` unchanged whatever its
+    /// colour. Like [`convert`](Self::convert), the blit goes through
+    /// `g2d-sys`, which handles the surface layout of G2D before 2.3.0.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `overlay` is not RGBA, if `dst_rect` is not a
+    /// non-empty, even region inside `dst`, or if either blit fails.
+    pub fn blend(
+        &self,
+        base: &Image,
+        overlay: &Image,
+        dst: &Image,
+        dst_rect: Rect,
+        global_alpha: u8,
+    ) -> Result<(), Box<dyn Error>> {
+        if overlay.format() != RGBA {
+            return Err(format!(
+                "blend overlay must be RGBA with an alpha channel, not {}",
+                overlay.format()
+            )
+            .into());
+        }
+        check_region("blend rectangle", dst_rect, dst.width(), dst.height())?;
+        let in_place = std::ptr::eq(base, dst);
+        self.take_budget(if in_place { 1 } else { 2 })?;
+        let Backend::Hardware(g2d) = &self.backend else {
+            if !in_place {
+                software_convert(base, dst, &ConvertOptions::default())?;
+            }
+            return software_blend(overlay, dst, dst_rect, global_alpha);
+        };
+
+        let g2d = g2d.lock();
+        if !in_place {
+            let src = base.as_g2d_surface()?;
+            let to = dst.as_g2d_surface()?;
+            Self::blit(&g2d, &src, &to)?;
+        }
+
+        let mut src = overlay.as_g2d_surface()?;
+        src.blendfunc = g2d_blend_func_G2D_SRC_ALPHA;
+        src.global_alpha = global_alpha as i32;
+        let mut to = dst.as_g2d_surface()?;
+        to.blendfunc = g2d_blend_func_G2D_ONE_MINUS_SRC_ALPHA;
+        to.left = dst_rect.x;
+        to.top = dst_rect.y;
+        to.right = dst_rect.x + dst_rect.width;
+        to.bottom = dst_rect.y + dst_rect.height;
+        set_capability(&g2d, g2d_cap_mode_G2D_BLEND, true)?;
+        let blended = set_capability(&g2d, g2d_cap_mode_G2D_GLOBAL_ALPHA, true)
+            .and_then(|()| Self::blit(&g2d, &src, &to));
+        // Later blits on the shared handle must not blend.
+        set_capability(&g2d, g2d_cap_mode_G2D_GLOBAL_ALPHA, false)?;
+        set_capability(&g2d, g2d_cap_mode_G2D_BLEND, false)?;
+        blended?;
+        g2d.finish()?;
+        drop(g2d);
+        sync_after_blit(dst);
+
+        Ok(())
+    }
+
+    /// Fills `rect` of `dst`, or all of it when `None`, with the colour
+    /// `rgba`, given as `0xRRGGBBAA`. YUYV and NV12 destinations get the
+    /// colour converted to limited-range YUV.
+    ///
+    /// G2D clears the region itself, so this is the way to pad letterboxed
+    /// frames or blank tiles at 4K rather than mapping and writing the
+    /// buffer on the CPU.
+    ///
+    /// ```no_run
+    /// use edgefirst_camera::image::{Image, ImageManager, Rect, RGBA};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let imgmgr = ImageManager::new()?;
+    /// let dst = Image::new(640, 640, RGBA)?;
+    /// imgmgr.fill(&dst, None, 0x000000ff)?;
+    /// // A grey bar along the bottom.
+    /// let bar = Rect { x: 0, y: 600, width: 640, height: 40 };
+    /// imgmgr.fill(&dst, Some(bar), 0x808080ff)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `rect` is not a non-empty, even region inside
+    /// `dst`, or if the clear fails.
+    pub fn fill(&self, dst: &Image, rect: Option<Rect>, rgba: u32) -> Result<(), Box<dyn Error>> {
+        let region = rect.unwrap_or(Rect {
+            x: 0,
+            y: 0,
+            width: dst.width() as i32,
+            height: dst.height() as i32,
+        });
+        check_region("fill rectangle", region, dst.width(), dst.height())?;
+        self.take_budget(1)?;
+        let Backend::Hardware(g2d) = &self.backend else {
+            return software_fill(dst, region, rgba);
+        };
+
+        let mut to = dst.as_g2d_surface()?;
+        to.left = region.x;
+        to.top = region.y;
+        to.right = region.x + region.width;
+        to.bottom = region.y + region.height;
+        let g2d = g2d.lock();
+        trace!(dst = %SurfaceTrace(&to), rgba = format_args!("{rgba:#010x}"), "G2D clear");
+        g2d.clear(&mut to, rgba.to_be_bytes())?;
+        g2d.finish()?;
+        drop(g2d);
+        sync_after_blit(dst);
+
+        Ok(())
+    }
+
+    /// Like [`convert`](Self::convert) from a V4L2 [`Frame`] addressed by
+    /// its physical address.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blit fails or the manager converts on the
+    /// CPU.
+    #[allow(dead_code)]
+    pub fn convert_phys(
+        &self,
+        from: &Frame,
+        to: &Image,
+        crop: &Option<Rect>,
+    ) -> Result<(), Box<dyn Error>> {
+        let g2d = self.g2d("converting a V4L2 frame")?;
+        self.take_budget(1)?;
+        let mut src = surface_from_frame(from)?;
+
+        if let Some(r) = crop {
+            src.left = r.x;
+            src.top = r.y;
+            src.right = r.x + r.width;
+            src.bottom = r.y + r.height;
+        }
+
+        let dst = to.as_g2d_surface()?;
+
+        let g2d = g2d.lock();
+        Self::blit(&g2d, &src, &dst)?;
+        g2d.finish()?;
+        drop(g2d);
+        sync_after_blit(to);
+
+        Ok(())
+    }
+}
+
+/// Turn the G2D capability `cap` on or off for the blits that follow on
+/// `g2d`'s handle.
+fn set_capability(g2d: &G2D, cap: g2d_cap_mode, on: bool) -> Result<(), Box<dyn Error>> {
+    let ret = unsafe {
+        if on {
+            g2d.lib.g2d_enable(g2d.handle, cap)
+        } else {
+            g2d.lib.g2d_disable(g2d.handle, cap)
+        }
+    };
+    if ret != 0 {
+        let call = if on { "g2d_enable" } else { "g2d_disable" };
+        return Err(io::Error::other(format!("{call}({cap}) failed: {ret}")).into());
+    }
+    Ok(())
+}
+
+/// Invalidate the CPU caches over `to` once G2D has written it. A buffer
+/// without DMA-BUF sync is left as it is, as [`Image::mmap`] does.
+fn sync_after_blit(to: &Image) {
+    if let Err(e) = to.sync_for_cpu() {
+        debug!("DMA-BUF sync unavailable for {to}: {e}");
+    }
+}
+
+/// Whether the software [`ImageManager`] reads and writes `format`.
+fn software_supports(format: FourCC) -> bool {
+    matches!(format, YUYV | NV12 | RGBA | RGBX | BGRA | RGB3)
+}
+
+/// Whether `a` and `b` are handles to the same buffer, through the same
+/// descriptor or a duplicate of it. The software backend maps both sides
+/// of an operation, so it rejects these rather than alias the mapping.
+fn same_buffer(a: &Image, b: &Image) -> bool {
+    let id = |img: &Image| {
+        let mut st = std::mem::MaybeUninit::<libc::stat>::uninit();
+        (unsafe { libc::fstat(img.raw_fd(), st.as_mut_ptr()) } == 0).then(|| {
+            let st = unsafe { st.assume_init() };
+            (st.st_dev, st.st_ino)
+        })
+    };
+    a.raw_fd() == b.raw_fd() || matches!((id(a), id(b)), (Some(a), Some(b)) if a == b)
+}
+
+/// [`ImageManager::convert_with`] on the CPU: decode the crop of `from` to
+/// RGBA, scale it with nearest-neighbour sampling, rotate it, and encode it
+/// into the destination region of `to`.
+fn software_convert(
+    from: &Image,
+    to: &Image,
+    options: &ConvertOptions,
+) -> Result<(), Box<dyn Error>> {
+    if same_buffer(from, to) {
+        return Err(format!("software conversion cannot write its source {from}").into());
+    }
+    for format in [from.format(), to.format()] {
+        if !software_supports(format) {
+            return Err(format!(
+                "software conversion from {} to {} is not supported",
+                from.format(),
+                to.format()
+            )
+            .into());
+        }
+    }
+    let full = |img: &Image| Rect {
+        x: 0,
+        y: 0,
+        width: img.width() as i32,
+        height: img.height() as i32,
+    };
+    let crop = match options.crop {
+        Some(crop) => {
+            let crop = align_crop(crop, from.format());
+            if crop.x < 0
+                || crop.y < 0
+                || crop.width <= 0
+                || crop.height <= 0
+                || crop.x as i64 + crop.width as i64 > from.width() as i64
+                || crop.y as i64 + crop.height as i64 > from.height() as i64
+            {
+                return Err(format!("crop {crop:?} does not fit in {from}").into());
+            }
+            crop
+        }
+        None => full(from),
+    };
+    let region = options.dst_rect.unwrap_or(full(to));
+    let (width, height) = options
+        .rotation
+        .rotated_size(region.width as u32, region.height as u32);
+    let (width, height) = (width as usize, height as usize);
+
+    let src = from.map()?;
+    let rgba = decode_rgba(src.as_slice(), from, crop);
+    drop(src);
+    let (crop_width, crop_height) = (crop.width as usize, crop.height as usize);
+    let rgba = resize_rgba(rgba, (crop_width, crop_height), (width, height));
+    let rgba = rotate_rgba(&rgba, width, height, options.rotation);
+    let rgba = flip_rgba(
+        rgba,
+        region.width as usize,
+        region.height as usize,
+        options.flip,
+    );
+
+    let mut dst = to.map()?;
+    encode_rgba(&rgba, dst.as_slice_mut(), to, region);
+    Ok(())
+}
+
+/// [`ImageManager::blend`] of `overlay` into the `region` of `to` on the
+/// CPU: scale the overlay to the region, decode the pixels beneath it to
+/// RGBA, blend, and encode the result back.
+fn software_blend(
+    overlay: &Image,
+    to: &Image,
+    region: Rect,
+    global_alpha: u8,
+) -> Result<(), Box<dyn Error>> {
+    if !software_supports(to.format()) {
+        return Err(format!("software blending into {} is not supported", to.format()).into());
+    }
+    if same_buffer(overlay, to) {
+        return Err(format!("software blending cannot write its overlay {overlay}").into());
+    }
+    let (width, height) = (overlay.width() as usize, overlay.height() as usize);
+    let whole = Rect {
+        x: 0,
+        y: 0,
+        width: width as i32,
+        height: height as i32,
+    };
+    let src = overlay.map()?;
+    let rgba = decode_rgba(src.as_slice(), overlay, whole);
+    drop(src);
+    let size = (region.width as usize, region.height as usize);
+    let rgba = resize_rgba(rgba, (width, height), size);
+
+    let mut dst = to.map()?;
+    let mut under = decode_rgba(dst.as_slice(), to, region);
+    cpu::blend_rgba_over(&rgba, &mut under, global_alpha);
+    encode_rgba(&under, dst.as_slice_mut(), to, region);
+    Ok(())
+}
+
+/// [`ImageManager::fill`] of `region` of `to` on the CPU.
+fn software_fill(to: &Image, region: Rect, rgba: u32) -> Result<(), Box<dyn Error>> {
+    if !software_supports(to.format()) {
+        return Err(format!("software fill of {} is not supported", to.format()).into());
+    }
+    let pixels = region.width as usize * region.height as usize;
+    let solid = rgba.to_be_bytes().repeat(pixels);
+    let mut dst = to.map()?;
+    encode_rgba(&solid, dst.as_slice_mut(), to, region);
+    Ok(())
+}
+
+/// The packed RGBA frame `rgba` of size `from` scaled to `to` with
+/// nearest-neighbour sampling, or unchanged when the sizes match.
+fn resize_rgba(rgba: Vec<u8>, from: (usize, usize), to: (usize, usize)) -> Vec<u8> {
+    if from == to {
+        return rgba;
+    }
+    let mut scaled = vec![0; to.0 * to.1 * 4];
+    cpu::resize_rgba_nearest(
+        &rgba,
+        from.0 * 4,
+        from.0,
+        from.1,
+        &mut scaled,
+        to.0 * 4,
+        to.0,
+        to.1,
+    );
+    scaled
+}
+
+/// The `crop` of the mapped image `img` as packed RGBA. The crop must fit
+/// in the image and, for YUYV and NV12, be aligned with [`align_crop`].
+fn decode_rgba(pix: &[u8], img: &Image, crop: Rect) -> Vec<u8> {
+    let (x0, y0) = (crop.x as usize, crop.y as usize);
+    let (width, height) = (crop.width as usize, crop.height as usize);
+    let stride = img.stride() as usize;
+    let mut rgba = vec![0; width * height * 4];
+    match img.format() {
+        YUYV => cpu::yuyv_to_rgba(
+            &pix[y0 * stride + x0 * 2..],
+            stride,
+            &mut rgba,
+            width * 4,
+            width,
+            height,
+        ),
+        NV12 => {
+            let (luma, chroma) = pix.split_at(stride * img.height() as usize);
+            for (row, out) in rgba.chunks_exact_mut(width * 4).enumerate() {
+                let y = &luma[(y0 + row) * stride + x0..];
+                let uv = &chroma[(y0 + row) / 2 * stride + x0..];
+                for (x, px) in out.chunks_exact_mut(4).enumerate() {
+                    let [r, g, b] = cpu::yuv_to_rgb(y[x], uv[x & !1], uv[x | 1]);
+                    px.copy_from_slice(&[r, g, b, 255]);
+                }
+            }
+        }
+        format => {
+            let bpp = if format == RGB3 { 3 } else { 4 };
+            for (row, out) in rgba.chunks_exact_mut(width * 4).enumerate() {
+                let input = &pix[(y0 + row) * stride + x0 * bpp..][..width * bpp];
+                for (px, inp) in out.chunks_exact_mut(4).zip(input.chunks_exact(bpp)) {
+                    px.copy_from_slice(&match format {
+                        RGBA => [inp[0], inp[1], inp[2], inp[3]],
+                        BGRA => [inp[2], inp[1], inp[0], inp[3]],
+                        _ => [inp[0], inp[1], inp[2], 255],
+                    });
+                }
+            }
+        }
+    }
+    rgba
+}
+
+/// Write the packed RGBA `rgba` into the `region` of the mapped image
+/// `img`, whose size it must have.
+fn encode_rgba(rgba: &[u8], pix: &mut [u8], img: &Image, region: Rect) {
+    let (x0, y0) = (region.x as usize, region.y as usize);
+    let (width, height) = (region.width as usize, region.height as usize);
+    let stride = img.stride() as usize;
+    match img.format() {
+        YUYV => cpu::rgba_to_yuyv(
+            rgba,
+            width * 4,
+            &mut pix[y0 * stride + x0 * 2..],
+            stride,
+            width,
+            height,
+        ),
+        NV12 => {
+            let (luma, chroma) = pix.split_at_mut(stride * img.height() as usize);
+            cpu::rgba_to_nv12(
+                rgba,
+                width * 4,
+                &mut luma[y0 * stride + x0..],
+                &mut chroma[y0 / 2 * stride + x0..],
+                stride,
+                width,
+                height,
+            );
+        }
+        format => {
+            let bpp = if format == RGB3 { 3 } else { 4 };
+            for (row, input) in rgba.chunks_exact(width * 4).enumerate() {
+                let out = &mut pix[(y0 + row) * stride + x0 * bpp..][..width * bpp];
+                for (px, inp) in out.chunks_exact_mut(bpp).zip(input.chunks_exact(4)) {
+                    match format {
+                        RGBA => px.copy_from_slice(inp),
+                        BGRA => px.copy_from_slice(&[inp[2], inp[1], inp[0], inp[3]]),
+                        RGBX => px.copy_from_slice(&[inp[0], inp[1], inp[2], 255]),
+                        _ => px.copy_from_slice(&inp[..3]),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The packed `width`x`height` RGBA frame `rgba` rotated clockwise by
+/// `rotation`, as G2D rotates its destination.
+fn rotate_rgba(rgba: &[u8], width: usize, height: usize, rotation: Rotation) -> Vec<u8> {
+    if rotation == Rotation::Rotation0 {
+        return rgba.to_vec();
+    }
+    let (out_width, _) = rotation.rotated_size(width as u32, height as u32);
+    let out_width = out_width as usize;
+    let mut out = vec![0; rgba.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let (ox, oy) = match rotation {
+                Rotation::Rotation90 => (height - 1 - y, x),
+                Rotation::Rotation180 => (width - 1 - x, height - 1 - y),
+                _ => (y, width - 1 - x),
+            };
+            let (from, to) = ((y * width + x) * 4, (oy * out_width + ox) * 4);
+            out[to..to + 4].copy_from_slice(&rgba[from..from + 4]);
+        }
+    }
+    out
+}
+
+/// Mirror a `width`x`height` RGBA image as `flip` directs.
+fn flip_rgba(mut rgba: Vec<u8>, width: usize, height: usize, flip: Flip) -> Vec<u8> {
+    let stride = width * 4;
+    if matches!(flip, Flip::Horizontal | Flip::Both) {
+        for row in rgba.chunks_exact_mut(stride) {
+            for x in 0..width / 2 {
+                let (left, right) = row.split_at_mut((width - 1 - x) * 4);
+                left[x * 4..x * 4 + 4].swap_with_slice(&mut right[..4]);
+            }
+        }
+    }
+    if matches!(flip, Flip::Vertical | Flip::Both) {
+        for y in 0..height / 2 {
+            let (top, bottom) = rgba.split_at_mut((height - 1 - y) * stride);
+            top[y * stride..(y + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+        }
+    }
+    rgba
+}
 
 /// DMA-backed image buffer for zero-copy image operations.
 ///
@@ -397,21 +1696,127 @@ pub struct Image {
     width: u32,
     height: u32,
     format: FourCC,
+    /// Bytes per row of the first plane, see [`Image::stride`].
+    stride: u32,
+    /// Physical address of the buffer, looked up on the first G2D
+    /// operation, see [`Image::as_g2d_surface`].
+    phys: OnceLock<u64>,
+}
+
+/// Bytes per row of the first plane of an unpadded `width`-pixel frame in
+/// `format`, or `None` for formats [`Image`] does not support. For NV12 and
+/// I420 that is the luma plane, one byte per pixel; the chroma planes
+/// follow it, see [`plane_rows`].
+pub const fn plane_stride(format: FourCC, width: u32) -> Option<usize> {
+    let width = width as usize;
+    Some(match format {
+        RGB3 => 3 * width,
+        RGBX | RGBA | BGRA => 4 * width,
+        YUYV => 2 * width,
+        NV12 | I420 => width,
+        SRGGB8 | SBGGR8 | SGBRG8 | SGRBG8 | GREY => width,
+        SRGGB10 | SBGGR10 | SGBRG10 | SGRBG10 | Y16 => 2 * width,
+        _ => return None,
+    })
+}
+
+/// Rows of first-plane stride a `height`-row frame in `format` occupies.
+/// The chroma of NV12 and I420 adds half as many rows again below the luma
+/// plane: one interleaved UV row per two luma rows for NV12, or two planes
+/// of half-width rows for I420.
+const fn plane_rows(format: FourCC, height: u32) -> usize {
+    match format {
+        NV12 | I420 => height as usize * 3 / 2,
+        _ => height as usize,
+    }
+}
+
+/// Bytes of an unpadded `width`x`height` frame in `format`, or `None` for
+/// formats [`Image`] does not know.
+const fn image_size(width: u32, height: u32, format: FourCC) -> Option<usize> {
+    match plane_stride(format, width) {
+        Some(stride) => Some(stride * plane_rows(format, height)),
+        None => None,
+    }
 }
 
-const fn format_row_stride(format: FourCC, width: u32) -> usize {
+/// [`plane_stride`] as an [`Image`] stride, 0 for formats it does not
+/// support.
+const fn packed_stride(format: FourCC, width: u32) -> u32 {
+    match plane_stride(format, width) {
+        Some(stride) => stride as u32,
+        None => 0,
+    }
+}
+
+/// Byte offset of each plane of a `width`x`height` frame in `format`.
+///
+/// Packed formats have a single plane at offset 0. NV12 stores the full
+/// resolution luma plane first, followed by the interleaved chroma plane.
+/// I420 follows the luma plane with the U and then the V plane, each a
+/// quarter of its size.
+pub fn plane_offsets(format: FourCC, width: u32, height: u32) -> Vec<usize> {
+    let luma = width as usize * height as usize;
     match format {
-        RGB3 => 3 * width as usize,
-        RGBX => 4 * width as usize,
-        RGBA => 4 * width as usize,
-        YUYV => 2 * width as usize,
-        NV12 => width as usize / 2 + width as usize,
-        _ => todo!(),
+        NV12 => vec![0, luma],
+        I420 => vec![0, luma, luma + luma / 4],
+        _ => vec![0],
     }
 }
 
-const fn image_size(width: u32, height: u32, format: FourCC) -> usize {
-    format_row_stride(format, width) * height as usize
+/// Size of each [`ImageManager::pyramid`] level of a `width`x`height`
+/// source.
+///
+/// Every scale must be larger than the one before it and divide the source
+/// size into even dimensions, so levels never split the chroma samples
+/// that YUYV and NV12 share between pixel pairs.
+pub fn pyramid_sizes(
+    width: u32,
+    height: u32,
+    scales: &[u32],
+) -> Result<Vec<(u32, u32)>, Box<dyn Error>> {
+    let mut prev = 0;
+    scales
+        .iter()
+        .map(|&scale| {
+            if scale <= prev {
+                return Err(format!(
+                    "pyramid scales must be increasing and non-zero, got {scales:?}"
+                )
+                .into());
+            }
+            prev = scale;
+            let (w, h) = (width / scale, height / scale);
+            if w * scale != width || h * scale != height || w % 2 != 0 || h % 2 != 0 {
+                return Err(format!(
+                    "pyramid scale 1/{scale} of {width}x{height} is not an even size"
+                )
+                .into());
+            }
+            Ok((w, h))
+        })
+        .collect()
+}
+
+/// The luma (Y) plane of a `width`x`height` frame in `format`, one byte per
+/// pixel with a row stride of `width`.
+///
+/// NV12, I420 and GREY borrow the first plane of `pix` directly. YUYV
+/// interleaves luma with chroma, so every other byte is copied out. Returns
+/// `None` for formats without a luma plane or when `pix` is too short.
+pub fn luma_plane(pix: &[u8], format: FourCC, width: u32, height: u32) -> Option<Cow<'_, [u8]>> {
+    let len = width as usize * height as usize;
+    match format {
+        // The luma plane ends where the chroma plane starts.
+        NV12 | I420 => pix
+            .get(..plane_offsets(format, width, height)[1])
+            .map(Cow::Borrowed),
+        GREY => pix.get(..len).map(Cow::Borrowed),
+        YUYV => pix
+            .get(..len * 2)
+            .map(|pix| Cow::Owned(pix.iter().step_by(2).copied().collect())),
+        _ => None,
+    }
 }
 
 impl Image {
@@ -434,8 +1839,12 @@ impl Image {
     ///
     /// Returns an error if:
     /// - DMA heap allocation fails (out of memory)
-    /// - Invalid dimensions or format specified
-    /// - DMA heap device is not accessible
+    /// - `width` or `height` is zero, reported as
+    ///   [`CameraError::InvalidDimensions`]
+    /// - `format` is not one [`Image`] knows the layout of, reported as
+    ///   [`CameraError::UnsupportedFormat`]
+    /// - DMA heap device is not accessible; a missing device node is
+    ///   reported as [`CameraError::DmaHeapUnavailable`]
     ///
     /// # Example
     ///
@@ -449,13 +1858,19 @@ impl Image {
     /// # }
     /// ```
     pub fn new(width: u32, height: u32, format: FourCC) -> Result<Self, Box<dyn Error>> {
-        let heap = Heap::new(HeapKind::Cma)?;
-        let fd = heap.allocate(image_size(width, height, format))?;
+        check_dimensions(width, height)?;
+        let size =
+            image_size(width, height, format).ok_or(CameraError::UnsupportedFormat { format })?;
+        let heap = Heap::new(HeapKind::Cma)
+            .map_err(|e| heap_open_error(Path::new(CMA_HEAP_PATH), e.into()))?;
+        let fd = heap.allocate(size)?;
         Ok(Self {
             fd,
             width,
             height,
             format,
+            stride: packed_stride(format, width),
+            phys: OnceLock::new(),
         })
     }
 
@@ -465,13 +1880,47 @@ impl Image {
             width,
             height,
             format,
+            stride: packed_stride(format, width),
+            phys: OnceLock::new(),
         }
     }
 
+    /// Wraps `fd` like [`new_preallocated`](Self::new_preallocated) for a
+    /// buffer whose rows are `stride` bytes apart, such as a camera frame
+    /// imported from another process with padded `bytesperline`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for a pixel format [`Image`] does not know
+    /// ([`CameraError::UnsupportedFormat`]) or a stride shorter than the
+    /// packed row of `width` pixels.
+    pub fn new_preallocated_with_stride(
+        fd: OwnedFd,
+        width: u32,
+        height: u32,
+        format: FourCC,
+        stride: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        check_format(format)?;
+        let packed = packed_stride(format, width);
+        if stride < packed {
+            return Err(format!(
+                "{width}-pixel {format} rows need {packed} bytes but the stride is {stride}"
+            )
+            .into());
+        }
+        Ok(Self {
+            stride,
+            ..Self::new_preallocated(fd, width, height, format)
+        })
+    }
+
     /// Creates an `Image` from a V4L2 camera buffer.
     ///
     /// Wraps an existing V4L2 camera buffer (from the videostream library)
     /// in an `Image` structure, enabling G2D operations on camera frames.
+    /// The stride is the buffer's `bytesperline`, which drivers may pad
+    /// beyond the packed row, e.g. to 64 bytes on the i.MX8 ISP.
     ///
     /// # Arguments
     ///
@@ -479,15 +1928,24 @@ impl Image {
     ///
     /// # Errors
     ///
-    /// Returns an error if the file descriptor cannot be duplicated.
+    /// Returns an error if the buffer reports a zero or negative size
+    /// ([`CameraError::InvalidDimensions`]), a pixel format [`Image`] does
+    /// not know ([`CameraError::UnsupportedFormat`]), or the file descriptor
+    /// cannot be duplicated.
     pub fn from_camera(buffer: &CameraBuffer) -> Result<Self, Box<dyn Error>> {
+        let (width, height) = (buffer.width() as u32, buffer.height() as u32);
+        check_dimensions(width, height)?;
         let fd = buffer.fd();
+        let format = buffer.format();
+        check_format(format)?;
 
         Ok(Self {
             fd: fd.try_clone_to_owned()?,
-            width: buffer.width() as u32,
-            height: buffer.height() as u32,
-            format: buffer.format(),
+            width,
+            height,
+            format,
+            stride: buffer.bytes_per_line()?.max(packed_stride(format, width)),
+            phys: OnceLock::new(),
         })
     }
 
@@ -499,10 +1957,74 @@ impl Image {
         self.fd.as_raw_fd()
     }
 
+    /// Returns an owned `DmaBuf` backed by a `dup()` of the image fd.
+    ///
+    /// Prefer [`with_dma_buf`](Self::with_dma_buf) for short-lived access
+    /// such as mapping the buffer for one encode.
     pub fn dmabuf(&self) -> DmaBuf {
         unsafe { DmaBuf::from_raw_fd(dup(self.fd.as_raw_fd())) }
     }
 
+    /// Calls `f` with a `DmaBuf` that borrows the image fd instead of
+    /// duplicating it, so per-frame access does not churn file descriptors.
+    ///
+    /// ```no_run
+    /// use edgefirst_camera::image::{encode_jpeg, Image, RGBA};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let img = Image::new(640, 480, RGBA)?;
+    /// let jpeg = img.with_dma_buf(|dma| {
+    ///     let buf = dma.memory_map()?.read(encode_jpeg, Some(&img))?;
+    ///     Ok::<_, Box<dyn std::error::Error>>(buf)
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_dma_buf<R>(&self, f: impl FnOnce(&DmaBuf) -> R) -> R {
+        // The DmaBuf must never close the fd it borrows from `self.fd`; the
+        // reference handed to `f` cannot outlive this call.
+        let dma = ManuallyDrop::new(unsafe { DmaBuf::from_raw_fd(self.fd.as_raw_fd()) });
+        f(&dma)
+    }
+
+    /// A full-frame [`G2DSurface`] of the buffer for a G2D blit.
+    ///
+    /// The physical address is looked up once, on the first call, and
+    /// reused for the life of the image: the fd, and the buffer behind it,
+    /// never change, so per-frame conversions make no further ioctl.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the physical address cannot be found, for
+    /// example for a buffer G2D cannot reach, or if the format or stride
+    /// cannot be described to G2D.
+    pub fn as_g2d_surface(&self) -> Result<G2DSurface, Box<dyn Error>> {
+        let addr = match self.phys.get() {
+            Some(&addr) => addr,
+            None => {
+                let addr = G2DPhysical::new(self.fd.as_raw_fd())?.address();
+                *self.phys.get_or_init(|| addr)
+            }
+        };
+        surface_at(self.format, self.width, self.height, self.stride, addr)
+    }
+
+    /// Creates a second handle to the same DMA buffer by duplicating the fd.
+    ///
+    /// Used to hand one captured frame to several consumers; the buffer is
+    /// released once every handle has been dropped.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            fd: self.fd.try_clone()?,
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            stride: self.stride,
+            // Same buffer, same address.
+            phys: self.phys.clone(),
+        })
+    }
+
     pub fn width(&self) -> u32 {
         self.width
     }
@@ -515,26 +2037,154 @@ impl Image {
         self.format
     }
 
+    /// Bytes per row of the first plane. Images allocated here are packed;
+    /// camera buffers may pad their rows. NV12 chroma rows share the luma
+    /// stride.
+    pub fn stride(&self) -> u32 {
+        self.stride
+    }
+
+    /// Bytes the image occupies: `stride * height`, plus the chroma rows of
+    /// NV12 and I420. See [`buffer_length`](Self::buffer_length) for the size of the
+    /// buffer holding it.
     pub fn size(&self) -> usize {
-        format_row_stride(self.format, self.width) * self.height as usize
+        if self.stride == packed_stride(self.format, self.width) {
+            if let Some(size) = image_size(self.width, self.height, self.format) {
+                return size;
+            }
+        }
+        self.stride as usize * plane_rows(self.format, self.height)
     }
 
-    pub fn mmap(&mut self) -> MappedImage {
-        let image_size = image_size(self.width, self.height, self.format);
-        unsafe {
-            let mmap = mmap(
+    /// Length in bytes of the DMA buffer behind the image, as the kernel
+    /// reports it. Allocators round buffers up, commonly to whole pages, and
+    /// drivers may pad them further, so this is at least
+    /// [`size`](Self::size) for any buffer that holds the image.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `lseek` error for a file that cannot report its size.
+    pub fn buffer_length(&self) -> io::Result<usize> {
+        // DMA-BUFs report their size at SEEK_END and accept only a rewind
+        // back to 0; nothing reads through the file offset.
+        let len = unsafe { libc::lseek(self.raw_fd(), 0, libc::SEEK_END) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        unsafe { libc::lseek(self.raw_fd(), 0, libc::SEEK_SET) };
+        Ok(len as usize)
+    }
+
+    /// Makes data a device wrote to the buffer visible to CPU reads by
+    /// starting and ending a DMA-BUF read access (`DMA_BUF_IOCTL_SYNC`),
+    /// which invalidates cache lines left over from earlier CPU access.
+    ///
+    /// [`ImageManager`] conversions call this on their destination once the
+    /// blit has finished, and [`mmap`](Self::mmap) syncs on its own; call it
+    /// after other device writes before reading the buffer through another
+    /// mapping.
+    ///
+    /// # Errors
+    ///
+    /// Returns the ioctl error, for example for a buffer that is not a
+    /// DMA-BUF.
+    pub fn sync_for_cpu(&self) -> io::Result<()> {
+        dma_buf_sync(self.fd(), DMA_BUF_SYNC_START | DMA_BUF_SYNC_READ)?;
+        dma_buf_sync(self.fd(), DMA_BUF_SYNC_END | DMA_BUF_SYNC_READ)
+    }
+
+    /// Maps the buffer for CPU access.
+    ///
+    /// The mapping starts a DMA-BUF CPU access (`DMA_BUF_IOCTL_SYNC`) that
+    /// ends when the [`MappedImage`] is dropped. Starting invalidates cache
+    /// lines left over from earlier CPU access, so data written by G2D or
+    /// another device is read back rather than stale cache contents; ending
+    /// flushes CPU writes before the next device operation. Map after the
+    /// device operation has finished and drop the mapping before the next.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `mmap` error, for example `EMFILE` or `ENOMEM` when the
+    /// process has run out of file descriptors or address space.
+    pub fn mmap(&mut self) -> io::Result<MappedImage<'_>> {
+        self.map()
+    }
+
+    /// Calls `f` with the pixels of the buffer, mapped as
+    /// [`mmap`](Self::mmap) maps them but through a shared reference and
+    /// without duplicating the fd, for per-frame reads such as JPEG
+    /// encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `mmap` error, as [`mmap`](Self::mmap) does.
+    pub fn with_mapping<R>(&self, f: impl FnOnce(&[u8]) -> R) -> io::Result<R> {
+        let mapped = self.map()?;
+        Ok(f(mapped.as_slice()))
+    }
+
+    /// [`mmap`](Self::mmap) through a shared reference, for the software
+    /// [`ImageManager`], which writes its destination while the caller
+    /// holds only `&Image` as it does for G2D. Callers must not hold two
+    /// mappings of one buffer at once, see [`same_buffer`]. The CPU
+    /// [`pipeline`](crate::pipeline) stages map their source and their own
+    /// output buffers with it too.
+    pub(crate) fn map(&self) -> io::Result<MappedImage<'_>> {
+        let image_size = self.size();
+        let sync = Some(self.fd()).filter(|fd| {
+            dma_buf_sync(*fd, DMA_BUF_SYNC_START | DMA_BUF_SYNC_RW)
+                .map_err(|e| debug!("DMA-BUF sync unavailable for {self}: {e}"))
+                .is_ok()
+        });
+        let mmap = unsafe {
+            mmap(
                 null_mut(),
                 image_size,
                 PROT_READ | PROT_WRITE,
                 MAP_SHARED,
                 self.raw_fd(),
                 0,
-            ) as *mut u8;
-            MappedImage {
-                mmap,
-                len: image_size,
+            )
+        };
+        if mmap == MAP_FAILED {
+            let err = io::Error::last_os_error();
+            if let Some(fd) = sync {
+                let _ = dma_buf_sync(fd, DMA_BUF_SYNC_END | DMA_BUF_SYNC_RW);
             }
+            return Err(err);
         }
+        Ok(MappedImage {
+            mmap: mmap.cast(),
+            len: image_size,
+            sync,
+        })
+    }
+
+    /// Overwrite the buffer with a black frame: zero RGB with opaque alpha,
+    /// limited-range black (Y 16, chroma 128) for YUYV and NV12, and zero
+    /// samples for raw Bayer and Y16.
+    pub fn fill_black(&mut self) -> Result<(), Box<dyn Error>> {
+        let format = self.format;
+        let luma_len = self.stride as usize * self.height as usize;
+        let mut mapped = self.mmap()?;
+        let data = mapped.as_slice_mut();
+        match format {
+            RGBX | RGBA => data
+                .chunks_exact_mut(4)
+                .for_each(|px| px.copy_from_slice(&[0, 0, 0, 255])),
+            YUYV => data
+                .chunks_exact_mut(4)
+                .for_each(|px| px.copy_from_slice(&[16, 128, 16, 128])),
+            NV12 => {
+                let (luma, chroma) = data.split_at_mut(luma_len.min(data.len()));
+                luma.fill(16);
+                chroma.fill(128);
+            }
+            RGB3 | Y16 => data.fill(0),
+            _ if BayerPattern::from_fourcc(format).is_some() => data.fill(0),
+            _ => return Err(format!("cannot fill a {format} image with black").into()),
+        }
+        Ok(())
     }
 }
 
@@ -563,21 +2213,61 @@ impl fmt::Display for Image {
     }
 }
 
+/// `DMA_BUF_IOCTL_SYNC`, i.e. `_IOW('b', 0, struct dma_buf_sync)`.
+const DMA_BUF_IOCTL_SYNC: u64 = 0x4008_6200;
+
+/// `DMA_BUF_SYNC_READ`
+const DMA_BUF_SYNC_READ: u64 = 1;
+
+/// `DMA_BUF_SYNC_READ | DMA_BUF_SYNC_WRITE`
+const DMA_BUF_SYNC_RW: u64 = 3;
+
+/// `DMA_BUF_SYNC_START`
+const DMA_BUF_SYNC_START: u64 = 0;
+
+/// `DMA_BUF_SYNC_END`
+const DMA_BUF_SYNC_END: u64 = 4;
+
+/// Mirror of the kernel's `struct dma_buf_sync`.
+#[repr(C)]
+struct DmaBufSync {
+    flags: u64,
+}
+
+/// Begin or end CPU access to a DMA buffer, retrying on `EINTR`.
+fn dma_buf_sync(fd: BorrowedFd<'_>, flags: u64) -> io::Result<()> {
+    let sync = DmaBufSync { flags };
+    loop {
+        let ret = unsafe { libc::ioctl(fd.as_raw_fd(), DMA_BUF_IOCTL_SYNC as _, &sync) };
+        if ret == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
 /// Memory-mapped view of an `Image` buffer.
 ///
 /// Provides CPU-accessible view of a DMA image buffer through memory mapping.
-/// The mapping is automatically unmapped when dropped.
+/// The mapping borrows the image it was made from and is automatically
+/// unmapped when dropped, ending the CPU access begun by [`Image::mmap`].
 ///
 /// # Safety
 ///
 /// While the API is safe, concurrent access from hardware and CPU can lead to
 /// race conditions. Ensure hardware operations complete before CPU access.
-pub struct MappedImage {
+pub struct MappedImage<'a> {
     mmap: *mut u8,
     len: usize,
+    /// The image's handle the CPU access was started on, `None` when the
+    /// buffer does not support DMA-BUF sync.
+    sync: Option<BorrowedFd<'a>>,
 }
 
-impl MappedImage {
+impl MappedImage<'_> {
     pub fn as_slice(&self) -> &[u8] {
         unsafe { from_raw_parts(self.mmap, self.len) }
     }
@@ -586,18 +2276,24 @@ impl MappedImage {
         unsafe { from_raw_parts_mut(self.mmap, self.len) }
     }
 }
-impl Drop for MappedImage {
+impl Drop for MappedImage<'_> {
     fn drop(&mut self) {
+        if let Some(fd) = self.sync {
+            if let Err(e) = dma_buf_sync(fd, DMA_BUF_SYNC_END | DMA_BUF_SYNC_RW) {
+                warn!("DMA-BUF sync end failed: {e}");
+            }
+        }
         if unsafe { munmap(self.mmap.cast::<c_void>(), self.len) } > 0 {
             warn!("unmap failed!");
         }
     }
 }
 
-/// Encodes an RGBA image to JPEG format using turbojpeg.
+/// Encodes an RGBA image to JPEG format, at [`jpeg::DEFAULT_QUALITY`] with
+/// 4:2:0 chroma; [`encode_jpeg_with`] chooses both.
 ///
-/// Uses the turbojpeg library with SIMD optimizations for fast JPEG
-/// compression.
+/// Uses the default [`jpeg::Backend`]: turbojpeg with SIMD optimizations,
+/// or the pure-Rust encoder when built without the `turbojpeg` feature.
 ///
 /// # Arguments
 ///
@@ -622,21 +2318,29 @@ impl Drop for MappedImage {
 ///
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// let mut img = Image::new(640, 480, RGBA)?;
-/// let mut mapped = img.mmap();
+/// let mut mapped = img.mmap()?;
 /// let jpeg = encode_jpeg(mapped.as_slice(), Some(&img))?;
 /// println!("Compressed to {} bytes", jpeg.len());
 /// # Ok(())
 /// # }
 /// ```
-pub fn encode_jpeg(pix: &[u8], img: Option<&Image>) -> Result<OwnedBuf, Box<dyn Error>> {
-    let img2 = match img {
-        Some(img) => turbojpeg::Image {
-            width: img.width() as usize,
-            height: img.height() as usize,
-            format: turbojpeg::PixelFormat::RGBA,
-            pixels: pix,
-            pitch: img.width() as usize * 4,
-        },
+pub fn encode_jpeg(pix: &[u8], img: Option<&Image>) -> Result<JpegBuf, Box<dyn Error>> {
+    encode_jpeg_with(pix, img, jpeg::DEFAULT_QUALITY, JpegSubsamp::Sub2x2)
+}
+
+/// [`encode_jpeg`] at `quality`, from 1 to 100, with `subsamp` chroma.
+///
+/// # Errors
+///
+/// As [`encode_jpeg`], and when `quality` is outside 1 to 100.
+pub fn encode_jpeg_with(
+    pix: &[u8],
+    img: Option<&Image>,
+    quality: u8,
+    subsamp: JpegSubsamp,
+) -> Result<JpegBuf, Box<dyn Error>> {
+    let img = match img {
+        Some(img) => img,
         None => {
             return Err(Box::new(io::Error::new(
                 io::ErrorKind::InvalidInput,
@@ -645,9 +2349,574 @@ pub fn encode_jpeg(pix: &[u8], img: Option<&Image>) -> Result<OwnedBuf, Box<dyn
         }
     };
 
-    let res = turbojpeg::compress(img2, 100, turbojpeg::Subsamp::Sub2x2);
-    match res {
-        Ok(buf) => Ok(buf),
-        Err(e) => Err(Box::new(e)),
+    jpeg::compress_rgba_with(
+        jpeg::Backend::default(),
+        pix,
+        img.width(),
+        img.height(),
+        quality,
+        subsamp,
+    )
+}
+
+/// Encode the luma plane of `img` as a greyscale JPEG.
+///
+/// Takes the same arguments as [`encode_jpeg`] so it can be passed to
+/// `MemoryMap::read`, but expects a YUYV or NV12 image. Only the Y plane
+/// is read (see [`luma_plane`]), so no G2D conversion is needed.
+///
+/// # Errors
+///
+/// Returns an error if image metadata is not provided, the image format
+/// has no luma plane, or JPEG compression fails.
+pub fn encode_jpeg_luma(pix: &[u8], img: Option<&Image>) -> Result<JpegBuf, Box<dyn Error>> {
+    encode_jpeg_luma_with(pix, img, jpeg::DEFAULT_QUALITY)
+}
+
+/// [`encode_jpeg_luma`] at `quality`, from 1 to 100.
+///
+/// # Errors
+///
+/// As [`encode_jpeg_luma`], and when `quality` is outside 1 to 100.
+pub fn encode_jpeg_luma_with(
+    pix: &[u8],
+    img: Option<&Image>,
+    quality: u8,
+) -> Result<JpegBuf, Box<dyn Error>> {
+    let img =
+        img.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no image provided"))?;
+    let luma = luma_plane(pix, img.format(), img.width(), img.height())
+        .ok_or_else(|| format!("cannot extract the luma plane of a {} image", img.format()))?;
+
+    jpeg::compress_gray_with(
+        jpeg::Backend::default(),
+        &luma,
+        img.width(),
+        img.height(),
+        quality,
+    )
+}
+
+/// Decodes the JPEG in `data` into `dst`, the inverse of [`encode_jpeg`],
+/// for example to feed a saved snapshot back into the pipeline.
+///
+/// # Errors
+///
+/// Returns an error if `dst` is neither RGBA nor RGB3, its size differs
+/// from the JPEG's, or the JPEG does not decode.
+#[cfg(feature = "turbojpeg")]
+pub fn decode_jpeg(data: &[u8], dst: &Image) -> Result<(), Box<dyn Error>> {
+    let channels = match dst.format() {
+        RGBA => 4,
+        RGB3 => 3,
+        format => return Err(format!("cannot decode a JPEG into a {format} image").into()),
+    };
+    let mut mapped = dst.map()?;
+    jpeg::decompress(
+        data,
+        mapped.as_slice_mut(),
+        dst.width(),
+        dst.height(),
+        dst.stride() as usize,
+        channels,
+    )
+}
+
+/// [`decode_jpeg`] into a new RGBA [`Image`] of the JPEG's size.
+///
+/// # Errors
+///
+/// As [`decode_jpeg`] and [`Image::new`].
+#[cfg(feature = "turbojpeg")]
+pub fn decode_jpeg_to_image(data: &[u8]) -> Result<Image, Box<dyn Error>> {
+    let (width, height) = jpeg::dimensions(data)?;
+    let img = Image::new(width, height, RGBA)?;
+    decode_jpeg(data, &img)?;
+    Ok(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_manager_is_send_and_sync() {
+        fn shareable<T: Send + Sync>() {}
+        shareable::<ImageManager>();
+    }
+
+    #[test]
+    fn align_crop_snaps_yuv_to_chroma_samples() {
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        // Aligned crops are unchanged, odd edges round down.
+        assert_eq!(
+            align_crop(rect(960, 0, 960, 540), YUYV),
+            rect(960, 0, 960, 540)
+        );
+        assert_eq!(
+            align_crop(rect(961, 1, 960, 541), YUYV),
+            rect(960, 1, 960, 541)
+        );
+        assert_eq!(
+            align_crop(rect(961, 1, 960, 541), NV12),
+            rect(960, 0, 960, 542)
+        );
+        assert_eq!(align_crop(rect(3, 3, 1, 1), NV12), rect(2, 2, 2, 2));
+        assert_eq!(align_crop(rect(3, 3, 1, 1), RGBA), rect(3, 3, 1, 1));
+    }
+
+    #[test]
+    fn blit_budget_refills_at_its_rate() {
+        // 10 blits/s holds the two-blit minimum.
+        let budget = BlitBudget::new(10);
+        let t0 = Instant::now();
+        assert_eq!(budget.try_take(2, t0), Ok(()));
+        let wait = budget.try_take(1, t0).unwrap_err();
+        assert!((wait.as_secs_f64() - 0.1).abs() < 1e-6, "{wait:?}");
+        assert_eq!(budget.try_take(1, t0 + Duration::from_millis(100)), Ok(()));
+        // Idle time refills no more than the capacity.
+        let later = t0 + Duration::from_secs(10);
+        assert_eq!(budget.try_take(2, later), Ok(()));
+        assert!(budget.try_take(1, later).is_err());
+        assert_eq!(budget.take(1, BlitPriority::Low), Err(BlitBudgetExceeded));
+    }
+
+    #[test]
+    fn blit_budget_keeps_measured_rate_under_cap() {
+        let cap = 50;
+        let budget = BlitBudget::new(cap);
+        let start = Instant::now();
+        let blits = 30;
+        for _ in 0..blits {
+            budget.take(1, BlitPriority::High).unwrap();
+        }
+        // Beyond the initial burst every blit waits for its token.
+        let elapsed = start.elapsed().as_secs_f64();
+        let allowed = budget.capacity + cap as f64 * elapsed;
+        assert!(blits as f64 <= allowed, "{blits} blits in {elapsed:.3}s");
+        assert!(elapsed >= (blits as f64 - budget.capacity) / cap as f64 - 0.01);
+    }
+
+    #[test]
+    fn surface_trace_lists_every_field() {
+        // A 64x32 NV12 image puts its chroma plane right after 64 * 32
+        // luma bytes.
+        let mut surface = surface_at(NV12, 64, 32, 64, 0x1000_0000).unwrap();
+        surface.rot = g2d_rotation_G2D_ROTATION_180;
+        let trace = SurfaceTrace(&surface).to_string();
+        assert_eq!(
+            trace,
+            format!(
+                "format=NV12(code {g2d_format_G2D_NV12}) planes=[0x10000000, 0x10000800, 0x0] \
+                 rect=(0,0)-(64,32) stride=64 size=64x32 rot={g2d_rotation_G2D_ROTATION_180}"
+            )
+        );
+
+        let rgba = surface_at(RGBA, 16, 8, 64, 0x2000).unwrap();
+        assert!(SurfaceTrace(&rgba)
+            .to_string()
+            .starts_with("format=RGBA8888("));
+        assert!(surface_at(SRGGB8, 16, 8, 16, 0x2000).is_err());
+    }
+
+    #[test]
+    fn surfaces_keep_padded_strides() {
+        // 600-pixel YUYV rows padded from 1200 to 1216 bytes, as the i.MX8
+        // ISP pads to 64 bytes.
+        let yuyv = surface_at(YUYV, 600, 4, 1216, 0x1000).unwrap();
+        assert_eq!((yuyv.stride, yuyv.right), (608, 600));
+
+        // The chroma plane starts after the padded luma rows.
+        let nv12 = surface_at(NV12, 600, 4, 640, 0x1000).unwrap();
+        assert_eq!(nv12.stride, 640);
+        assert_eq!(nv12.planes[1], 0x1000 + 640 * 4);
+
+        // I420's U and V planes hold a quarter of the luma bytes each.
+        let i420 = surface_at(I420, 600, 4, 640, 0x1000).unwrap();
+        assert_eq!(i420.stride, 640);
+        assert_eq!(
+            i420.planes,
+            [0x1000, 0x1000 + 640 * 4, 0x1000 + 640 * 4 + 320 * 2]
+        );
+
+        // Shorter than a packed row, or not a whole number of pixels.
+        assert!(surface_at(YUYV, 600, 4, 1198, 0x1000).is_err());
+        assert!(surface_at(RGBA, 16, 8, 66, 0x1000).is_err());
+    }
+
+    #[test]
+    fn dma_buf_sync_matches_kernel_abi() {
+        // _IOW('b', 0, struct dma_buf_sync)
+        let size = std::mem::size_of::<DmaBufSync>() as u64;
+        assert_eq!(size, 8);
+        assert_eq!(
+            DMA_BUF_IOCTL_SYNC,
+            (1 << 30) | (size << 16) | ((b'b' as u64) << 8)
+        );
+        // DMA_BUF_SYNC_READ (1 << 0) and DMA_BUF_SYNC_WRITE (1 << 1).
+        assert_eq!(DMA_BUF_SYNC_RW, DMA_BUF_SYNC_READ | 2);
+    }
+
+    #[test]
+    fn dma_buf_sync_rejects_other_files() {
+        // Buffers that are not DMA-BUFs are mapped without sync.
+        let file = std::fs::File::open("/dev/null").unwrap();
+        assert!(dma_buf_sync(file.as_fd(), DMA_BUF_SYNC_START | DMA_BUF_SYNC_RW).is_err());
+    }
+
+    #[test]
+    fn same_buffer_sees_through_duplicates() {
+        let dir = std::env::temp_dir();
+        let open = |name: &str| {
+            let path = dir.join(format!("{name}-{}", std::process::id()));
+            let file = std::fs::File::create(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            Image::new_preallocated(file.into(), 2, 2, RGBA)
+        };
+        let a = open("same-buffer-a");
+        let b = open("same-buffer-b");
+        assert!(same_buffer(&a, &a));
+        assert!(same_buffer(&a, &a.try_clone().unwrap()));
+        assert!(!same_buffer(&a, &b));
+
+        let err = software_convert(&a, &a.try_clone().unwrap(), &ConvertOptions::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cannot write its source"), "{err}");
+    }
+
+    #[test]
+    fn pyramid_sizes_halve_each_level() {
+        assert_eq!(
+            pyramid_sizes(1920, 1080, &[1, 2, 4]).unwrap(),
+            vec![(1920, 1080), (960, 540), (480, 270)]
+        );
+        assert_eq!(
+            pyramid_sizes(640, 480, &[2, 8]).unwrap(),
+            vec![(320, 240), (80, 60)]
+        );
+        assert!(pyramid_sizes(1920, 1080, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn pyramid_sizes_rejects_bad_scales() {
+        // 1080 / 8 = 135 is odd.
+        assert!(pyramid_sizes(1920, 1080, &[1, 2, 8]).is_err());
+        // 1080 / 7 does not divide evenly.
+        assert!(pyramid_sizes(1920, 1080, &[7]).is_err());
+        assert!(pyramid_sizes(1920, 1080, &[0]).is_err());
+        // Not increasing.
+        assert!(pyramid_sizes(1920, 1080, &[2, 1]).is_err());
+        assert!(pyramid_sizes(1920, 1080, &[2, 2]).is_err());
+    }
+
+    #[test]
+    fn composite_inset_in_bottom_right_corner() {
+        let options = CompositeOptions::bottom_right(1920, 1080, 4, 16);
+        assert_eq!(
+            options.inset,
+            Rect {
+                x: 1424,
+                y: 794,
+                width: 480,
+                height: 270,
+            }
+        );
+        assert!(options.check(1920, 1080).is_ok());
+        // Odd sizes are rounded down to even.
+        let options = CompositeOptions::bottom_right(1920, 1080, 3, 0);
+        assert_eq!((options.inset.width, options.inset.height), (640, 360));
+        assert!(options.check(1920, 1080).is_ok());
+    }
+
+    #[test]
+    fn composite_rejects_bad_insets() {
+        let inset = |x, y, width, height| CompositeOptions {
+            inset: Rect {
+                x,
+                y,
+                width,
+                height,
+            },
+        };
+        assert!(inset(0, 0, 0, 64).check(640, 480).is_err());
+        assert!(inset(-2, 0, 64, 64).check(640, 480).is_err());
+        assert!(inset(1, 0, 64, 64).check(640, 480).is_err());
+        assert!(inset(0, 0, 64, 63).check(640, 480).is_err());
+        assert!(inset(600, 0, 64, 64).check(640, 480).is_err());
+        assert!(inset(576, 416, 64, 64).check(640, 480).is_ok());
+    }
+
+    #[test]
+    fn convert_options_check_dst_rect() {
+        assert!(ConvertOptions::default().check(640, 480).is_ok());
+        let into = |x, y, width, height| ConvertOptions {
+            dst_rect: Some(Rect {
+                x,
+                y,
+                width,
+                height,
+            }),
+            ..Default::default()
+        };
+        assert!(into(320, 240, 320, 240).check(640, 480).is_ok());
+        let err = into(322, 240, 320, 240).check(640, 480).unwrap_err();
+        assert!(
+            err.to_string().starts_with("destination rectangle"),
+            "{err}"
+        );
+        assert!(into(320, 241, 320, 240).check(640, 480).is_err());
+        assert!(into(0, 0, 0, 240).check(640, 480).is_err());
+    }
+
+    fn version(major: i64, minor: i64, patch: i64, num: i64) -> Version {
+        Version {
+            major: major as _,
+            minor: minor as _,
+            patch: patch as _,
+            num: num as _,
+        }
+    }
+
+    #[test]
+    fn nv12_luma_plane_is_the_first_width_by_height_bytes() {
+        let (width, height) = (8, 4);
+        let pix: Vec<u8> = (0..image_size(width, height, NV12).unwrap())
+            .map(|i| i as u8)
+            .collect();
+        assert_eq!(plane_offsets(NV12, width, height), [0, 32]);
+
+        let luma = luma_plane(&pix, NV12, width, height).unwrap();
+        assert!(
+            matches!(luma, Cow::Borrowed(_)),
+            "NV12 luma must not be copied"
+        );
+        assert_eq!(&luma[..], &pix[..32]);
+    }
+
+    #[test]
+    fn yuyv_luma_plane_skips_chroma() {
+        // Y0 U Y1 V for a 2x1 image.
+        let pix = [10, 128, 20, 129];
+        assert_eq!(&luma_plane(&pix, YUYV, 2, 1).unwrap()[..], [10, 20]);
+        assert_eq!(plane_offsets(YUYV, 2, 1), [0]);
+    }
+
+    #[test]
+    fn luma_plane_rejects_rgb_and_short_buffers() {
+        assert!(luma_plane(&[0; 16], RGBA, 2, 2).is_none());
+        assert!(luma_plane(&[0; 3], NV12, 2, 2).is_none());
+        assert!(luma_plane(&[0; 7], YUYV, 2, 2).is_none());
+    }
+
+    #[test]
+    fn missing_dma_heap_is_explained() {
+        let dir = std::env::temp_dir().join(format!("no-dma-heap-{}", std::process::id()));
+        let err = heap_open_error(&dir.join("linux,cma"), "ENOENT".into());
+        match err.downcast_ref::<CameraError>() {
+            Some(CameraError::DmaHeapUnavailable { path }) => {
+                assert_eq!(path, &dir.join("linux,cma"))
+            }
+            _ => panic!("expected DmaHeapUnavailable, got: {err}"),
+        }
+        let msg = err.to_string();
+        assert!(msg.contains("CONFIG_DMABUF_HEAPS_CMA"), "{msg}");
+        assert!(msg.contains("linux,cma"), "{msg}");
+    }
+
+    #[test]
+    fn zero_dimensions_are_rejected() {
+        for (width, height) in [(0, 1080), (1920, 0), (0, 0)] {
+            let err = Image::new(width, height, YUYV).unwrap_err();
+            match err.downcast_ref::<CameraError>() {
+                Some(&CameraError::InvalidDimensions {
+                    width: w,
+                    height: h,
+                }) => {
+                    assert_eq!((w, h), (width, height))
+                }
+                _ => panic!("expected InvalidDimensions, got: {err}"),
+            }
+        }
+    }
+
+    #[test]
+    fn negative_dimensions_are_rejected() {
+        // A negative i32 from a driver or message, cast to u32.
+        assert!(check_dimensions(-1i32 as u32, 1080).is_err());
+        assert!(check_dimensions(1920, i32::MIN as u32).is_err());
+        assert!(check_dimensions(1, 1).is_ok());
+        assert!(check_dimensions(i32::MAX as u32, 1).is_ok());
+    }
+
+    #[test]
+    fn unsupported_formats_are_an_error_not_a_panic() {
+        let mjpg = FourCC(*b"MJPG");
+        assert_eq!(image_size(1920, 1080, mjpg), None);
+        assert_eq!(packed_stride(mjpg, 1920), 0);
+        assert!(check_format(YUYV).is_ok());
+
+        // Rejected before the DMA heap is opened, so this runs anywhere.
+        let err = Image::new(1920, 1080, mjpg).unwrap_err();
+        match err.downcast_ref::<CameraError>() {
+            Some(CameraError::UnsupportedFormat { format }) => assert_eq!(*format, mjpg),
+            _ => panic!("expected UnsupportedFormat, got: {err}"),
+        }
+        assert!(err.to_string().contains("MJPG"), "{err}");
+    }
+
+    #[test]
+    fn nv12_stride_is_the_luma_row() {
+        // The row stride is the luma plane's, not the frame size over the
+        // height; the chroma rows only add to the size.
+        let (width, height) = (1920, 1080);
+        let stride = packed_stride(NV12, width);
+        assert_eq!(stride, 1920);
+        assert_eq!(image_size(width, height, NV12), Some(1920 * 1080 * 3 / 2));
+
+        let surface = surface_at(NV12, width, height, stride, 0x1000).unwrap();
+        assert_eq!(surface.stride, 1920);
+        assert_eq!(surface.planes[1], 0x1000 + 1920 * 1080);
+    }
+
+    #[test]
+    fn i420_grey_and_bgra_layouts() {
+        let (width, height) = (8, 4);
+        assert_eq!(image_size(width, height, I420), Some(48));
+        assert_eq!(image_size(width, height, GREY), Some(32));
+        assert_eq!(image_size(width, height, BGRA), Some(128));
+        assert_eq!(packed_stride(I420, width), 8);
+        assert_eq!(packed_stride(GREY, width), 8);
+        assert_eq!(packed_stride(BGRA, width), 32);
+        assert_eq!(plane_stride(SRGGB10, width), Some(16));
+        assert_eq!(plane_stride(Y16, width), Some(16));
+        assert_eq!(plane_offsets(I420, width, height), [0, 32, 40]);
+
+        let pix: Vec<u8> = (0..48).collect();
+        for format in [I420, GREY] {
+            let luma = luma_plane(&pix, format, width, height).unwrap();
+            assert!(matches!(luma, Cow::Borrowed(_)), "{format}");
+            assert_eq!(&luma[..], &pix[..32]);
+        }
+        assert!(luma_plane(&pix, BGRA, width, height).is_none());
+    }
+
+    #[test]
+    fn other_heap_errors_pass_through() {
+        // The node exists, so the original error (say EACCES) is kept.
+        let err = heap_open_error(Path::new("/dev/null"), "permission denied".into());
+        assert!(err.downcast_ref::<CameraError>().is_none());
+        assert_eq!(err.to_string(), "permission denied");
+    }
+
+    #[test]
+    fn g2d_version_gate_boundaries() {
+        assert!(g2d_version_at_least(&G2D_2_3_0, &G2D_2_3_0));
+        assert!(!g2d_version_at_least(
+            &version(6, 4, 10, 1049710),
+            &G2D_2_3_0
+        ));
+        assert!(g2d_version_at_least(&version(6, 4, 12, 0), &G2D_2_3_0));
+        assert!(g2d_version_at_least(&version(6, 5, 0, 0), &G2D_2_3_0));
+        assert!(g2d_version_at_least(&version(7, 0, 0, 0), &G2D_2_3_0));
+        assert!(!g2d_version_at_least(&version(5, 9, 99, 0), &G2D_2_3_0));
+    }
+
+    #[test]
+    fn g2d_version_gate_ignores_build_number() {
+        // A larger build number must not lift an older patch release over
+        // the gate, and a smaller one must not drop a newer release below.
+        assert!(!g2d_version_at_least(
+            &version(6, 4, 10, i64::from(u32::MAX)),
+            &G2D_2_3_0
+        ));
+        assert!(g2d_version_at_least(&version(6, 4, 11, 0), &G2D_2_3_0));
+    }
+
+    #[test]
+    fn rotate_rgba_turns_clockwise() {
+        // A 3x2 frame of distinct pixels:
+        //   0 1 2
+        //   3 4 5
+        let rgba: Vec<u8> = (0..6u8).flat_map(|i| [i, 0, 0, 255]).collect();
+        let rotated = |rotation| -> Vec<u8> {
+            rotate_rgba(&rgba, 3, 2, rotation)
+                .chunks_exact(4)
+                .map(|px| px[0])
+                .collect()
+        };
+        assert_eq!(rotated(Rotation::Rotation0), [0, 1, 2, 3, 4, 5]);
+        // 2x3 with the left column on top.
+        assert_eq!(rotated(Rotation::Rotation90), [3, 0, 4, 1, 5, 2]);
+        assert_eq!(rotated(Rotation::Rotation180), [5, 4, 3, 2, 1, 0]);
+        assert_eq!(rotated(Rotation::Rotation270), [2, 5, 1, 4, 0, 3]);
+    }
+
+    #[test]
+    fn flip_rgba_mirrors_after_rotation() {
+        // The 3x2 frame above.
+        let rgba: Vec<u8> = (0..6u8).flat_map(|i| [i, 0, 0, 255]).collect();
+        let flipped = |flip| -> Vec<u8> {
+            flip_rgba(rgba.clone(), 3, 2, flip)
+                .chunks_exact(4)
+                .map(|px| px[0])
+                .collect()
+        };
+        assert_eq!(flipped(Flip::None), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(flipped(Flip::Horizontal), [2, 1, 0, 5, 4, 3]);
+        assert_eq!(flipped(Flip::Vertical), [3, 4, 5, 0, 1, 2]);
+        assert_eq!(flipped(Flip::Both), [5, 4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn g2d_orientation_needs_a_source_flip_only_with_quarter_turns() {
+        let rot0 = g2d_rotation_G2D_ROTATION_0;
+        for rotation in [Rotation::Rotation0, Rotation::Rotation180] {
+            for flip in [Flip::None, Flip::Horizontal, Flip::Vertical, Flip::Both] {
+                assert_eq!(
+                    g2d_orientation(rotation, flip).0,
+                    rot0,
+                    "{rotation:?} {flip:?}"
+                );
+            }
+        }
+        assert_eq!(
+            g2d_orientation(Rotation::Rotation0, Flip::Horizontal),
+            (rot0, g2d_rotation_G2D_FLIP_H)
+        );
+        assert_eq!(
+            g2d_orientation(Rotation::Rotation180, Flip::Horizontal),
+            (rot0, g2d_rotation_G2D_FLIP_V)
+        );
+        assert_eq!(
+            g2d_orientation(Rotation::Rotation90, Flip::Both),
+            (rot0, g2d_rotation_G2D_ROTATION_270)
+        );
+        assert_eq!(
+            g2d_orientation(Rotation::Rotation90, Flip::Horizontal),
+            (g2d_rotation_G2D_FLIP_V, g2d_rotation_G2D_ROTATION_90)
+        );
+        assert_eq!(
+            g2d_orientation(Rotation::Rotation270, Flip::Vertical),
+            (g2d_rotation_G2D_FLIP_H, g2d_rotation_G2D_ROTATION_270)
+        );
+
+        let cpu = ImageManager::software();
+        assert!(cpu.can_flip(Rotation::Rotation90, Flip::Horizontal));
+    }
+
+    #[test]
+    fn software_formats() {
+        for format in [YUYV, NV12, RGBA, RGBX, BGRA, RGB3] {
+            assert!(software_supports(format), "{format}");
+        }
+        for format in [I420, GREY, Y16, SRGGB8] {
+            assert!(!software_supports(format), "{format}");
+        }
     }
 }
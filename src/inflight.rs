@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! `--max-in-flight`: a process-wide cap on the frames handed to outputs.
+//!
+//! Every frame an output is offered is a new handle on a DMA buffer, with
+//! its own file descriptor. Each encoder channel is bounded, but with tiles
+//! and variants there are many of them, and a backed-up pipeline can hold
+//! enough handles to run out of descriptors or keep CMA buffers alive. A
+//! [`Permit`] is taken from [`IN_FLIGHT`] before the handle is duplicated
+//! and travels with the frame through the channel. The output returns it by
+//! dropping the frame, and when none is left the frame is dropped for that
+//! output, as for a full channel.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts frames in flight against a limit.
+pub struct InFlight {
+    /// Most frames in flight, 0 for no limit.
+    limit: AtomicUsize,
+    count: AtomicUsize,
+}
+
+/// The frames in flight between the capture loop and every output.
+pub static IN_FLIGHT: InFlight = InFlight::new();
+
+impl InFlight {
+    /// No limit until [`InFlight::set_limit`].
+    pub const fn new() -> Self {
+        Self {
+            limit: AtomicUsize::new(0),
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Allow at most `limit` frames in flight, 0 for no limit. Permits
+    /// already taken are kept.
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Frames in flight now.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// A permit for one more frame, or `None` when the limit is reached.
+    pub fn try_acquire(&self) -> Option<Permit<'_>> {
+        let limit = self.limit.load(Ordering::Relaxed);
+        self.count
+            .fetch_update(Ordering::AcqRel, Ordering::Relaxed, |count| {
+                (limit == 0 || count < limit).then_some(count + 1)
+            })
+            .ok()?;
+        Some(Permit(self))
+    }
+}
+
+/// One frame in flight, returned to its [`InFlight`] on drop.
+#[must_use]
+pub struct Permit<'a>(&'a InFlight);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.0.count.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::mpsc::{self, TrySendError},
+        thread,
+        time::Duration,
+    };
+
+    #[test]
+    fn permits_are_returned_on_drop() {
+        let in_flight = InFlight::new();
+        in_flight.set_limit(2);
+        let a = in_flight.try_acquire().unwrap();
+        let b = in_flight.try_acquire().unwrap();
+        assert!(in_flight.try_acquire().is_none());
+        drop(a);
+        assert_eq!(in_flight.count(), 1);
+        let _c = in_flight.try_acquire().unwrap();
+        drop(b);
+
+        in_flight.set_limit(0);
+        let unlimited: Vec<_> = (0..100).map(|_| in_flight.try_acquire()).collect();
+        assert!(unlimited.iter().all(Option::is_some));
+        assert_eq!(in_flight.count(), 101);
+    }
+
+    #[test]
+    fn backpressure_never_exceeds_the_limit() {
+        const LIMIT: usize = 4;
+        static IN_FLIGHT: InFlight = InFlight::new();
+        IN_FLIGHT.set_limit(LIMIT);
+
+        // Three slow outputs whose channels could hold nine frames between
+        // them.
+        let (txs, consumers): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (tx, rx) = mpsc::sync_channel::<Permit<'static>>(3);
+                let consumer = thread::spawn(move || {
+                    while let Ok(permit) = rx.recv() {
+                        assert!(IN_FLIGHT.count() <= LIMIT);
+                        thread::sleep(Duration::from_millis(2));
+                        drop(permit);
+                    }
+                });
+                (tx, consumer)
+            })
+            .unzip();
+
+        let mut refused = 0;
+        for _ in 0..500 {
+            for tx in &txs {
+                let Some(permit) = IN_FLIGHT.try_acquire() else {
+                    refused += 1;
+                    continue;
+                };
+                assert!(IN_FLIGHT.count() <= LIMIT);
+                match tx.try_send(permit) {
+                    Ok(()) | Err(TrySendError::Full(_)) => {}
+                    Err(TrySendError::Disconnected(_)) => unreachable!(),
+                }
+            }
+            thread::sleep(Duration::from_micros(100));
+        }
+        drop(txs);
+        for consumer in consumers {
+            consumer.join().unwrap();
+        }
+
+        assert!(refused > 0, "the limit never applied");
+        assert_eq!(IN_FLIGHT.count(), 0);
+    }
+}
@@ -0,0 +1,419 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! JPEG compression backends.
+//!
+//! [`Backend::TurboJpeg`] (the default `turbojpeg` feature) compresses with
+//! libjpeg-turbo and its SIMD code, and is the fast path. It links the
+//! system libjpeg-turbo at build time, which some minimal targets do not
+//! have. The `pure-jpeg` feature adds [`Backend::Pure`], the pure-Rust
+//! `jpeg-encoder` crate, which builds everywhere at several times the
+//! encode time. [`Backend::default`] is turbojpeg when it is compiled in,
+//! so building with `--no-default-features --features pure-jpeg` drops the
+//! libjpeg-turbo dependency.
+//!
+//! Both backends write baseline JPEGs, by default at quality
+//! [`DEFAULT_QUALITY`] with 4:2:0 chroma subsampling for colour images;
+//! [`compress_rgba_with`] and [`compress_gray_with`] choose both.
+//!
+//! [`decompress`] reads JPEGs back, for round-trip tests and snapshot
+//! ingest. Only libjpeg-turbo decodes, so it needs the `turbojpeg` feature.
+//!
+//! ```
+//! use edgefirst_camera::jpeg::{self, Backend};
+//!
+//! // A 16x16 mid-grey RGBA image.
+//! let pix = [128; 16 * 16 * 4];
+//! let jpeg = jpeg::compress_rgba(Backend::default(), &pix, 16, 16).unwrap();
+//! assert_eq!(&jpeg[..2], [0xff, 0xd8]);
+//! ```
+
+#[cfg(not(any(feature = "turbojpeg", feature = "pure-jpeg")))]
+compile_error!("JPEG output needs the turbojpeg or pure-jpeg feature");
+
+use std::{error::Error, ops::Deref};
+
+use crate::config::JpegSubsamp;
+
+/// Quality of [`compress_rgba`] and [`compress_gray`], and the default of
+/// `--jpeg-quality`.
+pub const DEFAULT_QUALITY: u8 = 85;
+
+/// A JPEG encoder implementation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// libjpeg-turbo through the `turbojpeg` crate.
+    #[cfg(feature = "turbojpeg")]
+    TurboJpeg,
+    /// The pure-Rust `jpeg-encoder` crate.
+    #[cfg(feature = "pure-jpeg")]
+    Pure,
+}
+
+#[cfg(feature = "turbojpeg")]
+const DEFAULT_BACKEND: Backend = Backend::TurboJpeg;
+#[cfg(not(feature = "turbojpeg"))]
+const DEFAULT_BACKEND: Backend = Backend::Pure;
+
+impl Default for Backend {
+    /// turbojpeg when it is compiled in, otherwise the pure-Rust encoder.
+    fn default() -> Self {
+        DEFAULT_BACKEND
+    }
+}
+
+/// A compressed JPEG, kept in the buffer of the backend that wrote it so
+/// the turbojpeg output is not copied.
+pub enum JpegBuf {
+    #[cfg(feature = "turbojpeg")]
+    TurboJpeg(turbojpeg::OwnedBuf),
+    #[cfg(feature = "pure-jpeg")]
+    Pure(Vec<u8>),
+}
+
+impl Deref for JpegBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(feature = "turbojpeg")]
+            JpegBuf::TurboJpeg(buf) => &buf[..],
+            #[cfg(feature = "pure-jpeg")]
+            JpegBuf::Pure(buf) => &buf[..],
+        }
+    }
+}
+
+impl AsRef<[u8]> for JpegBuf {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+/// Compress a tightly packed `width`x`height` RGBA image at
+/// [`DEFAULT_QUALITY`] with 4:2:0 chroma. Alpha is ignored.
+///
+/// # Errors
+///
+/// Returns an error if `pix` is shorter than the image, or the backend
+/// fails, e.g. the pure-Rust encoder on an image wider or taller than
+/// 65535 pixels.
+pub fn compress_rgba(
+    backend: Backend,
+    pix: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<JpegBuf, Box<dyn Error>> {
+    compress_rgba_with(
+        backend,
+        pix,
+        width,
+        height,
+        DEFAULT_QUALITY,
+        JpegSubsamp::Sub2x2,
+    )
+}
+
+/// [`compress_rgba`] at `quality`, from 1 to 100, with `subsamp` chroma.
+///
+/// # Errors
+///
+/// As [`compress_rgba`], and when `quality` is outside 1 to 100.
+pub fn compress_rgba_with(
+    backend: Backend,
+    pix: &[u8],
+    width: u32,
+    height: u32,
+    quality: u8,
+    subsamp: JpegSubsamp,
+) -> Result<JpegBuf, Box<dyn Error>> {
+    compress(backend, pix, width, height, 4, quality, subsamp)
+}
+
+/// Compress a tightly packed `width`x`height` 8-bit greyscale image at
+/// [`DEFAULT_QUALITY`].
+///
+/// # Errors
+///
+/// As [`compress_rgba`].
+pub fn compress_gray(
+    backend: Backend,
+    pix: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<JpegBuf, Box<dyn Error>> {
+    compress_gray_with(backend, pix, width, height, DEFAULT_QUALITY)
+}
+
+/// [`compress_gray`] at `quality`, from 1 to 100.
+///
+/// # Errors
+///
+/// As [`compress_rgba_with`].
+pub fn compress_gray_with(
+    backend: Backend,
+    pix: &[u8],
+    width: u32,
+    height: u32,
+    quality: u8,
+) -> Result<JpegBuf, Box<dyn Error>> {
+    compress(backend, pix, width, height, 1, quality, JpegSubsamp::Sub2x2)
+}
+
+/// The width and height of the JPEG in `data`, from its header.
+///
+/// # Errors
+///
+/// Returns an error if `data` does not start with a JPEG header.
+#[cfg(feature = "turbojpeg")]
+pub fn dimensions(data: &[u8]) -> Result<(u32, u32), Box<dyn Error>> {
+    let header = turbojpeg::read_header(data)?;
+    Ok((header.width as u32, header.height as u32))
+}
+
+/// Decompress the JPEG in `data` into `pix`, a `width`x`height` RGBA
+/// (`channels` 4) or RGB (`channels` 3) image with rows `pitch` bytes
+/// apart.
+///
+/// # Errors
+///
+/// Returns an error if the JPEG is not `width`x`height`, `pix` is shorter
+/// than the image, or libjpeg-turbo cannot decode `data`.
+#[cfg(feature = "turbojpeg")]
+pub fn decompress(
+    data: &[u8],
+    pix: &mut [u8],
+    width: u32,
+    height: u32,
+    pitch: usize,
+    channels: usize,
+) -> Result<(), Box<dyn Error>> {
+    let (w, h) = dimensions(data)?;
+    if (w, h) != (width, height) {
+        return Err(format!("{w}x{h} JPEG does not fit a {width}x{height} image").into());
+    }
+    let len = pitch * height as usize;
+    if pitch < width as usize * channels || pix.len() < len {
+        return Err(format!(
+            "{width}x{height} image with {pitch}-byte rows needs {len} bytes, got {}",
+            pix.len()
+        )
+        .into());
+    }
+    let format = match channels {
+        4 => turbojpeg::PixelFormat::RGBA,
+        _ => turbojpeg::PixelFormat::RGB,
+    };
+    let image = turbojpeg::Image {
+        width: width as usize,
+        height: height as usize,
+        format,
+        pixels: &mut pix[..len],
+        pitch,
+    };
+    turbojpeg::Decompressor::new()?.decompress(data, image)?;
+    Ok(())
+}
+
+/// Compress an RGBA (`channels` 4) or greyscale (`channels` 1) image.
+/// Greyscale images ignore `subsamp`.
+fn compress(
+    backend: Backend,
+    pix: &[u8],
+    width: u32,
+    height: u32,
+    channels: usize,
+    quality: u8,
+    subsamp: JpegSubsamp,
+) -> Result<JpegBuf, Box<dyn Error>> {
+    // Both backends would clamp it silently.
+    if !(1..=100).contains(&quality) {
+        return Err(format!("JPEG quality must be from 1 to 100, got {quality}").into());
+    }
+    let len = width as usize * height as usize * channels;
+    if pix.len() < len {
+        return Err(format!(
+            "{width}x{height} image needs {len} bytes, got {}",
+            pix.len()
+        )
+        .into());
+    }
+    match backend {
+        #[cfg(feature = "turbojpeg")]
+        Backend::TurboJpeg => {
+            let (format, subsamp) = match channels {
+                4 => (
+                    turbojpeg::PixelFormat::RGBA,
+                    match subsamp {
+                        JpegSubsamp::Sub1x1 => turbojpeg::Subsamp::None,
+                        JpegSubsamp::Sub2x1 => turbojpeg::Subsamp::Sub2x1,
+                        JpegSubsamp::Sub2x2 => turbojpeg::Subsamp::Sub2x2,
+                    },
+                ),
+                _ => (turbojpeg::PixelFormat::GRAY, turbojpeg::Subsamp::Gray),
+            };
+            let image = turbojpeg::Image {
+                width: width as usize,
+                height: height as usize,
+                format,
+                pixels: pix,
+                pitch: width as usize * channels,
+            };
+            let buf = turbojpeg::compress(image, quality as i32, subsamp)?;
+            Ok(JpegBuf::TurboJpeg(buf))
+        }
+        #[cfg(feature = "pure-jpeg")]
+        Backend::Pure => {
+            let (w, h) = match (u16::try_from(width), u16::try_from(height)) {
+                (Ok(w), Ok(h)) => (w, h),
+                _ => {
+                    return Err(format!(
+                        "pure-Rust JPEG encoder cannot encode a {width}x{height} image"
+                    )
+                    .into())
+                }
+            };
+            let color = match channels {
+                4 => jpeg_encoder::ColorType::Rgba,
+                _ => jpeg_encoder::ColorType::Luma,
+            };
+            let mut buf = Vec::new();
+            let mut encoder = jpeg_encoder::Encoder::new(&mut buf, quality);
+            encoder.set_sampling_factor(match subsamp {
+                JpegSubsamp::Sub1x1 => jpeg_encoder::SamplingFactor::R_4_4_4,
+                JpegSubsamp::Sub2x1 => jpeg_encoder::SamplingFactor::R_4_2_2,
+                JpegSubsamp::Sub2x2 => jpeg_encoder::SamplingFactor::R_4_2_0,
+            });
+            encoder.encode(&pix[..len], w, h, color)?;
+            Ok(JpegBuf::Pure(buf))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 64x48 RGBA image with smooth gradients in every channel.
+    fn gradient() -> Vec<u8> {
+        (0..48u32)
+            .flat_map(|y| {
+                (0..64u32).flat_map(move |x| [x as u8 * 4, y as u8 * 5, (x + y) as u8, 255])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn default_backend_writes_a_jpeg() {
+        let jpeg = compress_rgba(Backend::default(), &gradient(), 64, 48).unwrap();
+        assert_eq!(jpeg[..2], [0xff, 0xd8]);
+        assert_eq!(jpeg[jpeg.len() - 2..], [0xff, 0xd9]);
+
+        let grey = compress_gray(Backend::default(), &[200; 64 * 48], 64, 48).unwrap();
+        assert_eq!(grey[..2], [0xff, 0xd8]);
+
+        assert!(compress_rgba(Backend::default(), &gradient()[1..], 64, 48).is_err());
+    }
+
+    #[test]
+    fn quality_and_subsampling_trade_size() {
+        let src = gradient();
+        let size = |quality, subsamp| {
+            compress_rgba_with(Backend::default(), &src, 64, 48, quality, subsamp)
+                .unwrap()
+                .len()
+        };
+        assert!(size(30, JpegSubsamp::Sub2x2) < size(100, JpegSubsamp::Sub2x2));
+        assert!(size(90, JpegSubsamp::Sub2x2) < size(90, JpegSubsamp::Sub1x1));
+
+        for quality in [0, 101] {
+            let err = compress_rgba_with(
+                Backend::default(),
+                &src,
+                64,
+                48,
+                quality,
+                JpegSubsamp::Sub2x2,
+            )
+            .err()
+            .unwrap();
+            assert!(err.to_string().contains("1 to 100"), "{err}");
+            assert!(
+                compress_gray_with(Backend::default(), &[200; 64 * 48], 64, 48, quality).is_err()
+            );
+        }
+    }
+
+    /// Peak signal-to-noise ratio of `b` against `a`, in dB.
+    #[cfg(feature = "turbojpeg")]
+    fn psnr(a: &[u8], b: &[u8]) -> f64 {
+        let mse = a
+            .iter()
+            .zip(b)
+            .map(|(&a, &b)| (a as f64 - b as f64).powi(2))
+            .sum::<f64>()
+            / a.len() as f64;
+        10.0 * (255.0 * 255.0 / mse).log10()
+    }
+
+    #[cfg(feature = "turbojpeg")]
+    #[test]
+    fn decompress_round_trips_a_gradient() {
+        let src = gradient();
+        let jpeg =
+            compress_rgba_with(Backend::TurboJpeg, &src, 64, 48, 95, JpegSubsamp::Sub1x1).unwrap();
+        assert_eq!(dimensions(&jpeg).unwrap(), (64, 48));
+
+        let mut rgba = vec![0; src.len()];
+        decompress(&jpeg, &mut rgba, 64, 48, 64 * 4, 4).unwrap();
+        let psnr = psnr(&src, &rgba);
+        assert!(psnr > 35.0, "PSNR {psnr:.1} dB");
+
+        // RGB into rows padded to 256 bytes.
+        let mut rgb = vec![0; 256 * 48];
+        decompress(&jpeg, &mut rgb, 64, 48, 256, 3).unwrap();
+        let rgb_src: Vec<u8> = src
+            .chunks_exact(4)
+            .flat_map(|px| [px[0], px[1], px[2]])
+            .collect();
+        let rows: Vec<u8> = rgb
+            .chunks_exact(256)
+            .flat_map(|row| &row[..64 * 3])
+            .copied()
+            .collect();
+        assert!(psnr(&rgb_src, &rows) > 35.0);
+
+        assert!(decompress(&jpeg, &mut rgba, 32, 48, 32 * 4, 4).is_err());
+        assert!(decompress(&jpeg, &mut rgba[1..], 64, 48, 64 * 4, 4).is_err());
+        assert!(dimensions(&src).is_err());
+    }
+
+    #[cfg(all(feature = "turbojpeg", feature = "pure-jpeg"))]
+    #[test]
+    fn both_backends_encode_the_same_image() {
+        let src = gradient();
+        for backend in [Backend::TurboJpeg, Backend::Pure] {
+            let jpeg = compress_rgba_with(backend, &src, 64, 48, 100, JpegSubsamp::Sub2x2).unwrap();
+            let decoded: turbojpeg::Image<Vec<u8>> =
+                turbojpeg::decompress(&jpeg, turbojpeg::PixelFormat::RGBA).unwrap();
+            assert_eq!((decoded.width, decoded.height), (64, 48), "{backend:?}");
+            // Quality 100 with 4:2:0 chroma stays close on smooth gradients.
+            let error = decoded
+                .pixels
+                .iter()
+                .zip(&src)
+                .map(|(&a, &b)| (a as i32 - b as i32).unsigned_abs())
+                .sum::<u32>() as f64
+                / src.len() as f64;
+            assert!(error < 2.0, "{backend:?} mean error {error}");
+
+            let grey = compress_gray_with(backend, &[200; 64 * 48], 64, 48, 100).unwrap();
+            let decoded: turbojpeg::Image<Vec<u8>> =
+                turbojpeg::decompress(&grey, turbojpeg::PixelFormat::GRAY).unwrap();
+            assert!(
+                decoded.pixels.iter().all(|&p| p.abs_diff(200) <= 1),
+                "{backend:?}"
+            );
+        }
+    }
+}
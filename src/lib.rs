@@ -14,9 +14,31 @@
 //!   for zero-copy inter-process communication.
 //! - **Hardware Acceleration**: Leverage NXP i.MX8 G2D hardware for format
 //!   conversion, scaling, cropping, and rotation operations.
-//! - **JPEG Encoding**: Hardware-optimized JPEG compression using turbojpeg
-//!   with SIMD.
+//! - **JPEG Encoding**: Fast JPEG compression using turbojpeg with SIMD,
+//!   or a pure-Rust encoder for targets without libjpeg-turbo ([`jpeg`]).
+//! - **CPU Conversion**: YUYV/NV12 to RGBA in [`cpu`], with NEON inner
+//!   loops on aarch64, and raw Bayer demosaicing for sensors without an
+//!   ISP ([`pipeline::Debayer`]).
+//! - **H.264 Encoding**: Hardware video encoding through
+//!   [`video::VideoManager`], with SPS aspect-ratio signalling in [`sps`].
 //! - **V4L2 Integration**: Seamless integration with V4L2 camera buffers.
+//! - **Duplicate Frames**: Spot a sensor repeating its last frame with
+//!   [`dedup`].
+//! - **Dirty Rectangles**: Find the region of a frame that changed, to
+//!   encode only that, with [`dirty`].
+//! - **Debug Dumps**: Save frames as NumPy `.npy` arrays with [`npy`].
+//! - **Lens Correction**: Undistort frames from a camera calibration on
+//!   the CPU with [`rectify`].
+//! - **Depth Frames**: Encode 16-bit Y16 frames losslessly as PNG with
+//!   [`png`].
+//! - **Frame Checksums**: CRC-32 of published buffers for corruption
+//!   hunting in [`checksum`].
+//! - **Frame Import**: Turn a published `CameraFrame` back into an
+//!   [`image::Image`] with [`remote::decode_dma_message`].
+//! - **Pipeline**: Capture frames from any [`pipeline::CameraSource`] and fan
+//!   them out to closure sinks without the Zenoh/CLI binary.
+//! - **Test Patterns**: Synthetic colour bars and other frames from
+//!   [`pattern::PatternSource`], for bring-up without a camera.
 //!
 //! ## Example
 //!
@@ -40,8 +62,8 @@
 //! ## Platform Requirements
 //!
 //! - **Linux**: Kernel 5.10+ with V4L2 and DMA heap support
-//! - **Hardware Acceleration**: NXP i.MX8M Plus for G2D operations (software
-//!   fallback available on other platforms)
+//! - **Hardware Acceleration**: NXP i.MX8M Plus for G2D operations; elsewhere
+//!   [`image::ImageManager`] falls back to converting on the CPU
 //!
 //! ## Safety
 //!
@@ -49,4 +71,20 @@
 //! and DMA buffer operations. All unsafe operations are isolated to specific
 //! modules and wrapped with safe APIs.
 
+pub mod checksum;
+pub mod color;
+pub mod config;
+pub mod cpu;
+pub mod dedup;
+pub mod dirty;
 pub mod image;
+pub mod jpeg;
+pub mod nal;
+pub mod npy;
+pub mod pattern;
+pub mod pipeline;
+pub mod png;
+pub mod rectify;
+pub mod remote;
+pub mod sps;
+pub mod video;
@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Journald logging that cannot take the node down.
+//!
+//! `tracing_journald::layer()` only checks that the journald socket can be
+//! opened. In containers the socket may be bind-mounted yet unusable, and
+//! the layer then fails while formatting or sending an event. Send errors
+//! are already swallowed by `tracing_journald`; [`Resilient`] covers the
+//! rest by switching the layer off the first time it panics.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tracing::{span, Event, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+/// The journald layer, or `None` when `disabled` (`--no-journald`) is set or
+/// journald is not reachable.
+pub fn journald_layer(disabled: bool) -> Option<Resilient<tracing_journald::Layer>> {
+    if disabled {
+        return None;
+    }
+    tracing_journald::layer().ok().map(Resilient::new)
+}
+
+/// Wraps a layer and disables it for the rest of the process the first
+/// time one of its callbacks panics, instead of letting the panic unwind
+/// through the code that logged.
+pub struct Resilient<L> {
+    inner: L,
+    disabled: AtomicBool,
+}
+
+impl<L> Resilient<L> {
+    pub fn new(inner: L) -> Self {
+        Self {
+            inner,
+            disabled: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns `true` once the wrapped layer has failed and been switched off.
+    pub fn is_disabled(&self) -> bool {
+        self.disabled.load(Ordering::Relaxed)
+    }
+
+    fn guard(&self, f: impl FnOnce()) {
+        if self.is_disabled() {
+            return;
+        }
+        if panic::catch_unwind(AssertUnwindSafe(f)).is_err()
+            && !self.disabled.swap(true, Ordering::Relaxed)
+        {
+            // Logging through tracing here could re-enter the failing layer.
+            eprintln!("journald logging failed, disabling the journald layer");
+        }
+    }
+}
+
+impl<S, L> Layer<S> for Resilient<L>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    L: Layer<S>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        self.guard(|| self.inner.on_new_span(attrs, id, ctx));
+    }
+
+    fn on_record(&self, span: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        self.guard(|| self.inner.on_record(span, values, ctx));
+    }
+
+    fn on_follows_from(&self, span: &span::Id, follows: &span::Id, ctx: Context<'_, S>) {
+        self.guard(|| self.inner.on_follows_from(span, follows, ctx));
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        self.guard(|| self.inner.on_event(event, ctx));
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        self.guard(|| self.inner.on_enter(id, ctx));
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        self.guard(|| self.inner.on_exit(id, ctx));
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        self.guard(|| self.inner.on_close(id, ctx));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{atomic::AtomicUsize, Arc};
+    use tracing::info;
+    use tracing_subscriber::{layer::SubscriberExt as _, Registry};
+
+    /// Stands in for a journald layer whose socket breaks after startup.
+    struct FailingLayer {
+        events: Arc<AtomicUsize>,
+    }
+
+    impl<S: Subscriber> Layer<S> for FailingLayer {
+        fn on_event(&self, _event: &Event<'_>, _ctx: Context<'_, S>) {
+            self.events.fetch_add(1, Ordering::SeqCst);
+            panic!("journald socket went away");
+        }
+    }
+
+    #[test]
+    fn subscriber_stack_without_journald() {
+        let journald = journald_layer(true);
+        assert!(journald.is_none());
+
+        let subscriber = Registry::default()
+            .with(tracing_subscriber::fmt::layer().with_test_writer())
+            .with(journald);
+        tracing::subscriber::with_default(subscriber, || {
+            info!("logging works with journald forced off");
+        });
+    }
+
+    #[test]
+    fn failing_layer_is_disabled_instead_of_panicking() {
+        let events = Arc::new(AtomicUsize::new(0));
+        let layer = Arc::new(Resilient::new(FailingLayer {
+            events: events.clone(),
+        }));
+
+        let subscriber = Registry::default().with(ArcLayer(layer.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            info!("first event hits the failure");
+            info!("later events skip the disabled layer");
+        });
+
+        assert!(layer.is_disabled());
+        assert_eq!(events.load(Ordering::SeqCst), 1);
+    }
+
+    /// Lets the test keep a handle on the layer it installs.
+    struct ArcLayer<L>(Arc<L>);
+
+    impl<S, L> Layer<S> for ArcLayer<L>
+    where
+        S: Subscriber,
+        L: Layer<S>,
+    {
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            self.0.on_event(event, ctx);
+        }
+    }
+}
@@ -2,13 +2,54 @@
 // Copyright (c) 2025 Au-Zone Technologies. All Rights Reserved.
 
 mod args;
+mod cma;
+mod device;
+mod framerate;
+mod inflight;
+mod logging;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod output;
+mod pixfmt;
+mod recording;
 mod replay;
+mod roi;
+mod sensor;
 mod sidecar;
-mod video;
-
-use args::{Args, MirrorSetting};
-use clap::Parser;
-use edgefirst_camera::image::{encode_jpeg, Image, ImageManager, Rotation, RGBA};
+#[cfg(feature = "srt")]
+mod srt;
+mod stats;
+mod thermal;
+mod timing;
+mod transcode;
+mod unixsock;
+mod v4l2out;
+
+use args::{
+    Args, BayerPattern, Codec, FrameFormat, H264Framing, H264Stamp, H264Variant, JpegPlane,
+};
+use clap::{Parser, ValueEnum};
+use edgefirst_camera::{
+    checksum::{self, image_crc32},
+    color::{self, ColorCorrection},
+    dedup::{DedupAction, DuplicateDetector},
+    dirty::{self, Dirty, DirtyTracker},
+    image::{
+        self, encode_jpeg_luma_with, encode_jpeg_with, BlitBudgetExceeded, BlitPriority, Image,
+        ImageManager, Rect, Rotation, RGBA,
+    },
+    jpeg,
+    nal::{self, NAL_TYPE_SPS},
+    npy,
+    pipeline::{
+        is_interrupted, CameraSource, CapturedFrame, ColorCorrect, ConvertTarget, Debayer,
+        ErrorFrames, FormatConverter, Liveness, Pipeline, RateLimiter, Rectify, Watchdog,
+    },
+    png,
+    rectify::RemapTable,
+    sps,
+    video::{VideoManager, WallclockKeyframes},
+};
 use edgefirst_schemas::{
     builtin_interfaces::{self, Time},
     edgefirst_msgs::{CameraFrame, CameraPlaneView},
@@ -16,32 +57,47 @@ use edgefirst_schemas::{
     geometry_msgs::{Quaternion, Transform, TransformStamped, Vector3},
     sensor_msgs::{CameraInfo, CompressedImage, RegionOfInterest},
 };
-use kanal::{Receiver, Sender};
+use inflight::{Permit, IN_FLIGHT};
+use kanal::{ReceiveErrorTimeout, Receiver, Sender};
+use recording::Recorder;
+use roi::CropControl;
 use sidecar::Sidecar;
+use stats::{BitrateWindow, STATS};
 use std::{
     env,
     error::Error,
     fs::File,
+    future::Future,
+    path::Path,
     process,
-    sync::atomic::{AtomicBool, Ordering},
-    thread::{self},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
-use tracing::{error, info, info_span, instrument, level_filters::LevelFilter, warn, Instrument};
+use thermal::{ThermalGovernor, THERMAL};
+use tracing::{
+    debug, error, info, info_span, instrument, level_filters::LevelFilter, warn, Instrument,
+};
 use tracing_subscriber::{layer::SubscriberExt as _, EnvFilter, Layer as _, Registry};
 use tracy_client::{frame_mark, plot, secondary_frame_mark};
+use transcode::FrameSource;
 use unix_ts::Timestamp;
-use video::VideoManager;
+use unixsock::{FrameKind, UnixSocketOutput};
+use v4l2out::V4l2Output;
 use videostream::{
-    camera::{create_camera, CameraBuffer, CameraReader, Mirror},
+    camera::{create_camera, CameraReader, Mirror},
     colorimetry::{ColorEncoding, ColorRange, ColorSpace, ColorTransfer},
     fourcc::FourCC,
 };
 use zenoh::{
     bytes::{Encoding, ZBytes},
+    pubsub::Publisher,
     qos::{CongestionControl, Priority},
     time::{Timestamp as ZenohTimestamp, NTP64},
-    Session,
+    Session, Wait,
 };
 
 /// Global shutdown flag for graceful termination
@@ -52,8 +108,6 @@ static SHUTDOWN: AtomicBool = AtomicBool::new(false);
 static GLOBAL: tracy_client::ProfiledAllocator<std::alloc::System> =
     tracy_client::ProfiledAllocator::new(std::alloc::System, 100);
 
-const TARGET_FPS: i32 = 30;
-
 #[derive(Clone, Copy, Debug)]
 enum TilePosition {
     TopLeft,
@@ -101,10 +155,20 @@ fn update_fps(prev: &mut Instant, history: &mut [f64], index: &mut usize) -> f64
     1e9 / avg
 }
 
-fn get_env_filter() -> EnvFilter {
-    tracing_subscriber::EnvFilter::builder()
+/// Filter directive that `--g2d-trace` adds to show the per-blit surface
+/// logs of the image module.
+const G2D_TRACE_DIRECTIVE: &str = "edgefirst_camera::image=trace";
+
+/// The `RUST_LOG` filter, defaulting to `info`, with the image module at
+/// trace level when `g2d_trace` is set.
+fn get_env_filter(g2d_trace: bool) -> EnvFilter {
+    let mut filter = tracing_subscriber::EnvFilter::builder()
         .with_default_directive(LevelFilter::INFO.into())
-        .from_env_lossy()
+        .from_env_lossy();
+    if g2d_trace {
+        filter = filter.add_directive(G2D_TRACE_DIRECTIVE.parse().expect("valid directive"));
+    }
+    filter
 }
 
 #[tokio::main]
@@ -128,20 +192,56 @@ async fn main() -> Result<(), Box<dyn Error>> {
     });
 
     let mut args = Args::parse();
+    args.apply_topic_namespace()?;
 
     // Validate record/replay arg combinations before touching anything.
     validate_record_replay_args(&args)?;
+    transcode::validate_transcode_args(&args)?;
+    if args.h264 {
+        let (width, height) = args
+            .h264_output_rotation()
+            .rotated_size(args.stream_size[0], args.stream_size[1]);
+        VideoManager::check_size(width as i32, height as i32)?;
+    }
+    validate_h264_variants(&args)?;
+    validate_codec(&args)?;
+    if args.h264 || args.h264_tiles || !args.h264_variant.is_empty() {
+        VideoManager::check_b_frames(args.h264_bframes)?;
+    }
+    if args.jpeg
+        && args.jpeg_plane == JpegPlane::Luma
+        && args.jpeg_output_rotation() != Rotation::Rotation0
+    {
+        return Err(Box::from(
+            "--jpeg-plane luma cannot rotate the JPEG output, unset --jpeg-rotation or --rotation",
+        ));
+    }
+    if args.hires_interval.is_some() && !args.jpeg {
+        return Err(Box::from(
+            "--hires-interval requires --jpeg (it encodes the JPEG output's frames)",
+        ));
+    }
+    if args.jpeg_dirty_rect && (!args.jpeg || args.jpeg_plane != JpegPlane::All) {
+        return Err(Box::from(
+            "--jpeg-dirty-rect requires --jpeg with --jpeg-plane all (it compares colour frames)",
+        ));
+    }
 
     args.tracy.then(tracy_client::Client::start);
 
+    if let Some(ops) = args.g2d_max_ops_per_sec {
+        image::set_max_blits_per_sec(ops)?;
+    }
+    if let Some(frames) = args.max_in_flight {
+        IN_FLIGHT.set_limit(frames as usize);
+    }
+
     let stdout_log = tracing_subscriber::fmt::layer()
         .pretty()
-        .with_filter(get_env_filter());
+        .with_filter(get_env_filter(args.g2d_trace));
 
-    let journald = match tracing_journald::layer() {
-        Ok(journald) => Some(journald.with_filter(get_env_filter())),
-        Err(_) => None,
-    };
+    let journald = logging::journald_layer(args.no_journald)
+        .map(|journald| journald.with_filter(get_env_filter(args.g2d_trace)));
 
     let (console, console_server) = match args.tokio_console {
         true => {
@@ -158,18 +258,47 @@ async fn main() -> Result<(), Box<dyn Error>> {
     };
 
     let tracy = match args.tracy {
-        true => Some(tracing_tracy::TracyLayer::default().with_filter(get_env_filter())),
+        true => {
+            Some(tracing_tracy::TracyLayer::default().with_filter(get_env_filter(args.g2d_trace)))
+        }
         false => None,
     };
 
+    let timings = args
+        .timing_log
+        .map(|_| Arc::new(timing::SpanTimings::default()));
+    let timing = timings.clone().map(timing::TimingLayer::new);
+
     let subscriber = Registry::default()
         .with(stdout_log)
         .with(journald)
         .with(console)
-        .with(tracy);
+        .with(tracy)
+        .with(timing);
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
     tracing_log::LogTracer::init()?;
 
+    if let (Some(timings), Some(secs)) = (timings, args.timing_log) {
+        timing::spawn_reporter(timings, Duration::from_secs(secs))?;
+    }
+
+    if let Some(zone) = args.thermal_zone {
+        let governor = ThermalGovernor::new(
+            args.thermal_threshold as i32 * 1000,
+            args.thermal_step as i32 * 1000,
+            args.thermal_hysteresis as i32 * 1000,
+        );
+        thermal::spawn(thermal::zone_path(zone), governor, Duration::from_secs(1))
+            .map_err(|e| format!("Cannot watch --thermal-zone {zone}: {e}"))?;
+    }
+
+    #[cfg(feature = "metrics")]
+    if let Some(port) = args.metrics_port {
+        let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
+        metrics::serve(addr, &STATS)
+            .map_err(|e| format!("Cannot serve metrics on port {port}: {e}"))?;
+    }
+
     let session = zenoh::open(args.clone()).await.unwrap();
 
     if args.replay.is_some() {
@@ -188,20 +317,48 @@ async fn main() -> Result<(), Box<dyn Error>> {
         return Ok(());
     }
 
-    let mirror = match args.mirror {
-        MirrorSetting::None => Mirror::None,
-        MirrorSetting::Horizontal => Mirror::Horizontal,
-        MirrorSetting::Vertical => Mirror::Vertical,
-        MirrorSetting::Both => Mirror::Both,
-    };
+    if let Some(pattern) = args.test_pattern {
+        // Test pattern mode: the transcoder's outputs on synthetic frames.
+        let pattern_task = transcode::run_test_pattern(session, args, pattern);
+        if let Some(console_server) = console_server {
+            let console_task = console_server.serve();
+            let (console_task, pattern_task) = tokio::join!(console_task, pattern_task);
+            console_task.unwrap();
+            pattern_task?;
+        } else {
+            pattern_task.await?;
+        }
+        return Ok(());
+    }
 
-    let cam = create_camera()
-        .with_device(&args.camera)
-        .with_resolution(args.camera_size[0] as i32, args.camera_size[1] as i32)
-        .with_format(FourCC(*b"YUYV"))
-        .with_mirror(mirror)
-        .open()?;
-    cam.start()?;
+    if let FrameSource::Dma(topic) = args.source.clone() {
+        // Transcoder mode: encode the frames another node captures. The
+        // V4L2 device is left to that node.
+        let transcode_task = transcode::run_transcode(session, args, topic);
+        if let Some(console_server) = console_server {
+            let console_task = console_server.serve();
+            let (console_task, transcode_task) = tokio::join!(console_task, transcode_task);
+            console_task.unwrap();
+            transcode_task?;
+        } else {
+            transcode_task.await?;
+        }
+        return Ok(());
+    }
+
+    // At boot the node can start before the camera is enumerated, so keep
+    // trying, selector lookup included, for --camera-open-timeout.
+    let selector = args.camera.clone();
+    let open_timeout = Duration::from_secs(args.camera_open_timeout);
+    let cam = retry_open(open_timeout, OPEN_RETRY_DELAY, || {
+        let device = device::resolve(&selector)?;
+        if device != Path::new(&selector) {
+            info!("Camera {selector} resolved to {}", device.display());
+            args.camera = device.display().to_string();
+        }
+        open_camera(&args)
+    })
+    .await?;
     if cam.width() as u32 != args.camera_size[0] || cam.height() as u32 != args.camera_size[1] {
         warn!(
             "User requested {}x{} resolution but camera set {}x{} resolution",
@@ -219,11 +376,44 @@ async fn main() -> Result<(), Box<dyn Error>> {
         cam.height(),
         args.stream_size[0],
         args.stream_size[1],
-        mirror
+        Mirror::from(args.mirror)
     );
     args.camera_size[0] = cam.width() as u32;
     args.camera_size[1] = cam.height() as u32;
 
+    // Run the encoder, the low-fps warning, and the recording sidecar at
+    // the rate the camera actually negotiated rather than assuming
+    // --target-fps.
+    let interval = match framerate::query(Path::new(&args.camera)) {
+        Ok(Some(interval)) => {
+            info!("Camera frame interval: {}", interval);
+            Some(interval)
+        }
+        Ok(None) => {
+            info!(
+                "Camera does not report a frame interval, assuming {} fps",
+                args.target_fps
+            );
+            None
+        }
+        Err(e) => {
+            warn!(
+                "Cannot query camera frame interval, assuming {} fps: {e}",
+                args.target_fps
+            );
+            None
+        }
+    };
+    args.target_fps = framerate::derive_fps(interval, args.target_fps);
+
+    if args.sensor_diagnostics {
+        match sensor::spawn(Path::new(&args.camera), Duration::from_secs(1)) {
+            Ok(Some(_)) => {}
+            Ok(None) => warn!("--sensor-diagnostics: the camera has no diagnostic controls"),
+            Err(e) => warn!("Cannot read the camera's diagnostic controls: {e}"),
+        }
+    }
+
     // Automatically enable tiling for resolutions greater than 1080p
     if args.camera_size[1] > 1080 {
         if !args.h264_tiles {
@@ -245,6 +435,22 @@ async fn main() -> Result<(), Box<dyn Error>> {
         );
     }
 
+    // The stream size may still change here, so the check runs before
+    // anything is allocated for the outputs.
+    match cma::available() {
+        Ok(Some(free)) => cma::check(&mut args, free),
+        Ok(None) => debug!("Kernel reports no CMA pool, skipping the buffer estimate"),
+        Err(e) => warn!("Cannot read free CMA: {e}"),
+    }
+
+    match pixfmt::query(Path::new(&args.camera)) {
+        Ok(format) => {
+            info!("Camera pixel format: {}", format);
+            validate_camera_format(format, &args)?;
+        }
+        Err(e) => warn!("Cannot query camera pixel format: {e}"),
+    }
+
     let stream_task = stream(cam, session, args);
     if let Some(console_server) = console_server {
         let console_task = console_server.serve();
@@ -258,6 +464,191 @@ async fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Pixel format requested from the capture device.
+const CAMERA_FORMAT: FourCC = FourCC(*b"YUYV");
+
+/// First wait between camera open attempts, doubled after each failure up
+/// to [`OPEN_RETRY_MAX`].
+const OPEN_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Longest wait between camera open attempts.
+const OPEN_RETRY_MAX: Duration = Duration::from_secs(4);
+
+/// Call `open` until it succeeds, backing off from `delay` between failed
+/// attempts. Gives up with the last error once `timeout` has passed since
+/// the first attempt; a zero `timeout` makes a single attempt.
+async fn retry_open<T>(
+    timeout: Duration,
+    mut delay: Duration,
+    mut open: impl FnMut() -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    let deadline = Instant::now() + timeout;
+    let mut attempt = 1;
+    loop {
+        let e = match open() {
+            Ok(v) => {
+                if attempt > 1 {
+                    info!("Camera opened on attempt {attempt}");
+                }
+                return Ok(v);
+            }
+            Err(e) => e,
+        };
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(e);
+        }
+        warn!("Camera open attempt {attempt} failed, retrying in {delay:?}: {e}");
+        tokio::time::sleep(delay.min(left)).await;
+        delay = (delay * 2).min(OPEN_RETRY_MAX);
+        attempt += 1;
+    }
+}
+
+/// Open and start the capture device described by `args`.
+fn open_camera(args: &Args) -> Result<CameraReader, Box<dyn Error>> {
+    let cam = create_camera()
+        .with_device(&args.camera)
+        .with_resolution(args.camera_size[0] as i32, args.camera_size[1] as i32)
+        .with_format(args.bayer.map_or(CAMERA_FORMAT, BayerPattern::fourcc))
+        .with_mirror(Mirror::from(args.mirror))
+        .open()?;
+    cam.start()?;
+    Ok(cam)
+}
+
+/// The live V4L2 camera as a pipeline source. A capture watchdog restart
+/// closes the device before reopening it with the same settings, since
+/// V4L2 drivers generally refuse a second open while streaming. With
+/// `--bayer` every raw frame is demosaiced, with `--rectify-output`
+/// undistorted, and with a calibrated `color_correction` colour corrected,
+/// before the pipeline sees it.
+struct LiveCamera {
+    cam: Option<CameraReader>,
+    debayer: Option<Debayer>,
+    rectify: Option<Rectify>,
+    color: Option<ColorCorrect>,
+    args: Args,
+}
+
+impl CameraSource for LiveCamera {
+    fn read_frame(&mut self) -> Result<CapturedFrame, Box<dyn Error>> {
+        let frame = match self.cam.as_mut() {
+            Some(cam) => cam.read_frame()?,
+            None => return Err("camera is not open".into()),
+        };
+        let frame = match self.debayer.as_mut() {
+            Some(debayer) => {
+                let rgba = info_span!("debayer").in_scope(|| debayer.convert(&frame))?;
+                rgba.unwrap_or(frame)
+            }
+            None => frame,
+        };
+        let frame = match self.rectify.as_mut() {
+            Some(rectify) => info_span!("rectify").in_scope(|| rectify.convert(&frame))?,
+            None => frame,
+        };
+        match self.color.as_mut() {
+            Some(color) => info_span!("color_correct").in_scope(|| color.convert(&frame)),
+            None => Ok(frame),
+        }
+    }
+
+    fn restart(&mut self) -> Result<(), Box<dyn Error>> {
+        self.cam = None;
+        let cam = open_camera(&self.args)?;
+        info!(
+            "Reopened camera: {} resolution: {}x{}",
+            self.args.camera,
+            cam.width(),
+            cam.height()
+        );
+        self.cam = Some(cam);
+        Ok(())
+    }
+}
+
+/// Fail startup when the camera negotiated a compressed `format` (for
+/// example MJPG instead of the requested YUYV) while outputs that convert or
+/// encode raw frames are enabled, naming those outputs. Compressed frames
+/// are not decoded, so the camera frame topic is the only output that can
+/// carry them.
+fn validate_camera_format(format: FourCC, args: &Args) -> Result<(), Box<dyn Error>> {
+    if let Some((pattern, _)) = BayerPattern::from_fourcc(format) {
+        if args.bayer.is_none() {
+            let name = pattern.to_possible_value().expect("no skipped variants");
+            return Err(format!(
+                "camera {} delivers {} Bayer frames; pass --bayer {} to demosaic them",
+                args.camera,
+                format,
+                name.get_name(),
+            )
+            .into());
+        }
+        return Ok(());
+    }
+    if args.bayer.is_some() {
+        warn!(
+            "--bayer is set but camera {} delivers {} frames, which are used as is",
+            args.camera, format
+        );
+    }
+    if args.rectify_output && format != image::YUYV && format != RGBA {
+        return Err(format!(
+            "camera {} delivers {} frames but --rectify-output only undistorts YUYV and RGBA",
+            args.camera, format
+        )
+        .into());
+    }
+    if image::g2d_supports(format) {
+        return Ok(());
+    }
+    let mut outputs: Vec<&str> = [
+        (args.h264, "--h264"),
+        (args.h264_tiles, "--h264-tiles"),
+        (args.jpeg, "--jpeg"),
+        (args.v4l2_output.is_some(), "--v4l2-output"),
+        (args.dma_format.is_some(), "--dma-format"),
+        (args.dma_crop_topic.is_some(), "--dma-crop-topic"),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, flag)| enabled.then_some(flag))
+    .collect();
+    if format == image::Y16 {
+        // Depth frames skip the conversion and the JPEG output publishes
+        // them as 16-bit PNG; nothing else can take them.
+        outputs.retain(|&flag| flag != "--jpeg");
+        if outputs.is_empty() {
+            return Ok(());
+        }
+        return Err(format!(
+            "camera {} delivers 16-bit Y16 depth frames, which {} cannot convert; only --jpeg \
+             (published as 16-bit PNG) and the camera frame topic carry them",
+            args.camera,
+            outputs.join(", "),
+        )
+        .into());
+    }
+    if outputs.is_empty() {
+        warn!(
+            "Camera delivers {} frames, which are published undecoded on {}",
+            format, args.frame_topic
+        );
+        return Ok(());
+    }
+    Err(format!(
+        "camera {} delivers {} frames instead of {}, but {} need raw frames; choose a raw \
+         mode of the camera (see `v4l2-ctl -d {} --list-formats-ext`) or disable {}",
+        args.camera,
+        format,
+        CAMERA_FORMAT,
+        outputs.join(", "),
+        args.camera,
+        if outputs.len() == 1 { "it" } else { "them" },
+    )
+    .into())
+}
+
 /// Validate the `--record` / `--replay` / `--replay-*` arg combinations up
 /// front so we can fail the process with a single clear message before
 /// opening the camera or any file handles.
@@ -270,6 +661,12 @@ fn validate_record_replay_args(args: &Args) -> Result<(), Box<dyn Error>> {
             )));
         }
     }
+    #[cfg(feature = "srt")]
+    if args.srt.is_some() && !args.h264 {
+        return Err(Box::from(
+            "--srt requires --h264 (it sends the main H.264 stream)",
+        ));
+    }
     if args.replay.is_some() {
         if args.jpeg {
             return Err(Box::from(
@@ -281,6 +678,27 @@ fn validate_record_replay_args(args: &Args) -> Result<(), Box<dyn Error>> {
                 "--replay does not support --h264-tiles (recorded files carry only the main stream)",
             ));
         }
+        if args.v4l2_output.is_some() {
+            return Err(Box::from(
+                "--replay does not support --v4l2-output (recorded files carry H.264 only)",
+            ));
+        }
+        if args.unix_socket.is_some() {
+            return Err(Box::from(
+                "--replay does not support --unix-socket (it serves the live encoders)",
+            ));
+        }
+        #[cfg(feature = "srt")]
+        if args.srt.is_some() {
+            return Err(Box::from(
+                "--replay does not support --srt (it sends the live encoder output)",
+            ));
+        }
+        if !args.h264_variant.is_empty() {
+            return Err(Box::from(
+                "--replay does not support --h264-variant (recorded H.264 is forwarded, not re-encoded)",
+            ));
+        }
     } else {
         // --replay-loop / --replay-fps are only meaningful with --replay.
         if args.replay_loop {
@@ -307,7 +725,7 @@ async fn stream(cam: CameraReader, session: Session, args: Args) -> Result<(), B
         .congestion_control(CongestionControl::Drop)
         .await
     {
-        Ok(v) => v,
+        Ok(v) => Arc::new(v),
         Err(e) => {
             error!(
                 "Error while declaring camera info publisher {}: {:?}",
@@ -316,33 +734,58 @@ async fn stream(cam: CameraReader, session: Session, args: Args) -> Result<(), B
             return Err(e);
         }
     };
+    let info_enc = Encoding::APPLICATION_CDR.with_schema("sensor_msgs/msg/CameraInfo");
+    let latest_info = LatestSample::default();
+    let reemit_task = tokio::spawn(reemit_on_match(
+        publ_info.clone(),
+        latest_info.clone(),
+        info_enc.clone(),
+    ));
+    std::mem::drop(reemit_task);
 
     // The h264 thread is spawned later (after the recorder file is
     // opened and the sidecar is written) so a doomed `--record` run
     // fails the whole process before any thread is running.
-    let (h264_tx, h264_rx) = kanal::bounded(1);
+    let (h264_tx, h264_rx) = encode_channel(&args, ENCODE_QUEUE_DEPTH);
 
-    let (jpeg_tx, rx) = kanal::bounded(1);
+    let socket = bind_unix_socket(&args)?;
+    let (jpeg_tx, rx) = encode_channel(&args, ENCODE_QUEUE_DEPTH);
+    let mut threads = OutputThreads::default();
     if args.jpeg {
-        let session = session.clone();
+        threads.push(spawn_jpeg_thread(
+            &session,
+            &args,
+            rx,
+            clock_offset,
+            socket.clone(),
+        )?);
+    }
+
+    // Open the V4L2 output device up front so a bad path or a rejected
+    // format fails the run before any frames flow.
+    let (v4l2_tx, v4l2_rx) = kanal::bounded(1);
+    if let Some(path) = args.v4l2_output.as_ref() {
+        let output = V4l2Output::open(
+            path,
+            args.v4l2_output_format,
+            args.stream_size[0],
+            args.stream_size[1],
+        )?;
+        info!(
+            "V4L2 output: {:?} {}x{} {}",
+            path,
+            args.stream_size[0],
+            args.stream_size[1],
+            args.v4l2_output_format.fourcc()
+        );
         let args = args.clone();
-        thread::Builder::new()
-            .name("jpeg".to_string())
-            .spawn(move || {
-                // Multi-thread with one worker is what Zenoh 1.6+
-                // requires for `Session::drop`'s internal close path —
-                // it calls `block_in_place` from `ZRuntime::Net` and
-                // panics if the surrounding runtime is current-thread
-                // ("Zenoh runtime doesn't support Tokio's current
-                // thread scheduler"). One worker preserves the
-                // single-encoder-per-thread shape we want here.
-                tokio::runtime::Builder::new_multi_thread()
-                    .worker_threads(1)
-                    .enable_all()
-                    .build()
-                    .unwrap()
-                    .block_on(jpeg_task(session, args, rx, clock_offset));
-            })?;
+        threads.push(
+            thread::Builder::new()
+                .name("v4l2out".to_string())
+                .spawn(move || v4l2_output_task(args, v4l2_rx, output))?,
+        );
+    } else {
+        drop(v4l2_rx);
     }
 
     let mut h264_tiles_txs = Vec::new();
@@ -356,17 +799,17 @@ async fn stream(cam: CameraReader, session: Session, args: Args) -> Result<(), B
         ];
 
         for (i, &tile_pos) in tile_positions.iter().enumerate() {
-            let (tx, rx) = kanal::bounded(3);
+            let (tx, rx) = encode_channel(&args, TILE_QUEUE_DEPTH);
             let session = session.clone();
             let args = args.clone();
             let tile_topic = args.h264_tiles_topics[i].clone();
 
-            thread::Builder::new()
+            let handle = thread::Builder::new()
                 .name(format!("h264_tile_{:?}", tile_pos).to_lowercase())
                 .spawn(move || {
-                    // Multi-thread with one worker — see the matching
-                    // comment on the h264 spawn above for why current-
-                    // thread is not viable with Zenoh 1.6+.
+                    // Multi-thread with one worker — see the comment in
+                    // `spawn_h264_thread` for why current-thread is not
+                    // viable with Zenoh 1.6+.
                     tokio::runtime::Builder::new_multi_thread()
                         .worker_threads(1)
                         .enable_all()
@@ -382,6 +825,7 @@ async fn stream(cam: CameraReader, session: Session, args: Args) -> Result<(), B
                         ));
                 })?;
 
+            threads.push(handle);
             h264_tiles_txs.push(tx);
         }
     }
@@ -392,12 +836,52 @@ async fn stream(cam: CameraReader, session: Session, args: Args) -> Result<(), B
     let colorimetry = Colorimetry::from_camera(&cam);
 
     let tf_fields = TfStaticFields::from_args(&args);
-    let info_fields = CameraInfoFields::from_args(&args)?;
+    let tf_links = TfStaticFields::links_from_args(&args)?;
+    let calibration = read_calibration(&args)?;
+    let mut info_fields = CameraInfoFields::from_calibration(&args, calibration.as_ref())?;
+    let rectify = if args.rectify_output {
+        let (width, height) = (cam.width() as u32, cam.height() as u32);
+        let table = info_fields.remap_table(width, height)?;
+        info_fields = info_fields.rectified();
+        if table.is_identity() {
+            warn!("--rectify-output is set but the calibration has no distortion to remove");
+            None
+        } else {
+            info!("Rectifying {width}x{height} frames with the calibrated distortion");
+            Some(Rectify::new(
+                table,
+                args.bayer.map_or(CAMERA_FORMAT, |_| RGBA),
+            )?)
+        }
+    } else {
+        None
+    };
+    let color = match calibration
+        .as_ref()
+        .map(|json| color_correction_from_calibration(json, &args.cam_info_path))
+        .transpose()?
+        .flatten()
+    {
+        Some(correction) if !correction.is_identity() => {
+            if args.bayer.is_none() {
+                return Err(format!(
+                    "{} sets color_correction, which applies to RGBA frames only; \
+                     it needs --bayer",
+                    args.cam_info_path
+                )
+                .into());
+            }
+            info!("Colour correcting frames with the calibrated matrix and gamma");
+            let (width, height) = (cam.width() as u32, cam.height() as u32);
+            Some(ColorCorrect::new(correction, width, height)?)
+        }
+        _ => None,
+    };
 
     // When --record is set, open the H.264 output file and the
     // matching sidecar before any frames flow. Order matters:
     //
-    //   1. Open the BufWriter on the .h264 file. If creation fails
+    //   1. Create the .h264 file and its .idx keyframe index. If creation fails
     //      (path missing, no perms, FS full) we surface the error
     //      here and abort the run cleanly — never produce an
     //      orphaned sidecar for a recording that never started.
@@ -407,55 +891,57 @@ async fn stream(cam: CameraReader, session: Session, args: Args) -> Result<(), B
     // Use the encoder's stream dimensions in the sidecar (what the
     // recorded .h264 file will actually contain), not the camera
     // capture dimensions — those can differ when --stream-size
-    // rescales from --camera-size.
-    let recorder: Option<std::io::BufWriter<std::fs::File>> = match args.record.as_ref() {
+    // rescales from --camera-size, and --h264-rotation can swap them.
+    let recorder: Option<Recorder> = match args.record.as_ref() {
         Some(path) => {
-            let file = std::fs::File::create(path)
-                .map_err(|e| format!("Cannot create recording file {:?}: {e}", path))?;
-            let bw = std::io::BufWriter::with_capacity(256 * 1024, file);
+            let path = &output::resolve(
+                path,
+                args.output_dir.as_deref(),
+                &args.output_pattern,
+                "h264",
+                stamp_ns(&timestamp()?),
+                0,
+                &args.camera_frame_id,
+            );
+            output::create_parent(path)
+                .map_err(|e| format!("Cannot create the directory for {:?}: {e}", path))?;
+            let recorder = Recorder::create(path)?;
 
+            let rotation = args.h264_output_rotation();
+            let (width, height) = rotation.rotated_size(args.stream_size[0], args.stream_size[1]);
             let sidecar = Sidecar::from_live(
-                TARGET_FPS as u32,
-                args.stream_size[0],
-                args.stream_size[1],
+                args.target_fps,
+                width,
+                height,
                 &cam,
-                info_fields.clone(),
+                info_fields.rotated(rotation),
                 tf_fields.clone(),
+                tf_links.clone(),
             );
             let written = sidecar.write_paired(path)?;
             info!(
-                "Recording: H.264 bitstream → {:?}, sidecar → {:?}",
-                path, written
+                "Recording: H.264 bitstream → {:?}, sidecar → {:?}, keyframe index → {:?}",
+                path,
+                written,
+                recording::index_path(path)
             );
-            Some(bw)
+            Some(recorder)
         }
         None => None,
     };
 
     // Spawn the h264 thread now that the recorder file (if any) is
-    // open. The thread takes ownership of the BufWriter; flushes on
-    // every keyframe; final flush on drop.
+    // open. The thread takes ownership of the recorder, which flushes
+    // and indexes every keyframe; final flush on drop.
     if args.h264 {
-        let session = session.clone();
-        let args = args.clone();
-        let rx = h264_rx;
-        thread::Builder::new()
-            .name("h264".to_string())
-            .spawn(move || {
-                // Multi-thread with one worker is what Zenoh 1.6+
-                // requires for `Session::drop`'s internal close path —
-                // it calls `block_in_place` from `ZRuntime::Net` and
-                // panics if the surrounding runtime is current-thread
-                // ("Zenoh runtime doesn't support Tokio's current
-                // thread scheduler"). One worker preserves the
-                // single-encoder-per-thread shape we want here.
-                tokio::runtime::Builder::new_multi_thread()
-                    .worker_threads(1)
-                    .enable_all()
-                    .build()
-                    .unwrap()
-                    .block_on(h264_task(session, args, rx, clock_offset, recorder));
-            })?;
+        threads.push(spawn_h264_thread(
+            &session,
+            &args,
+            h264_rx,
+            clock_offset,
+            recorder,
+            socket,
+        )?);
     } else {
         // --record requires --h264 (enforced by validate_record_replay_args),
         // so an open recorder always pairs with the spawn above. Drop the
@@ -465,16 +951,116 @@ async fn stream(cam: CameraReader, session: Session, args: Args) -> Result<(), B
     }
 
     let tf_session = session.clone();
-    let tf_msg = ZBytes::from(tf_fields.build_msg()?.into_cdr());
+    let tf_msgs = build_tf_static_msgs(std::iter::once(&tf_fields).chain(&tf_links))?;
     let tf_enc = Encoding::APPLICATION_CDR.with_schema("geometry_msgs/msg/TransformStamped");
-    let tf_task = tokio::spawn(async move { tf_static(tf_session, tf_msg, tf_enc).await });
+    let tf_topic = args.tf_static_topic();
+    let tf_task =
+        tokio::spawn(async move { tf_static(tf_session, tf_topic, tf_msgs, tf_enc).await });
     std::mem::drop(tf_task);
 
-    let info_msg = ZBytes::from(info_fields.build_msg()?.into_cdr());
-    let info_enc = Encoding::APPLICATION_CDR.with_schema("sensor_msgs/msg/CameraInfo");
-
     let src_pid = process::id();
 
+    // Each enabled output is a pipeline sink that hands its own handle on
+    // the frame's DMA buffer to the matching encoder thread.
+    let debayer = match args.bayer {
+        Some(pattern) => {
+            info!("Demosaicing {:?} Bayer frames to RGBA", pattern);
+            Some(Debayer::new(
+                pattern,
+                cam.width() as u32,
+                cam.height() as u32,
+            )?)
+        }
+        None => None,
+    };
+    let mut pipeline = Pipeline::new(LiveCamera {
+        cam: Some(cam),
+        debayer,
+        rectify,
+        color,
+        args: args.clone(),
+    });
+    let connected_session = session.clone();
+    let connected_topic = args.connected_topic.clone();
+    let liveness = pipeline.liveness();
+    let connected_task = tokio::spawn(async move {
+        publish_connected(connected_session, connected_topic, liveness).await
+    });
+    std::mem::drop(connected_task);
+    let shared = args.shared_convert_target();
+    if args.h264 {
+        add_encoder_output(&mut pipeline, shared, h264_tx, "H264")?;
+    }
+    if args.jpeg {
+        add_encoder_output(&mut pipeline, shared, jpeg_tx, "JPEG")?;
+    }
+    add_h264_variants(&mut pipeline, &mut threads, &session, &args, clock_offset)?;
+    if args.v4l2_output.is_some() {
+        pipeline.add_sink(move |frame| send_frame(&v4l2_tx, frame, "V4L2"));
+    }
+    if args.h264_tiles {
+        pipeline.add_sink(move |frame| {
+            for (i, tx) in h264_tiles_txs.iter().enumerate() {
+                send_frame(tx, frame, &format!("H264_TILE_{}", i));
+            }
+        });
+    }
+
+    // A wedged driver can leave `read()` blocked forever without an
+    // error. The watchdog interrupts the blocked read with SIGUSR1 (the
+    // read then fails with EINTR) and the pipeline reopens the camera.
+    // Registering the signal through tokio replaces its default action,
+    // which would terminate the process.
+    let _sigusr1 = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1())?;
+    if args.capture_watchdog_ms > 0 {
+        let timeout = Duration::from_millis(args.capture_watchdog_ms);
+        let capture_thread = unsafe { libc::pthread_self() };
+        pipeline.set_watchdog(Watchdog::spawn(timeout, move || {
+            error!(
+                "No camera frame for {} ms, restarting capture",
+                timeout.as_millis()
+            );
+            unsafe { libc::pthread_kill(capture_thread, libc::SIGUSR1) };
+        })?);
+    }
+
+    // Consumers that want another pixel format get a converted copy; the
+    // encoder sinks keep reading the camera buffer. Bayer frames reach this
+    // point already demosaiced to RGBA.
+    let (frame_format, frame_fourcc) = match args.bayer {
+        Some(_) => (FrameFormat::Rgba, RGBA),
+        None => (FrameFormat::Yuyv, CAMERA_FORMAT),
+    };
+    let mut converter = match args.dma_format {
+        Some(format) if format.fourcc() != frame_fourcc => {
+            info!(
+                "Converting {} frames to {} for {}",
+                frame_fourcc,
+                format.fourcc(),
+                args.frame_topic
+            );
+            Some(FormatConverter::new(
+                format,
+                args.camera_size[0],
+                args.camera_size[1],
+            )?)
+        }
+        // Cropping needs a buffer of its own even without a format change.
+        _ if args.dma_crop_topic.is_some() => Some(FormatConverter::new(
+            args.dma_format.unwrap_or(frame_format),
+            args.camera_size[0],
+            args.camera_size[1],
+        )?),
+        _ => None,
+    };
+    let crop_control = match &args.dma_crop_topic {
+        Some(topic) => {
+            info!("Accepting {} crop requests on {}", args.frame_topic, topic);
+            Some(CropControl::subscribe(&session, topic).await?)
+        }
+        None => None,
+    };
+
     let mut prev = Instant::now();
     let mut history = vec![0.0; 60];
     let mut index = 0;
@@ -485,11 +1071,13 @@ async fn stream(cam: CameraReader, session: Session, args: Args) -> Result<(), B
     // extra `cam.read()` outside the loop. Avoids a per-frame
     // allocation in the hot publish path.
     let mut fourcc_str: Option<String> = None;
+    let mut dump_npy = args.dump_npy.clone();
+    let mut dedup = DuplicateDetector::new(args.dedup_frames);
 
     while !SHUTDOWN.load(Ordering::SeqCst) {
-        let camera_buffer = match info_span!("camera_read").in_scope(|| cam.read()) {
-            Ok(buf) => buf,
-            Err(videostream::Error::Io(e)) if e.kind() == std::io::ErrorKind::Interrupted => {
+        let frame = match info_span!("camera_read").in_scope(|| pipeline.next_frame()) {
+            Ok(frame) => frame,
+            Err(e) if is_interrupted(e.as_ref()) => {
                 // System call was interrupted by signal - check if shutdown requested
                 if SHUTDOWN.load(Ordering::SeqCst) {
                     info!("Camera read interrupted by shutdown signal");
@@ -497,28 +1085,99 @@ async fn stream(cam: CameraReader, session: Session, args: Args) -> Result<(), B
                 }
                 continue;
             }
-            Err(e) => return Err(e.into()),
+            Err(e) => return Err(e),
         };
 
         let fps = update_fps(&mut prev, &mut history, &mut index);
-        if fps < TARGET_FPS as f64 * 0.9 {
-            warn!("low camera fps {} (target {})", fps, TARGET_FPS);
+        STATS.record_frame(fps);
+        if fps < args.target_fps as f64 * 0.9 {
+            warn!("low camera fps {} (target {})", fps, args.target_fps);
         }
         args.tracy.then(|| plot!("fps", fps));
 
-        let fourcc = fourcc_str.get_or_insert_with(|| camera_buffer.format().to_string());
+        match info_span!("frame_dedup").in_scope(|| dedup.check_image(&frame.image)) {
+            Ok(DedupAction::Publish) => {}
+            Ok(action) => {
+                STATS.record_duplicate();
+                // Warn once per run of repeats rather than on every frame.
+                if dedup.repeats() == 1 {
+                    warn!("camera frame {} repeats the previous frame", frame.sequence);
+                }
+                if action == DedupAction::Skip {
+                    continue;
+                }
+            }
+            Err(e) => warn!("Cannot check frame {} for repeats: {e}", frame.sequence),
+        }
+
+        let converted = match converter.as_mut() {
+            Some(converter) => {
+                if let Some(crop) = crop_control.as_ref().and_then(CropControl::take) {
+                    match converter.set_crop(crop) {
+                        Ok(()) => info!("Cropping {} to {:?}", args.frame_topic, crop),
+                        Err(e) => warn!("Ignoring crop request {:?}: {e}", crop),
+                    }
+                }
+                let converted =
+                    info_span!("frame_convert").in_scope(|| converter.convert(&frame))?;
+                // Encoders converting to the same target reuse this one.
+                if let Some(converted) = &converted {
+                    if let Err(e) = pipeline.share_conversion(converter.target(), converted) {
+                        warn!("Cannot share frame {}: {e}", frame.sequence);
+                    }
+                }
+                converted
+            }
+            None => None,
+        };
+        let shared = converted.as_ref().unwrap_or(&frame);
+        let fourcc = fourcc_str.get_or_insert_with(|| shared.image.format().to_string());
+        if let Some(path) = dump_npy.take() {
+            let path = output::resolve(
+                &path,
+                args.output_dir.as_deref(),
+                &args.output_pattern,
+                "npy",
+                stamp_ns(&clock_offset.to_realtime(&frame.timestamp)),
+                shared.sequence,
+                &args.camera_frame_id,
+            );
+            let saved = output::create_parent(&path)
+                .map_err(Box::from)
+                .and_then(|()| npy::save_image(&path, &shared.image));
+            match saved {
+                Ok(()) => info!("Saved frame {} to {:?}", shared.sequence, path),
+                Err(e) => warn!("Cannot save frame to {:?}: {e}", path),
+            }
+        }
+
+        let checksum = if args.dma_checksum {
+            match info_span!("frame_checksum").in_scope(|| image_crc32(&shared.image)) {
+                Ok(crc) => Some(checksum::attachment(crc)),
+                Err(e) => {
+                    warn!("Cannot checksum frame {}: {e}", shared.sequence);
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
-        let cam_ts = camera_buffer.timestamp()?;
-        let frame_sample_ts = zenoh_ts_for_frame(&session, &clock_offset, &cam_ts);
+        // One stamp for everything published about this frame. The encoder
+        // threads derive theirs from the same capture timestamp through the
+        // same fixed clock offset, so JPEG and H.264 carry it too.
+        let stamp = clock_offset.to_realtime(&frame.timestamp);
+        let frame_sample_ts = zenoh_ts_from_ros_time(&session, stamp);
         let (msg, enc) = camera_frame_serialize(
-            &camera_buffer,
-            &cam_ts,
+            shared,
+            stamp,
             src_pid,
-            &args.camera_frame_id,
-            &clock_offset,
+            &args.message_frame_id(stamp.sec, stamp.nanosec, shared.sequence),
             &colorimetry,
             fourcc,
         )?;
+        let info_msg = ZBytes::from(info_fields.build_msg(stamp)?.into_cdr());
+        latest_info.set(info_msg.clone(), frame_sample_ts);
         let span = info_span!("camera_publish");
         let local_session = session.clone();
         let frame_topic = args.frame_topic.clone();
@@ -526,86 +1185,549 @@ async fn stream(cam: CameraReader, session: Session, args: Args) -> Result<(), B
             local_session
                 .put(frame_topic, msg)
                 .encoding(enc)
+                .attachment(checksum)
                 .timestamp(frame_sample_ts)
                 .priority(Priority::Data)
                 .congestion_control(CongestionControl::Drop)
                 .await
-                .unwrap();
         }
         .instrument(span);
         let info_task = publ_info
-            .put(info_msg.clone())
+            .put(info_msg)
             .encoding(info_enc.clone())
-            .timestamp(session.new_timestamp());
-
-        if args.h264 {
-            let ts = camera_buffer.timestamp()?;
-            let src_img = Image::from_camera(&camera_buffer)?;
-            try_send(&h264_tx, src_img, ts, "H264");
-        }
+            .timestamp(frame_sample_ts);
 
-        if args.jpeg {
-            let ts = camera_buffer.timestamp()?;
-            let src_img = Image::from_camera(&camera_buffer)?;
-            try_send(&jpeg_tx, src_img, ts, "JPEG");
-        }
+        pipeline.dispatch(&frame);
 
-        if args.h264_tiles {
-            let ts = camera_buffer.timestamp()?;
-            for (i, tx) in h264_tiles_txs.iter().enumerate() {
-                let src_img = Image::from_camera(&camera_buffer)?;
-                try_send(tx, src_img, ts, &format!("H264_TILE_{}", i));
-            }
+        let (frame_task, info_task) = tokio::join!(frame_task, info_task);
+        if let Err(e) = frame_task.and(info_task) {
+            warn!("Cannot publish frame {}: {e}", frame.sequence);
         }
 
-        let (_frame_task, info_task) = tokio::join!(frame_task, info_task);
-        info_task.unwrap();
-
         args.tracy.then(frame_mark);
     }
 
+    // Dropping the pipeline closes the camera and every output channel, so
+    // each encoder thread sees its `recv()` fail, finishes the frame it
+    // holds and returns, flushing any recording on the way out.
+    drop(pipeline);
+    threads.join();
     info!("Shutdown complete");
     Ok(())
 }
 
-fn try_send(tx: &Sender<(Image, Timestamp)>, img: Image, ts: Timestamp, _name: &str) {
-    match tx.try_send((img, ts)) {
-        Ok(_) => {}
-        Err(_) => {
-            // Channel issue - likely full due to slow encoding, which is
-            // expected with 4 tile threads Silently drop frames
-            // when channels are full to avoid log spam
+/// The output threads of a run, joined at shutdown so none is killed
+/// mid-frame.
+#[derive(Default)]
+pub(crate) struct OutputThreads(Vec<JoinHandle<()>>);
+
+impl OutputThreads {
+    pub(crate) fn push(&mut self, handle: JoinHandle<()>) {
+        self.0.push(handle);
+    }
+
+    /// Wait for every thread to return. The senders of their channels must
+    /// be dropped first, or this never returns.
+    pub(crate) fn join(self) {
+        for handle in self.0 {
+            let name = handle.thread().name().unwrap_or("output").to_string();
+            if handle.join().is_err() {
+                error!("{name} thread panicked");
+            }
         }
     }
 }
 
-async fn tf_static(
-    session: Session,
-    msg: ZBytes,
-    enc: Encoding,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let topic = "rt/tf_static".to_string();
-    let mut interval = tokio::time::interval(Duration::from_secs(1));
+/// Listen on `--unix-socket`, if set, for the encoder threads to serve
+/// their frames on.
+pub(crate) fn bind_unix_socket(args: &Args) -> Result<Option<UnixSocketOutput>, Box<dyn Error>> {
+    let Some(path) = args.unix_socket.as_ref() else {
+        return Ok(None);
+    };
+    if !args.h264 && !args.jpeg {
+        warn!("--unix-socket serves nothing without --h264 or --jpeg");
+    }
+    let socket = UnixSocketOutput::bind(path)
+        .map_err(|e| format!("Cannot listen on --unix-socket {}: {e}", path.display()))?;
+    Ok(Some(socket))
+}
 
-    loop {
-        interval.tick().await;
-        session
-            .put(&topic, msg.clone())
-            .encoding(enc.clone())
-            .timestamp(session.new_timestamp())
-            .await?;
+/// Nanoseconds since the Unix epoch of a ROS `stamp`, as carried in the
+/// `--unix-socket` frame header.
+fn stamp_ns(stamp: &builtin_interfaces::Time) -> u64 {
+    stamp.sec.max(0) as u64 * 1_000_000_000 + stamp.nanosec as u64
+}
+
+/// Inverse of [`stamp_ns`], saturating past the ROS `Time` range.
+fn stamp_from_ns(ns: i64) -> builtin_interfaces::Time {
+    let ns = ns.max(0);
+    match i32::try_from(ns / 1_000_000_000) {
+        Ok(sec) => builtin_interfaces::Time {
+            sec,
+            nanosec: (ns % 1_000_000_000) as u32,
+        },
+        Err(_) => SATURATED_TIME,
     }
 }
 
-async fn h264_task(
-    session: Session,
-    args: Args,
-    rx: Receiver<(Image, Timestamp)>,
+/// Run [`h264_task`] on its own thread.
+fn spawn_h264_thread(
+    session: &Session,
+    args: &Args,
+    rx: Receiver<OutputFrame>,
     clock_offset: ClockOffset,
-    // Pre-opened in `stream()` before the sidecar write so a doomed
-    // record run aborts the whole process before producing orphaned
+    recorder: Option<Recorder>,
+    socket: Option<UnixSocketOutput>,
+) -> std::io::Result<JoinHandle<()>> {
+    let session = session.clone();
+    let args = args.clone();
+    thread::Builder::new()
+        .name("h264".to_string())
+        .spawn(move || {
+            // Multi-thread with one worker is what Zenoh 1.6+ requires for
+            // `Session::drop`'s internal close path — it calls
+            // `block_in_place` from `ZRuntime::Net` and panics if the
+            // surrounding runtime is current-thread ("Zenoh runtime doesn't
+            // support Tokio's current thread scheduler"). One worker
+            // preserves the single-encoder-per-thread shape we want here.
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(h264_task(session, args, rx, clock_offset, recorder, socket));
+        })
+}
+
+/// Run [`jpeg_task`] on its own thread. See [`spawn_h264_thread`] for the
+/// runtime shape.
+fn spawn_jpeg_thread(
+    session: &Session,
+    args: &Args,
+    rx: Receiver<OutputFrame>,
+    clock_offset: ClockOffset,
+    socket: Option<UnixSocketOutput>,
+) -> std::io::Result<JoinHandle<()>> {
+    let session = session.clone();
+    let args = args.clone();
+    thread::Builder::new()
+        .name("jpeg".to_string())
+        .spawn(move || {
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(jpeg_task(session, args, rx, clock_offset, socket));
+        })
+}
+
+/// Connect an encoder thread to `pipeline`. With a `shared` target (see
+/// [`Args::shared_convert_target`]) the thread receives frames the capture
+/// loop already converted, once for every output with that target;
+/// otherwise it receives the source frames and converts them itself.
+pub(crate) fn add_encoder_output<S: CameraSource>(
+    pipeline: &mut Pipeline<S>,
+    shared: Option<ConvertTarget>,
+    tx: Sender<OutputFrame>,
+    name: &'static str,
+) -> Result<(), Box<dyn Error>> {
+    let sink = move |frame: &CapturedFrame| send_frame(&tx, frame, name);
+    match shared {
+        Some(target) => {
+            info!(
+                "{name}: sharing the {}x{} {} conversion done at capture",
+                target.width, target.height, target.format
+            );
+            pipeline.add_converted_sink(target, sink)?;
+        }
+        None => pipeline.add_sink(sink),
+    }
+    Ok(())
+}
+
+/// `args` as the encoder thread of `variant` sees them: the variant's topic,
+/// bitrate, size and frame rate in place of the main stream's. Variants do
+/// not share the capture-time conversion, since their sizes differ.
+pub(crate) fn variant_args(args: &Args, variant: &H264Variant) -> Args {
+    let mut args = args.clone();
+    args.h264_topic = variant.topic.clone();
+    args.h264_meta_topic = variant.meta_topic();
+    args.h264_init_topic = variant.init_topic();
+    args.h264_keyframe_topic = variant.keyframe_topic();
+    args.h264_bitrate = variant.bitrate;
+    if let Some((width, height)) = variant.size {
+        args.stream_size = vec![width, height];
+    }
+    if let Some(fps) = variant.fps {
+        args.target_fps = args.target_fps.min(fps);
+    }
+    args.shared_convert = false;
+    args
+}
+
+/// Checks that every `--h264-variant` can be encoded and that the variants
+/// publish on topics of their own.
+fn validate_h264_variants(args: &Args) -> Result<(), Box<dyn Error>> {
+    let mut topics: Vec<String> = Vec::new();
+    if args.h264 {
+        topics.extend([
+            args.h264_topic.clone(),
+            args.h264_meta_topic.clone(),
+            args.h264_init_topic.clone(),
+            args.h264_keyframe_topic.clone(),
+        ]);
+    }
+    for variant in &args.h264_variant {
+        let variant_args = variant_args(args, variant);
+        let (width, height) = variant_args
+            .h264_output_rotation()
+            .rotated_size(variant_args.stream_size[0], variant_args.stream_size[1]);
+        VideoManager::check_size(width as i32, height as i32)
+            .map_err(|e| format!("--h264-variant {}: {e}", variant.topic))?;
+        for topic in [
+            variant.topic.clone(),
+            variant.meta_topic(),
+            variant.init_topic(),
+            variant.keyframe_topic(),
+        ] {
+            if topics.contains(&topic) {
+                return Err(format!("--h264-variant topic {topic} is already published").into());
+            }
+            topics.push(topic);
+        }
+    }
+    Ok(())
+}
+
+/// Rejects the outputs that only handle H.264 when `--codec` is H.265.
+fn validate_codec(args: &Args) -> Result<(), Box<dyn Error>> {
+    if args.codec == Codec::H264 {
+        return Ok(());
+    }
+    if args.record.is_some() {
+        return Err("--record writes H.264 only and does not support --codec h265".into());
+    }
+    if args.unix_socket.is_some() && args.h264 {
+        return Err("--unix-socket serves H.264 only and does not support --codec h265".into());
+    }
+    #[cfg(feature = "srt")]
+    if args.srt.is_some() {
+        return Err("--srt sends H.264 only and does not support --codec h265".into());
+    }
+    if args.pixel_aspect != sps::SampleAspectRatio::SQUARE {
+        return Err(format!(
+            "--pixel-aspect {} is only signalled in H.264 streams, not with --codec h265",
+            args.pixel_aspect
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Start an encoder thread for every `--h264-variant`, each fed by its own
+/// pipeline sink that drops frames beyond the variant's frame rate.
+pub(crate) fn add_h264_variants<S: CameraSource>(
+    pipeline: &mut Pipeline<S>,
+    threads: &mut OutputThreads,
+    session: &Session,
+    args: &Args,
+    clock_offset: ClockOffset,
+) -> Result<(), Box<dyn Error>> {
+    for variant in &args.h264_variant {
+        let variant_args = variant_args(args, variant);
+        info!(
+            "H264 variant: {} {}x{} at up to {} fps, bitrate {:?}",
+            variant.topic,
+            variant_args.stream_size[0],
+            variant_args.stream_size[1],
+            variant_args.target_fps,
+            variant.bitrate
+        );
+        let (tx, rx) = encode_channel(args, ENCODE_QUEUE_DEPTH);
+        threads.push(spawn_h264_thread(
+            session,
+            &variant_args,
+            rx,
+            clock_offset,
+            None,
+            None,
+        )?);
+        let mut limiter = variant.fps.map(RateLimiter::new);
+        pipeline.add_sink(move |frame| {
+            if limiter
+                .as_mut()
+                .is_some_and(|l| !l.ready_at(&frame.timestamp))
+            {
+                return;
+            }
+            send_frame(&tx, frame, "H264 variant");
+        });
+    }
+    Ok(())
+}
+
+/// A frame offered to an output, with the `--max-in-flight` permit it holds
+/// until the output drops it.
+pub(crate) type OutputFrame = (Image, Timestamp, Permit<'static>);
+
+/// Default capacity of the H.264 and JPEG frame channels: the encoder works
+/// on the newest frame and anything arriving meanwhile is dropped.
+pub(crate) const ENCODE_QUEUE_DEPTH: usize = 1;
+
+/// Default capacity of each tile encoder's frame channel. Four tile
+/// encoders share the VPU, so each gets a little slack.
+const TILE_QUEUE_DEPTH: usize = 3;
+
+/// Channel feeding an encoder thread, sized by [`Args::encode_queue_depth`].
+pub(crate) fn encode_channel<T>(args: &Args, default: usize) -> (Sender<T>, Receiver<T>) {
+    kanal::bounded(args.encode_queue_depth(default))
+}
+
+/// Pipeline sink body shared by every encoder output: take a new handle on
+/// the frame's DMA buffer and offer it to the output's channel.
+fn send_frame(tx: &Sender<OutputFrame>, frame: &CapturedFrame, name: &str) {
+    // Before the handle is duplicated, so a frame over --max-in-flight
+    // costs no file descriptor.
+    let Some(permit) = IN_FLIGHT.try_acquire() else {
+        STATS.record_drop();
+        return;
+    };
+    match frame.image.try_clone() {
+        Ok(img) => try_send(tx, img, frame.timestamp, permit, name),
+        Err(e) => warn!("{name}: cannot duplicate frame handle: {e}"),
+    }
+}
+
+fn try_send(
+    tx: &Sender<OutputFrame>,
+    img: Image,
+    ts: Timestamp,
+    permit: Permit<'static>,
+    _name: &str,
+) {
+    // A refused frame returns its permit as it is dropped.
+    match tx.try_send((img, ts, permit)) {
+        Ok(_) => {}
+        Err(_) => {
+            // Channel issue - likely full due to slow encoding, which is
+            // expected with 4 tile threads Silently drop frames
+            // when channels are full to avoid log spam
+            STATS.record_drop();
+        }
+    }
+}
+
+/// Receive the next frame for an encoder output, standing in for frames
+/// that do not arrive on time as `missed` directs. `None` once the capture
+/// loop has exited. Stand-in frames are the output's own copies and hold
+/// no `--max-in-flight` permit.
+fn recv_frame(
+    rx: &Receiver<OutputFrame>,
+    missed: &mut ErrorFrames,
+    imgmgr: &ImageManager,
+    name: &str,
+) -> Option<(Image, Timestamp, Option<Permit<'static>>)> {
+    loop {
+        let (img, ts, permit) = match missed.timeout(Instant::now()) {
+            None => rx.recv().ok()?,
+            Some(timeout) => match rx.recv_timeout(timeout) {
+                Ok(frame) => frame,
+                Err(ReceiveErrorTimeout::Timeout) => match missed.fill() {
+                    Ok(Some((img, ts))) => {
+                        if missed.missed() == 1 {
+                            warn!(
+                                "{name}: camera frame missed, emitting {:?} frames",
+                                missed.policy()
+                            );
+                        }
+                        return Some((img, ts, None));
+                    }
+                    Ok(None) => continue,
+                    Err(e) => {
+                        warn!("{name}: cannot fill in a missed frame: {e}");
+                        continue;
+                    }
+                },
+                Err(_) => return None,
+            },
+        };
+        if missed.missed() > 0 {
+            info!(
+                "{name}: camera frames resumed after {} missed",
+                missed.missed()
+            );
+        }
+        if let Err(e) = missed.received(imgmgr, &img, ts, Instant::now()) {
+            warn!("{name}: cannot keep frame for --error-frame-policy: {e}");
+        }
+        return Some((img, ts, Some(permit)));
+    }
+}
+
+/// Publish every transform in `msgs` on `topic` (see
+/// [`Args::tf_static_topic`]) once a second, and right away when the topic
+/// gains its first subscriber so a consumer starting up does not wait for
+/// the next tick.
+pub(crate) async fn tf_static(
+    session: Session,
+    topic: String,
+    msgs: Vec<ZBytes>,
+    enc: Encoding,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let publisher = session.declare_publisher(topic.clone()).await?;
+    let listener = publisher.matching_listener().await?;
+    let (listener, publisher, session, msgs, enc) = (&listener, &publisher, &session, &msgs, &enc);
+    let next_match = move || async move { listener.recv_async().await.ok().map(|s| s.matching()) };
+    let publish = move || async move {
+        for msg in msgs {
+            publisher
+                .put(msg.clone())
+                .encoding(enc.clone())
+                .timestamp(session.new_timestamp())
+                .await?;
+        }
+        Ok(())
+    };
+    publish_on_match(&topic, Some(Duration::from_secs(1)), next_match, publish).await
+}
+
+/// The newest sample published on a topic, kept for [`reemit_on_match`].
+#[derive(Clone, Default)]
+struct LatestSample(Arc<Mutex<Option<(ZBytes, ZenohTimestamp)>>>);
+
+impl LatestSample {
+    fn set(&self, payload: ZBytes, timestamp: ZenohTimestamp) {
+        *self.0.lock().unwrap() = Some((payload, timestamp));
+    }
+
+    fn get(&self) -> Option<(ZBytes, ZenohTimestamp)> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Re-send the `latest` sample on `publisher` when it gains its first
+/// subscriber. CameraInfo goes out with every frame, so this only matters
+/// while frames are slow or stalled.
+async fn reemit_on_match(
+    publisher: Arc<Publisher<'static>>,
+    latest: LatestSample,
+    enc: Encoding,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let listener = publisher.matching_listener().await?;
+    let topic = publisher.key_expr().to_string();
+    let (listener, publisher, latest, enc) = (&listener, &publisher, &latest, &enc);
+    let next_match = move || async move { listener.recv_async().await.ok().map(|s| s.matching()) };
+    let publish = move || async move {
+        if let Some((payload, timestamp)) = latest.get() {
+            publisher
+                .put(payload)
+                .encoding(enc.clone())
+                .timestamp(timestamp)
+                .await?;
+        }
+        Ok(())
+    };
+    publish_on_match(&topic, None, next_match, publish).await
+}
+
+/// Call `publish` for `topic` every `period`, if given, and whenever `next_match`
+/// reports that the publisher went from no matching subscriber to at least
+/// one (`Some(true)`). Zenoh reports only that transition, so a subscriber
+/// joining a topic that already has one still waits for the next period.
+/// Once `next_match` returns `None` the listener is gone and only the
+/// period remains.
+async fn publish_on_match<M, MF, P, PF>(
+    topic: &str,
+    period: Option<Duration>,
+    mut next_match: M,
+    mut publish: P,
+) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    M: FnMut() -> MF,
+    MF: Future<Output = Option<bool>>,
+    P: FnMut() -> PF,
+    PF: Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+{
+    let mut interval = period.map(tokio::time::interval);
+    let mut listening = true;
+    loop {
+        tokio::select! {
+            _ = async { interval.as_mut().unwrap().tick().await }, if interval.is_some() => {}
+            matched = next_match(), if listening => match matched {
+                Some(true) => info!("Subscriber matched {topic}, publishing immediately"),
+                Some(false) => continue,
+                None => {
+                    listening = false;
+                    if interval.is_none() {
+                        return Ok(());
+                    }
+                    continue;
+                }
+            },
+        }
+        publish().await?;
+    }
+}
+
+/// Publish the pipeline's [`Liveness`] as a `std_msgs/Bool` on `topic`:
+/// promptly when it changes, and once a second otherwise so late
+/// subscribers (dashboards) learn the current state without waiting for a
+/// change.
+async fn publish_connected(
+    session: Session,
+    topic: String,
+    liveness: Liveness,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    const POLL: Duration = Duration::from_millis(100);
+    const REPUBLISH: Duration = Duration::from_secs(1);
+
+    let enc = Encoding::APPLICATION_CDR.with_schema("std_msgs/msg/Bool");
+    let mut interval = tokio::time::interval(POLL);
+    let mut last: Option<(bool, Instant)> = None;
+    loop {
+        interval.tick().await;
+        let connected = liveness.is_connected();
+        if let Some((prev, at)) = last {
+            if prev == connected && at.elapsed() < REPUBLISH {
+                continue;
+            }
+            if prev != connected {
+                info!(
+                    "Camera {}",
+                    if connected {
+                        "connected"
+                    } else {
+                        "disconnected"
+                    }
+                );
+            }
+        }
+        session
+            .put(&topic, build_bool_msg(connected))
+            .encoding(enc.clone())
+            .timestamp(session.new_timestamp())
+            .await?;
+        last = Some((connected, Instant::now()));
+    }
+}
+
+/// CDR payload of a `std_msgs/Bool`: the little-endian encapsulation
+/// header followed by the one-byte value.
+fn build_bool_msg(value: bool) -> ZBytes {
+    ZBytes::from(vec![0x00, 0x01, 0x00, 0x00, value as u8])
+}
+
+async fn h264_task(
+    session: Session,
+    args: Args,
+    rx: Receiver<OutputFrame>,
+    clock_offset: ClockOffset,
+    // Pre-opened in `stream()` before the sidecar write so a doomed
+    // record run aborts the whole process before producing orphaned
     // metadata. `None` when `--record` is not set.
-    mut recorder: Option<std::io::BufWriter<std::fs::File>>,
+    mut recorder: Option<Recorder>,
+    socket: Option<UnixSocketOutput>,
 ) {
     let publisher = match session
         .declare_publisher(args.h264_topic.clone())
@@ -623,73 +1745,273 @@ async fn h264_task(
         }
     };
 
+    let meta_publisher = match session
+        .declare_publisher(args.h264_meta_topic.clone())
+        .priority(Priority::Data)
+        .congestion_control(CongestionControl::Drop)
+        .await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            error!(
+                "Error while declaring H264 metadata publisher {}: {:?}",
+                args.h264_meta_topic, e
+            );
+            return;
+        }
+    };
+
+    let init_publisher = match session
+        .declare_publisher(args.h264_init_topic.clone())
+        .priority(Priority::Data)
+        .await
+    {
+        Ok(v) => Arc::new(v),
+        Err(e) => {
+            error!(
+                "Error while declaring H264 init publisher {}: {:?}",
+                args.h264_init_topic, e
+            );
+            return;
+        }
+    };
+    // Zenoh keeps no samples, so the parameter sets are re-sent to new
+    // subscribers and answered to queries instead.
+    let init_latest = LatestSample::default();
+    let reemit_task = tokio::spawn(reemit_on_match(
+        init_publisher.clone(),
+        init_latest.clone(),
+        Encoding::VIDEO_H264,
+    ));
+    std::mem::drop(reemit_task);
+    let latest = init_latest.clone();
+    let _init_queryable = match session
+        .declare_queryable(args.h264_init_topic.clone())
+        .callback(move |query| {
+            let Some((payload, timestamp)) = latest.get() else {
+                return;
+            };
+            let reply = query
+                .reply(query.key_expr().clone(), payload)
+                .encoding(Encoding::VIDEO_H264)
+                .timestamp(timestamp)
+                .wait();
+            if let Err(e) = reply {
+                warn!("H264 init query reply failed: {e}");
+            }
+        })
+        .await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            error!(
+                "Error while declaring H264 init queryable {}: {:?}",
+                args.h264_init_topic, e
+            );
+            return;
+        }
+    };
+    let mut init = InitSegment::default();
+    // Set by queries on --h264-keyframe-topic, for the encoder loop to
+    // force a keyframe.
+    let keyframe_requested = Arc::new(AtomicBool::new(false));
+    let requested = keyframe_requested.clone();
+    let _keyframe_queryable = match session
+        .declare_queryable(args.h264_keyframe_topic.clone())
+        .callback(move |query| {
+            requested.store(true, Ordering::Relaxed);
+            let reply = query
+                .reply(query.key_expr().clone(), ZBytes::default())
+                .wait();
+            if let Err(e) = reply {
+                warn!("H264 keyframe query reply failed: {e}");
+            }
+        })
+        .await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            error!(
+                "Error while declaring H264 keyframe queryable {}: {:?}",
+                args.h264_keyframe_topic, e
+            );
+            return;
+        }
+    };
+
+    #[cfg(feature = "srt")]
+    let srt = args.srt.clone().map(|config| {
+        info!("Sending H264 over SRT to {config}");
+        srt::SrtOutput::spawn(config, args.h264_bitrate, keyframe_requested.clone())
+    });
+
     let imgmgr = ImageManager::new().unwrap();
-    info!("Opened G2D with version {}", imgmgr.version());
-
-    let img_h264 = Image::new(args.stream_size[0], args.stream_size[1], RGBA).unwrap();
-    let mut vidmgr = VideoManager::new(
-        FourCC(*b"H264"),
-        args.stream_size[0] as i32,
-        args.stream_size[1] as i32,
-        args.h264_bitrate,
-    )
-    .unwrap();
+    if imgmgr.is_hardware() {
+        info!("Opened G2D with version {}", imgmgr.version());
+    }
+
+    let rotation = args.h264_output_rotation();
+    let (width, height) = rotation.rotated_size(args.stream_size[0], args.stream_size[1]);
+    let encoder = VideoManager::builder(args.codec.fourcc(), width as i32, height as i32)
+        .fps(args.target_fps as i32)
+        .sample_aspect_ratio(args.pixel_aspect)
+        .keyframe_interval(args.h264_keyframe_interval);
+    let mut bitrate_preset = args.h264_bitrate;
+    let mut vidmgr = encoder.clone().bitrate(bitrate_preset).build().unwrap();
+    let mut wallclock = args
+        .h264_keyframe_align_wallclock
+        .map(|secs| WallclockKeyframes::new(Duration::from_secs(secs)));
+    // Frames arrive already converted and rotated when the capture loop
+    // shares the conversion with the JPEG output. Those, and camera frames
+    // that already match the encoder input, skip G2D.
+    let pending_rotation = match args.shared_convert_target() {
+        Some(_) => Rotation::Rotation0,
+        None => rotation,
+    };
+    let mut staging: Option<Image> = None;
+    // Reused for every frame so encoding stops allocating once it has held
+    // the largest frame.
+    let mut data = Vec::new();
+    // Likewise for the --h264-framing copy of each frame.
+    let mut framing = Vec::new();
+    let mut sequence: u64 = 0;
+    let mut bitrate = BitrateWindow::new(Duration::from_secs(1));
+    let mut missed = ErrorFrames::new(args.error_frame_policy, args.target_fps);
 
     loop {
-        let (msg, ts) = match rx.recv() {
-            Ok(v) => v,
-            Err(_) => {
+        let (msg, ts, _permit) = match recv_frame(&rx, &mut missed, &imgmgr, "h264") {
+            Some(v) => v,
+            None => {
                 // main thread exited
                 break;
             }
         };
 
+        // --thermal-zone lowers the preset while the SoC is hot. The new
+        // encoder starts on a keyframe, which also refreshes the init
+        // segment.
+        let wanted = THERMAL.level().h264_bitrate(args.h264_bitrate);
+        if wanted != bitrate_preset {
+            match encoder.clone().bitrate(wanted).build() {
+                Ok(mgr) => {
+                    info!("H264 bitrate {bitrate_preset:?} -> {wanted:?}");
+                    vidmgr = mgr;
+                    bitrate_preset = wanted;
+                }
+                Err(e) => error!("Cannot reopen the H264 encoder at {wanted:?}: {e}"),
+            }
+        }
+        if keyframe_requested.swap(false, Ordering::Relaxed) {
+            debug!("H264 keyframe requested");
+            vidmgr.request_keyframe();
+        }
+
         let span = info_span!("h264");
         let sample_ts = zenoh_ts_for_frame(&session, &clock_offset, &ts);
         let stamp = clock_offset.to_realtime(&ts);
+        if let Some(wallclock) = wallclock.as_mut() {
+            if wallclock.crossed(stamp_ns(&stamp)) {
+                vidmgr.request_keyframe();
+            }
+        }
         async {
             // Encode once. The bytes feed both the recorder tap and the
             // Zenoh publish path so a late publish-side drop doesn't
             // cost us a recorded frame.
-            let (data, is_key) = match info_span!("h264_resize_encode")
-                .in_scope(|| vidmgr.resize_and_encode(&msg, &imgmgr, &img_h264))
-            {
+            let encode_start = Instant::now();
+            let encode =
+                || vidmgr.encode_into(&msg, &imgmgr, &mut staging, pending_rotation, &mut data);
+            let (is_key, pts) = match info_span!("h264_resize_encode").in_scope(encode) {
                 Ok(v) => v,
                 Err(e) => {
                     error!("h264 encode failed: {e}");
                     return;
                 }
             };
+            let now = Instant::now();
+            STATS.record_encode(now - encode_start, data.len());
+            if let Some(bps) = bitrate.add(data.len(), now) {
+                STATS.set_h264_bitrate(bps);
+            }
 
             if let Some(w) = recorder.as_mut() {
-                use std::io::Write;
-                if let Err(e) = w.write_all(&data) {
+                if let Err(e) = w.write(&data, is_key, stamp_ns(&stamp)) {
                     error!("h264 recorder write failed: {e}");
-                } else if is_key {
-                    if let Err(e) = w.flush() {
-                        error!("h264 recorder flush failed: {e}");
-                    }
                 }
             }
 
-            let (msg, enc) = build_h264_msg(&data, stamp, &args.camera_frame_id).unwrap();
+            if let Some(socket) = &socket {
+                socket.send(FrameKind::H264, stamp_ns(&stamp), &data);
+            }
+
+            #[cfg(feature = "srt")]
+            if let Some(srt) = &srt {
+                srt.send(&data, is_key);
+            }
+
+            // The parameter set parsing is H.264 only.
+            let h264_key = is_key && args.codec == Codec::H264;
+            if let Some(sets) = h264_key.then(|| init.update(&data)).flatten() {
+                match init_picture_size(sets) {
+                    Ok((w, h)) => info!("H264 parameter sets for {w}x{h}"),
+                    Err(e) => warn!("H264 parameter sets: {e}"),
+                }
+                let payload = ZBytes::from(args.h264_framing.apply(sets, &mut framing).to_vec());
+                init_latest.set(payload.clone(), sample_ts);
+                init_publisher
+                    .put(payload)
+                    .encoding(Encoding::VIDEO_H264)
+                    .timestamp(sample_ts)
+                    .await
+                    .unwrap();
+            }
+
+            // The recorder and socket above keep the capture time.
+            let stamp = match (args.h264_stamp, pts) {
+                (H264Stamp::Encoder, Some(pts)) => stamp_from_ns(pts),
+                _ => stamp,
+            };
+            let framed = args.h264_framing.apply(&data, &mut framing);
+            let meta = H264FrameMeta {
+                sec: stamp.sec,
+                nanosec: stamp.nanosec,
+                frame_id: args.camera_frame_id.clone(),
+                sequence,
+                width,
+                height,
+                is_keyframe: is_key,
+                size: framed.len(),
+                framing: args.h264_framing,
+                sensor: sensor::DIAGNOSTICS.latest(),
+            };
+            sequence += 1;
+
+            let frame_id = args.message_frame_id(stamp.sec, stamp.nanosec, meta.sequence);
+            let (msg, enc) = build_h264_msg(framed, args.codec, stamp, &frame_id).unwrap();
             publisher
                 .put(msg)
                 .encoding(enc)
                 .timestamp(sample_ts)
                 .await
                 .unwrap();
+
+            let (msg, enc) = build_h264_meta_msg(&meta).unwrap();
+            meta_publisher
+                .put(msg)
+                .encoding(enc)
+                .timestamp(sample_ts)
+                .await
+                .unwrap();
         }
         .instrument(span)
         .await;
         args.tracy.then(|| secondary_frame_mark!("h264"));
     }
 
-    // BufWriter flushes on drop, but make the ordering explicit so the
+    // The BufWriter flushes on drop, but make the ordering explicit so the
     // last GOP hits disk before we return and the tokio runtime tears
     // this thread down.
     if let Some(mut w) = recorder.take() {
-        use std::io::Write;
         if let Err(e) = w.flush() {
             error!("h264 recorder final flush failed: {e}");
         }
@@ -699,8 +2021,9 @@ async fn h264_task(
 async fn jpeg_task(
     session: Session,
     args: Args,
-    rx: Receiver<(Image, Timestamp)>,
+    rx: Receiver<OutputFrame>,
     clock_offset: ClockOffset,
+    socket: Option<UnixSocketOutput>,
 ) {
     let publisher = match session
         .declare_publisher(args.jpeg_topic.clone())
@@ -718,29 +2041,167 @@ async fn jpeg_task(
         }
     };
 
-    let imgmgr = ImageManager::new().unwrap();
-    let img_jpeg = Image::new(args.stream_size[0], args.stream_size[1], RGBA).unwrap();
+    // JPEG frames are the first to go when --g2d-max-ops-per-sec is spent.
+    let mut imgmgr = ImageManager::new().unwrap();
+    imgmgr.set_priority(BlitPriority::Low);
+    let rotation = args.jpeg_output_rotation();
+    let (width, height) = rotation.rotated_size(args.stream_size[0], args.stream_size[1]);
+    // Frames arrive already converted when the capture loop shares the
+    // conversion with the H.264 output, and the luma plane is encoded
+    // straight from the source frame.
+    let img_jpeg = (args.shared_convert_target().is_none() && args.jpeg_plane == JpegPlane::All)
+        .then(|| Image::new(width, height, RGBA).unwrap());
+    let mut limiter = args.jpeg_fps.map(RateLimiter::new);
+    let mut missed = ErrorFrames::new(args.error_frame_policy, args.target_fps);
+    // JPEG frames published since the stream started, for --debug-frame-id.
+    let mut sequence: u64 = 0;
+
+    // --hires-interval: the same frame again at its full size, on a
+    // schedule of its own.
+    let mut hires = match args.hires_interval {
+        Some(secs) => match session
+            .declare_publisher(args.hires_topic.clone())
+            .priority(Priority::Data)
+            .congestion_control(CongestionControl::Drop)
+            .await
+        {
+            Ok(publisher) => {
+                info!(
+                    "Publishing a full-size JPEG every {secs} s on {}",
+                    args.hires_topic
+                );
+                Some(HiresOutput {
+                    publisher,
+                    schedule: RateLimiter::every(Duration::from_secs(secs)),
+                    image: None,
+                    sequence: 0,
+                })
+            }
+            Err(e) => {
+                error!(
+                    "Error while declaring JPEG publisher {}: {:?}",
+                    args.hires_topic, e
+                );
+                return;
+            }
+        },
+        None => None,
+    };
+
+    // --jpeg-dirty-rect: only the part of each frame that changed, with
+    // where it goes on a topic of its own.
+    let mut dirty = match args.jpeg_dirty_rect {
+        true => match session
+            .declare_publisher(args.jpeg_region_topic.clone())
+            .priority(Priority::Data)
+            .congestion_control(CongestionControl::Drop)
+            .await
+        {
+            Ok(publisher) => Some(DirtyOutput {
+                publisher,
+                tracker: DirtyTracker::new(args.jpeg_dirty_threshold),
+                refresh: RateLimiter::every(Duration::from_secs(args.jpeg_dirty_refresh)),
+            }),
+            Err(e) => {
+                error!(
+                    "Error while declaring JPEG region publisher {}: {:?}",
+                    args.jpeg_region_topic, e
+                );
+                return;
+            }
+        },
+        false => None,
+    };
 
     loop {
-        let (msg, ts) = match rx.recv() {
-            Ok(v) => v,
-            Err(_) => {
+        let (msg, ts, _permit) = match recv_frame(&rx, &mut missed, &imgmgr, "jpeg") {
+            Some(v) => v,
+            None => {
                 // main thread exited
                 return;
             }
         };
+        if let Some(hires) = hires.as_mut().filter(|_| THERMAL.level().hires()) {
+            if hires.schedule.ready_at(&ts) {
+                let sample_ts = zenoh_ts_for_frame(&session, &clock_offset, &ts);
+                hires
+                    .publish(
+                        &msg,
+                        &ts,
+                        sample_ts,
+                        &imgmgr,
+                        rotation,
+                        &args,
+                        &clock_offset,
+                    )
+                    .instrument(info_span!("jpeg_hires"))
+                    .await;
+            }
+        }
+        // Drop frames outside the --jpeg-fps schedule before paying for the
+        // conversion and encode.
+        if limiter.as_mut().is_some_and(|l| !l.ready_at(&ts)) {
+            continue;
+        }
 
         let span = info_span!("jpeg");
         let sample_ts = zenoh_ts_for_frame(&session, &clock_offset, &ts);
         async {
-            let (msg, enc) =
-                build_jpeg_msg(&msg, &ts, &imgmgr, &img_jpeg, &args, &clock_offset).unwrap();
+            // Y16 depth frames are always published whole, as PNG.
+            let built = match dirty.as_mut() {
+                Some(dirty) if msg.format() != image::Y16 => dirty.build_msgs(
+                    &msg,
+                    &ts,
+                    &imgmgr,
+                    img_jpeg.as_ref(),
+                    rotation,
+                    &args,
+                    &clock_offset,
+                    socket.as_ref(),
+                    sequence,
+                ),
+                _ => build_jpeg_msg(
+                    &msg,
+                    &ts,
+                    &imgmgr,
+                    img_jpeg.as_ref(),
+                    rotation,
+                    &args,
+                    &clock_offset,
+                    socket.as_ref(),
+                    sequence,
+                )
+                .map(|msg| Some((msg, None))),
+            };
+            let ((msg, enc), region) = match built {
+                Ok(Some(v)) => v,
+                // Nothing changed since the last --jpeg-dirty-rect frame.
+                Ok(None) => return,
+                Err(e) if e.is::<BlitBudgetExceeded>() => {
+                    debug!("dropping JPEG frame: {e}");
+                    return;
+                }
+                Err(e) => {
+                    warn!("dropping JPEG frame: {e}");
+                    return;
+                }
+            };
             publisher
                 .put(msg)
                 .encoding(enc)
                 .timestamp(sample_ts)
                 .await
                 .unwrap();
+            if let (Some(dirty), Some((msg, enc))) = (dirty.as_ref(), region) {
+                dirty
+                    .publisher
+                    .put(msg)
+                    .encoding(enc)
+                    .timestamp(sample_ts)
+                    .await
+                    .unwrap();
+            }
+            sequence += 1;
         }
         .instrument(span)
         .await;
@@ -748,10 +2209,227 @@ async fn jpeg_task(
     }
 }
 
+/// The `--hires-interval` JPEG output: the JPEG thread's frames encoded at
+/// their full size, at most once per interval.
+struct HiresOutput {
+    publisher: Publisher<'static>,
+    schedule: RateLimiter,
+    /// Conversion target, allocated for the first frame and again when
+    /// the frame size changes.
+    image: Option<Image>,
+    /// Images published, for --debug-frame-id.
+    sequence: u64,
+}
+
+impl HiresOutput {
+    #[allow(clippy::too_many_arguments)]
+    async fn publish(
+        &mut self,
+        frame: &Image,
+        ts: &Timestamp,
+        sample_ts: ZenohTimestamp,
+        imgmgr: &ImageManager,
+        rotation: Rotation,
+        args: &Args,
+        clock_offset: &ClockOffset,
+    ) {
+        // The luma plane is encoded straight from the frame, as for the
+        // stream-size JPEG.
+        let target = match args.jpeg_plane {
+            JpegPlane::All => {
+                let (width, height) = rotation.rotated_size(frame.width(), frame.height());
+                let stale = self
+                    .image
+                    .as_ref()
+                    .is_none_or(|img| (img.width(), img.height()) != (width, height));
+                if stale {
+                    match Image::new(width, height, RGBA) {
+                        Ok(img) => self.image = Some(img),
+                        Err(e) => {
+                            warn!("dropping full-size JPEG frame: {e}");
+                            return;
+                        }
+                    }
+                }
+                self.image.as_ref()
+            }
+            JpegPlane::Luma => None,
+        };
+        // Not served on --unix-socket, whose clients expect the stream.
+        let (msg, enc) = match build_jpeg_msg(
+            frame,
+            ts,
+            imgmgr,
+            target,
+            rotation,
+            args,
+            clock_offset,
+            None,
+            self.sequence,
+        ) {
+            Ok(v) => v,
+            Err(e) if e.is::<BlitBudgetExceeded>() => {
+                debug!("dropping full-size JPEG frame: {e}");
+                return;
+            }
+            Err(e) => {
+                warn!("dropping full-size JPEG frame: {e}");
+                return;
+            }
+        };
+        if let Err(e) = self
+            .publisher
+            .put(msg)
+            .encoding(enc)
+            .timestamp(sample_ts)
+            .await
+        {
+            warn!("Cannot publish full-size JPEG frame: {e}");
+            return;
+        }
+        self.sequence += 1;
+    }
+}
+
+/// The `--jpeg-dirty-rect` JPEG output: the region of each frame that
+/// changed, and where it goes on `--jpeg-region-topic`.
+struct DirtyOutput {
+    publisher: Publisher<'static>,
+    tracker: DirtyTracker,
+    /// Schedule of the full frames late subscribers start from.
+    refresh: RateLimiter,
+}
+
+impl DirtyOutput {
+    /// As [`build_jpeg_msg`], but encode only what changed, and return the
+    /// region message to publish with the image. `None` when nothing
+    /// changed.
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    fn build_msgs(
+        &mut self,
+        buf: &Image,
+        ts: &Timestamp,
+        imgmgr: &ImageManager,
+        img: Option<&Image>,
+        rotation: Rotation,
+        args: &Args,
+        clock_offset: &ClockOffset,
+        socket: Option<&UnixSocketOutput>,
+        seq: u64,
+    ) -> Result<Option<((ZBytes, Encoding), Option<(ZBytes, Encoding)>)>, Box<dyn Error>> {
+        let img = match img {
+            Some(img) => {
+                info_span!("jpeg_convert").in_scope(|| imgmgr.convert(buf, img, None, rotation))?;
+                img
+            }
+            None => buf,
+        };
+        let (width, height) = (img.width(), img.height());
+        let full = self.refresh.ready_at(ts);
+        let tracker = &mut self.tracker;
+        let encode = |pix: &[u8], width, height| {
+            jpeg::compress_rgba_with(
+                jpeg::Backend::default(),
+                pix,
+                width,
+                height,
+                args.jpeg_quality,
+                args.jpeg_subsamp,
+            )
+        };
+        let encoded = img.with_mapping(|pix| -> Result<_, Box<dyn Error>> {
+            let change =
+                info_span!("jpeg_dirty").in_scope(|| tracker.update_rgba(pix, width, height, full));
+            let rect = match change {
+                Dirty::Unchanged => return Ok(None),
+                Dirty::Full => Rect {
+                    x: 0,
+                    y: 0,
+                    width: width as i32,
+                    height: height as i32,
+                },
+                Dirty::Region(rect) => rect,
+            };
+            let jpeg = info_span!("jpeg_encode").in_scope(|| match change {
+                Dirty::Region(_) => encode(
+                    &dirty::crop_rgba(pix, width, rect),
+                    rect.width as u32,
+                    rect.height as u32,
+                ),
+                _ => encode(pix, width, height),
+            })?;
+            Ok(Some((change, rect, jpeg)))
+        })??;
+        let Some((change, rect, jpeg)) = encoded else {
+            return Ok(None);
+        };
+
+        // --unix-socket clients expect whole frames.
+        let full = change == Dirty::Full;
+        let image = compressed_image_msg(
+            &jpeg,
+            ImageCodec::Jpeg,
+            ts,
+            args,
+            clock_offset,
+            socket.filter(|_| full),
+            seq,
+        )?;
+        let stamp = clock_offset.to_realtime(ts);
+        let meta = JpegRegionMeta {
+            sec: stamp.sec,
+            nanosec: stamp.nanosec,
+            frame_id: args.camera_frame_id.clone(),
+            sequence: seq,
+            x: rect.x as u32,
+            y: rect.y as u32,
+            width: rect.width as u32,
+            height: rect.height as u32,
+            frame_width: width,
+            frame_height: height,
+            full,
+        };
+        Ok(Some((image, Some(build_jpeg_region_msg(&meta)?))))
+    }
+}
+
+/// Convert each frame into the negotiated V4L2 output format and write it
+/// to the device. Runs on a plain thread: nothing here touches Zenoh.
+fn v4l2_output_task(args: Args, rx: Receiver<OutputFrame>, mut output: V4l2Output) {
+    let mut imgmgr = ImageManager::new().unwrap();
+    imgmgr.set_priority(BlitPriority::Low);
+    let mut img = Image::new(
+        args.stream_size[0],
+        args.stream_size[1],
+        args.v4l2_output_format.fourcc(),
+    )
+    .unwrap();
+
+    while let Ok((src, _ts, _permit)) = rx.recv() {
+        let _span = info_span!("v4l2_output").entered();
+        if let Err(e) = imgmgr.convert(&src, &img, None, Rotation::Rotation0) {
+            if !e.is::<BlitBudgetExceeded>() {
+                error!("V4L2 output convert failed: {e}");
+            }
+            continue;
+        }
+        let mapped = match img.mmap() {
+            Ok(mapped) => mapped,
+            Err(e) => {
+                error!("V4L2 output map failed: {e}");
+                continue;
+            }
+        };
+        if let Err(e) = output.write_frame(mapped.as_slice()) {
+            error!("V4L2 output write failed: {e}");
+        }
+    }
+}
+
 async fn h264_single_tile_task(
     session: Session,
     args: Args,
-    rx: Receiver<(Image, Timestamp)>,
+    rx: Receiver<OutputFrame>,
     tile_pos: TilePosition,
     topic: String,
     clock_offset: ClockOffset,
@@ -779,19 +2457,22 @@ async fn h264_single_tile_task(
     let (crop_x, crop_y, crop_width, crop_height) =
         tile_pos.get_crop_params(initial_width, initial_height);
 
-    let mut vid_mgr = match VideoManager::new_with_crop(
-        FourCC(*b"H264"),
+    let builder = VideoManager::builder(
+        args.codec.fourcc(),
         output_width as i32,
         output_height as i32,
-        (
-            crop_x as i32,
-            crop_y as i32,
-            crop_width as i32,
-            crop_height as i32,
-        ),
-        args.h264_bitrate,
-        Some(args.h264_tiles_fps as i32),
-    ) {
+    )
+    .crop((
+        crop_x as i32,
+        crop_y as i32,
+        crop_width as i32,
+        crop_height as i32,
+    ))
+    .bitrate(args.h264_bitrate)
+    .fps(args.h264_tiles_fps as i32)
+    .keyframe_interval(args.h264_keyframe_interval)
+    .sample_aspect_ratio(args.pixel_aspect);
+    let mut vid_mgr = match builder.build() {
         Ok(mgr) => mgr,
         Err(e) => {
             error!(
@@ -803,12 +2484,12 @@ async fn h264_single_tile_task(
     };
 
     let mut last_source_size = (initial_width, initial_height);
-    let tile_fps_limit = args.h264_tiles_fps;
-    let frame_interval = Duration::from_millis(1000 / tile_fps_limit as u64);
-    let mut last_encode_time = Instant::now();
+    let mut limiter = RateLimiter::new(args.h264_tiles_fps);
+    let mut data = Vec::new();
+    let mut framing = Vec::new();
 
     loop {
-        let (source_img, ts) = match rx.recv() {
+        let (source_img, ts, _permit) = match rx.recv() {
             Ok(v) => v,
             Err(_) => {
                 // main thread exited
@@ -818,11 +2499,9 @@ async fn h264_single_tile_task(
 
         let span = info_span!("h264_tile", tile = ?tile_pos);
         async {
-            let now = Instant::now();
-            if now.duration_since(last_encode_time) < frame_interval {
+            if !limiter.ready_at(&ts) || !THERMAL.level().tiles() {
                 return;
             }
-            last_encode_time = now;
             let current_source_size = (source_img.width(), source_img.height());
             if current_source_size != last_source_size {
                 let (new_crop_x, new_crop_y, new_crop_width, new_crop_height) =
@@ -836,22 +2515,25 @@ async fn h264_single_tile_task(
                 last_source_size = current_source_size;
             }
 
-            match vid_mgr.encode_direct(&source_img) {
-                Ok((data, _is_key)) => {
-                    match build_tile_video_msg(&data, &ts, &args, tile_pos, &clock_offset) {
-                        Ok((msg, enc)) => {
-                            let sample_ts = zenoh_ts_for_frame(&session, &clock_offset, &ts);
-                            if let Err(e) =
-                                publisher.put(msg).encoding(enc).timestamp(sample_ts).await
-                            {
-                                error!("Failed to publish tile {:?}: {:?}", tile_pos, e);
-                            }
-                        }
-                        Err(e) => {
-                            error!("Failed to build tile video message: {:?}", e);
+            match vid_mgr.encode_direct_into(&source_img, &mut data) {
+                Ok(_) => match build_tile_video_msg(
+                    args.h264_framing.apply(&data, &mut framing),
+                    &ts,
+                    &args,
+                    tile_pos,
+                    &clock_offset,
+                ) {
+                    Ok((msg, enc)) => {
+                        let sample_ts = zenoh_ts_for_frame(&session, &clock_offset, &ts);
+                        if let Err(e) = publisher.put(msg).encoding(enc).timestamp(sample_ts).await
+                        {
+                            error!("Failed to publish tile {:?}: {:?}", tile_pos, e);
                         }
                     }
-                }
+                    Err(e) => {
+                        error!("Failed to build tile video message: {:?}", e);
+                    }
+                },
                 Err(e) => {
                     error!("Failed to encode tile {:?}: {:?}", tile_pos, e);
                 }
@@ -863,55 +2545,222 @@ async fn h264_single_tile_task(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_jpeg_msg(
     buf: &Image,
     ts: &Timestamp,
     imgmgr: &ImageManager,
-    img: &Image,
+    img: Option<&Image>,
+    rotation: Rotation,
     args: &Args,
     clock_offset: &ClockOffset,
+    socket: Option<&UnixSocketOutput>,
+    seq: u64,
 ) -> Result<(ZBytes, Encoding), Box<dyn Error>> {
-    info_span!("jpeg_convert").in_scope(|| imgmgr.convert(buf, img, None, Rotation::Rotation0))?;
+    // Y16 depth frames keep all 16 bits as PNG at the source size; G2D
+    // cannot convert them and an 8-bit JPEG would drop the low byte.
+    if buf.format() == image::Y16 {
+        let png = info_span!("png_encode").in_scope(|| {
+            buf.with_dma_buf(|dma| {
+                let png = dma.memory_map()?.read(png::encode_image, Some(buf))?;
+                Ok::<_, Box<dyn Error>>(png)
+            })
+        })?;
+        return compressed_image_msg(&png, ImageCodec::Png, ts, args, clock_offset, socket, seq);
+    }
 
+    // Without a conversion target `buf` is already the image to encode.
+    let img = match img {
+        Some(img) => {
+            info_span!("jpeg_convert").in_scope(|| imgmgr.convert(buf, img, None, rotation))?;
+            img
+        }
+        None => buf,
+    };
     let jpeg = info_span!("jpeg_encode").in_scope(|| {
-        let dma = img.dmabuf();
-        let buf = dma.memory_map()?.read(encode_jpeg, Some(img))?;
-        Ok::<_, Box<dyn Error>>(buf)
+        img.with_mapping(|pix| match args.jpeg_plane {
+            JpegPlane::All => {
+                encode_jpeg_with(pix, Some(img), args.jpeg_quality, args.jpeg_subsamp)
+            }
+            JpegPlane::Luma => encode_jpeg_luma_with(pix, Some(img), args.jpeg_quality),
+        })?
     })?;
+    compressed_image_msg(&jpeg, ImageCodec::Jpeg, ts, args, clock_offset, socket, seq)
+}
+
+/// How the JPEG output encoded a frame: JPEG, or PNG for Y16 depth frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ImageCodec {
+    Jpeg,
+    Png,
+}
+
+impl ImageCodec {
+    /// The `CompressedImage.format` value.
+    fn format(self) -> &'static str {
+        match self {
+            ImageCodec::Jpeg => "jpeg",
+            ImageCodec::Png => "png",
+        }
+    }
+
+    fn frame_kind(self) -> FrameKind {
+        match self {
+            ImageCodec::Jpeg => FrameKind::Jpeg,
+            ImageCodec::Png => FrameKind::Png,
+        }
+    }
+}
 
+/// Serve an encoded frame on the Unix socket and package it for the JPEG
+/// topic.
+#[allow(clippy::too_many_arguments)]
+fn compressed_image_msg(
+    data: &[u8],
+    codec: ImageCodec,
+    ts: &Timestamp,
+    args: &Args,
+    clock_offset: &ClockOffset,
+    socket: Option<&UnixSocketOutput>,
+    seq: u64,
+) -> Result<(ZBytes, Encoding), Box<dyn Error>> {
     args.tracy
-        .then(|| plot!("jpeg_kb", (jpeg.len() / 1024) as f64));
+        .then(|| plot!("jpeg_kb", (data.len() / 1024) as f64));
 
-    info_span!("jpeg_publish").in_scope(|| {
-        let msg = CompressedImage::new(
-            clock_offset.to_realtime(ts),
-            &args.camera_frame_id,
-            "jpeg",
-            &jpeg,
-        )?;
-        let bytes = ZBytes::from(msg.into_cdr());
-        let enc = Encoding::APPLICATION_CDR.with_schema("sensor_msgs/msg/CompressedImage");
-        Ok((bytes, enc))
-    })
+    let stamp = clock_offset.to_realtime(ts);
+    if let Some(socket) = socket {
+        socket.send(codec.frame_kind(), stamp_ns(&stamp), data);
+    }
+
+    let frame_id = args.message_frame_id(stamp.sec, stamp.nanosec, seq);
+    info_span!("jpeg_publish")
+        .in_scope(|| build_compressed_image_msg(data, codec.format(), stamp, &frame_id))
+}
+
+/// Package encoded image bytes into a `sensor_msgs/CompressedImage` CDR
+/// payload, with `format` naming the codec (`jpeg` or `png`).
+fn build_compressed_image_msg(
+    data: &[u8],
+    format: &str,
+    stamp: builtin_interfaces::Time,
+    frame_id: &str,
+) -> Result<(ZBytes, Encoding), Box<dyn Error>> {
+    let msg = CompressedImage::new(stamp, frame_id, format, data)?;
+    let bytes = ZBytes::from(msg.into_cdr());
+    let enc = Encoding::APPLICATION_CDR.with_schema("sensor_msgs/msg/CompressedImage");
+    Ok((bytes, enc))
 }
 
-/// Package already-encoded (or already-read) H.264 Annex-B bytes into a
-/// `foxglove_msgs/CompressedVideo` CDR payload. Shared by the live
+/// Package already-encoded (or already-read) `codec` bytes, in
+/// `--h264-framing`, into a `foxglove_msgs/CompressedVideo` CDR payload.
+/// The schema calls for Annex-B, so AVCC payloads only decode in consumers
+/// that check the `framing` of [`H264FrameMeta`]. Shared by the live
 /// encode path and by replay (which reads the bytes from disk and
-/// forwards them verbatim).
+/// forwards them, reframed if need be).
 fn build_h264_msg(
     data: &[u8],
+    codec: Codec,
     stamp: builtin_interfaces::Time,
     frame_id: &str,
 ) -> Result<(ZBytes, Encoding), Box<dyn Error>> {
     info_span!("h264_publish").in_scope(|| {
-        let msg = FoxgloveCompressedVideo::new(stamp, frame_id, data, "h264")?;
+        let msg = FoxgloveCompressedVideo::new(stamp, frame_id, data, codec.format())?;
         let bytes = ZBytes::from(msg.into_cdr());
         let enc = Encoding::APPLICATION_CDR.with_schema("foxglove_msgs/msg/CompressedVideo");
         Ok((bytes, enc))
     })
 }
 
+/// Per-frame companion to the H.264 stream. `foxglove_msgs/CompressedVideo`
+/// only carries a format string, so resolution, keyframe and sequence
+/// information is published alongside it as JSON, with the same Zenoh
+/// timestamp and header stamp as the video sample it describes.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct H264FrameMeta {
+    pub sec: i32,
+    pub nanosec: u32,
+    pub frame_id: String,
+    /// Frames published on the H.264 topic since the stream started.
+    pub sequence: u64,
+    pub width: u32,
+    pub height: u32,
+    pub is_keyframe: bool,
+    /// Size of the published frame in bytes, in `framing`.
+    pub size: usize,
+    /// NAL unit framing of the published frame, see `--h264-framing`.
+    /// `foxglove_msgs/CompressedVideo` expects Annex-B, so consumers of
+    /// `avcc` frames must convert them back.
+    #[serde(default)]
+    pub framing: H264Framing,
+    /// The latest `--sensor-diagnostics` readings, by control name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensor: Option<sensor::SensorReadings>,
+}
+
+fn build_h264_meta_msg(meta: &H264FrameMeta) -> Result<(ZBytes, Encoding), Box<dyn Error>> {
+    let bytes = ZBytes::from(serde_json::to_vec(meta)?);
+    Ok((bytes, Encoding::APPLICATION_JSON))
+}
+
+/// Where a `--jpeg-dirty-rect` JPEG goes in the full frame, published as
+/// JSON on `--jpeg-region-topic` with the same Zenoh timestamp and header
+/// stamp as the image. Consumers draw each JPEG at (`x`, `y`) over the
+/// image built up since the last full frame.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct JpegRegionMeta {
+    pub sec: i32,
+    pub nanosec: u32,
+    pub frame_id: String,
+    /// Frames published on the JPEG topic since the stream started.
+    pub sequence: u64,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Size of the full frame the region belongs to.
+    pub frame_width: u32,
+    pub frame_height: u32,
+    /// Whether the JPEG is the whole frame, which replaces the image built
+    /// so far.
+    pub full: bool,
+}
+
+fn build_jpeg_region_msg(meta: &JpegRegionMeta) -> Result<(ZBytes, Encoding), Box<dyn Error>> {
+    let bytes = ZBytes::from(serde_json::to_vec(meta)?);
+    Ok((bytes, Encoding::APPLICATION_JSON))
+}
+
+/// The SPS and PPS last seen in the H.264 stream, published on
+/// `--h264-init-topic`. Decoders need them before the first keyframe, and
+/// a subscriber that joins mid-stream has missed the ones in front of the
+/// keyframes it did not receive.
+#[derive(Default)]
+struct InitSegment {
+    current: Option<Vec<u8>>,
+}
+
+impl InitSegment {
+    /// Take the parameter sets of a keyframe access unit, returning them
+    /// when they differ from the previous ones, as after a resolution or
+    /// bitrate change.
+    fn update(&mut self, access_unit: &[u8]) -> Option<&[u8]> {
+        let sets = nal::parameter_sets(access_unit)?;
+        if self.current.as_ref() == Some(&sets) {
+            return None;
+        }
+        self.current = Some(sets);
+        self.current.as_deref()
+    }
+}
+
+/// Picture size coded by the SPS in parameter sets from [`InitSegment`].
+fn init_picture_size(sets: &[u8]) -> Result<(u32, u32), Box<dyn Error>> {
+    let sps = nal::iter_nal_units(sets)
+        .find(|unit| nal::nal_type(unit) == NAL_TYPE_SPS)
+        .ok_or("no SPS")?;
+    sps::picture_size(sps)
+}
+
 fn build_tile_video_msg(
     data: &[u8],
     ts: &Timestamp,
@@ -921,8 +2770,8 @@ fn build_tile_video_msg(
 ) -> Result<(ZBytes, Encoding), Box<dyn Error>> {
     info_span!("h264_tile_publish").in_scope(|| {
         let frame_id = format!("{}_{:?}", args.camera_frame_id, tile_pos).to_lowercase();
-        let msg =
-            FoxgloveCompressedVideo::new(clock_offset.to_realtime(ts), &frame_id, data, "h264")?;
+        let stamp = clock_offset.to_realtime(ts);
+        let msg = FoxgloveCompressedVideo::new(stamp, &frame_id, data, args.codec.format())?;
         let bytes = ZBytes::from(msg.into_cdr());
         let enc = Encoding::APPLICATION_CDR.with_schema("foxglove_msgs/msg/CompressedVideo");
         Ok((bytes, enc))
@@ -976,6 +2825,7 @@ pub(crate) fn build_camera_frame_msg(
     plane_fd: i32,
     plane_stride: u32,
     plane_len: u32,
+    plane_used: u32,
     colorimetry: &Colorimetry,
 ) -> Result<(ZBytes, Encoding), Box<dyn Error>> {
     // Single-plane, contiguous DMA-BUF. Plane 0 covers the whole buffer;
@@ -983,13 +2833,14 @@ pub(crate) fn build_camera_frame_msg(
     // chroma plane lives inside the same fd via its natural offset but
     // is not described by a second CameraPlane entry until videostream
     // exposes multi-plane offsets (known limitation, tracked in the
-    // 2.7.0 release notes).
+    // 2.7.0 release notes). `size` is the whole buffer and `used` the
+    // image in it, so consumers can tell padding from pixels.
     let plane = CameraPlaneView {
         fd: plane_fd,
         offset: 0,
         stride: plane_stride,
         size: plane_len,
-        used: plane_len,
+        used: plane_used,
         data: &[],
     };
 
@@ -1014,27 +2865,27 @@ pub(crate) fn build_camera_frame_msg(
     Ok((bytes, enc))
 }
 
-#[instrument(skip_all, fields(width = buf.width(), height = buf.height(), format = fourcc))]
+#[instrument(skip_all, fields(width = frame.image.width(), height = frame.image.height(), format = fourcc))]
 fn camera_frame_serialize(
-    buf: &CameraBuffer<'_>,
-    ts: &Timestamp,
+    frame: &CapturedFrame,
+    stamp: builtin_interfaces::Time,
     pid: u32,
     frame_id: &str,
-    clock_offset: &ClockOffset,
     colorimetry: &Colorimetry,
     fourcc: &str,
 ) -> Result<(ZBytes, Encoding), Box<dyn Error>> {
     build_camera_frame_msg(
-        clock_offset.to_realtime(ts),
+        stamp,
         frame_id,
-        buf.sequence()? as u64,
+        frame.sequence,
         pid,
-        buf.width() as u32,
-        buf.height() as u32,
+        frame.image.width(),
+        frame.image.height(),
         fourcc,
-        buf.rawfd(),
-        buf.bytes_per_line()?,
-        buf.length()? as u32,
+        frame.fd,
+        frame.stride,
+        frame.length,
+        frame.image.size() as u32,
         colorimetry,
     )
 }
@@ -1111,27 +2962,101 @@ impl From<RoiFields> for RegionOfInterest {
     }
 }
 
+/// The calibration JSON at `args.cam_info_path`, or `None` when no path is
+/// set. Parsed once at startup for both [`CameraInfoFields::from_calibration`]
+/// and [`color_correction_from_calibration`].
+fn read_calibration(args: &Args) -> Result<Option<serde_json::Value>, Box<dyn Error>> {
+    if args.cam_info_path.is_empty() {
+        return Ok(None);
+    }
+    let file = File::open(&args.cam_info_path)
+        .map_err(|e| format!("Cannot open file {:?}: {e:?}", &args.cam_info_path))?;
+    let json = serde_json::from_reader(file).map_err(|e| {
+        format!(
+            "Cannot parse camera info JSON from {:?}: {e}",
+            &args.cam_info_path
+        )
+    })?;
+    Ok(Some(json))
+}
+
+/// The `color_correction` object of the calibration JSON `json`, read from
+/// `path`, if any: a row-major 3x3 `matrix` (identity when absent) and a
+/// `gamma` for each of R, G and B, or one for all three (1 when absent).
+fn color_correction_from_calibration(
+    json: &serde_json::Value,
+    path: &str,
+) -> Result<Option<ColorCorrection>, Box<dyn Error>> {
+    let config = &json["color_correction"];
+    if config.is_null() {
+        return Ok(None);
+    }
+    let numbers = |key: &str| -> Result<Option<Vec<f64>>, Box<dyn Error>> {
+        let value = &config[key];
+        let values = match value {
+            serde_json::Value::Null => return Ok(None),
+            serde_json::Value::Number(_) => std::slice::from_ref(value),
+            serde_json::Value::Array(values) => values.as_slice(),
+            _ => return Err(format!("color_correction {key} must be numbers").into()),
+        };
+        values
+            .iter()
+            .map(|v| {
+                v.as_f64()
+                    .ok_or_else(|| format!("color_correction {key} must be numbers").into())
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()
+            .map(Some)
+    };
+    let matrix = match numbers("matrix")? {
+        None => color::IDENTITY,
+        Some(m) => m.try_into().map_err(|m: Vec<f64>| {
+            format!(
+                "color_correction matrix needs 9 elements but has {}",
+                m.len()
+            )
+        })?,
+    };
+    let gamma = match numbers("gamma")?.as_deref() {
+        None => [1.0; 3],
+        Some(&[g]) => [g; 3],
+        Some(&[r, g, b]) => [r, g, b],
+        Some(g) => {
+            return Err(format!(
+                "color_correction gamma needs 1 or 3 values but has {}",
+                g.len()
+            )
+            .into())
+        }
+    };
+    ColorCorrection::new(matrix, gamma)
+        .map(Some)
+        .map_err(|e| format!("{path:?}: {e}").into())
+}
+
 impl CameraInfoFields {
     /// Compute the fields that would populate a live `/camera/info` message
-    /// from `Args`. Reads the optional calibration JSON at
-    /// `args.cam_info_path`; falls back to reasonable defaults when not
+    /// from `Args` and the optional calibration JSON from
+    /// [`read_calibration`]; falls back to reasonable defaults when not
     /// provided.
-    pub(crate) fn from_args(args: &Args) -> Result<Self, Box<dyn Error>> {
-        let (width, height, distortion_model, d, k, r, p) = if !args.cam_info_path.is_empty() {
-            let file = File::open(&args.cam_info_path)
-                .map_err(|e| format!("Cannot open file {:?}: {e:?}", &args.cam_info_path))?;
-            let json: serde_json::Value = serde_json::from_reader(file).map_err(|e| {
-                format!(
-                    "Cannot parse camera info JSON from {:?}: {e}",
-                    &args.cam_info_path
-                )
-            })?;
+    pub(crate) fn from_calibration(
+        args: &Args,
+        calibration: Option<&serde_json::Value>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (width, height, distortion_model, d, k, r, p) = if let Some(json) = calibration {
             let bypass = json["bypass"].as_bool().unwrap_or(false);
-            let dewarp_configs = &json["dewarpConfigArray"];
-            if !dewarp_configs.is_array() {
+            let Some(dewarp_configs) = json["dewarpConfigArray"].as_array() else {
                 return Err(Box::from("Did not find dewarpConfigArray as an array"));
-            }
-            let dewarp_config = &dewarp_configs[0];
+            };
+            let Some(dewarp_config) = dewarp_configs.get(args.dewarp_index) else {
+                return Err(format!(
+                    "--dewarp-index {} is out of range, {:?} has {} dewarpConfigArray entries",
+                    args.dewarp_index,
+                    &args.cam_info_path,
+                    dewarp_configs.len()
+                )
+                .into());
+            };
             let d: Vec<f64> = if bypass {
                 let distortion_coeff = dewarp_config["distortion_coeff"].as_array();
                 match distortion_coeff {
@@ -1210,17 +3135,90 @@ impl CameraInfoFields {
         })
     }
 
-    /// Serialize these fields into a fresh `sensor_msgs/CameraInfo` CDR
-    /// buffer stamped with the current wall-clock time.
-    pub(crate) fn build_msg(&self) -> Result<CameraInfo<Vec<u8>>, Box<dyn Error>> {
-        let stamp = match timestamp() {
-            Ok(t) => t,
-            Err(TimestampError::Overflow) => {
-                warn!("Timestamp overflow: system clock exceeds i32 range (Y2038), saturating");
-                SATURATED_TIME
-            }
-            Err(e) => return Err(e.into()),
+    /// Table that removes the calibrated lens distortion from
+    /// `width`x`height` frames, with the camera matrix scaled from the
+    /// calibration's image size.
+    pub(crate) fn remap_table(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<RemapTable, Box<dyn Error>> {
+        if self.width == 0 || self.height == 0 {
+            return Err("calibration has an empty image size".into());
+        }
+        let (sx, sy) = (
+            width as f64 / self.width as f64,
+            height as f64 / self.height as f64,
+        );
+        let mut k = self.k;
+        (k[0], k[2], k[4], k[5]) = (k[0] * sx, k[2] * sx, k[4] * sy, k[5] * sy);
+        RemapTable::undistort(width, height, &k, &self.d)
+    }
+
+    /// Describe the image after `--rectify-output` removed the lens
+    /// distortion: the same camera matrix with zero distortion, and nothing
+    /// left for consumers to rectify.
+    pub(crate) fn rectified(&self) -> Self {
+        CameraInfoFields {
+            d: vec![0.0; self.d.len()],
+            roi: RoiFields {
+                do_rectify: false,
+                ..self.roi
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Describe the image after `rotation` (clockwise) has been applied by
+    /// an output's convert step. The size and full-frame region of interest
+    /// are transposed for quarter turns, the principal point is moved with the
+    /// pixels, and the plumb_bob tangential terms are rotated with them.
+    /// Skew is assumed to be zero, as it is in every isp-imx calibration.
+    pub(crate) fn rotated(&self, rotation: Rotation) -> Self {
+        let (w, h) = (self.width as f64, self.height as f64);
+        let (fx, cx, fy, cy) = (self.k[0], self.k[2], self.k[4], self.k[5]);
+        let ((fx, cx, fy, cy), (width, height)) = match rotation {
+            Rotation::Rotation0 => return self.clone(),
+            Rotation::Rotation90 => ((fy, h - cy, fx, cx), (self.height, self.width)),
+            Rotation::Rotation180 => ((fx, w - cx, fy, h - cy), (self.width, self.height)),
+            Rotation::Rotation270 => ((fy, cy, fx, w - cx), (self.height, self.width)),
         };
+
+        let mut d = self.d.clone();
+        if d.len() >= 4 {
+            let (p1, p2) = (d[2], d[3]);
+            (d[2], d[3]) = match rotation {
+                Rotation::Rotation90 => (p2, -p1),
+                Rotation::Rotation180 => (-p1, -p2),
+                _ => (-p2, p1),
+            };
+        }
+
+        let k = [fx, 0.0, cx, 0.0, fy, cy, 0.0, 0.0, 1.0];
+        let p = [
+            k[0], k[1], k[2], 0.0, k[3], k[4], k[5], 0.0, k[6], k[7], k[8], 0.0,
+        ];
+        CameraInfoFields {
+            width,
+            height,
+            d,
+            k,
+            p,
+            roi: RoiFields {
+                width,
+                height,
+                ..self.roi
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Serialize these fields into a fresh `sensor_msgs/CameraInfo` CDR
+    /// buffer carrying `stamp`, the stamp of the frame it is published with.
+    pub(crate) fn build_msg(
+        &self,
+        stamp: builtin_interfaces::Time,
+    ) -> Result<CameraInfo<Vec<u8>>, Box<dyn Error>> {
         Ok(CameraInfo::new(
             stamp,
             &self.frame_id,
@@ -1265,6 +3263,54 @@ impl TfStaticFields {
         }
     }
 
+    /// Extra links from `--tf-config`, empty when it is not set. A link may
+    /// not redefine the camera frame, whose parent is `--base-frame-id`.
+    pub(crate) fn links_from_args(args: &Args) -> Result<Vec<Self>, Box<dyn Error>> {
+        let Some(path) = args.tf_config.as_ref() else {
+            return Ok(Vec::new());
+        };
+        let links = Self::load_links(path)?;
+        if let Some(link) = links
+            .iter()
+            .find(|l| l.child_frame_id == args.camera_frame_id)
+        {
+            return Err(format!(
+                "{:?} redefines the camera frame {:?} (parent {:?}); describe the links up to \
+                 --base-frame-id there and set the camera transform with --cam-tf-*",
+                path, link.child_frame_id, link.base_frame_id
+            )
+            .into());
+        }
+        info!(
+            "Publishing {} extra static transforms from {:?}",
+            links.len(),
+            path
+        );
+        Ok(links)
+    }
+
+    /// Load a JSON array of transforms, in the same shape as the sidecar's
+    /// `tf_static` entry.
+    pub(crate) fn load_links(path: &Path) -> Result<Vec<Self>, Box<dyn Error>> {
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("Cannot read TF config {:?}: {e}", path))?;
+        let links: Vec<Self> = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Failed to parse TF config {:?}: {e}", path))?;
+        for link in &links {
+            if link.base_frame_id.is_empty()
+                || link.child_frame_id.is_empty()
+                || link.base_frame_id == link.child_frame_id
+            {
+                return Err(format!(
+                    "TF config {:?}: invalid transform {:?} -> {:?}",
+                    path, link.base_frame_id, link.child_frame_id
+                )
+                .into());
+            }
+        }
+        Ok(links)
+    }
+
     pub(crate) fn build_msg(&self) -> Result<TransformStamped<Vec<u8>>, Box<dyn Error>> {
         let stamp = match timestamp() {
             Ok(t) => t,
@@ -1278,18 +3324,14 @@ impl TfStaticFields {
             }
         };
 
+        let [x, y, z, w] = normalize_quaternion(self.rotation)?;
         let transform = Transform {
             translation: Vector3 {
                 x: self.translation[0],
                 y: self.translation[1],
                 z: self.translation[2],
             },
-            rotation: Quaternion {
-                x: self.rotation[0],
-                y: self.rotation[1],
-                z: self.rotation[2],
-                w: self.rotation[3],
-            },
+            rotation: Quaternion { x, y, z, w },
         };
 
         Ok(TransformStamped::new(
@@ -1301,6 +3343,31 @@ impl TfStaticFields {
     }
 }
 
+/// Serialize each transform into its own `TransformStamped` payload for the
+/// [`tf_static`] loop.
+pub(crate) fn build_tf_static_msgs<'a>(
+    transforms: impl IntoIterator<Item = &'a TfStaticFields>,
+) -> Result<Vec<ZBytes>, Box<dyn Error>> {
+    transforms
+        .into_iter()
+        .map(|tf| Ok(ZBytes::from(tf.build_msg()?.into_cdr())))
+        .collect()
+}
+
+/// Scale the rotation quaternion `q` (x, y, z, w) to unit length, which TF
+/// consumers assume. Warns when `q` was noticeably off unit length and
+/// fails when it has no usable direction at all.
+fn normalize_quaternion(q: [f64; 4]) -> Result<[f64; 4], Box<dyn Error>> {
+    let norm = q.iter().map(|c| c * c).sum::<f64>().sqrt();
+    if !norm.is_finite() || norm < 1e-9 {
+        return Err(format!("camera TF rotation {q:?} is not a valid quaternion").into());
+    }
+    if (norm - 1.0).abs() > 1e-3 {
+        warn!("camera TF rotation {q:?} has norm {norm:.4}, normalizing to unit length");
+    }
+    Ok(q.map(|c| c / norm))
+}
+
 /// Errors that can occur when generating timestamps.
 #[derive(Debug)]
 enum TimestampError {
@@ -1430,23 +3497,204 @@ impl ClockOffset {
             nanosec: real_nsec as u32,
         }
     }
+
+    /// Inverse of [`ClockOffset::to_realtime`], for frames that arrive with a
+    /// realtime header stamp (transcoder mode) but feed encoder threads that
+    /// expect CLOCK_MONOTONIC.
+    fn to_monotonic(self, stamp: &builtin_interfaces::Time) -> Timestamp {
+        let real_ns = stamp.sec as i128 * 1_000_000_000 + stamp.nanosec as i128;
+        let offset_ns = self.offset_sec as i128 * 1_000_000_000 + self.offset_nsec as i128;
+        let mono_ns = real_ns - offset_ns;
+        Timestamp::new(
+            mono_ns.div_euclid(1_000_000_000) as i64,
+            mono_ns.rem_euclid(1_000_000_000) as u32,
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use args::{H264Bitrate, JpegSubsamp};
     use std::path::PathBuf;
 
-    /// Build an `Args` pre-populated with the clap defaults so tests can
-    /// flip individual fields without rebuilding the whole struct.
-    fn default_args() -> Args {
-        Args::parse_from(["edgefirst-camera"])
+    /// Build an `Args` pre-populated with the clap defaults so tests can
+    /// flip individual fields without rebuilding the whole struct.
+    fn default_args() -> Args {
+        Args::parse_from(["edgefirst-camera"])
+    }
+
+    /// [`CameraInfoFields`] from the calibration at `args.cam_info_path`,
+    /// as the live path builds them.
+    fn info_fields_from_args(args: &Args) -> Result<CameraInfoFields, Box<dyn Error>> {
+        CameraInfoFields::from_calibration(args, read_calibration(args)?.as_ref())
+    }
+
+    /// Run [`retry_open`] on an `open` that fails `failures` times before
+    /// succeeding, returning the result and the attempts made.
+    fn retry_open_after(failures: u32, timeout: Duration) -> (Result<u32, String>, u32) {
+        let mut attempts = 0;
+        let open = || {
+            attempts += 1;
+            if attempts > failures {
+                Ok(attempts)
+            } else {
+                Err(format!("no such device ({attempts})").into())
+            }
+        };
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(retry_open(timeout, Duration::from_millis(1), open))
+            .map_err(|e| e.to_string());
+        (result, attempts)
+    }
+
+    #[test]
+    fn retry_open_waits_for_the_device() {
+        // The device appears on the third attempt and the node proceeds
+        // with it.
+        assert_eq!(retry_open_after(2, Duration::from_secs(5)), (Ok(3), 3));
+        // Without a timeout the first failure is final.
+        assert_eq!(
+            retry_open_after(2, Duration::ZERO),
+            (Err("no such device (1)".to_string()), 1)
+        );
+        // Once the timeout passes the last error is returned.
+        let (result, attempts) = retry_open_after(u32::MAX, Duration::from_millis(50));
+        assert!(attempts > 1, "{attempts}");
+        assert_eq!(result, Err(format!("no such device ({attempts})")));
+    }
+
+    #[test]
+    fn output_threads_finish_their_frames_once_the_senders_drop() {
+        let (tx, rx) = kanal::bounded::<u32>(4);
+        let (flushed_tx, flushed_rx) = kanal::bounded(1);
+        let mut threads = OutputThreads::default();
+        threads.push(
+            thread::Builder::new()
+                .name("test_output".to_string())
+                .spawn(move || {
+                    let mut sum = 0;
+                    while let Ok(n) = rx.recv() {
+                        sum += n;
+                    }
+                    flushed_tx.send(sum).unwrap();
+                })
+                .unwrap(),
+        );
+        for n in 1..=3 {
+            tx.send(n).unwrap();
+        }
+        drop(tx);
+        threads.join();
+        assert_eq!(flushed_rx.try_recv().unwrap(), Some(6));
+    }
+
+    #[test]
+    fn keyframe_wallclock_alignment_replaces_the_interval() {
+        let args = Args::parse_from(["edgefirst-camera", "--h264-keyframe-align-wallclock"]);
+        assert_eq!(args.h264_keyframe_align_wallclock, Some(1));
+        let args = Args::parse_from(["edgefirst-camera", "--h264-keyframe-align-wallclock", "2"]);
+        assert_eq!(args.h264_keyframe_align_wallclock, Some(2));
+        assert_eq!(default_args().h264_keyframe_align_wallclock, None);
+
+        for bad in [
+            &["--h264-keyframe-align-wallclock", "0"][..],
+            &[
+                "--h264-keyframe-align-wallclock",
+                "--h264-keyframe-interval",
+                "30",
+            ],
+        ] {
+            let argv = std::iter::once("edgefirst-camera").chain(bad.iter().copied());
+            assert!(Args::try_parse_from(argv).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn validate_accepts_live_capture_with_no_record_or_replay() {
+        let args = default_args();
+        validate_record_replay_args(&args).expect("plain live path must validate");
+    }
+
+    #[test]
+    fn validate_camera_format_rejects_mjpg_with_h264() {
+        let mjpg = FourCC(*b"MJPG");
+        let mut args = default_args();
+        args.h264 = true;
+        let err = validate_camera_format(mjpg, &args).unwrap_err().to_string();
+        assert!(err.contains("MJPG") && err.contains("--h264"), "{err}");
+        assert!(err.contains("--list-formats-ext"), "{err}");
+
+        args.jpeg = true;
+        let err = validate_camera_format(mjpg, &args).unwrap_err().to_string();
+        assert!(err.contains("--h264, --jpeg"), "{err}");
+
+        // Raw formats feed every output, and MJPG alone is still published.
+        assert!(validate_camera_format(CAMERA_FORMAT, &args).is_ok());
+        assert!(validate_camera_format(image::NV12, &args).is_ok());
+        assert!(validate_camera_format(mjpg, &default_args()).is_ok());
+    }
+
+    #[test]
+    fn validate_camera_format_requires_bayer_pattern() {
+        let mut args = default_args();
+        let err = validate_camera_format(image::SRGGB10, &args)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("--bayer rggb"), "{err}");
+
+        args.bayer = Some(BayerPattern::Rggb);
+        args.h264 = true;
+        assert!(validate_camera_format(image::SRGGB10, &args).is_ok());
+        assert!(validate_camera_format(image::SGRBG8, &args).is_ok());
+    }
+
+    #[test]
+    fn g2d_trace_enables_image_module_trace_logs() {
+        assert!(get_env_filter(true)
+            .to_string()
+            .contains(G2D_TRACE_DIRECTIVE));
+        assert!(!get_env_filter(false)
+            .to_string()
+            .contains(G2D_TRACE_DIRECTIVE));
+    }
+
+    #[test]
+    fn validate_camera_format_routes_y16_to_png() {
+        let mut args = default_args();
+        args.jpeg = true;
+        assert!(validate_camera_format(image::Y16, &args).is_ok());
+        assert!(validate_camera_format(image::Y16, &default_args()).is_ok());
+
+        args.h264 = true;
+        args.dma_format = Some(FrameFormat::Rgba);
+        let err = validate_camera_format(image::Y16, &args)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Y16") && err.contains("--h264, --dma-format"),
+            "{err}"
+        );
+        assert!(!err.contains("--jpeg,"), "{err}");
     }
 
     #[test]
-    fn validate_accepts_live_capture_with_no_record_or_replay() {
-        let args = default_args();
-        validate_record_replay_args(&args).expect("plain live path must validate");
+    fn validate_camera_format_rectify_needs_yuyv_or_rgba() {
+        let mut args = default_args();
+        args.rectify_output = true;
+        assert!(validate_camera_format(CAMERA_FORMAT, &args).is_ok());
+        assert!(validate_camera_format(RGBA, &args).is_ok());
+        let err = validate_camera_format(image::NV12, &args)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("--rectify-output"), "{err}");
+
+        // Bayer frames are demosaiced to RGBA first.
+        args.bayer = Some(BayerPattern::Rggb);
+        assert!(validate_camera_format(image::SRGGB8, &args).is_ok());
     }
 
     #[test]
@@ -1461,6 +3709,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn validate_codec_rejects_h264_only_outputs_for_h265() {
+        let mut args = Args::parse_from(["edgefirst-camera", "--h264", "--codec", "h265"]);
+        assert_eq!(args.codec, Codec::H265);
+        validate_codec(&args).unwrap();
+
+        args.record = Some(PathBuf::from("/tmp/not-written.h264"));
+        let err = validate_codec(&args).unwrap_err().to_string();
+        assert!(err.contains("--record"), "{err}");
+        args.record = None;
+
+        args.unix_socket = Some(PathBuf::from("/tmp/not-bound.sock"));
+        let err = validate_codec(&args).unwrap_err().to_string();
+        assert!(err.contains("--unix-socket"), "{err}");
+        args.unix_socket = None;
+
+        args.pixel_aspect = "4:3".parse().unwrap();
+        let err = validate_codec(&args).unwrap_err().to_string();
+        assert!(err.contains("--pixel-aspect 4:3"), "{err}");
+
+        // H.264 supports all of them.
+        args.codec = Codec::H264;
+        args.record = Some(PathBuf::from("/tmp/not-written.h264"));
+        validate_codec(&args).unwrap();
+    }
+
+    #[cfg(feature = "srt")]
+    #[test]
+    fn validate_srt_needs_live_h264() {
+        let mut args = Args::parse_from(["edgefirst-camera", "--srt", "srt://relay:9000"]);
+        let err = validate_record_replay_args(&args).unwrap_err().to_string();
+        assert!(err.contains("--srt requires --h264"), "{err}");
+
+        args.h264 = true;
+        validate_record_replay_args(&args).unwrap();
+        validate_codec(&args).unwrap();
+
+        args.codec = Codec::H265;
+        let err = validate_codec(&args).unwrap_err().to_string();
+        assert!(err.contains("--srt"), "{err}");
+        args.codec = Codec::H264;
+
+        args.replay = Some(PathBuf::from("/tmp/replay.h264"));
+        let err = validate_record_replay_args(&args).unwrap_err().to_string();
+        assert!(err.contains("--replay does not support --srt"), "{err}");
+    }
+
     #[test]
     fn validate_record_with_h264_is_ok() {
         let mut args = default_args();
@@ -1493,6 +3788,156 @@ mod tests {
         );
     }
 
+    #[test]
+    fn validate_replay_rejects_v4l2_output() {
+        let mut args = default_args();
+        args.replay = Some(PathBuf::from("/tmp/not-read.h264"));
+        args.v4l2_output = Some(PathBuf::from("/dev/video10"));
+        let err = validate_record_replay_args(&args).unwrap_err().to_string();
+        assert!(
+            err.contains("--replay") && err.contains("--v4l2-output"),
+            "expected replay-rejects-v4l2-output error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn validate_replay_rejects_unix_socket() {
+        let mut args = default_args();
+        args.replay = Some(PathBuf::from("/tmp/not-read.h264"));
+        args.unix_socket = Some(PathBuf::from("/tmp/camera.sock"));
+        let err = validate_record_replay_args(&args).unwrap_err().to_string();
+        assert!(
+            err.contains("--replay") && err.contains("--unix-socket"),
+            "expected replay-rejects-unix-socket error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn debug_frame_id_appends_stamp_and_sequence() {
+        let args = default_args();
+        assert_eq!(
+            args.message_frame_id(1_700_000_000, 5, 42),
+            "camera_optical"
+        );
+
+        let args = Args::parse_from(["edgefirst-camera", "--debug-frame-id"]);
+        assert_eq!(
+            args.message_frame_id(1_700_000_000, 5, 42),
+            "camera_optical@1700000000.000000005"
+        );
+
+        let args = Args::parse_from(["edgefirst-camera", "--debug-frame-id", "stamp-seq"]);
+        assert_eq!(
+            args.message_frame_id(1_700_000_000, 5, 42),
+            "camera_optical@1700000000.000000005#42"
+        );
+
+        let stamp = builtin_interfaces::Time {
+            sec: 1_700_000_000,
+            nanosec: 5,
+        };
+        let frame_id = args.message_frame_id(stamp.sec, stamp.nanosec, 42);
+        let (bytes, _) = build_compressed_image_msg(b"\xff\xd8", "jpeg", stamp, &frame_id).unwrap();
+        let msg = CompressedImage::from_cdr(&bytes.to_bytes()).unwrap();
+        assert_eq!(msg.frame_id(), "camera_optical@1700000000.000000005#42");
+    }
+
+    #[test]
+    fn h264_variants_get_their_own_encoder_settings() {
+        let args = Args::parse_from([
+            "edgefirst-camera",
+            "--h264",
+            "--target-fps",
+            "30",
+            "--h264-variant",
+            "topic=rt/camera/h264_hq,fps=30,bitrate=mbps25",
+            "--h264-variant",
+            "topic=rt/camera/h264_low,fps=10,bitrate=mbps5,size=640x360",
+        ]);
+        assert_eq!(args.h264_variant.len(), 2);
+        validate_h264_variants(&args).unwrap();
+
+        let encoders: Vec<Args> = args
+            .h264_variant
+            .iter()
+            .map(|v| variant_args(&args, v))
+            .collect();
+        assert_eq!(encoders[0].h264_topic, "rt/camera/h264_hq");
+        assert_eq!(encoders[0].h264_meta_topic, "camera/h264_hq/meta");
+        assert_eq!(encoders[0].h264_init_topic, "rt/camera/h264_hq/init");
+        assert_eq!(
+            encoders[0].h264_keyframe_topic,
+            "rt/camera/h264_hq/force_keyframe"
+        );
+        assert_eq!(encoders[0].h264_bitrate, H264Bitrate::Mbps25);
+        assert_eq!(encoders[0].stream_size, args.stream_size);
+        assert_eq!(encoders[0].target_fps, 30);
+        assert_eq!(encoders[1].h264_topic, "rt/camera/h264_low");
+        assert_eq!(encoders[1].h264_bitrate, H264Bitrate::Mbps5);
+        assert_eq!(encoders[1].stream_size, [640, 360]);
+        assert_eq!(encoders[1].target_fps, 10);
+        assert!(encoders.iter().all(|a| a.shared_convert_target().is_none()));
+    }
+
+    #[test]
+    fn h264_variants_reject_taken_topics_and_bad_sizes() {
+        let args = Args::parse_from([
+            "edgefirst-camera",
+            "--h264",
+            "--h264-variant",
+            "topic=rt/camera/h264",
+        ]);
+        let err = validate_h264_variants(&args).unwrap_err().to_string();
+        assert!(err.contains("already published"), "{err}");
+
+        let args = Args::parse_from([
+            "edgefirst-camera",
+            "--h264-variant",
+            "topic=a,size=642x360",
+            "--h264-variant",
+            "topic=a",
+        ]);
+        let err = validate_h264_variants(&args).unwrap_err().to_string();
+        assert!(err.contains("642x360"), "{err}");
+
+        let args = Args::parse_from([
+            "edgefirst-camera",
+            "--h264-variant",
+            "topic=a",
+            "--h264-variant",
+            "topic=a",
+        ]);
+        assert!(validate_h264_variants(&args).is_err());
+    }
+
+    #[test]
+    fn no_multicast_scouting_leaves_the_interface_unset() {
+        let interface =
+            |config: &zenoh::Config| config.get_json("scouting/multicast/interface").unwrap();
+        let config = zenoh::Config::from(default_args());
+        assert_eq!(interface(&config), r#""lo""#);
+
+        let args = Args::parse_from(["edgefirst-camera", "--no-multicast-scouting"]);
+        let config = zenoh::Config::from(args);
+        assert_eq!(
+            config.get_json("scouting/multicast/enabled").unwrap(),
+            "false"
+        );
+        assert_eq!(interface(&config), interface(&zenoh::Config::default()));
+    }
+
+    #[test]
+    fn stamp_ns_counts_from_the_epoch() {
+        let stamp = builtin_interfaces::Time {
+            sec: 1_700_000_000,
+            nanosec: 5,
+        };
+        assert_eq!(stamp_ns(&stamp), 1_700_000_000_000_000_005);
+        let back = stamp_from_ns(1_700_000_000_000_000_005);
+        assert_eq!((back.sec, back.nanosec), (stamp.sec, stamp.nanosec));
+        assert_eq!(stamp_from_ns(i64::MAX).sec, i32::MAX);
+    }
+
     #[test]
     fn validate_replay_with_h264_forward_is_ok() {
         let mut args = default_args();
@@ -1505,7 +3950,7 @@ mod tests {
     fn camera_info_fields_from_args_with_no_json_path_uses_defaults() {
         let mut args = default_args();
         args.cam_info_path = String::new();
-        let f = CameraInfoFields::from_args(&args).unwrap();
+        let f = info_fields_from_args(&args).unwrap();
         assert_eq!(f.width, 1920);
         assert_eq!(f.height, 1080);
         assert_eq!(f.distortion_model, "plumb_bob");
@@ -1546,7 +3991,7 @@ mod tests {
 
         let mut args = default_args();
         args.cam_info_path = path.to_string_lossy().into_owned();
-        let err = CameraInfoFields::from_args(&args).unwrap_err().to_string();
+        let err = info_fields_from_args(&args).unwrap_err().to_string();
         assert!(
             err.contains("camera_matrix"),
             "error must reference camera_matrix, got: {err}"
@@ -1559,6 +4004,44 @@ mod tests {
         std::fs::remove_file(&path).ok();
     }
 
+    #[test]
+    fn color_correction_reads_matrix_and_gamma_from_calibration() {
+        let read = |json: &str| {
+            let json = serde_json::from_str(json).unwrap();
+            color_correction_from_calibration(&json, "calibration.json")
+        };
+
+        assert!(read(r#"{"bypass": true}"#).unwrap().is_none());
+        assert!(read(r#"{"color_correction": {}}"#)
+            .unwrap()
+            .unwrap()
+            .is_identity());
+        let swap =
+            read(r#"{"color_correction": {"matrix": [0, 0, 1, 0, 1, 0, 1, 0, 0], "gamma": 2.2}}"#)
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            swap,
+            ColorCorrection::new([0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0], [2.2; 3]).unwrap()
+        );
+        let per_channel = read(r#"{"color_correction": {"gamma": [1.0, 2.2, 1.0]}}"#);
+        assert!(!per_channel.unwrap().unwrap().is_identity());
+
+        for (json, expected) in [
+            (
+                r#"{"color_correction": {"matrix": [1, 0, 0]}}"#,
+                "9 elements",
+            ),
+            (r#"{"color_correction": {"gamma": [1, 2]}}"#, "1 or 3"),
+            (r#"{"color_correction": {"gamma": "srgb"}}"#, "numbers"),
+            (r#"{"color_correction": {"gamma": 0}}"#, "not positive"),
+        ] {
+            let err = read(json).unwrap_err().to_string();
+            assert!(err.contains(expected), "{json}: {err}");
+        }
+        assert!(read_calibration(&default_args()).unwrap().is_none());
+    }
+
     #[test]
     fn camera_info_fields_rejects_malformed_json() {
         // A non-JSON file at cam_info_path used to panic via
@@ -1571,7 +4054,7 @@ mod tests {
 
         let mut args = default_args();
         args.cam_info_path = path.to_string_lossy().into_owned();
-        let err = CameraInfoFields::from_args(&args).unwrap_err().to_string();
+        let err = info_fields_from_args(&args).unwrap_err().to_string();
         assert!(
             err.to_lowercase().contains("parse"),
             "error must say it failed to parse, got: {err}"
@@ -1584,6 +4067,87 @@ mod tests {
         std::fs::remove_file(&path).ok();
     }
 
+    #[test]
+    fn camera_info_fields_selects_dewarp_index() {
+        let tmp = std::env::temp_dir();
+        let pid = std::process::id();
+        let path = tmp.join(format!("edgefirst_cam_info_two_dewarp_{pid}.json"));
+        std::fs::write(
+            &path,
+            r#"{
+                "bypass": false,
+                "dewarpConfigArray": [
+                    {
+                        "camera_matrix": [1000, 0, 960, 0, 1000, 540, 0, 0, 1],
+                        "source_image": {"width": 1920, "height": 1080}
+                    },
+                    {
+                        "camera_matrix": [500, 0, 640, 0, 500, 360, 0, 0, 1],
+                        "source_image": {"width": 1280, "height": 720}
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let mut args = default_args();
+        args.cam_info_path = path.to_string_lossy().into_owned();
+        let first = info_fields_from_args(&args).unwrap();
+        assert_eq!((first.width, first.height), (1920, 1080));
+        assert_eq!(first.k[0], 1000.0);
+
+        args.dewarp_index = 1;
+        let second = info_fields_from_args(&args).unwrap();
+        assert_eq!((second.width, second.height), (1280, 720));
+        assert_eq!(second.k[0], 500.0);
+        assert_eq!(second.k[2], 640.0);
+
+        args.dewarp_index = 2;
+        let err = info_fields_from_args(&args).unwrap_err().to_string();
+        assert!(
+            err.contains("--dewarp-index 2") && err.contains("2 dewarpConfigArray entries"),
+            "{err}"
+        );
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn camera_info_fields_rectified_drops_distortion() {
+        let path = std::env::temp_dir().join(format!(
+            "edgefirst_cam_info_bypass_{}.json",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"{
+                "bypass": true,
+                "dewarpConfigArray": [{
+                    "camera_matrix": [1000, 0, 960, 0, 1000, 540, 0, 0, 1],
+                    "distortion_coeff": [-0.3, 0.1, 0.001, -0.002, 0.0],
+                    "source_image": {"width": 1920, "height": 1080}
+                }]
+            }"#,
+        )
+        .unwrap();
+        let mut args = default_args();
+        args.cam_info_path = path.to_string_lossy().into_owned();
+        let raw = info_fields_from_args(&args).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(raw.d[0], -0.3);
+        assert!(!raw.remap_table(960, 540).unwrap().is_identity());
+        let rectified = raw.rectified();
+        assert_eq!(rectified.d, [0.0; 5]);
+        assert_eq!(rectified.k, raw.k);
+        assert!(!rectified.roi.do_rectify);
+        assert!(rectified.remap_table(960, 540).unwrap().is_identity());
+
+        // The default calibration has no distortion to remove.
+        let plain = info_fields_from_args(&default_args()).unwrap();
+        assert!(plain.remap_table(1920, 1080).unwrap().is_identity());
+    }
+
     #[test]
     fn camera_info_fields_rejects_missing_dewarp_array() {
         let tmp = std::env::temp_dir();
@@ -1593,7 +4157,7 @@ mod tests {
 
         let mut args = default_args();
         args.cam_info_path = path.to_string_lossy().into_owned();
-        let err = CameraInfoFields::from_args(&args).unwrap_err().to_string();
+        let err = info_fields_from_args(&args).unwrap_err().to_string();
         assert!(err.to_lowercase().contains("dewarp"));
 
         std::fs::remove_file(&path).ok();
@@ -1620,15 +4184,592 @@ mod tests {
         assert!(!msg.as_cdr().is_empty());
     }
 
+    #[test]
+    fn tf_config_links_are_published_with_camera_transform() {
+        let path = std::env::temp_dir().join(format!(
+            "edgefirst_camera_tf_config_test_{}.json",
+            process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"[
+                {"base_frame_id": "base_link", "child_frame_id": "mast",
+                 "translation": [0.1, 0.0, 0.8], "rotation": [0.0, 0.0, 0.0, 1.0]},
+                {"base_frame_id": "mast", "child_frame_id": "camera_mount",
+                 "translation": [0.0, 0.0, 0.05], "rotation": [0.0, 0.0, 0.7071068, 0.7071068]}
+            ]"#,
+        )
+        .unwrap();
+
+        let mut args = default_args();
+        args.tf_config = Some(path.clone());
+        args.base_frame_id = "camera_mount".into();
+        let links = TfStaticFields::links_from_args(&args).unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[1].base_frame_id, "mast");
+        assert_eq!(links[1].translation, [0.0, 0.0, 0.05]);
+
+        let camera = TfStaticFields::from_args(&args);
+        let msgs = build_tf_static_msgs(std::iter::once(&camera).chain(&links)).unwrap();
+        assert_eq!(msgs.len(), 3);
+        for (msg, child) in msgs.iter().zip(["camera_optical", "mast", "camera_mount"]) {
+            let bytes = msg.to_bytes();
+            assert!(
+                bytes.windows(child.len()).any(|w| w == child.as_bytes()),
+                "{child} missing from its TransformStamped"
+            );
+        }
+
+        // A link may not give the camera frame a second parent.
+        args.camera_frame_id = "camera_mount".into();
+        let err = TfStaticFields::links_from_args(&args).unwrap_err();
+        assert!(err.to_string().contains("redefines the camera frame"));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn tf_config_rejects_malformed_links() {
+        let path = std::env::temp_dir().join(format!(
+            "edgefirst_camera_tf_config_bad_test_{}.json",
+            process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"[{"base_frame_id": "a", "child_frame_id": "a",
+                 "translation": [0, 0, 0], "rotation": [0, 0, 0, 1]}]"#,
+        )
+        .unwrap();
+        assert!(TfStaticFields::load_links(&path).is_err());
+
+        std::fs::write(&path, r#"{"base_frame_id": "a"}"#).unwrap();
+        assert!(TfStaticFields::load_links(&path).is_err());
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn default_tf_rotation_is_unit_quaternion() {
+        let q = TfStaticFields::from_args(&default_args()).rotation;
+        let norm = q.iter().map(|c| c * c).sum::<f64>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-12, "default norm {norm}");
+        assert_eq!(normalize_quaternion(q).unwrap(), q);
+    }
+
+    #[test]
+    fn tf_rotation_is_normalized() {
+        assert_eq!(
+            normalize_quaternion([-1.0, 1.0, -1.0, 1.0]).unwrap(),
+            [-0.5, 0.5, -0.5, 0.5]
+        );
+        assert_eq!(
+            normalize_quaternion([0.0, 0.0, 0.0, 3.0]).unwrap(),
+            [0.0, 0.0, 0.0, 1.0]
+        );
+        assert!(normalize_quaternion([0.0; 4]).is_err());
+        assert!(normalize_quaternion([f64::NAN, 0.0, 0.0, 1.0]).is_err());
+
+        let mut tf = TfStaticFields::from_args(&default_args());
+        tf.rotation = [0.0; 4];
+        assert!(tf.build_msg().is_err());
+    }
+
     #[test]
     fn camera_info_fields_build_msg_produces_nonempty_cdr() {
         let mut args = default_args();
         args.cam_info_path = String::new();
-        let info = CameraInfoFields::from_args(&args).unwrap();
-        let msg = info.build_msg().expect("info CDR build must succeed");
+        let info = info_fields_from_args(&args).unwrap();
+        let msg = info
+            .build_msg(Time { sec: 0, nanosec: 0 })
+            .expect("info CDR build must succeed");
         assert!(!msg.as_cdr().is_empty());
     }
 
+    #[test]
+    fn topic_namespace_prefixes_default_topics() {
+        let mut args = Args::parse_from(["edgefirst-camera", "--topic-namespace", "robot1"]);
+        args.apply_topic_namespace().unwrap();
+        assert_eq!(args.jpeg_topic, "rt/robot1/camera/jpeg");
+        assert_eq!(args.info_topic, "rt/robot1/camera/info");
+        assert_eq!(args.connected_topic, "rt/robot1/camera/connected");
+        assert_eq!(args.h264_topic, "rt/robot1/camera/h264");
+        assert_eq!(args.h264_meta_topic, "robot1/camera/h264/meta");
+        assert_eq!(args.h264_init_topic, "rt/robot1/camera/h264/init");
+        assert_eq!(
+            args.h264_keyframe_topic,
+            "rt/robot1/camera/h264/force_keyframe"
+        );
+        assert_eq!(args.frame_topic, "robot1/camera/frame");
+        assert_eq!(args.h264_tiles_topics[0], "rt/robot1/camera/h264/tl");
+        assert_eq!(args.h264_tiles_topics[3], "rt/robot1/camera/h264/br");
+        assert_eq!(args.tf_static_topic(), "rt/robot1/tf_static");
+    }
+
+    #[test]
+    fn topic_namespace_keeps_explicit_topics() {
+        let mut args = Args::parse_from([
+            "edgefirst-camera",
+            "--topic-namespace",
+            "fleet/robot1",
+            "--jpeg-topic",
+            "rt/front/jpeg",
+        ]);
+        args.apply_topic_namespace().unwrap();
+        assert_eq!(args.jpeg_topic, "rt/front/jpeg");
+        assert_eq!(args.h264_topic, "rt/fleet/robot1/camera/h264");
+
+        let mut args = default_args();
+        args.apply_topic_namespace().unwrap();
+        assert_eq!(args.jpeg_topic, "rt/camera/jpeg");
+        assert_eq!(args.tf_static_topic(), "rt/tf_static");
+    }
+
+    #[test]
+    fn topic_namespace_must_be_a_key_expression() {
+        for ns in [
+            "",
+            "/robot1",
+            "robot1/",
+            "a//b",
+            "robot?",
+            "robot#1",
+            "robots/**",
+            "$*",
+        ] {
+            let mut args = Args::parse_from(["edgefirst-camera", "--topic-namespace", ns]);
+            assert!(args.apply_topic_namespace().is_err(), "{ns:?}");
+            assert_eq!(args.jpeg_topic, "rt/camera/jpeg");
+        }
+    }
+
+    #[test]
+    fn output_rotation_defaults_to_global_rotation() {
+        let args = Args::parse_from(["edgefirst-camera", "--rotation", "90"]);
+        assert_eq!(args.h264_output_rotation(), Rotation::Rotation90);
+        assert_eq!(args.jpeg_output_rotation(), Rotation::Rotation90);
+
+        let args = Args::parse_from([
+            "edgefirst-camera",
+            "--rotation",
+            "90",
+            "--jpeg-rotation",
+            "0",
+        ]);
+        assert_eq!(args.h264_output_rotation(), Rotation::Rotation90);
+        assert_eq!(args.jpeg_output_rotation(), Rotation::Rotation0);
+    }
+
+    #[test]
+    fn shared_convert_target_requires_matching_outputs() {
+        let args = Args::parse_from(["edgefirst-camera", "--jpeg", "--h264", "--rotation", "90"]);
+        let target = args
+            .shared_convert_target()
+            .expect("same rotation must share");
+        assert_eq!(target.format, RGBA);
+        assert_eq!((target.width, target.height), (1080, 1920));
+        assert_eq!(target.rotation, Rotation::Rotation90);
+
+        // Differing rotations fall back to per-output conversion.
+        let args = Args::parse_from([
+            "edgefirst-camera",
+            "--jpeg",
+            "--h264",
+            "--jpeg-rotation",
+            "180",
+        ]);
+        assert_eq!(args.shared_convert_target(), None);
+
+        // So do a single output and an explicit opt-out.
+        let args = Args::parse_from(["edgefirst-camera", "--jpeg"]);
+        assert_eq!(args.shared_convert_target(), None);
+        let args = Args::parse_from([
+            "edgefirst-camera",
+            "--jpeg",
+            "--h264",
+            "--shared-convert",
+            "false",
+        ]);
+        assert_eq!(args.shared_convert_target(), None);
+
+        // A luma-only JPEG reads the source frame, not an RGBA conversion.
+        let args = Args::parse_from([
+            "edgefirst-camera",
+            "--jpeg",
+            "--h264",
+            "--jpeg-plane",
+            "luma",
+        ]);
+        assert_eq!(args.shared_convert_target(), None);
+    }
+
+    #[test]
+    fn encode_queue_depth_sizes_encoder_channels() {
+        let args = Args::parse_from(["edgefirst-camera"]);
+        let (tx, _rx) = encode_channel::<()>(&args, ENCODE_QUEUE_DEPTH);
+        assert_eq!(tx.capacity(), 1);
+        let (tx, _rx) = encode_channel::<()>(&args, TILE_QUEUE_DEPTH);
+        assert_eq!(tx.capacity(), 3);
+
+        let args = Args::parse_from(["edgefirst-camera", "--encode-queue-depth", "4"]);
+        let (tx, _rx) = encode_channel::<()>(&args, ENCODE_QUEUE_DEPTH);
+        assert_eq!(tx.capacity(), 4);
+        let (tx, _rx) = encode_channel::<()>(&args, TILE_QUEUE_DEPTH);
+        assert_eq!(tx.capacity(), 4);
+
+        // A zero-depth channel would drop every frame.
+        assert!(Args::try_parse_from(["edgefirst-camera", "--encode-queue-depth", "0"]).is_err());
+    }
+
+    #[test]
+    fn camera_info_fields_rotated_quarter_turn_transposes() {
+        let mut args = default_args();
+        args.cam_info_path = String::new();
+        let f = info_fields_from_args(&args).unwrap();
+        let r = f.rotated(Rotation::Rotation90);
+        assert_eq!((r.width, r.height), (1080, 1920));
+        assert_eq!((r.roi.width, r.roi.height), (1080, 1920));
+        // The principal point follows the pixels: 90° clockwise maps
+        // (cx, cy) to (h - cy, cx).
+        assert_eq!(r.k[2], 540.0);
+        assert_eq!(r.k[5], 960.0);
+        assert_eq!(r.p[2], r.k[2]);
+        assert_eq!(r.p[6], r.k[5]);
+
+        let r = f.rotated(Rotation::Rotation180);
+        assert_eq!((r.width, r.height), (1920, 1080));
+    }
+
+    #[test]
+    fn camera_info_fields_rotated_full_turn_is_identity() {
+        let mut args = default_args();
+        args.cam_info_path = String::new();
+        let mut f = info_fields_from_args(&args).unwrap();
+        f.k[2] = 950.0;
+        f.k[5] = 530.0;
+        f.d = vec![0.1, 0.01, 0.002, -0.003, 0.0];
+        let r = f
+            .rotated(Rotation::Rotation90)
+            .rotated(Rotation::Rotation180)
+            .rotated(Rotation::Rotation90);
+        assert_eq!((r.width, r.height), (f.width, f.height));
+        assert_eq!(r.k, f.k);
+        assert_eq!(r.d, f.d);
+
+        let r = f
+            .rotated(Rotation::Rotation90)
+            .rotated(Rotation::Rotation270);
+        assert_eq!(r.k, f.k);
+        assert_eq!(r.d, f.d);
+    }
+
+    #[test]
+    fn h264_meta_msg_carries_keyframe_flag() {
+        let mut meta = H264FrameMeta {
+            sec: 12,
+            nanosec: 345,
+            frame_id: "camera".to_string(),
+            sequence: 0,
+            width: 1920,
+            height: 1080,
+            is_keyframe: true,
+            size: 4096,
+            framing: H264Framing::AnnexB,
+            sensor: None,
+        };
+
+        let (msg, enc) = build_h264_meta_msg(&meta).unwrap();
+        assert_eq!(enc, Encoding::APPLICATION_JSON);
+        let back: H264FrameMeta = serde_json::from_slice(&msg.to_bytes()).unwrap();
+        assert_eq!(back, meta);
+        assert!(back.is_keyframe);
+
+        meta.sequence = 1;
+        meta.is_keyframe = false;
+        let (msg, _) = build_h264_meta_msg(&meta).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&msg.to_bytes()).unwrap();
+        assert_eq!(json["is_keyframe"], false);
+        assert_eq!(json["sequence"], 1);
+        assert_eq!(json["width"], 1920);
+        assert_eq!(json["height"], 1080);
+        assert_eq!(json["framing"], "annexb");
+        assert!(json.get("sensor").is_none());
+    }
+
+    #[test]
+    fn h264_meta_msg_carries_sensor_errors() {
+        let crc = sensor::Control {
+            id: 0x009a_0901,
+            name: "MIPI CRC Errors".to_string(),
+            kind: sensor::ControlKind::ErrorCount,
+        };
+        let mut monitor = sensor::Monitor::new(vec![crc]);
+        let mut errors = 0;
+        let mut driver = |_id: u32| -> std::io::Result<i64> {
+            errors += 2;
+            Ok(errors)
+        };
+        monitor.poll(&mut driver);
+        let (readings, rises) = monitor.poll(&mut driver);
+        assert_eq!(rises, [("MIPI CRC Errors", 2)]);
+
+        let meta = H264FrameMeta {
+            sec: 12,
+            nanosec: 345,
+            frame_id: "camera".to_string(),
+            sequence: 0,
+            width: 1920,
+            height: 1080,
+            is_keyframe: false,
+            size: 4096,
+            framing: H264Framing::Avcc,
+            sensor: Some(readings),
+        };
+        let (msg, _) = build_h264_meta_msg(&meta).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&msg.to_bytes()).unwrap();
+        assert_eq!(json["sensor"]["mipi_crc_errors"], 4);
+        assert_eq!(json["framing"], "avcc");
+        let back: H264FrameMeta = serde_json::from_slice(&msg.to_bytes()).unwrap();
+        assert_eq!(back, meta);
+    }
+
+    #[test]
+    fn jpeg_region_msg_places_the_region() {
+        let meta = JpegRegionMeta {
+            sec: 12,
+            nanosec: 345,
+            frame_id: "camera".to_string(),
+            sequence: 7,
+            x: 16,
+            y: 32,
+            width: 48,
+            height: 16,
+            frame_width: 640,
+            frame_height: 480,
+            full: false,
+        };
+        let (msg, enc) = build_jpeg_region_msg(&meta).unwrap();
+        assert_eq!(enc, Encoding::APPLICATION_JSON);
+        let json: serde_json::Value = serde_json::from_slice(&msg.to_bytes()).unwrap();
+        assert_eq!(json["x"], 16);
+        assert_eq!(json["y"], 32);
+        assert_eq!(json["frame_width"], 640);
+        assert_eq!(json["full"], false);
+        let back: JpegRegionMeta = serde_json::from_slice(&msg.to_bytes()).unwrap();
+        assert_eq!(back, meta);
+    }
+
+    #[test]
+    fn jpeg_quality_and_subsampling() {
+        let args = default_args();
+        assert_eq!(args.jpeg_quality, 85);
+        assert_eq!(args.jpeg_subsamp, JpegSubsamp::Sub2x2);
+
+        let args = Args::parse_from(["camera", "--jpeg-quality", "60", "--jpeg-subsamp", "444"]);
+        assert_eq!(args.jpeg_quality, 60);
+        assert_eq!(args.jpeg_subsamp, JpegSubsamp::Sub1x1);
+        for quality in ["0", "101"] {
+            assert!(Args::try_parse_from(["camera", "--jpeg-quality", quality]).is_err());
+        }
+        assert!(Args::try_parse_from(["camera", "--jpeg-subsamp", "411"]).is_err());
+    }
+
+    #[test]
+    fn jpeg_dirty_rect_defaults() {
+        let args = Args::parse_from(["camera", "--jpeg", "--jpeg-dirty-rect"]);
+        assert!(args.jpeg_dirty_rect);
+        assert_eq!(args.jpeg_dirty_threshold, 16);
+        assert_eq!(args.jpeg_dirty_refresh, 2);
+        assert_eq!(args.jpeg_region_topic, "rt/camera/jpeg/region");
+        assert!(!default_args().jpeg_dirty_rect);
+        assert!(Args::try_parse_from(["camera", "--jpeg-dirty-refresh", "0"]).is_err());
+    }
+
+    #[test]
+    fn thermal_zone_defaults() {
+        let args = Args::parse_from(["camera", "--thermal-zone", "1"]);
+        assert_eq!(args.thermal_zone, Some(1));
+        assert_eq!(args.thermal_threshold, 85);
+        assert_eq!(args.thermal_step, 5);
+        assert_eq!(args.thermal_hysteresis, 5);
+        assert_eq!(default_args().thermal_zone, None);
+        assert!(Args::try_parse_from(["camera", "--thermal-step", "0"]).is_err());
+    }
+
+    /// A keyframe access unit: AUD, `sps`, a PPS and an IDR slice.
+    fn keyframe(sps: &[u8]) -> Vec<u8> {
+        let mut au = vec![0, 0, 0, 1, 0x09, 0x10, 0, 0, 0, 1];
+        au.extend_from_slice(sps);
+        au.extend_from_slice(&[0, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80, 0, 0, 1, 0x65, 0x88]);
+        au
+    }
+
+    #[test]
+    fn init_segment_follows_parameter_set_changes() {
+        // Baseline SPSs for 1920x1080 (cropped from 1088) and 1280x720.
+        let sps_1080 = [0x67, 0x42, 0xc0, 0x28, 0xda, 0x01, 0xe0, 0x08, 0x9f, 0x95];
+        let sps_720 = [0x67, 0x42, 0xc0, 0x28, 0xda, 0x01, 0x40, 0x16, 0xe4];
+        let mut init = InitSegment::default();
+
+        // Nothing until the first keyframe carries its parameter sets.
+        assert_eq!(init.update(&[0, 0, 0, 1, 0x41, 0x9a]), None);
+        let sets = init.update(&keyframe(&sps_1080)).unwrap().to_vec();
+        assert_eq!(init_picture_size(&sets).unwrap(), (1920, 1080));
+        let types: Vec<u8> = nal::iter_nal_units(&sets).map(nal::nal_type).collect();
+        assert_eq!(types, [nal::NAL_TYPE_SPS, nal::NAL_TYPE_PPS]);
+        assert!(sets.starts_with(&[0, 0, 0, 1, 0x67]));
+
+        // Repeated parameter sets are not published again; new ones are.
+        assert_eq!(init.update(&keyframe(&sps_1080)), None);
+        let sets = init.update(&keyframe(&sps_720)).unwrap();
+        assert_eq!(init_picture_size(sets).unwrap(), (1280, 720));
+        assert_eq!(init.update(&keyframe(&sps_720)), None);
+    }
+
+    #[test]
+    fn clock_offset_to_monotonic_inverts_to_realtime() {
+        for (offset_sec, offset_nsec) in [(1_700_000_000, 999_999_999), (1_700_000_000, 0)] {
+            let offset = ClockOffset {
+                offset_sec,
+                offset_nsec,
+            };
+            let mono = Timestamp::new(12_345, 500_000_000);
+            let real = offset.to_realtime(&mono);
+            let back = offset.to_monotonic(&real);
+            assert_eq!(back.seconds(), 12_345);
+            assert_eq!(back.subsec(9), 500_000_000);
+        }
+    }
+
+    #[test]
+    fn frame_messages_share_one_stamp() {
+        let offset = ClockOffset {
+            offset_sec: 1_700_000_000,
+            offset_nsec: 250_000_000,
+        };
+        let capture = Timestamp::new(12_345, 900_000_000);
+        let stamp = offset.to_realtime(&capture);
+        let expected = (stamp.sec, stamp.nanosec);
+
+        let (dma, _) = build_camera_frame_msg(
+            stamp,
+            "camera",
+            7,
+            process::id(),
+            64,
+            48,
+            "YUYV",
+            3,
+            128,
+            128 * 48,
+            128 * 48,
+            &Colorimetry::default(),
+        )
+        .unwrap();
+        let dma = edgefirst_camera::remote::decode_camera_frame(&dma.to_bytes()).unwrap();
+        assert_eq!((dma.stamp.sec, dma.stamp.nanosec), expected);
+
+        let mut args = default_args();
+        args.cam_info_path = String::new();
+        let info = info_fields_from_args(&args)
+            .unwrap()
+            .build_msg(stamp)
+            .unwrap();
+        let info = CameraInfo::from_cdr(info.as_cdr()).unwrap().stamp();
+        assert_eq!((info.sec, info.nanosec), expected);
+
+        // The JPEG thread stamps from the same capture timestamp.
+        let (jpeg, _) = build_compressed_image_msg(
+            &[0xff, 0xd8, 0xff, 0xd9],
+            "jpeg",
+            offset.to_realtime(&capture),
+            "camera",
+        )
+        .unwrap();
+        let jpeg = CompressedImage::from_cdr(&jpeg.to_bytes()).unwrap().stamp();
+        assert_eq!((jpeg.sec, jpeg.nanosec), expected);
+    }
+
+    #[test]
+    fn camera_frame_plane_separates_padding() {
+        // 64x48 YUYV in a buffer padded to two pages.
+        let (msg, _) = build_camera_frame_msg(
+            builtin_interfaces::Time { sec: 1, nanosec: 0 },
+            "camera",
+            1,
+            process::id(),
+            64,
+            48,
+            "YUYV",
+            3,
+            128,
+            8192,
+            128 * 48,
+            &Colorimetry::default(),
+        )
+        .unwrap();
+        let bytes = msg.to_bytes();
+        let frame = CameraFrame::from_cdr(&bytes).unwrap();
+        let planes = frame.planes();
+        assert_eq!((planes[0].size, planes[0].used), (8192, 128 * 48));
+        let remote = edgefirst_camera::remote::decode_camera_frame(&bytes).unwrap();
+        assert_eq!(remote.size, 8192);
+    }
+
+    /// Drive [`publish_on_match`] with scripted matching-status events and
+    /// count the publishes until it goes quiet.
+    fn count_publishes(period: Option<Duration>, events: &[Option<bool>]) -> usize {
+        use std::{cell::RefCell, collections::VecDeque};
+
+        let events = RefCell::new(events.iter().copied().collect::<VecDeque<_>>());
+        let published = RefCell::new(0);
+        let next_match = || async {
+            // Popped when polled, like a channel receive, so an event is
+            // not lost when the period wins a round.
+            let event = events.borrow_mut().pop_front();
+            match event {
+                Some(event) => event,
+                None => std::future::pending().await,
+            }
+        };
+        let publish = || async {
+            *published.borrow_mut() += 1;
+            Ok(())
+        };
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let run = publish_on_match("rt/tf_static", period, next_match, publish);
+                let _ = tokio::time::timeout(Duration::from_millis(200), run).await;
+            });
+        published.into_inner()
+    }
+
+    #[test]
+    fn new_subscriber_triggers_immediate_publish() {
+        let hour = Some(Duration::from_secs(3600));
+        // Only the first tick fires within the test.
+        assert_eq!(count_publishes(hour, &[]), 1);
+        // Every transition to matched publishes again; losing the last
+        // subscriber does not.
+        assert_eq!(count_publishes(hour, &[Some(true)]), 2);
+        assert_eq!(
+            count_publishes(hour, &[Some(true), Some(false), Some(true)]),
+            3
+        );
+        // A closed listener leaves the periodic publish running.
+        assert_eq!(count_publishes(hour, &[None, Some(true)]), 1);
+        // Without a period only matches publish.
+        assert_eq!(count_publishes(None, &[Some(true), None]), 1);
+    }
+
+    #[test]
+    fn bool_msg_is_cdr_encoded() {
+        assert_eq!(build_bool_msg(true).to_bytes().as_ref(), [0, 1, 0, 0, 1]);
+        assert_eq!(build_bool_msg(false).to_bytes().as_ref(), [0, 1, 0, 0, 0]);
+    }
+
     #[test]
     fn colorimetry_default_is_all_unknown_empty_strings() {
         let c = Colorimetry::default();
@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Prometheus `/metrics` endpoint (`--metrics-port`, `metrics` feature).
+//!
+//! Scrapes are rare and tiny, so a blocking listener on its own thread
+//! serves them without pulling an HTTP stack into the node.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::{self, JoinHandle},
+};
+use tracing::{info, warn};
+
+use crate::stats::Stats;
+
+/// Render `stats` in the Prometheus text exposition format.
+pub fn render(stats: &Stats) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} {kind}");
+        let _ = writeln!(out, "{name} {value}");
+    };
+
+    metric(
+        "camera_frames_total",
+        "counter",
+        "Frames captured.",
+        stats.frames().to_string(),
+    );
+    metric(
+        "camera_dropped_frames_total",
+        "counter",
+        "Frames an output dropped because its encoder was busy.",
+        stats.dropped_frames().to_string(),
+    );
+    metric(
+        "camera_duplicate_frames_total",
+        "counter",
+        "Captured frames identical to the previous frame (--dedup-frames).",
+        stats.duplicate_frames().to_string(),
+    );
+    metric(
+        "camera_fps",
+        "gauge",
+        "Capture rate in frames per second.",
+        stats.fps().to_string(),
+    );
+    metric(
+        "camera_h264_encode_latency_seconds",
+        "gauge",
+        "Duration of the most recent H.264 resize and encode.",
+        stats.encode_latency().as_secs_f64().to_string(),
+    );
+    metric(
+        "camera_h264_bytes_total",
+        "counter",
+        "Encoded H.264 bytes.",
+        stats.h264_bytes().to_string(),
+    );
+    metric(
+        "camera_h264_bitrate_bits_per_second",
+        "gauge",
+        "H.264 output bitrate.",
+        stats.h264_bitrate().to_string(),
+    );
+    metric(
+        "camera_temperature_celsius",
+        "gauge",
+        "Temperature of the --thermal-zone, 0 without one.",
+        stats.temperature().to_string(),
+    );
+    metric(
+        "camera_thermal_level",
+        "gauge",
+        "Outputs shed by the thermal governor: 0 none, 1 tiles, 2 hires JPEG, 3 H.264 bitrate.",
+        stats.thermal_level().to_string(),
+    );
+    if let Some(rss) = resident_memory_bytes() {
+        metric(
+            "process_resident_memory_bytes",
+            "gauge",
+            "Resident memory size in bytes.",
+            rss.to_string(),
+        );
+    }
+    out
+}
+
+/// Resident set size from `/proc/self/statm`.
+fn resident_memory_bytes() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    (page_size > 0).then(|| pages * page_size as u64)
+}
+
+/// Serve `stats` on `addr` from a background thread. Returns the bound
+/// address, which differs from `addr` when it asks for port 0.
+pub fn serve(addr: SocketAddr, stats: &'static Stats) -> io::Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    info!("Serving Prometheus metrics on http://{}/metrics", local);
+
+    let thread = thread::Builder::new()
+        .name("metrics".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|stream| respond(stream, stats));
+                if let Err(e) = result {
+                    warn!("metrics request failed: {e}");
+                }
+            }
+        })?;
+    Ok((local, thread))
+}
+
+fn respond(mut stream: TcpStream, stats: &Stats) -> io::Result<()> {
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone()?);
+    reader.read_line(&mut request_line)?;
+    // Drain the headers; the request has no body we care about.
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = match path {
+        "/metrics" => ("200 OK", render(stats)),
+        _ => ("404 Not Found", String::from("not found\n")),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Read, time::Duration};
+
+    static TEST_STATS: Stats = Stats::new();
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn render_includes_every_metric() {
+        let stats = Stats::new();
+        stats.record_frame(30.0);
+        stats.record_encode(Duration::from_millis(5), 1000);
+        stats.set_thermal(71_500, 1);
+        let text = render(&stats);
+        for name in [
+            "camera_frames_total 1",
+            "camera_dropped_frames_total 0",
+            "camera_duplicate_frames_total 0",
+            "camera_fps 30",
+            "camera_h264_encode_latency_seconds 0.005",
+            "camera_h264_bytes_total 1000",
+            "camera_h264_bitrate_bits_per_second 0",
+            "camera_temperature_celsius 71.5",
+            "camera_thermal_level 1",
+            "# TYPE camera_frames_total counter",
+            "# TYPE camera_fps gauge",
+        ] {
+            assert!(text.contains(name), "missing {name:?} in:\n{text}");
+        }
+    }
+
+    #[test]
+    fn scrape_metrics_endpoint() {
+        TEST_STATS.record_frame(15.0);
+        let (addr, _thread) = serve("127.0.0.1:0".parse().unwrap(), &TEST_STATS).unwrap();
+
+        let response = get(addr, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.contains("camera_fps 15"), "{response}");
+        assert!(response.contains("process_resident_memory_bytes"));
+
+        let response = get(addr, "/other");
+        assert!(response.starts_with("HTTP/1.1 404"), "{response}");
+    }
+}
@@ -0,0 +1,565 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! H.264 Annex-B byte stream parsing.
+//!
+//! Annex-B separates NAL units with `00 00 01` or `00 00 00 01` start
+//! codes. Inside a NAL the encoder inserts an emulation-prevention byte
+//! (`00 00 03`) wherever the payload would otherwise contain a start code,
+//! so scanning for `00 00 01` never splits a unit. Everything that looks
+//! inside the encoded stream (recording, replay, parameter-set extraction)
+//! shares these helpers so they agree on where units begin and end.
+//!
+//! ```
+//! use edgefirst_camera::nal::{iter_nal_units, nal_type, NAL_TYPE_PPS, NAL_TYPE_SPS};
+//!
+//! let stream = [0, 0, 0, 1, 0x67, 0x42, 0, 0, 1, 0x68, 0xce];
+//! let types: Vec<u8> = iter_nal_units(&stream).map(nal_type).collect();
+//! assert_eq!(types, [NAL_TYPE_SPS, NAL_TYPE_PPS]);
+//! ```
+
+use std::{borrow::Cow, error::Error};
+
+use crate::sps::BitReader;
+
+/// Coded slice of a non-IDR picture.
+pub const NAL_TYPE_SLICE: u8 = 1;
+/// Coded slice of an IDR picture.
+pub const NAL_TYPE_IDR: u8 = 5;
+/// Supplemental enhancement information.
+pub const NAL_TYPE_SEI: u8 = 6;
+/// Sequence parameter set.
+pub const NAL_TYPE_SPS: u8 = 7;
+/// Picture parameter set.
+pub const NAL_TYPE_PPS: u8 = 8;
+/// Access unit delimiter.
+pub const NAL_TYPE_AUD: u8 = 9;
+/// End of sequence.
+pub const NAL_TYPE_EOS: u8 = 10;
+
+/// Iterate over the NAL units in an Annex-B buffer.
+///
+/// Each item is one NAL unit without its start code, beginning with the
+/// NAL header byte. Bytes before the first start code are skipped, and the
+/// trailing zero bytes that pad a unit up to a 4-byte start code are not
+/// part of the unit. The last unit runs to the end of `buf`, so callers
+/// reading a stream in chunks should hold it back until the next start
+/// code arrives (see [`next_nal_unit_len`]).
+pub fn iter_nal_units(buf: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = buf;
+    std::iter::from_fn(move || loop {
+        let start = find_start_code(rest)?;
+        let sc_len = leading_start_code_len(&rest[start..])?;
+        let body = &rest[start + sc_len..];
+        let end = find_start_code(body).unwrap_or(body.len());
+        rest = &body[end..];
+
+        let unit = trim_trailing_zeros(&body[..end]);
+        if !unit.is_empty() {
+            return Some(unit);
+        }
+    })
+}
+
+/// The `nal_unit_type` of a NAL unit, from the low five bits of its header.
+///
+/// A leading start code is skipped, so slices that still carry one (such
+/// as those delimited by [`next_nal_unit_len`]) can be passed directly.
+/// Returns 0, which H.264 leaves unspecified, for an empty unit.
+pub fn nal_type(unit: &[u8]) -> u8 {
+    let header = match leading_start_code_len(unit) {
+        Some(n) => unit.get(n),
+        None => unit.first(),
+    };
+    header.map_or(0, |h| h & 0x1f)
+}
+
+/// Returns `true` if the access unit in `buf` contains an IDR slice, i.e.
+/// a decoder can start from it.
+pub fn is_keyframe(buf: &[u8]) -> bool {
+    iter_nal_units(buf).any(|unit| nal_type(unit) == NAL_TYPE_IDR)
+}
+
+/// The SPS and PPS units of the access unit in `buf`, in stream order and
+/// each behind a 4-byte start code, ready to be prepended to a keyframe.
+/// Returns `None` unless `buf` carries at least one of each.
+pub fn parameter_sets(buf: &[u8]) -> Option<Vec<u8>> {
+    let (mut sps, mut pps) = (false, false);
+    let mut sets = Vec::new();
+    for unit in iter_nal_units(buf) {
+        match nal_type(unit) {
+            NAL_TYPE_SPS => sps = true,
+            NAL_TYPE_PPS => pps = true,
+            _ => continue,
+        }
+        sets.extend_from_slice(&[0, 0, 0, 1]);
+        sets.extend_from_slice(unit);
+    }
+    (sps && pps).then_some(sets)
+}
+
+/// Rewrite the Annex-B access unit in `buf` with each NAL unit behind its
+/// length, a 4-byte big-endian integer, instead of a start code. This is
+/// the framing of MP4 samples (AVCC, or HVCC for H.265).
+pub fn to_avcc(buf: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(buf.len());
+    to_avcc_into(buf, &mut out);
+    out
+}
+
+/// [`to_avcc`], replacing the contents of `out` so a caller can reuse one
+/// buffer for every access unit.
+pub fn to_avcc_into(buf: &[u8], out: &mut Vec<u8>) {
+    out.clear();
+    for unit in iter_nal_units(buf) {
+        out.extend_from_slice(&(unit.len() as u32).to_be_bytes());
+        out.extend_from_slice(unit);
+    }
+}
+
+/// Rewrite a length-prefixed access unit, as from [`to_avcc`], with a
+/// 4-byte start code before each NAL unit.
+///
+/// # Errors
+///
+/// Returns an error if a length prefix is cut short or runs past the end
+/// of `buf`.
+pub fn to_annex_b(buf: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut out = Vec::with_capacity(buf.len());
+    let mut rest = buf;
+    while !rest.is_empty() {
+        let Some((len, body)) = rest.split_first_chunk::<4>() else {
+            return Err(format!("{} trailing bytes after the last NAL unit", rest.len()).into());
+        };
+        let len = u32::from_be_bytes(*len) as usize;
+        let Some(unit) = body.get(..len) else {
+            return Err(format!("{len}-byte NAL unit with {} bytes left", body.len()).into());
+        };
+        out.extend_from_slice(&[0, 0, 0, 1]);
+        out.extend_from_slice(unit);
+        rest = &body[len..];
+    }
+    Ok(out)
+}
+
+/// Picture coding type of a slice, from its `slice_type` (Table 7-6).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SliceType {
+    P,
+    B,
+    I,
+    Sp,
+    Si,
+}
+
+/// The type of a coded slice NAL unit (non-IDR or IDR), or `None` for any
+/// other unit. A leading start code is skipped as in [`nal_type`].
+pub fn slice_type(unit: &[u8]) -> Result<Option<SliceType>, Box<dyn Error>> {
+    if !matches!(nal_type(unit), NAL_TYPE_SLICE | NAL_TYPE_IDR) {
+        return Ok(None);
+    }
+    let unit = &unit[leading_start_code_len(unit).unwrap_or(0)..];
+    let rbsp = unescape(unit);
+    let mut r = BitReader::new(&rbsp[1..]);
+    r.ue()?; // first_mb_in_slice
+    Ok(Some(match r.ue()? % 5 {
+        0 => SliceType::P,
+        1 => SliceType::B,
+        2 => SliceType::I,
+        3 => SliceType::Sp,
+        _ => SliceType::Si,
+    }))
+}
+
+/// Returns `true` if the access unit in `buf` contains a B slice, whose
+/// display order differs from its decode order.
+pub fn has_b_slices(buf: &[u8]) -> bool {
+    iter_nal_units(buf).any(|unit| matches!(slice_type(unit), Ok(Some(SliceType::B))))
+}
+
+/// Strip emulation-prevention bytes from a NAL unit, returning its raw
+/// byte sequence payload. As in a decoder, every `03` that follows two
+/// zero bytes is dropped. Borrows when the unit contains none.
+pub fn unescape(unit: &[u8]) -> Cow<'_, [u8]> {
+    if !unit.windows(3).any(|w| w == [0, 0, 3]) {
+        return Cow::Borrowed(unit);
+    }
+
+    let mut out = Vec::with_capacity(unit.len());
+    let mut zeros = 0;
+    for &b in unit {
+        if zeros >= 2 && b == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+        out.push(b);
+    }
+    Cow::Owned(out)
+}
+
+/// Insert emulation-prevention bytes, the inverse of [`unescape`]: a `03`
+/// goes before any byte of `00`..=`03` that follows two zero bytes.
+pub fn escape(rbsp: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(rbsp.len() + rbsp.len() / 64);
+    let mut zeros = 0;
+    for &b in rbsp {
+        if zeros >= 2 && b <= 3 {
+            out.push(3);
+            zeros = 0;
+        }
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+        out.push(b);
+    }
+    out
+}
+
+/// Length of the leading NAL unit in `buf`, start code included,
+/// terminated by the *next* start code, or `None` if either no
+/// terminating start code is present yet in `buf` or `buf` is not
+/// start-code-aligned. The caller distinguishes the two cases via
+/// [`starts_with_start_code`] / [`find_start_code`].
+///
+/// V4L2 decoders expect one access unit per queued OUTPUT buffer, so the
+/// replay path uses this to feed one NAL per `decode_frame` call.
+pub fn next_nal_unit_len(buf: &[u8]) -> Option<usize> {
+    // Skip past the leading start code and the NAL header byte before
+    // scanning, otherwise the scanner would trigger on the leading start
+    // code itself (the tail 00 00 01 of a 4-byte start code matches the
+    // 3-byte form starting at offset 1).
+    let leading_sc_len = leading_start_code_len(buf)?;
+    let body = buf.get(leading_sc_len + 1..)?;
+    find_start_code(body).map(|off| leading_sc_len + 1 + off)
+}
+
+/// Length of the Annex-B start code at the very front of `buf`, or
+/// `None` if there isn't one. Returns 3 for `00 00 01`, 4 for
+/// `00 00 00 01`.
+#[inline]
+pub fn leading_start_code_len(buf: &[u8]) -> Option<usize> {
+    if buf.starts_with(&[0, 0, 0, 1]) {
+        Some(4)
+    } else if buf.starts_with(&[0, 0, 1]) {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+#[inline]
+pub fn starts_with_start_code(buf: &[u8]) -> bool {
+    leading_start_code_len(buf).is_some()
+}
+
+/// Find the offset of the next Annex-B start code anywhere in `buf`
+/// (3- or 4-byte form). Returns `None` if the buffer contains no full
+/// start code. A 4-byte start code is reported at its first zero.
+pub fn find_start_code(buf: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i + 2 < buf.len() {
+        if buf[i] == 0 && buf[i + 1] == 0 {
+            if buf[i + 2] == 1 {
+                return Some(i);
+            }
+            if buf[i + 2] == 0 && i + 3 < buf.len() && buf[i + 3] == 1 {
+                return Some(i);
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+fn trim_trailing_zeros(unit: &[u8]) -> &[u8] {
+    let len = unit.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    &unit[..len]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn units(buf: &[u8]) -> Vec<&[u8]> {
+        iter_nal_units(buf).collect()
+    }
+
+    #[test]
+    fn escape_inverts_unescape() {
+        let rbsp = [0x67, 0, 0, 0, 0, 0, 1, 0, 0, 2, 0, 0, 3, 0, 0, 4, 0, 0];
+        let escaped = escape(&rbsp);
+        assert_eq!(
+            escaped,
+            [0x67, 0, 0, 3, 0, 0, 3, 0, 1, 0, 0, 3, 2, 0, 0, 3, 3, 0, 0, 4, 0, 0]
+        );
+        assert_eq!(unescape(&escaped).as_ref(), rbsp);
+        // Units without zero runs pass through unchanged.
+        assert_eq!(escape(&[0x68, 0xce, 0x3c, 0x80]), [0x68, 0xce, 0x3c, 0x80]);
+    }
+
+    #[test]
+    fn is_keyframe_detects_idr_slices() {
+        // SPS, PPS, IDR slice: a keyframe access unit.
+        let idr = [
+            0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xce, 0, 0, 1, 0x65, 0x88,
+        ];
+        assert!(is_keyframe(&idr));
+
+        // AUD and a non-IDR slice.
+        let p_frame = [0, 0, 0, 1, 0x09, 0xf0, 0, 0, 0, 1, 0x41, 0x9a];
+        assert!(!is_keyframe(&p_frame));
+        assert!(!is_keyframe(&[]));
+    }
+
+    #[test]
+    fn parameter_sets_keep_sps_and_pps_only() {
+        // AUD, SPS and PPS behind 3-byte start codes, IDR slice.
+        let idr = [
+            0, 0, 1, 0x09, 0xf0, 0, 0, 1, 0x67, 0x42, 0, 0, 1, 0x68, 0xce, 0, 0, 1, 0x65, 0x88,
+        ];
+        assert_eq!(
+            parameter_sets(&idr).unwrap(),
+            [0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xce]
+        );
+
+        // A keyframe without its PPS, and a P frame, carry no usable sets.
+        assert_eq!(
+            parameter_sets(&[0, 0, 0, 1, 0x67, 0x42, 0, 0, 1, 0x65]),
+            None
+        );
+        assert_eq!(parameter_sets(&[0, 0, 0, 1, 0x41, 0x9a]), None);
+    }
+
+    #[test]
+    fn avcc_round_trip() {
+        // SPS, PPS and an IDR slice with an escaped start code inside.
+        let annex_b = [
+            0, 0, 0, 1, 0x67, 0x42, 0xe0, 0, 0, 0, 1, 0x68, 0xce, 0, 0, 0, 1, 0x65, 0, 0, 3, 1,
+        ];
+        let avcc = [
+            0, 0, 0, 3, 0x67, 0x42, 0xe0, 0, 0, 0, 2, 0x68, 0xce, 0, 0, 0, 5, 0x65, 0, 0, 3, 1,
+        ];
+        assert_eq!(to_avcc(&annex_b), avcc);
+        assert_eq!(to_annex_b(&avcc).unwrap(), annex_b);
+
+        // 3-byte start codes come back as 4-byte ones.
+        let short = [0, 0, 1, 0x09, 0xf0, 0, 0, 1, 0x41, 0x9a];
+        let long = to_annex_b(&to_avcc(&short)).unwrap();
+        assert_eq!(units(&long), units(&short));
+
+        assert!(to_avcc(&[]).is_empty());
+
+        let mut out = vec![0xff; 64];
+        to_avcc_into(&annex_b, &mut out);
+        assert_eq!(out, avcc);
+        assert!(to_annex_b(&avcc[..avcc.len() - 1]).is_err());
+        assert!(to_annex_b(&[0, 0, 0]).is_err());
+    }
+
+    // Slice headers: first_mb_in_slice = 0, then slice_type, then the stop bit.
+    const IDR_I: [u8; 6] = [0, 0, 0, 1, 0x65, 0x88]; // slice_type 7 (I)
+    const SLICE_P: [u8; 5] = [0, 0, 1, 0x41, 0x9a]; // slice_type 5 (P)
+    const SLICE_B: [u8; 5] = [0, 0, 1, 0x01, 0x9e]; // slice_type 6 (B)
+
+    #[test]
+    fn slice_types_from_headers() {
+        assert_eq!(slice_type(&IDR_I).unwrap(), Some(SliceType::I));
+        assert_eq!(slice_type(&SLICE_P).unwrap(), Some(SliceType::P));
+        assert_eq!(slice_type(&SLICE_B).unwrap(), Some(SliceType::B));
+        assert_eq!(slice_type(&[0x67, 0x42]).unwrap(), None);
+        assert!(slice_type(&[0x41]).is_err());
+    }
+
+    #[test]
+    fn ip_stream_needs_no_reordering() {
+        // With B-frames off every access unit is an I or P picture, so
+        // frames leave the encoder in display order.
+        let gop = [&IDR_I[..], &SLICE_P, &SLICE_P, &SLICE_P];
+        assert!(gop.iter().all(|au| !has_b_slices(au)));
+        assert!(!has_b_slices(&gop.concat()));
+
+        assert!(has_b_slices(&[&SLICE_P[..], &SLICE_B].concat()));
+    }
+
+    #[test]
+    fn returns_none_for_short_input() {
+        assert_eq!(next_nal_unit_len(&[0, 0, 0]), None);
+        assert_eq!(next_nal_unit_len(&[]), None);
+    }
+
+    #[test]
+    fn returns_none_when_only_one_start_code_present() {
+        // Single 4-byte start code + NAL bytes, no terminator yet.
+        let buf = [0, 0, 0, 1, 0x67, 0xaa, 0xbb, 0xcc];
+        assert_eq!(next_nal_unit_len(&buf), None);
+    }
+
+    #[test]
+    fn finds_next_4byte_start_code() {
+        // First NAL starts at 0 (4-byte SC), second NAL starts at 7
+        // (4-byte SC).  Expected len = 7.
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&[0, 0, 0, 1, 0x67, 0x42, 0xe0]); // NAL #1 (7 bytes incl SC)
+        buf.extend_from_slice(&[0, 0, 0, 1, 0x28, 0xce]); // NAL #2
+        assert_eq!(next_nal_unit_len(&buf), Some(7));
+    }
+
+    #[test]
+    fn finds_next_3byte_start_code() {
+        // First NAL starts with 4-byte SC, second with 3-byte SC.
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&[0, 0, 0, 1, 0x67, 0x42]); // NAL #1
+        buf.extend_from_slice(&[0, 0, 1, 0x28, 0xce]); // NAL #2 (3-byte SC)
+        assert_eq!(next_nal_unit_len(&buf), Some(6));
+    }
+
+    #[test]
+    fn scan_skips_leading_zeros_inside_nal() {
+        // Valid NAL payload may contain 00 00 sequences that are not
+        // start codes (emulation-prevention bytes ensure they're
+        // never followed by 01 inside a NAL). Here we have
+        // 00 00 03 01 inside NAL #1 — scanner must treat the 03 as
+        // not-a-start-code and keep scanning to the real boundary.
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&[0, 0, 0, 1, 0x65, 0x00, 0x00, 0x03, 0x01]); // NAL #1 (9 bytes)
+        buf.extend_from_slice(&[0, 0, 0, 1, 0x28]); // NAL #2
+        assert_eq!(next_nal_unit_len(&buf), Some(9));
+    }
+
+    #[test]
+    fn starts_with_start_code_recognizes_both_forms() {
+        assert!(starts_with_start_code(&[0, 0, 1, 0x67]));
+        assert!(starts_with_start_code(&[0, 0, 0, 1, 0x67]));
+        assert!(!starts_with_start_code(&[0, 0, 2, 0x67]));
+        assert!(!starts_with_start_code(&[0xff, 0, 0, 1]));
+        assert!(!starts_with_start_code(&[]));
+    }
+
+    #[test]
+    fn find_start_code_reports_offset_in_garbage() {
+        // 5 bytes of garbage, then a 4-byte SC.
+        let buf = [0xab, 0xcd, 0xef, 0x12, 0x34, 0, 0, 0, 1, 0x67];
+        assert_eq!(find_start_code(&buf), Some(5));
+    }
+
+    #[test]
+    fn find_start_code_returns_none_on_pure_garbage() {
+        let buf = [0xff; 32];
+        assert_eq!(find_start_code(&buf), None);
+    }
+
+    #[test]
+    fn find_start_code_handles_zero_runs_without_one() {
+        // Long run of zeroes without a terminating 0x01 must not be
+        // treated as a start code.
+        let buf = [0u8; 16];
+        assert_eq!(find_start_code(&buf), None);
+    }
+
+    #[test]
+    fn find_start_code_skips_to_3byte_form() {
+        // Garbage prefix + 3-byte SC.
+        let buf = [0xaa, 0xbb, 0, 0, 1, 0x67];
+        assert_eq!(find_start_code(&buf), Some(2));
+    }
+
+    #[test]
+    fn next_nal_unit_len_rejects_unaligned_input() {
+        // No leading start code → caller must resync.
+        let buf = [0xab, 0xcd, 0, 0, 0, 1, 0x67, 0, 0, 0, 1, 0x28];
+        assert_eq!(next_nal_unit_len(&buf), None);
+        // ...but find_start_code still locates the next SC for resync.
+        assert_eq!(find_start_code(&buf), Some(2));
+    }
+
+    #[test]
+    fn iter_mixed_start_code_lengths() {
+        let buf = [
+            0, 0, 0, 1, 0x67, 0x42, 0xe0, // SPS, 4-byte SC
+            0, 0, 1, 0x68, 0xce, // PPS, 3-byte SC
+            0, 0, 0, 1, 0x65, 0x88, 0x84, // IDR, 4-byte SC
+            0, 0, 1, 0x41, 0x9a, // slice, 3-byte SC
+        ];
+        assert_eq!(
+            units(&buf),
+            [
+                &[0x67, 0x42, 0xe0][..],
+                &[0x68, 0xce][..],
+                &[0x65, 0x88, 0x84][..],
+                &[0x41, 0x9a][..],
+            ]
+        );
+        let types: Vec<u8> = iter_nal_units(&buf).map(nal_type).collect();
+        assert_eq!(
+            types,
+            [NAL_TYPE_SPS, NAL_TYPE_PPS, NAL_TYPE_IDR, NAL_TYPE_SLICE]
+        );
+    }
+
+    #[test]
+    fn iter_skips_leading_garbage_and_keeps_trailing_bytes() {
+        // Garbage before the first SC is not a unit; the last unit runs
+        // to the end of the buffer.
+        let buf = [
+            0xde, 0xad, 0, 0, 1, 0x09, 0xf0, 0, 0, 1, 0x41, 0x01, 0x02, 0x03,
+        ];
+        assert_eq!(
+            units(&buf),
+            [&[0x09, 0xf0][..], &[0x41, 0x01, 0x02, 0x03][..]]
+        );
+    }
+
+    #[test]
+    fn iter_trims_zero_padding_between_units() {
+        // trailing_zero_8bits before a start code belong to neither unit.
+        let buf = [0, 0, 1, 0x67, 0x42, 0, 0, 0, 0, 0, 1, 0x68, 0xce, 0, 0];
+        assert_eq!(units(&buf), [&[0x67, 0x42][..], &[0x68, 0xce][..]]);
+    }
+
+    #[test]
+    fn iter_keeps_emulation_prevention_runs_inside_unit() {
+        // 00 00 03 01 and 00 00 03 00 00 03 are escaped payload, not
+        // start codes.
+        let buf = [
+            0, 0, 0, 1, 0x65, 0, 0, 3, 1, 0, 0, 3, 0, 0, 3, 0xff, 0, 0, 1, 0x41,
+        ];
+        assert_eq!(
+            units(&buf),
+            [&[0x65, 0, 0, 3, 1, 0, 0, 3, 0, 0, 3, 0xff][..], &[0x41][..]]
+        );
+    }
+
+    #[test]
+    fn iter_handles_empty_and_degenerate_input() {
+        assert!(units(&[]).is_empty());
+        assert!(units(&[0u8; 32]).is_empty());
+        assert!(units(&[0, 0, 1]).is_empty());
+        assert!(units(&[0, 0, 0, 1, 0, 0, 1]).is_empty());
+        assert_eq!(units(&[0, 0, 1, 0x0a]), [&[0x0a][..]]);
+    }
+
+    #[test]
+    fn nal_type_accepts_units_with_or_without_start_code() {
+        assert_eq!(nal_type(&[0x67, 0x42]), NAL_TYPE_SPS);
+        assert_eq!(nal_type(&[0, 0, 1, 0x68]), NAL_TYPE_PPS);
+        assert_eq!(nal_type(&[0, 0, 0, 1, 0x65]), NAL_TYPE_IDR);
+        assert_eq!(nal_type(&[0x0a]), NAL_TYPE_EOS);
+        assert_eq!(nal_type(&[]), 0);
+    }
+
+    #[test]
+    fn unescape_removes_emulation_prevention_bytes() {
+        assert!(matches!(unescape(&[0x67, 0, 0, 4]), Cow::Borrowed(_)));
+        assert_eq!(
+            unescape(&[0x65, 0, 0, 3, 1, 0, 0, 3, 0, 0, 3, 0xff]).as_ref(),
+            [0x65, 0, 0, 1, 0, 0, 0, 0, 0xff]
+        );
+        // A trailing 00 00 03 escapes cabac_zero_words.
+        assert_eq!(unescape(&[0x65, 0, 0, 3]).as_ref(), [0x65, 0, 0]);
+        // The zero run restarts after each escape: 00 00 03 00 03 keeps
+        // the second 03.
+        assert_eq!(
+            unescape(&[0x65, 0, 0, 3, 0, 3]).as_ref(),
+            [0x65, 0, 0, 0, 3]
+        );
+    }
+}
@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Minimal NumPy `.npy` writer for dumping frames to compare against a
+//! Python preprocessing pipeline.
+//!
+//! Only what a camera frame needs is supported: version 1.0 files holding a
+//! C-ordered `uint8` array. The result loads with `numpy.load` as-is.
+//!
+//! ```no_run
+//! use edgefirst_camera::{image::{Image, RGBA}, npy};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let img = Image::new(640, 480, RGBA)?;
+//! npy::save_image("frame.npy", &img)?;
+//! // Python: numpy.load("frame.npy").shape == (480, 640, 4)
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+use videostream::fourcc::FourCC;
+
+use crate::image::{Image, NV12, RGB3, RGBA, RGBX, YUYV};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Array shape NumPy users expect for a `width`x`height` frame in `format`:
+/// `(height, width, channels)` for packed formats and `(height * 3 / 2,
+/// width)` for NV12, matching OpenCV's layout for YUV 4:2:0.
+pub fn image_shape(format: FourCC, width: u32, height: u32) -> Option<Vec<usize>> {
+    let (w, h) = (width as usize, height as usize);
+    Some(match format {
+        RGBA | RGBX => vec![h, w, 4],
+        RGB3 => vec![h, w, 3],
+        YUYV => vec![h, w, 2],
+        NV12 => vec![h * 3 / 2, w],
+        _ => return None,
+    })
+}
+
+/// Write `data` as a `uint8` array of `shape`.
+pub fn write<W: Write>(out: &mut W, shape: &[usize], data: &[u8]) -> io::Result<()> {
+    let len: usize = shape.iter().product();
+    if len != data.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("shape {shape:?} needs {len} bytes, got {}", data.len()),
+        ));
+    }
+
+    let dims = match shape {
+        [n] => format!("{n},"),
+        _ => shape
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    let mut header = format!("{{'descr': '|u1', 'fortran_order': False, 'shape': ({dims}), }}");
+    // Pad with spaces so the data starts 64-byte aligned, ending in '\n'.
+    let unpadded = MAGIC.len() + 2 + 2 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    header.push('\n');
+    let header_len = u16::try_from(header.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "npy header too long"))?;
+
+    out.write_all(MAGIC)?;
+    out.write_all(&[1, 0])?;
+    out.write_all(&header_len.to_le_bytes())?;
+    out.write_all(header.as_bytes())?;
+    out.write_all(data)
+}
+
+/// Save `image` to `path` with the shape from [`image_shape`].
+pub fn save_image(path: impl AsRef<Path>, image: &Image) -> Result<(), Box<dyn Error>> {
+    let shape = image_shape(image.format(), image.width(), image.height())
+        .ok_or_else(|| format!("cannot dump {} frames as .npy", image.format()))?;
+    let mut image = image.try_clone()?;
+    let mapped = image.mmap()?;
+    let mut out = BufWriter::new(File::create(path)?);
+    write(&mut out, &shape, mapped.as_slice())?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Split an `.npy` file into its header dictionary and data.
+    fn parse(file: &[u8]) -> (&str, &[u8]) {
+        assert_eq!(&file[..6], MAGIC);
+        assert_eq!(&file[6..8], [1, 0]);
+        let header_len = u16::from_le_bytes([file[8], file[9]]) as usize;
+        let data_start = 10 + header_len;
+        assert_eq!(data_start % 64, 0, "data must be 64-byte aligned");
+        let header = std::str::from_utf8(&file[10..data_start]).unwrap();
+        assert!(header.ends_with('\n'));
+        (header.trim_end(), &file[data_start..])
+    }
+
+    #[test]
+    fn writes_shape_and_dtype_header() {
+        // A 2x3 RGBA image.
+        let pixels: Vec<u8> = (0..24).collect();
+        let mut file = Vec::new();
+        write(&mut file, &image_shape(RGBA, 3, 2).unwrap(), &pixels).unwrap();
+
+        let (header, data) = parse(&file);
+        assert_eq!(
+            header,
+            "{'descr': '|u1', 'fortran_order': False, 'shape': (2, 3, 4), }"
+        );
+        assert_eq!(data, pixels);
+    }
+
+    #[test]
+    fn one_dimensional_shape_keeps_tuple_comma() {
+        let mut file = Vec::new();
+        write(&mut file, &[3], &[1, 2, 3]).unwrap();
+        let (header, data) = parse(&file);
+        assert!(header.contains("'shape': (3,)"), "{header}");
+        assert_eq!(data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn rejects_data_that_does_not_fill_the_shape() {
+        let mut file = Vec::new();
+        assert!(write(&mut file, &[2, 2], &[0; 3]).is_err());
+        assert!(file.is_empty());
+    }
+
+    #[test]
+    fn image_shapes() {
+        assert_eq!(image_shape(RGBA, 640, 480), Some(vec![480, 640, 4]));
+        assert_eq!(image_shape(RGB3, 640, 480), Some(vec![480, 640, 3]));
+        assert_eq!(image_shape(YUYV, 640, 480), Some(vec![480, 640, 2]));
+        assert_eq!(image_shape(NV12, 640, 480), Some(vec![720, 640]));
+        assert_eq!(image_shape(FourCC(*b"GREY"), 640, 480), None);
+    }
+}
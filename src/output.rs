@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Where `--record` and `--dump-npy` write their files.
+//!
+//! A relative path is placed under `--output-dir`. A path that names a
+//! directory (an existing one, or any path ending in `/`) gets a file name
+//! from `--output-pattern` plus the output's extension, so repeated runs
+//! and multiple cameras write unique files:
+//!
+//! | Placeholder   | Expands to                                                        |
+//! |---------------|-------------------------------------------------------------------|
+//! | `{timestamp}` | UTC time as `20231114T221320.123456789Z`, without `:` so it is a valid file name everywhere |
+//! | `{seq}`       | Frame sequence number; 0 for `--record`, which is named at startup |
+//! | `{frame_id}`  | `--camera-frame-id`, with characters other than `A-Z a-z 0-9 . _ -` replaced by `_` |
+//!
+//! `{{` and `}}` write literal braces.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// One piece of an [`OutputPattern`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Timestamp,
+    Seq,
+    FrameId,
+}
+
+/// A validated `--output-pattern`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputPattern {
+    source: String,
+    parts: Vec<Part>,
+}
+
+impl FromStr for OutputPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(['/', '\0']) {
+            return Err(format!(
+                "output pattern {s:?} names a file, it must not contain '/' or NUL"
+            ));
+        }
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| format!("output pattern {s:?} has an unclosed '{{'"))?;
+                    let part = match &rest[..end] {
+                        "timestamp" => Part::Timestamp,
+                        "seq" => Part::Seq,
+                        "frame_id" => Part::FrameId,
+                        name => {
+                            return Err(format!(
+                                "output pattern {s:?} has unknown placeholder {{{name}}}, \
+                                 expected {{timestamp}}, {{seq}} or {{frame_id}}"
+                            ))
+                        }
+                    };
+                    chars = rest[end + 1..].chars();
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(part);
+                }
+                '}' => return Err(format!("output pattern {s:?} has an unmatched '}}'")),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        if matches!(parts.as_slice(), [] | [Part::Text(_)]) {
+            return Err(format!(
+                "output pattern {s:?} needs a {{timestamp}}, {{seq}} or {{frame_id}} placeholder"
+            ));
+        }
+        Ok(Self {
+            source: s.to_string(),
+            parts,
+        })
+    }
+}
+
+impl fmt::Display for OutputPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl OutputPattern {
+    /// The file name for an output captured at `stamp_ns` (nanoseconds
+    /// since the Unix epoch).
+    pub fn expand(&self, stamp_ns: u64, seq: u64, frame_id: &str) -> String {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => name.push_str(text),
+                Part::Timestamp => name.push_str(&format_timestamp(stamp_ns)),
+                Part::Seq => name.push_str(&seq.to_string()),
+                Part::FrameId => name.extend(frame_id.chars().map(|c| {
+                    if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                        c
+                    } else {
+                        '_'
+                    }
+                })),
+            }
+        }
+        name
+    }
+}
+
+/// UTC time of `stamp_ns` as `YYYYMMDDThhmmss.nnnnnnnnnZ`, the ISO 8601
+/// basic format, which has no `:`.
+pub fn format_timestamp(stamp_ns: u64) -> String {
+    let secs = stamp_ns / 1_000_000_000;
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let time = secs % 86_400;
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}.{:09}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        stamp_ns % 1_000_000_000
+    )
+}
+
+/// Gregorian year, month and day of `days` since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days_from_civil inverse, on 400-year eras that
+    // start on 0000-03-01.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The file an output configured with `path` writes: `path` under `dir`
+/// when it is relative, with a name from `pattern` and `extension` when it
+/// names a directory.
+pub fn resolve(
+    path: &Path,
+    dir: Option<&Path>,
+    pattern: &OutputPattern,
+    extension: &str,
+    stamp_ns: u64,
+    seq: u64,
+    frame_id: &str,
+) -> PathBuf {
+    let mut resolved = match dir {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    };
+    if path.as_os_str().to_string_lossy().ends_with('/') || resolved.is_dir() {
+        // Not `set_extension`: a timestamp has a '.' of its own.
+        resolved.push(format!(
+            "{}.{extension}",
+            pattern.expand(stamp_ns, seq, frame_id)
+        ));
+    }
+    resolved
+}
+
+/// Create the directory `path` is written into, if it is missing.
+pub fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAMP_NS: u64 = 1_700_000_000_123_456_789;
+
+    #[test]
+    fn expands_placeholders() {
+        let pattern: OutputPattern = "{frame_id}_{timestamp}_{seq}".parse().unwrap();
+        assert_eq!(
+            pattern.expand(STAMP_NS, 42, "camera/optical"),
+            "camera_optical_20231114T221320.123456789Z_42"
+        );
+        let braces: OutputPattern = "{{cam}}-{seq}".parse().unwrap();
+        assert_eq!(braces.expand(STAMP_NS, 7, "x"), "{cam}-7");
+    }
+
+    #[test]
+    fn timestamps_are_filesystem_safe() {
+        assert_eq!(format_timestamp(0), "19700101T000000.000000000Z");
+        // Leap day.
+        assert_eq!(
+            format_timestamp(951_782_400_000_000_001),
+            "20000229T000000.000000001Z"
+        );
+        let name = format_timestamp(STAMP_NS);
+        assert!(!name.contains([':', '/', ' ']), "{name}");
+    }
+
+    #[test]
+    fn rejects_bad_patterns() {
+        for bad in [
+            "",
+            "capture",
+            "{time}",
+            "{seq",
+            "seq}",
+            "run/{seq}",
+            "a\0{seq}",
+        ] {
+            assert!(bad.parse::<OutputPattern>().is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn resolves_directories_and_relative_paths() {
+        let pattern: OutputPattern = "{frame_id}_{seq}".parse().unwrap();
+        let dir = Some(Path::new("/data/captures"));
+        let resolve =
+            |path: &str, dir| resolve(Path::new(path), dir, &pattern, "npy", STAMP_NS, 3, "cam");
+
+        assert_eq!(resolve("frame.npy", None), Path::new("frame.npy"));
+        assert_eq!(
+            resolve("frame.npy", dir),
+            Path::new("/data/captures/frame.npy")
+        );
+        assert_eq!(resolve("/tmp/frame.npy", dir), Path::new("/tmp/frame.npy"));
+        assert_eq!(
+            resolve("dumps/", dir),
+            Path::new("/data/captures/dumps/cam_3.npy")
+        );
+        let stamped: OutputPattern = "{timestamp}".parse().unwrap();
+        assert_eq!(
+            super::resolve(
+                Path::new("rec/"),
+                None,
+                &stamped,
+                "h264",
+                STAMP_NS,
+                0,
+                "cam"
+            ),
+            Path::new("rec/20231114T221320.123456789Z.h264")
+        );
+        // An existing directory without a trailing '/'.
+        let tmp = std::env::temp_dir();
+        assert_eq!(resolve(tmp.to_str().unwrap(), None), tmp.join("cam_3.npy"));
+    }
+}
@@ -0,0 +1,259 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Synthetic test frames, for bringing up displays and networks before a
+//! camera is connected.
+//!
+//! [`PatternSource`] is a [`CameraSource`] that hands out a YUYV
+//! [`TestPattern`] at a fixed frame rate, stamped with CLOCK_MONOTONIC like
+//! V4L2 frames, so everything downstream of the camera runs unchanged.
+//!
+//! ```no_run
+//! use edgefirst_camera::{config::TestPattern, pattern::PatternSource, pipeline::Pipeline};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let source = PatternSource::new(TestPattern::Colorbars, 1280, 720, 30)?;
+//! let mut pipeline = Pipeline::new(source);
+//! pipeline.add_sink(|frame| println!("frame {} {}", frame.sequence, frame.image));
+//! pipeline.step()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    error::Error,
+    io, thread,
+    time::{Duration, Instant},
+};
+use unix_ts::Timestamp;
+
+use crate::{
+    config::TestPattern,
+    image::{Image, YUYV},
+    pipeline::{CameraSource, CapturedFrame},
+};
+
+/// BT.601 limited-range Y, U and V of the 75% colour bars, left to right.
+const BARS: [[u8; 3]; 8] = [
+    [180, 128, 128],
+    [162, 44, 142],
+    [131, 156, 44],
+    [112, 72, 58],
+    [84, 184, 198],
+    [65, 100, 212],
+    [35, 212, 114],
+    [16, 128, 128],
+];
+
+/// Limited-range black and white luma.
+const BLACK: u8 = 16;
+const WHITE: u8 = 235;
+
+/// Columns of the [`TestPattern::Checker`] board.
+const CHECKER_COLUMNS: usize = 16;
+
+/// Y, U and V of `pattern` at pixel (`x`, `y`) of an image `width` wide.
+fn pixel(pattern: TestPattern, x: usize, y: usize, width: usize) -> [u8; 3] {
+    match pattern {
+        TestPattern::Colorbars => BARS[x * BARS.len() / width],
+        TestPattern::Gradient => {
+            let span = (WHITE - BLACK) as usize;
+            let luma = BLACK as usize + x * span / (width - 1).max(1);
+            [luma as u8, 128, 128]
+        }
+        TestPattern::Checker => {
+            let cell = (width / CHECKER_COLUMNS).max(1);
+            let luma = match (x / cell + y / cell) % 2 {
+                0 => WHITE,
+                _ => BLACK,
+            };
+            [luma, 128, 128]
+        }
+    }
+}
+
+/// Draw `pattern` into a tightly packed `width`x`height` YUYV buffer. Each
+/// pixel pair takes the chroma of its left pixel.
+///
+/// # Errors
+///
+/// Returns an error if `width` is odd or `buf` is shorter than the image.
+pub fn render_yuyv(
+    pattern: TestPattern,
+    width: u32,
+    height: u32,
+    buf: &mut [u8],
+) -> Result<(), Box<dyn Error>> {
+    let (width, height) = (width as usize, height as usize);
+    if width % 2 != 0 {
+        return Err(format!("YUYV test pattern needs an even width, got {width}").into());
+    }
+    let len = width * height * 2;
+    if buf.len() < len {
+        return Err(format!(
+            "{width}x{height} YUYV test pattern needs {len} bytes, got {}",
+            buf.len()
+        )
+        .into());
+    }
+    for (y, row) in buf[..len].chunks_exact_mut(width * 2).enumerate() {
+        for (pair, px) in row.chunks_exact_mut(4).enumerate() {
+            let x = pair * 2;
+            let [y0, u, v] = pixel(pattern, x, y, width);
+            let [y1, ..] = pixel(pattern, x + 1, y, width);
+            px.copy_from_slice(&[y0, u, y1, v]);
+        }
+    }
+    Ok(())
+}
+
+/// CLOCK_MONOTONIC now, the clock V4L2 stamps frames with.
+fn monotonic_now() -> io::Result<Timestamp> {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Timestamp::new(now.tv_sec as i64, now.tv_nsec as u32))
+}
+
+/// A [`CameraSource`] repeating one rendered [`TestPattern`] frame.
+///
+/// The pattern is drawn once into a DMA buffer and every frame is a new
+/// handle on it, so a frame costs no more than a camera frame. Reads are
+/// paced to the frame rate; after a stall the schedule restarts instead of
+/// bursting to catch up.
+pub struct PatternSource {
+    image: Image,
+    /// Time between frames, zero for as fast as frames are read.
+    interval: Duration,
+    due: Option<Instant>,
+    sequence: u64,
+}
+
+impl PatternSource {
+    /// Render `pattern` into a `width`x`height` YUYV frame delivered `fps`
+    /// times a second. An `fps` of zero delivers frames as fast as they are
+    /// read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `width` is odd or the DMA buffer cannot be
+    /// allocated.
+    pub fn new(
+        pattern: TestPattern,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut image = Image::new(width, height, YUYV)?;
+        render_yuyv(pattern, width, height, image.mmap()?.as_slice_mut())?;
+        let interval = match fps {
+            0 => Duration::ZERO,
+            fps => Duration::from_secs(1) / fps,
+        };
+        Ok(Self {
+            image,
+            interval,
+            due: None,
+            sequence: 0,
+        })
+    }
+
+    fn emit(&mut self) -> Result<CapturedFrame, Box<dyn Error>> {
+        let image = self.image.try_clone()?;
+        self.sequence += 1;
+        Ok(CapturedFrame {
+            fd: image.raw_fd(),
+            timestamp: monotonic_now()?,
+            sequence: self.sequence,
+            stride: image.stride(),
+            length: image.size() as u32,
+            image,
+        })
+    }
+}
+
+impl CameraSource for PatternSource {
+    fn read_frame(&mut self) -> Result<CapturedFrame, Box<dyn Error>> {
+        loop {
+            if let Some(frame) = self.read_frame_timeout(Duration::from_secs(1))? {
+                return Ok(frame);
+            }
+        }
+    }
+
+    fn read_frame_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<CapturedFrame>, Box<dyn Error>> {
+        let now = Instant::now();
+        let due = self.due.unwrap_or(now);
+        let wait = due.saturating_duration_since(now);
+        if wait > timeout {
+            thread::sleep(timeout);
+            return Ok(None);
+        }
+        thread::sleep(wait);
+        self.due = Some(if now >= due + self.interval {
+            now + self.interval
+        } else {
+            due + self.interval
+        });
+        self.emit().map(Some)
+    }
+
+    fn restart(&mut self) -> Result<(), Box<dyn Error>> {
+        // Nothing to reopen; start a fresh schedule.
+        self.due = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(pattern: TestPattern, width: u32, height: u32) -> Vec<u8> {
+        let mut buf = vec![0; (width * height * 2) as usize];
+        render_yuyv(pattern, width, height, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn colorbars_cover_the_width_in_order() {
+        let buf = render(TestPattern::Colorbars, 64, 4);
+        // Each bar is eight pixels, four YUYV pairs, wide.
+        for (bar, &[y, u, v]) in BARS.iter().enumerate() {
+            let pair = &buf[bar * 16..bar * 16 + 4];
+            assert_eq!(pair, [y, u, y, v], "bar {bar}");
+        }
+        // Every row is the same.
+        assert!(buf.chunks_exact(128).all(|row| row == &buf[..128]));
+    }
+
+    #[test]
+    fn gradient_and_checker_are_neutral_grey() {
+        let ramp = render(TestPattern::Gradient, 32, 2);
+        assert_eq!(ramp[0], BLACK);
+        assert_eq!(ramp[62], WHITE);
+        assert!(ramp[..64].chunks_exact(2).all(|px| px[1] == 128));
+        let luma: Vec<u8> = ramp[..64].iter().step_by(2).copied().collect();
+        assert!(luma.windows(2).all(|pair| pair[0] <= pair[1]), "{luma:?}");
+
+        let board = render(TestPattern::Checker, 32, 4);
+        // Two-pixel cells: one pair per cell, alternating along rows and
+        // between rows.
+        assert_eq!(board[..8], [WHITE, 128, WHITE, 128, BLACK, 128, BLACK, 128]);
+        assert_eq!(board[128..132], [BLACK, 128, BLACK, 128]);
+    }
+
+    #[test]
+    fn rejects_odd_widths_and_short_buffers() {
+        let mut buf = vec![0; 64];
+        assert!(render_yuyv(TestPattern::Colorbars, 7, 2, &mut buf).is_err());
+        assert!(render_yuyv(TestPattern::Colorbars, 8, 8, &mut buf).is_err());
+    }
+}
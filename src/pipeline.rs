@@ -0,0 +1,1154 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Reusable capture and fan-out pipeline.
+//!
+//! A [`Pipeline`] pulls frames from a [`CameraSource`] and hands each one to
+//! every registered sink. Sinks are plain closures, so the capture → convert
+//! → encode flow used by the camera node can be embedded without Zenoh or
+//! the CLI: a sink can forward the frame to an encoder thread, convert it
+//! with an [`ImageManager`](crate::image::ImageManager), or simply collect it.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use edgefirst_camera::pipeline::Pipeline;
+//! use videostream::{camera::create_camera, fourcc::FourCC};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let cam = create_camera()
+//!     .with_device("/dev/video3")
+//!     .with_format(FourCC(*b"YUYV"))
+//!     .open()?;
+//! cam.start()?;
+//!
+//! let mut pipeline = Pipeline::new(cam);
+//! pipeline.add_sink(|frame| println!("frame {} {}", frame.sequence, frame.image));
+//! pipeline.step()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Outputs that encode from the same intermediate (say JPEG and H.264 both
+//! wanting RGBA at the stream size) can register with
+//! [`Pipeline::add_converted_sink`] so the pipeline converts each frame once
+//! and shares the result instead of every output converting on its own.
+//! A conversion the caller already made of the frame, such as the
+//! [`FormatConverter`] output the camera node publishes, can be offered
+//! with [`Pipeline::share_conversion`] so sinks wanting the same target
+//! reuse it for that frame.
+
+use std::{
+    error::Error,
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+use unix_ts::Timestamp;
+use videostream::{camera::CameraReader, fourcc::FourCC};
+
+use crate::{
+    color::ColorCorrection,
+    config::{BayerPattern, ErrorFramePolicy, FrameFormat},
+    cpu,
+    image::{Image, ImageManager, Rect, Rotation, RGBA, YUYV},
+    rectify::RemapTable,
+};
+
+/// A single captured frame as handed to every pipeline sink.
+#[derive(Debug)]
+pub struct CapturedFrame {
+    /// The frame's DMA buffer. Sinks that keep the frame beyond the callback
+    /// should take their own handle with [`Image::try_clone`].
+    pub image: Image,
+    /// Capture timestamp (CLOCK_MONOTONIC for V4L2 sources).
+    pub timestamp: Timestamp,
+    /// Source frame counter.
+    pub sequence: u64,
+    /// File descriptor advertised to other processes. For V4L2 sources this
+    /// is the driver's exported buffer fd, which stays valid for the lifetime
+    /// of the source; other sources default to the image's own fd.
+    pub fd: i32,
+    /// Bytes per row of the first plane.
+    pub stride: u32,
+    /// Length in bytes of the underlying buffer, at least the image's
+    /// [`size`](Image::size) and possibly padded beyond it, see
+    /// [`frame_length`].
+    pub length: u32,
+}
+
+impl CapturedFrame {
+    /// Another handle on the same frame, with a duplicate of the image fd.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            image: self.image.try_clone()?,
+            timestamp: self.timestamp,
+            sequence: self.sequence,
+            fd: self.fd,
+            stride: self.stride,
+            length: self.length,
+        })
+    }
+}
+
+/// A source of camera frames for a [`Pipeline`].
+///
+/// Implemented for [`CameraReader`]; tests and alternative front-ends (file
+/// replay, synthetic patterns, remote buffers) provide their own.
+pub trait CameraSource {
+    /// Block until the next frame is available.
+    fn read_frame(&mut self) -> Result<CapturedFrame, Box<dyn Error>>;
+
+    /// Wait up to `timeout` for the next frame, returning `None` if none
+    /// arrived. Sources that can only block keep the default, which fails
+    /// the read.
+    fn read_frame_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<CapturedFrame>, Box<dyn Error>> {
+        let _ = timeout;
+        Err("camera source does not support timed reads".into())
+    }
+
+    /// Recover a source that stopped delivering frames, e.g. by reopening
+    /// the device. Called by the pipeline when its [`Watchdog`] fires.
+    /// Sources that cannot recover keep the default, which fails the read.
+    fn restart(&mut self) -> Result<(), Box<dyn Error>> {
+        Err("camera source does not support restart".into())
+    }
+}
+
+impl CameraSource for CameraReader {
+    fn read_frame(&mut self) -> Result<CapturedFrame, Box<dyn Error>> {
+        let buf = self.read()?;
+        let image = Image::from_camera(&buf)?;
+        Ok(CapturedFrame {
+            timestamp: buf.timestamp()?,
+            sequence: buf.sequence()? as u64,
+            fd: buf.rawfd(),
+            stride: image.stride(),
+            length: frame_length(buf.length()?, image.size(), || image.buffer_length())?,
+            image,
+        })
+    }
+}
+
+/// The buffer length to advertise for a frame whose driver reports
+/// `reported` bytes and whose image needs `size` bytes (`stride * height`).
+///
+/// Drivers usually report the whole buffer, padding included. Some report
+/// only the bytes used, less than the image consumers map; `actual` is
+/// then asked for the buffer's own length, see [`Image::buffer_length`].
+///
+/// # Errors
+///
+/// Returns an error if the buffer cannot hold `size` bytes, its length
+/// cannot be read, or it does not fit a `u32`.
+pub fn frame_length(
+    reported: usize,
+    size: usize,
+    actual: impl FnOnce() -> io::Result<usize>,
+) -> Result<u32, Box<dyn Error>> {
+    let length = if reported >= size {
+        reported
+    } else {
+        let actual = actual().map_err(|e| {
+            format!(
+                "camera buffer length {reported} is below the {size} bytes of its image \
+                 and its own length is unavailable: {e}"
+            )
+        })?;
+        if actual < size {
+            return Err(format!(
+                "camera buffer of {actual} bytes is smaller than the {size} bytes of its image"
+            )
+            .into());
+        }
+        actual
+    };
+    u32::try_from(length)
+        .map_err(|_| format!("camera buffer of {length} bytes does not fit a CameraFrame").into())
+}
+
+/// A pipeline output. Called once per frame, in registration order.
+pub type Sink = Box<dyn FnMut(&CapturedFrame) + Send>;
+
+/// Whether a [`Pipeline`]'s source is delivering frames.
+///
+/// Starts disconnected, becomes connected with the first frame, and drops
+/// back to disconnected while the pipeline restarts a source its
+/// [`Watchdog`] found stalled. Clones share the same flag, so one can be
+/// handed to another thread to report the state.
+#[derive(Clone, Debug, Default)]
+pub struct Liveness(Arc<AtomicBool>);
+
+impl Liveness {
+    pub fn is_connected(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn set(&self, connected: bool) {
+        self.0.store(connected, Ordering::SeqCst);
+    }
+}
+
+/// Drives a [`CameraSource`] and fans every frame out to the registered sinks.
+pub struct Pipeline<S> {
+    source: S,
+    sinks: Vec<Sink>,
+    stages: Vec<ConvertStage>,
+    /// Conversions of the current frame offered with
+    /// [`Pipeline::share_conversion`], dropped when the next frame is read.
+    shared: Vec<(ConvertTarget, CapturedFrame)>,
+    watchdog: Option<Watchdog>,
+    liveness: Liveness,
+}
+
+impl<S: CameraSource> Pipeline<S> {
+    /// Create a pipeline with no sinks.
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            sinks: Vec::new(),
+            stages: Vec::new(),
+            shared: Vec::new(),
+            watchdog: None,
+            liveness: Liveness::default(),
+        }
+    }
+
+    /// Guard reads with `watchdog`. Its stall callback must interrupt the
+    /// blocked read (for V4L2 sources, by signalling the capturing thread);
+    /// the interrupted read is then answered with
+    /// [`CameraSource::restart`] and retried.
+    pub fn set_watchdog(&mut self, watchdog: Watchdog) {
+        self.watchdog = Some(watchdog);
+    }
+
+    /// Register a sink. Sinks run on the capturing thread and should hand
+    /// heavy work off to another thread rather than block the capture loop.
+    pub fn add_sink<F>(&mut self, sink: F)
+    where
+        F: FnMut(&CapturedFrame) + Send + 'static,
+    {
+        self.sinks.push(Box::new(sink));
+    }
+
+    /// Builder-style variant of [`Pipeline::add_sink`].
+    pub fn with_sink<F>(mut self, sink: F) -> Self
+    where
+        F: FnMut(&CapturedFrame) + Send + 'static,
+    {
+        self.add_sink(sink);
+        self
+    }
+
+    /// Register a sink that receives frames already converted to `target`.
+    ///
+    /// Sinks registered with equal targets share one conversion per frame,
+    /// done on the capturing thread before they are called. The first sink
+    /// for a target allocates its conversion buffers.
+    pub fn add_converted_sink<F>(
+        &mut self,
+        target: ConvertTarget,
+        sink: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&CapturedFrame) + Send + 'static,
+    {
+        let stage = match self.stages.iter().position(|s| s.target == target) {
+            Some(i) => &mut self.stages[i],
+            None => {
+                self.stages.push(ConvertStage::new(target)?);
+                self.stages.last_mut().unwrap()
+            }
+        };
+        stage.sinks.push(Box::new(sink));
+        Ok(())
+    }
+
+    /// Number of registered sinks, including converted sinks.
+    pub fn sink_count(&self) -> usize {
+        self.sinks.len() + self.stages.iter().map(|s| s.sinks.len()).sum::<usize>()
+    }
+
+    /// Number of conversions run for converted sinks since the pipeline
+    /// was created. Frames served from [`Pipeline::share_conversion`] are
+    /// not counted.
+    pub fn conversion_count(&self) -> u64 {
+        self.stages.iter().map(|s| s.conversions).sum()
+    }
+
+    /// Offer `converted`, a conversion of the current frame to `target`
+    /// made outside the pipeline, to the converted sinks. Those registered
+    /// for an equal target receive it on the next [`Pipeline::dispatch`] of
+    /// the same frame instead of converting again. Offers are dropped when
+    /// the next frame is read.
+    pub fn share_conversion(
+        &mut self,
+        target: ConvertTarget,
+        converted: &CapturedFrame,
+    ) -> io::Result<()> {
+        if !self.stages.iter().any(|s| s.target == target) {
+            return Ok(());
+        }
+        let converted = converted.try_clone()?;
+        self.shared.retain(|(t, _)| *t != target);
+        self.shared.push((target, converted));
+        Ok(())
+    }
+
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    pub fn source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// A handle on whether the source is currently delivering frames.
+    pub fn liveness(&self) -> Liveness {
+        self.liveness.clone()
+    }
+
+    /// Read the next frame without dispatching it. Pair with
+    /// [`Pipeline::dispatch`] when the caller needs to act on the frame
+    /// (e.g. publish it) before the sinks see it.
+    pub fn next_frame(&mut self) -> Result<CapturedFrame, Box<dyn Error>> {
+        loop {
+            match self.source.read_frame() {
+                Ok(frame) => {
+                    self.received();
+                    return Ok(frame);
+                }
+                Err(e) if is_interrupted(e.as_ref()) && self.watchdog_fired() => {
+                    self.restart_stalled()?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Like [`Pipeline::next_frame`], but give up after `timeout` and
+    /// return `None`, so the caller can check for shutdown or run its own
+    /// stall handling between frames. Needs a source that implements
+    /// [`CameraSource::read_frame_timeout`].
+    pub fn read_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<CapturedFrame>, Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.source.read_frame_timeout(remaining) {
+                Ok(Some(frame)) => {
+                    self.received();
+                    return Ok(Some(frame));
+                }
+                Ok(None) => return Ok(None),
+                Err(e) if is_interrupted(e.as_ref()) && self.watchdog_fired() => {
+                    self.restart_stalled()?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn received(&mut self) {
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.feed();
+        }
+        self.liveness.set(true);
+        self.shared.clear();
+    }
+
+    fn restart_stalled(&mut self) -> Result<(), Box<dyn Error>> {
+        warn!("Capture stalled, restarting camera source");
+        self.liveness.set(false);
+        self.source.restart()?;
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.feed();
+        }
+        Ok(())
+    }
+
+    fn watchdog_fired(&self) -> bool {
+        self.watchdog.as_ref().is_some_and(Watchdog::take_fired)
+    }
+
+    /// Hand `frame` to every sink. Converted sinks whose conversion fails
+    /// miss this frame; the failure is logged rather than returned so one
+    /// output cannot stop the capture loop.
+    pub fn dispatch(&mut self, frame: &CapturedFrame) {
+        for sink in self.sinks.iter_mut() {
+            sink(frame);
+        }
+        for stage in self.stages.iter_mut() {
+            let offered = self.shared.iter().find(|(target, converted)| {
+                *target == stage.target && converted.sequence == frame.sequence
+            });
+            if let Some((_, converted)) = offered {
+                for sink in stage.sinks.iter_mut() {
+                    sink(converted);
+                }
+                continue;
+            }
+            match stage.convert(frame) {
+                Ok(converted) => {
+                    for sink in stage.sinks.iter_mut() {
+                        sink(&converted);
+                    }
+                }
+                Err(e) => warn!(
+                    "Conversion to {}x{} {} failed: {e}",
+                    stage.target.width, stage.target.height, stage.target.format
+                ),
+            }
+        }
+    }
+
+    /// Read one frame and dispatch it.
+    pub fn step(&mut self) -> Result<CapturedFrame, Box<dyn Error>> {
+        let frame = self.next_frame()?;
+        self.dispatch(&frame);
+        Ok(frame)
+    }
+
+    /// Run until `stop` is set, returning the number of frames dispatched.
+    /// Interrupted reads (EINTR from a signal) re-check `stop` instead of
+    /// failing the run.
+    pub fn run(&mut self, stop: &AtomicBool) -> Result<u64, Box<dyn Error>> {
+        let mut frames = 0;
+        while !stop.load(Ordering::SeqCst) {
+            match self.step() {
+                Ok(_) => frames += 1,
+                Err(e) if is_interrupted(e.as_ref()) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(frames)
+    }
+}
+
+/// Converts captured frames into another pixel format for sharing.
+///
+/// Conversions land in a small ring of DMA-heap images rather than a single
+/// buffer, so a consumer still reading the previous frame's fd is not
+/// overwritten by the next conversion. Frames already in the target format
+/// pass through untouched unless a crop is set with
+/// [`FormatConverter::set_crop`].
+pub struct FormatConverter {
+    imgmgr: ImageManager,
+    format: FrameFormat,
+    width: u32,
+    height: u32,
+    crop: Option<Rect>,
+    images: Vec<Image>,
+    next: usize,
+}
+
+impl FormatConverter {
+    /// Buffers in the conversion ring, matching a typical V4L2 queue depth.
+    pub const BUFFERS: usize = 4;
+
+    /// Allocate a converter producing `width`x`height` frames in `format`.
+    pub fn new(format: FrameFormat, width: u32, height: u32) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            imgmgr: ImageManager::new()?,
+            format,
+            width,
+            height,
+            crop: None,
+            images: Self::ring(format, width, height)?,
+            next: 0,
+        })
+    }
+
+    fn ring(format: FrameFormat, width: u32, height: u32) -> Result<Vec<Image>, Box<dyn Error>> {
+        (0..Self::BUFFERS)
+            .map(|_| Image::new(width, height, format.fourcc()))
+            .collect()
+    }
+
+    pub fn format(&self) -> FrameFormat {
+        self.format
+    }
+
+    /// The region frames are currently cropped to, if any.
+    pub fn crop(&self) -> Option<Rect> {
+        self.crop
+    }
+
+    /// The conversion this converter makes, for
+    /// [`Pipeline::share_conversion`].
+    pub fn target(&self) -> ConvertTarget {
+        let (width, height) = match &self.crop {
+            Some(rect) => (rect.width as u32, rect.height as u32),
+            None => (self.width, self.height),
+        };
+        ConvertTarget {
+            format: self.format.fourcc(),
+            width,
+            height,
+            rotation: Rotation::Rotation0,
+            crop: self.crop,
+        }
+    }
+
+    /// Crop every following frame to `crop`, given in pixels of the
+    /// full-size frame, or go back to full frames with `None`.
+    ///
+    /// The ring is reallocated at the new size, so this belongs between
+    /// frames when the region changes rather than on every frame. Consumers
+    /// holding fds of the old ring keep their buffers. On error the previous
+    /// crop stays in effect.
+    pub fn set_crop(&mut self, crop: Option<Rect>) -> Result<(), Box<dyn Error>> {
+        if crop == self.crop {
+            return Ok(());
+        }
+        let (width, height) = match &crop {
+            Some(rect) => {
+                check_crop(rect, self.width, self.height)?;
+                (rect.width as u32, rect.height as u32)
+            }
+            None => (self.width, self.height),
+        };
+        self.images = Self::ring(self.format, width, height)?;
+        self.next = 0;
+        self.crop = crop;
+        Ok(())
+    }
+
+    /// Returns `frame` converted to the target format and cropped, or
+    /// `None` when it is already in that format, no crop is set, and it can
+    /// be shared as is.
+    pub fn convert(
+        &mut self,
+        frame: &CapturedFrame,
+    ) -> Result<Option<CapturedFrame>, Box<dyn Error>> {
+        if self.crop.is_none() && frame.image.format() == self.format.fourcc() {
+            return Ok(None);
+        }
+
+        let target = &self.images[self.next];
+        self.next = (self.next + 1) % self.images.len();
+        self.imgmgr
+            .convert(&frame.image, target, self.crop, Rotation::Rotation0)?;
+
+        let image = target.try_clone()?;
+        Ok(Some(CapturedFrame {
+            fd: target.raw_fd(),
+            stride: image.stride(),
+            length: image.size() as u32,
+            image,
+            timestamp: frame.timestamp,
+            sequence: frame.sequence,
+        }))
+    }
+}
+
+/// Demosaics raw Bayer frames to RGBA on the CPU, so a raw sensor can feed
+/// the G2D conversions and encoders that cannot read Bayer formats.
+///
+/// The bit depth comes from each frame's format, while the colour filter
+/// layout is the configured `pattern`, since drivers do not always report
+/// it correctly (for example after a sensor flip). Output frames go to a
+/// ring of [`FormatConverter::BUFFERS`] RGBA buffers.
+pub struct Debayer {
+    pattern: BayerPattern,
+    images: Vec<Image>,
+    next: usize,
+}
+
+impl Debayer {
+    /// Allocate a debayer stage for `width`x`height` frames laid out in
+    /// `pattern`.
+    pub fn new(pattern: BayerPattern, width: u32, height: u32) -> Result<Self, Box<dyn Error>> {
+        if width < 2 || height < 2 {
+            return Err(format!("Bayer frames of {width}x{height} are smaller than 2x2").into());
+        }
+        Ok(Self {
+            pattern,
+            images: (0..FormatConverter::BUFFERS)
+                .map(|_| Image::new(width, height, RGBA))
+                .collect::<Result<_, _>>()?,
+            next: 0,
+        })
+    }
+
+    pub fn pattern(&self) -> BayerPattern {
+        self.pattern
+    }
+
+    /// Returns `frame` demosaiced to RGBA, or `None` when it is not a Bayer
+    /// frame and can be used as is.
+    pub fn convert(
+        &mut self,
+        frame: &CapturedFrame,
+    ) -> Result<Option<CapturedFrame>, Box<dyn Error>> {
+        let Some((_, bits)) = BayerPattern::from_fourcc(frame.image.format()) else {
+            return Ok(None);
+        };
+        let target = &self.images[self.next];
+        let (width, height) = (frame.image.width(), frame.image.height());
+        if (target.width(), target.height()) != (width, height) {
+            return Err(format!(
+                "Bayer frame is {width}x{height} but the debayer buffers are {}x{}",
+                target.width(),
+                target.height()
+            )
+            .into());
+        }
+        self.next = (self.next + 1) % self.images.len();
+
+        let src = frame.image.map()?;
+        let src = src.as_slice();
+        let row = width as usize * if bits == 8 { 1 } else { 2 };
+        let src_stride = (frame.stride as usize).max(row);
+        if src.len() < src_stride * (height as usize - 1) + row {
+            return Err(format!(
+                "Bayer frame of {} bytes is too short for {width}x{height} with stride {src_stride}",
+                src.len()
+            )
+            .into());
+        }
+        let mut dst = target.map()?;
+        cpu::bayer_to_rgba(
+            src,
+            src_stride,
+            dst.as_slice_mut(),
+            width as usize * 4,
+            width as usize,
+            height as usize,
+            self.pattern,
+            bits,
+        );
+
+        let image = target.try_clone()?;
+        Ok(Some(CapturedFrame {
+            fd: target.raw_fd(),
+            stride: width * 4,
+            length: image.size() as u32,
+            image,
+            timestamp: frame.timestamp,
+            sequence: frame.sequence,
+        }))
+    }
+}
+
+/// Removes lens distortion from YUYV or RGBA frames on the CPU with a
+/// [`RemapTable`], for cameras whose ISP does not undistort. Output frames
+/// keep the source format and go to a ring of [`FormatConverter::BUFFERS`]
+/// buffers.
+pub struct Rectify {
+    table: RemapTable,
+    format: FourCC,
+    images: Vec<Image>,
+    next: usize,
+}
+
+impl Rectify {
+    /// Allocate a rectify stage for `format` frames of the table's size.
+    pub fn new(table: RemapTable, format: FourCC) -> Result<Self, Box<dyn Error>> {
+        if format != YUYV && format != RGBA {
+            return Err(format!("cannot rectify {format} frames, only YUYV and RGBA").into());
+        }
+        let images = (0..FormatConverter::BUFFERS)
+            .map(|_| Image::new(table.width(), table.height(), format))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            table,
+            format,
+            images,
+            next: 0,
+        })
+    }
+
+    /// Returns `frame` with the lens distortion removed.
+    pub fn convert(&mut self, frame: &CapturedFrame) -> Result<CapturedFrame, Box<dyn Error>> {
+        let (width, height) = (frame.image.width(), frame.image.height());
+        if frame.image.format() != self.format
+            || (width, height) != (self.table.width(), self.table.height())
+        {
+            return Err(format!(
+                "cannot rectify a {width}x{height} {} frame with a {}x{} {} stage",
+                frame.image.format(),
+                self.table.width(),
+                self.table.height(),
+                self.format
+            )
+            .into());
+        }
+        let target = &self.images[self.next];
+        self.next = (self.next + 1) % self.images.len();
+
+        let bpp = if self.format == YUYV { 2 } else { 4 };
+        let row = width as usize * bpp;
+        let src_stride = (frame.stride as usize).max(row);
+        let src = frame.image.map()?;
+        let src = src.as_slice();
+        if src.len() < src_stride * (height as usize - 1) + row {
+            return Err(format!(
+                "frame of {} bytes is too short for {width}x{height} with stride {src_stride}",
+                src.len()
+            )
+            .into());
+        }
+        let mut dst = target.map()?;
+        if self.format == YUYV {
+            self.table
+                .remap_yuyv(src, src_stride, dst.as_slice_mut(), row);
+        } else {
+            self.table
+                .remap_rgba(src, src_stride, dst.as_slice_mut(), row);
+        }
+
+        let image = target.try_clone()?;
+        Ok(CapturedFrame {
+            fd: target.raw_fd(),
+            stride: row as u32,
+            length: image.size() as u32,
+            image,
+            timestamp: frame.timestamp,
+            sequence: frame.sequence,
+        })
+    }
+}
+
+/// Applies a [`ColorCorrection`] to RGBA frames on the CPU, for sensors
+/// whose colour the ISP does not correct. Output frames go to a ring of
+/// [`FormatConverter::BUFFERS`] buffers.
+pub struct ColorCorrect {
+    correction: ColorCorrection,
+    images: Vec<Image>,
+    next: usize,
+}
+
+impl ColorCorrect {
+    /// Allocate a colour correction stage for `width`x`height` RGBA frames.
+    pub fn new(
+        correction: ColorCorrection,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let images = (0..FormatConverter::BUFFERS)
+            .map(|_| Image::new(width, height, RGBA))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            correction,
+            images,
+            next: 0,
+        })
+    }
+
+    /// Returns `frame` with its colours corrected.
+    pub fn convert(&mut self, frame: &CapturedFrame) -> Result<CapturedFrame, Box<dyn Error>> {
+        let target = &self.images[self.next];
+        let (width, height) = (frame.image.width(), frame.image.height());
+        if frame.image.format() != RGBA || (width, height) != (target.width(), target.height()) {
+            return Err(format!(
+                "cannot colour correct a {width}x{height} {} frame with a {}x{} RGBA stage",
+                frame.image.format(),
+                target.width(),
+                target.height()
+            )
+            .into());
+        }
+        self.next = (self.next + 1) % self.images.len();
+
+        let row = width as usize * 4;
+        let src_stride = (frame.stride as usize).max(row);
+        let src = frame.image.map()?;
+        let src = src.as_slice();
+        if src.len() < src_stride * (height as usize - 1) + row {
+            return Err(format!(
+                "frame of {} bytes is too short for {width}x{height} with stride {src_stride}",
+                src.len()
+            )
+            .into());
+        }
+        let mut dst = target.map()?;
+        self.correction.apply_rgba(
+            src,
+            src_stride,
+            dst.as_slice_mut(),
+            row,
+            width as usize,
+            height as usize,
+        );
+
+        let image = target.try_clone()?;
+        Ok(CapturedFrame {
+            fd: target.raw_fd(),
+            stride: row as u32,
+            length: image.size() as u32,
+            image,
+            timestamp: frame.timestamp,
+            sequence: frame.sequence,
+        })
+    }
+}
+
+/// Checks that `crop` is non-empty, lies within a `width`x`height` frame,
+/// and starts and ends on even pixels so it never splits the chroma samples
+/// that YUYV and NV12 share between pixel pairs.
+pub fn check_crop(crop: &Rect, width: u32, height: u32) -> Result<(), Box<dyn Error>> {
+    let Rect {
+        x,
+        y,
+        width: w,
+        height: h,
+    } = *crop;
+    if w <= 0 || h <= 0 {
+        return Err(format!("crop {w}x{h} is empty").into());
+    }
+    if x < 0 || y < 0 || x as i64 + w as i64 > width as i64 || y as i64 + h as i64 > height as i64 {
+        return Err(format!(
+            "crop {w}x{h} at ({x}, {y}) does not fit in the {width}x{height} frame"
+        )
+        .into());
+    }
+    if [x, y, w, h].iter().any(|v| v % 2 != 0) {
+        return Err(format!("crop {w}x{h} at ({x}, {y}) must have even position and size").into());
+    }
+    Ok(())
+}
+
+/// Image an output converts frames into before encoding. Outputs with equal
+/// targets can share one conversion, see [`Pipeline::add_converted_sink`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConvertTarget {
+    pub format: FourCC,
+    /// Width after `rotation` is applied.
+    pub width: u32,
+    /// Height after `rotation` is applied.
+    pub height: u32,
+    pub rotation: Rotation,
+    /// Region of the source frame converted, the whole frame when `None`.
+    pub crop: Option<Rect>,
+}
+
+/// A shared conversion and the sinks fed from it.
+///
+/// Like [`FormatConverter`] it converts into a ring of images, here so an
+/// encoder still working on one frame is not overwritten by the next.
+struct ConvertStage {
+    target: ConvertTarget,
+    imgmgr: ImageManager,
+    images: Vec<Image>,
+    next: usize,
+    conversions: u64,
+    sinks: Vec<Sink>,
+}
+
+impl ConvertStage {
+    fn new(target: ConvertTarget) -> Result<Self, Box<dyn Error>> {
+        let images = (0..FormatConverter::BUFFERS)
+            .map(|_| Image::new(target.width, target.height, target.format))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            target,
+            imgmgr: ImageManager::new()?,
+            images,
+            next: 0,
+            conversions: 0,
+            sinks: Vec::new(),
+        })
+    }
+
+    fn convert(&mut self, frame: &CapturedFrame) -> Result<CapturedFrame, Box<dyn Error>> {
+        let target = &self.images[self.next];
+        self.next = (self.next + 1) % self.images.len();
+        self.imgmgr
+            .convert(&frame.image, target, self.target.crop, self.target.rotation)?;
+        self.conversions += 1;
+
+        let image = target.try_clone()?;
+        Ok(CapturedFrame {
+            fd: target.raw_fd(),
+            // Luma plane for NV12; the interleaved chroma plane below
+            // shares it.
+            stride: image.stride(),
+            length: image.size() as u32,
+            image,
+            timestamp: frame.timestamp,
+            sequence: frame.sequence,
+        })
+    }
+}
+
+/// Thins a frame stream down to a target rate, for outputs that do not
+/// need every captured frame.
+///
+/// Decisions are made on frame timestamps rather than arrival time, so the
+/// emitted frames keep their capture stamps and the rate is exact on
+/// average even when the source rate is not a multiple of the target: each
+/// accepted frame schedules the next one a whole interval later instead of
+/// an interval after itself.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Option<Duration>,
+}
+
+impl RateLimiter {
+    /// Limit to `fps` frames per second. Zero disables limiting.
+    pub fn new(fps: u32) -> Self {
+        let interval = match fps {
+            0 => Duration::ZERO,
+            fps => Duration::from_secs(1) / fps,
+        };
+        Self::every(interval)
+    }
+
+    /// Emit one frame per `interval`, for schedules slower than one frame
+    /// a second. A zero interval disables limiting.
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: None,
+        }
+    }
+
+    /// Returns `true` if the frame captured at `time` should be emitted.
+    pub fn ready(&mut self, time: Duration) -> bool {
+        let next = match self.next {
+            Some(next) => next,
+            None => {
+                self.next = Some(time + self.interval);
+                return true;
+            }
+        };
+        // Accept frames a little early so capture jitter does not push an
+        // emit to the following frame.
+        if time + self.interval / 10 < next {
+            return false;
+        }
+        // After a gap (or a timestamp jump) restart the schedule rather
+        // than emitting a burst to catch up.
+        self.next = Some(if time >= next + self.interval {
+            time + self.interval
+        } else {
+            next + self.interval
+        });
+        true
+    }
+
+    /// Convenience for [`RateLimiter::ready`] with a capture timestamp.
+    pub fn ready_at(&mut self, timestamp: &Timestamp) -> bool {
+        self.ready(Duration::new(
+            timestamp.seconds().max(0) as u64,
+            timestamp.subsec(9),
+        ))
+    }
+}
+
+/// Fills in for frames an output misses, following an [`ErrorFramePolicy`],
+/// so the output keeps its cadence while the camera stalls.
+///
+/// An output reports every frame it receives with [`ErrorFrames::received`]
+/// and waits at most [`ErrorFrames::timeout`] for the next one. When that
+/// runs out, [`ErrorFrames::fill`] gives the frame to emit instead: a copy
+/// of the last good frame (`freeze`) or a black frame (`black`), stamped
+/// one frame interval after the previous one. A frame is missed once it is
+/// half an interval late, and filled frames then follow at the frame rate
+/// until the camera recovers. Nothing is filled in before the first frame.
+///
+/// `freeze` copies every received frame with G2D, since camera buffers are
+/// recycled by the driver and a held buffer does not keep its content.
+pub struct ErrorFrames {
+    policy: ErrorFramePolicy,
+    interval: Duration,
+    last: Option<(Image, Timestamp)>,
+    due: Option<Instant>,
+    missed: u64,
+}
+
+impl ErrorFrames {
+    /// Fill in missed frames of a `fps` frames-per-second stream following
+    /// `policy`.
+    pub fn new(policy: ErrorFramePolicy, fps: u32) -> Self {
+        Self {
+            policy,
+            interval: Duration::from_secs(1) / fps.max(1),
+            last: None,
+            due: None,
+            missed: 0,
+        }
+    }
+
+    pub fn policy(&self) -> ErrorFramePolicy {
+        self.policy
+    }
+
+    /// Frames filled in since the last received frame.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// How long after `now` the next frame counts as missed, or `None` to
+    /// wait indefinitely (`gap`, or no frame received yet).
+    pub fn timeout(&self, now: Instant) -> Option<Duration> {
+        let due = self.due?;
+        Some((due + self.interval / 2).saturating_duration_since(now))
+    }
+
+    /// Note that `image`, captured at `timestamp`, arrived at `now`.
+    pub fn received(
+        &mut self,
+        imgmgr: &ImageManager,
+        image: &Image,
+        timestamp: Timestamp,
+        now: Instant,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.policy == ErrorFramePolicy::Gap {
+            return Ok(());
+        }
+        self.due = Some(now + self.interval);
+        self.missed = 0;
+        let reuse = self.last.take().filter(|(last, _)| {
+            (last.width(), last.height(), last.format())
+                == (image.width(), image.height(), image.format())
+        });
+        let last = match reuse {
+            Some((last, _)) => last,
+            None => {
+                let mut last = Image::new(image.width(), image.height(), image.format())?;
+                if self.policy == ErrorFramePolicy::Black {
+                    last.fill_black()?;
+                }
+                last
+            }
+        };
+        if self.policy == ErrorFramePolicy::Freeze {
+            imgmgr.convert(image, &last, None, Rotation::Rotation0)?;
+        }
+        self.last = Some((last, timestamp));
+        Ok(())
+    }
+
+    /// The frame to emit in place of the one that did not arrive, or `None`
+    /// when there is nothing to fill in.
+    pub fn fill(&mut self) -> Result<Option<(Image, Timestamp)>, Box<dyn Error>> {
+        let (Some(due), Some((last, timestamp))) = (self.due, self.last.as_mut()) else {
+            return Ok(None);
+        };
+        // Move on to the next slot first so a failed fill does not retry
+        // the same slot in a tight loop.
+        self.due = Some(due + self.interval);
+        self.missed += 1;
+        let nanos = timestamp.seconds() as i128 * 1_000_000_000
+            + timestamp.subsec(9) as i128
+            + self.interval.as_nanos() as i128;
+        *timestamp = Timestamp::new(
+            nanos.div_euclid(1_000_000_000) as i64,
+            nanos.rem_euclid(1_000_000_000) as u32,
+        );
+        Ok(Some((last.try_clone()?, *timestamp)))
+    }
+}
+
+/// Fires a callback when no frame has been fed for longer than a timeout.
+///
+/// The watchdog runs on its own thread so it still fires while the capture
+/// thread is blocked inside a read that never returns. After firing it
+/// re-arms, so a source that stays stalled is reported again one timeout
+/// later.
+pub struct Watchdog {
+    state: Arc<WatchdogState>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct WatchdogState {
+    timeout: Duration,
+    last_feed: Mutex<Instant>,
+    fired: AtomicBool,
+    fire_count: AtomicU64,
+    stop: AtomicBool,
+}
+
+impl Watchdog {
+    /// Start a watchdog that calls `on_stall` from its own thread whenever
+    /// `timeout` passes without a [`Watchdog::feed`].
+    pub fn spawn<F>(timeout: Duration, on_stall: F) -> io::Result<Self>
+    where
+        F: Fn() + Send + 'static,
+    {
+        let state = Arc::new(WatchdogState {
+            timeout,
+            last_feed: Mutex::new(Instant::now()),
+            fired: AtomicBool::new(false),
+            fire_count: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+        });
+
+        let poll = (timeout / 4).max(Duration::from_millis(1));
+        let shared = state.clone();
+        let thread = thread::Builder::new()
+            .name("watchdog".to_string())
+            .spawn(move || {
+                while !shared.stop.load(Ordering::SeqCst) {
+                    thread::sleep(poll);
+                    let mut last_feed = shared.last_feed.lock().unwrap();
+                    if last_feed.elapsed() < shared.timeout {
+                        continue;
+                    }
+                    *last_feed = Instant::now();
+                    drop(last_feed);
+
+                    shared.fired.store(true, Ordering::SeqCst);
+                    shared.fire_count.fetch_add(1, Ordering::SeqCst);
+                    on_stall();
+                }
+            })?;
+
+        Ok(Self {
+            state,
+            thread: Some(thread),
+        })
+    }
+
+    /// Record that a frame arrived, restarting the timeout and clearing a
+    /// pending stall.
+    pub fn feed(&self) {
+        *self.state.last_feed.lock().unwrap() = Instant::now();
+        self.state.fired.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns `true`, once, if the watchdog fired since the last feed.
+    pub fn take_fired(&self) -> bool {
+        self.state.fired.swap(false, Ordering::SeqCst)
+    }
+
+    /// Number of times the watchdog has fired.
+    pub fn fire_count(&self) -> u64 {
+        self.state.fire_count.load(Ordering::SeqCst)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.state.timeout
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.state.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Returns `true` if `err` is an interrupted system call, either as a bare
+/// [`io::Error`] or wrapped by videostream.
+pub fn is_interrupted(err: &(dyn Error + 'static)) -> bool {
+    if let Some(videostream::Error::Io(e)) = err.downcast_ref::<videostream::Error>() {
+        return e.kind() == io::ErrorKind::Interrupted;
+    }
+    matches!(err.downcast_ref::<io::Error>(), Some(e) if e.kind() == io::ErrorKind::Interrupted)
+}
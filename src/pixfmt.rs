@@ -0,0 +1,90 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Query the pixel format a V4L2 capture device negotiated.
+//!
+//! The node requests YUYV, but a driver is free to settle on another format
+//! it supports, such as MJPG on many USB cameras. Reading the format back
+//! with `VIDIOC_G_FMT` lets startup reject output combinations the
+//! negotiated format cannot feed, instead of failing later inside an
+//! encoder. Like [`framerate::query`](crate::framerate::query) this runs on
+//! a second handle to the device.
+
+use std::{fs::OpenOptions, io, os::fd::AsRawFd, path::Path};
+use videostream::fourcc::FourCC;
+
+/// `V4L2_BUF_TYPE_VIDEO_CAPTURE`
+const V4L2_BUF_TYPE_VIDEO_CAPTURE: u32 = 1;
+
+/// `VIDIOC_G_FMT`, i.e. `_IOWR('V', 4, struct v4l2_format)` on 64-bit.
+const VIDIOC_G_FMT: u64 = 0xc0d0_5604;
+
+/// Mirror of the kernel's `struct v4l2_pix_format`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct V4l2PixFormat {
+    width: u32,
+    height: u32,
+    pixelformat: u32,
+    field: u32,
+    bytesperline: u32,
+    sizeimage: u32,
+    colorspace: u32,
+    priv_: u32,
+    flags: u32,
+    ycbcr_enc: u32,
+    quantization: u32,
+    xfer_func: u32,
+}
+
+/// The `fmt` union of `struct v4l2_format`. Only `pix` is used; `raw`
+/// pads the union to the kernel's 200 bytes, and its `u64`s give it the
+/// 8-byte alignment the pointers in `struct v4l2_window` give the kernel's.
+#[repr(C)]
+union V4l2FormatUnion {
+    pix: V4l2PixFormat,
+    raw: [u64; 25],
+}
+
+/// Mirror of the kernel's `struct v4l2_format`.
+#[repr(C)]
+struct V4l2Format {
+    type_: u32,
+    fmt: V4l2FormatUnion,
+}
+
+/// Read the negotiated capture pixel format of `device`.
+pub fn query(device: &Path) -> io::Result<FourCC> {
+    let file = OpenOptions::new().read(true).write(true).open(device)?;
+
+    let mut format = V4l2Format {
+        type_: V4L2_BUF_TYPE_VIDEO_CAPTURE,
+        fmt: V4l2FormatUnion { raw: [0; 25] },
+    };
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), VIDIOC_G_FMT as _, &mut format) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let pix = unsafe { format.fmt.pix };
+    Ok(FourCC(pix.pixelformat.to_le_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v4l2_format_matches_kernel_layout() {
+        assert_eq!(std::mem::size_of::<V4l2PixFormat>(), 48);
+        assert_eq!(std::mem::size_of::<V4l2Format>(), 208);
+        assert_eq!(std::mem::offset_of!(V4l2Format, fmt), 8);
+    }
+
+    #[test]
+    #[ignore = "requires a V4L2 capture device at /dev/video3"]
+    fn query_camera_pixel_format() {
+        let format = query(Path::new("/dev/video3")).unwrap();
+        println!("pixel format: {format}");
+    }
+}
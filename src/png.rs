@@ -0,0 +1,222 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Minimal 16-bit greyscale PNG writer for depth frames.
+//!
+//! An 8-bit JPEG keeps only the high byte of a 16-bit depth sample, and the
+//! turbojpeg bindings offer no 12- or 16-bit compression. PNG holds 16-bit
+//! samples exactly and every image viewer and ROS tool decodes it, so the
+//! JPEG output publishes [`Y16`] frames as PNG instead.
+//!
+//! Only what a depth frame needs is supported: greyscale, 16 bits per
+//! sample, no interlacing. The image data is written as stored deflate
+//! blocks, so the file is about the size of the raw frame; the point is an
+//! exact container rather than a small one.
+//!
+//! ```
+//! use edgefirst_camera::png;
+//!
+//! // Two pixels, 0x1234 and 0xabcd, as little-endian Y16 samples.
+//! let png = png::encode_gray16(&[0x34, 0x12, 0xcd, 0xab], 2, 1).unwrap();
+//! assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+//! ```
+
+use std::{error::Error, io};
+
+use crate::{
+    checksum::crc32,
+    image::{Image, Y16},
+};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Largest payload of one stored deflate block.
+const STORED_BLOCK: usize = 65535;
+
+/// Adler-32 of `data`, the checksum that ends a zlib stream.
+fn adler32(data: &[u8]) -> u32 {
+    // 5552 bytes is the most that can be summed before `b` overflows.
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// A zlib stream holding non-empty `data` in stored (uncompressed) blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(STORED_BLOCK);
+    let mut out = Vec::with_capacity(2 + blocks * 5 + data.len() + 4);
+    // Deflate with a 32 KiB window, no preset dictionary, fastest level.
+    out.extend_from_slice(&[0x78, 0x01]);
+    let mut chunks = data.chunks(STORED_BLOCK).peekable();
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none() as u8;
+        let len = chunk.len() as u16;
+        out.push(last);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Encode `width`x`height` little-endian 16-bit samples, rows packed
+/// without padding, as a 16-bit greyscale PNG.
+pub fn encode_gray16(samples: &[u8], width: u32, height: u32) -> Result<Vec<u8>, Box<dyn Error>> {
+    let row = width as usize * 2;
+    let len = row * height as usize;
+    if width == 0 || height == 0 {
+        return Err(format!("cannot encode a {width}x{height} PNG").into());
+    }
+    let samples = samples.get(..len).ok_or_else(|| {
+        format!(
+            "{width}x{height} Y16 frame needs {len} bytes, got {}",
+            samples.len()
+        )
+    })?;
+
+    // Each row starts with filter type 0 (none); PNG samples are big-endian.
+    let mut raw = Vec::with_capacity(len + height as usize);
+    for line in samples.chunks_exact(row) {
+        raw.push(0);
+        raw.extend(line.chunks_exact(2).flat_map(|s| [s[1], s[0]]));
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 16 bits, greyscale, deflate, adaptive filtering, no interlace.
+    header.extend_from_slice(&[16, 0, 0, 0, 0]);
+
+    let idat = zlib_stored(&raw);
+    let mut png = Vec::with_capacity(SIGNATURE.len() + 3 * 12 + header.len() + idat.len());
+    png.extend_from_slice(SIGNATURE);
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &idat);
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+/// Encode a [`Y16`] image as PNG.
+///
+/// Takes the same arguments as
+/// [`encode_jpeg`](crate::image::encode_jpeg) so it can be passed to
+/// `MemoryMap::read`.
+///
+/// # Errors
+///
+/// Returns an error if image metadata is not provided, the image is not
+/// Y16, or `pix` is shorter than the frame.
+pub fn encode_image(pix: &[u8], img: Option<&Image>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let img =
+        img.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no image provided"))?;
+    if img.format() != Y16 {
+        return Err(format!("cannot encode a {} image as 16-bit PNG", img.format()).into());
+    }
+    encode_gray16(pix, img.width(), img.height())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decode a PNG written by [`encode_gray16`] back to little-endian
+    /// samples, checking every CRC and the Adler-32 on the way.
+    fn decode_gray16(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        assert_eq!(&png[..8], SIGNATURE);
+        let mut rest = &png[8..];
+        let (mut size, mut idat) = (None, Vec::new());
+        loop {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + len]);
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+            assert_eq!(crc, crc32(&rest[4..8 + len]), "CRC of {kind:?}");
+            rest = &rest[12 + len..];
+            match kind {
+                b"IHDR" => {
+                    assert_eq!(&data[8..], [16, 0, 0, 0, 0]);
+                    let width = u32::from_be_bytes(data[..4].try_into().unwrap());
+                    let height = u32::from_be_bytes(data[4..8].try_into().unwrap());
+                    size = Some((width, height));
+                }
+                b"IDAT" => idat.extend_from_slice(data),
+                b"IEND" => break,
+                _ => panic!("unexpected chunk {kind:?}"),
+            }
+        }
+        assert!(rest.is_empty());
+
+        assert_eq!(&idat[..2], [0x78, 0x01]);
+        let (mut stream, mut raw) = (&idat[2..], Vec::new());
+        loop {
+            let last = stream[0] & 1 == 1;
+            assert_eq!(stream[0] >> 1, 0, "only stored blocks are written");
+            let len = u16::from_le_bytes([stream[1], stream[2]]);
+            assert_eq!(!len, u16::from_le_bytes([stream[3], stream[4]]));
+            raw.extend_from_slice(&stream[5..5 + len as usize]);
+            stream = &stream[5 + len as usize..];
+            if last {
+                break;
+            }
+        }
+        assert_eq!(stream, adler32(&raw).to_be_bytes());
+
+        let (width, height) = size.unwrap();
+        let mut samples = Vec::new();
+        for line in raw.chunks_exact(width as usize * 2 + 1) {
+            assert_eq!(line[0], 0, "filter type");
+            samples.extend(line[1..].chunks_exact(2).flat_map(|s| [s[1], s[0]]));
+        }
+        assert_eq!(samples.len(), (width * height * 2) as usize);
+        (width, height, samples)
+    }
+
+    #[test]
+    fn y16_gradient_round_trips_with_low_byte() {
+        // Samples step by 7 and wrap, so every low byte value appears,
+        // and 256x300 spans several stored blocks.
+        let (width, height) = (256, 300);
+        let samples: Vec<u8> = (0..width * height)
+            .flat_map(|i| ((i * 7) as u16).to_le_bytes())
+            .collect();
+        let png = encode_gray16(&samples, width, height).unwrap();
+        assert!(png.len() > STORED_BLOCK * 2);
+
+        let (w, h, decoded) = decode_gray16(&png);
+        assert_eq!((w, h), (width, height));
+        assert_eq!(decoded, samples);
+        // The low bytes an 8-bit encoding would drop are all there.
+        assert!(decoded.iter().step_by(2).any(|&low| low != 0));
+    }
+
+    #[test]
+    fn adler32_matches_zlib() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        // Long enough to need the modulo inside the loop.
+        assert_eq!(adler32(&[0xff; 100_000]), 0x149a_302c);
+    }
+
+    #[test]
+    fn rejects_short_or_empty_frames() {
+        assert!(encode_gray16(&[0; 7], 2, 2).is_err());
+        assert!(encode_gray16(&[], 0, 2).is_err());
+        assert!(encode_gray16(&[0; 8], 2, 2).is_ok());
+    }
+}
@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! `--record` writer: the raw Annex-B `.h264` file plus a keyframe index.
+//!
+//! The bitstream has no container, so finding a point to start decoding
+//! means scanning for an IDR slice from the beginning of the file. Editors
+//! and long-recording tools want to jump straight to a time instead, so
+//! every keyframe is also listed in `<name>.idx`, a CSV file next to the
+//! recording:
+//!
+//! ```text
+//! stamp_ns,offset
+//! 1700000000000000000,0
+//! 1700000001000000000,524288
+//! ```
+//!
+//! `stamp_ns` is the keyframe's capture time in nanoseconds since the Unix
+//! epoch, the header stamp it was published with. `offset` is the byte
+//! offset of its access unit in the `.h264` file, which starts with the
+//! SPS and PPS, so decoding can begin there. Each line is written after
+//! the bitstream up to the keyframe is flushed, so after a power loss the
+//! index never points past the end of the recording.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Header line of the index file.
+const INDEX_HEADER: &str = "stamp_ns,offset";
+
+/// Derive the index path from an `.h264` path by swapping the extension to
+/// `.idx`, as [`Sidecar::paired_path`](crate::sidecar::Sidecar::paired_path)
+/// does for the sidecar.
+pub(crate) fn index_path(h264_path: &Path) -> PathBuf {
+    h264_path.with_extension("idx")
+}
+
+/// Writes encoded access units to the `.h264` file and their keyframes to
+/// the index.
+pub(crate) struct Recorder {
+    out: BufWriter<File>,
+    index: File,
+    /// Bytes of bitstream handed to `out` so far.
+    written: u64,
+}
+
+impl Recorder {
+    /// Create `h264_path` and its index, replacing earlier files.
+    pub(crate) fn create(h264_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::create(h264_path)
+            .map_err(|e| format!("Cannot create recording file {:?}: {e}", h264_path))?;
+        let path = index_path(h264_path);
+        let mut index = File::create(&path)
+            .map_err(|e| format!("Cannot create keyframe index {:?}: {e}", path))?;
+        writeln!(index, "{INDEX_HEADER}")?;
+        Ok(Self {
+            out: BufWriter::with_capacity(256 * 1024, file),
+            index,
+            written: 0,
+        })
+    }
+
+    /// Append one access unit captured at `stamp_ns`. A keyframe flushes
+    /// the bitstream and then adds its offset to the index.
+    pub(crate) fn write(&mut self, data: &[u8], is_key: bool, stamp_ns: u64) -> io::Result<()> {
+        let offset = self.written;
+        self.out.write_all(data)?;
+        self.written += data.len() as u64;
+        if is_key {
+            self.out.flush()?;
+            writeln!(self.index, "{stamp_ns},{offset}")?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use edgefirst_camera::nal;
+
+    /// One keyframe in the index.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct SeekPoint {
+        stamp_ns: u64,
+        offset: u64,
+    }
+
+    /// Read the index paired with `h264_path`.
+    fn load_index(h264_path: &Path) -> Result<Vec<SeekPoint>, Box<dyn Error>> {
+        let path = index_path(h264_path);
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Cannot open keyframe index {:?}: {e}", path))?;
+        let mut lines = text.lines();
+        if lines.next() != Some(INDEX_HEADER) {
+            return Err(format!(
+                "Keyframe index {:?} does not start with {INDEX_HEADER:?}",
+                path
+            )
+            .into());
+        }
+        lines
+            .enumerate()
+            .map(|(i, line)| {
+                let parsed = line
+                    .split_once(',')
+                    .and_then(|(stamp, offset)| Some((stamp.parse().ok()?, offset.parse().ok()?)));
+                let (stamp_ns, offset) = parsed
+                    .ok_or_else(|| format!("Keyframe index {:?} line {}: {line:?}", path, i + 2))?;
+                Ok(SeekPoint { stamp_ns, offset })
+            })
+            .collect()
+    }
+
+    /// A keyframe access unit (SPS, PPS, IDR slice) or a P frame, padded so
+    /// access units are not all the same size.
+    fn access_unit(key: bool, pad: usize) -> Vec<u8> {
+        let mut au = if key {
+            vec![
+                0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xce, 0, 0, 1, 0x65, 0x88,
+            ]
+        } else {
+            vec![0, 0, 0, 1, 0x41, 0x9a]
+        };
+        au.resize(au.len() + pad, 0xaa);
+        au
+    }
+
+    #[test]
+    fn index_points_at_keyframes() {
+        let path = std::env::temp_dir().join(format!(
+            "edgefirst_camera_index_{}.h264",
+            std::process::id()
+        ));
+        let mut recorder = Recorder::create(&path).unwrap();
+        // Four GOPs of five frames at 10 fps.
+        let mut expected = Vec::new();
+        let mut offset = 0;
+        for frame in 0..20u64 {
+            let key = frame % 5 == 0;
+            let au = access_unit(key, frame as usize * 3);
+            let stamp_ns = 1_700_000_000_000_000_000 + frame * 100_000_000;
+            if key {
+                expected.push(SeekPoint { stamp_ns, offset });
+            }
+            recorder.write(&au, key, stamp_ns).unwrap();
+            offset += au.len() as u64;
+        }
+        drop(recorder);
+
+        let index = load_index(&path).unwrap();
+        assert_eq!(index, expected);
+        let bitstream = std::fs::read(&path).unwrap();
+        assert_eq!(bitstream.len() as u64, offset);
+        let ends = index.iter().skip(1).map(|p| p.offset).chain([offset]);
+        for (point, end) in index.iter().zip(ends) {
+            // Each GOP starts with the parameter sets and its keyframe.
+            let gop = &bitstream[point.offset as usize..end as usize];
+            let first = nal::iter_nal_units(gop).next().unwrap();
+            assert!(gop.starts_with(&[0, 0, 0, 1]));
+            assert_eq!(nal::nal_type(first), nal::NAL_TYPE_SPS);
+            assert!(nal::is_keyframe(gop));
+        }
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(index_path(&path)).ok();
+    }
+}
@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Lens distortion correction on the CPU.
+//!
+//! The ISP normally undistorts frames itself, and the node then publishes
+//! zero distortion in `CameraInfo`. When the ISP is bypassed the frames
+//! keep the lens distortion described by the calibration, which consumers
+//! would have to remove on their own. A [`RemapTable`] removes it with the
+//! plumb_bob model: for every pixel of the undistorted output it holds the
+//! source pixel that the lens moved there. The table keeps the camera
+//! matrix, so the rectified image is described by the same `K` with zero
+//! distortion.
+//!
+//! Pixels are sampled from the nearest source pixel. Output pixels whose
+//! source lies outside the frame are black.
+//!
+//! ```
+//! use edgefirst_camera::rectify::RemapTable;
+//!
+//! let k = [100.0, 0.0, 2.0, 0.0, 100.0, 1.0, 0.0, 0.0, 1.0];
+//! let table = RemapTable::undistort(4, 2, &k, &[0.0; 5]).unwrap();
+//! assert!(table.is_identity());
+//! ```
+
+use std::error::Error;
+
+/// Marks an output pixel whose source lies outside the frame.
+const OUTSIDE: u32 = u32::MAX;
+
+/// Source pixel of every output pixel of an undistorted frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemapTable {
+    width: u32,
+    height: u32,
+    /// Row-major source pixel index per output pixel, or [`OUTSIDE`].
+    map: Vec<u32>,
+}
+
+impl RemapTable {
+    /// Build the table that undistorts `width`x`height` frames taken
+    /// through a lens with camera matrix `k` (row-major 3x3) and plumb_bob
+    /// coefficients `d` (`k1, k2, p1, p2[, k3]`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for an empty frame, a frame with more pixels than a
+    /// `u32` can index, a camera matrix without a positive focal length, or
+    /// a coefficient count other than 4 or 5.
+    pub fn undistort(
+        width: u32,
+        height: u32,
+        k: &[f64; 9],
+        d: &[f64],
+    ) -> Result<Self, Box<dyn Error>> {
+        if width == 0 || height == 0 || width as u64 * height as u64 >= OUTSIDE as u64 {
+            return Err(format!("cannot rectify a {width}x{height} frame").into());
+        }
+        let (fx, cx, fy, cy) = (k[0], k[2], k[4], k[5]);
+        if !(fx > 0.0 && fy > 0.0) {
+            return Err(format!("camera matrix has focal lengths {fx} and {fy}").into());
+        }
+        let (k1, k2, p1, p2, k3) = match *d {
+            [k1, k2, p1, p2] => (k1, k2, p1, p2, 0.0),
+            [k1, k2, p1, p2, k3] => (k1, k2, p1, p2, k3),
+            _ => {
+                return Err(format!(
+                    "plumb_bob needs 4 or 5 distortion coefficients, got {}",
+                    d.len()
+                )
+                .into())
+            }
+        };
+
+        let mut map = Vec::with_capacity(width as usize * height as usize);
+        for v in 0..height {
+            let y = (v as f64 - cy) / fy;
+            for u in 0..width {
+                let x = (u as f64 - cx) / fx;
+                let r2 = x * x + y * y;
+                let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+                let xd = x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+                let yd = y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+                let (su, sv) = ((fx * xd + cx).round(), (fy * yd + cy).round());
+                let inside =
+                    (0.0..width as f64).contains(&su) && (0.0..height as f64).contains(&sv);
+                map.push(if inside {
+                    sv as u32 * width + su as u32
+                } else {
+                    OUTSIDE
+                });
+            }
+        }
+        Ok(Self { width, height, map })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns `true` if every output pixel is its own source, as with zero
+    /// distortion, so remapping would copy the frame unchanged.
+    pub fn is_identity(&self) -> bool {
+        self.map.iter().enumerate().all(|(i, &s)| s == i as u32)
+    }
+
+    /// Undistort an RGBA frame. Pixels from outside the frame are opaque
+    /// black.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either buffer is too small for the table's frame size.
+    pub fn remap_rgba(&self, src: &[u8], src_stride: usize, dst: &mut [u8], dst_stride: usize) {
+        let width = self.width as usize;
+        for (row, map) in self.map.chunks_exact(width).enumerate() {
+            let out = &mut dst[row * dst_stride..][..width * 4];
+            for (px, &s) in out.chunks_exact_mut(4).zip(map) {
+                if s == OUTSIDE {
+                    px.copy_from_slice(&[0, 0, 0, 255]);
+                    continue;
+                }
+                let (su, sv) = (s as usize % width, s as usize / width);
+                px.copy_from_slice(&src[sv * src_stride + su * 4..][..4]);
+            }
+        }
+    }
+
+    /// Undistort a YUYV frame. Each output pixel takes the luma of its
+    /// source pixel; each output pair takes the chroma of the pair its left
+    /// pixel comes from. Pixels from outside the frame are black.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the width is odd or either buffer is too small for the
+    /// table's frame size.
+    pub fn remap_yuyv(&self, src: &[u8], src_stride: usize, dst: &mut [u8], dst_stride: usize) {
+        let width = self.width as usize;
+        assert!(width.is_multiple_of(2), "YUYV frames have an even width");
+        let source = |s: u32| (s as usize / width) * src_stride + (s as usize % width) * 2;
+        for (row, map) in self.map.chunks_exact(width).enumerate() {
+            let out = &mut dst[row * dst_stride..][..width * 2];
+            for (pair, sources) in out.chunks_exact_mut(4).zip(map.chunks_exact(2)) {
+                let luma = |s: u32| if s == OUTSIDE { 16 } else { src[source(s)] };
+                let (u, v) = match sources[0] {
+                    OUTSIDE => (128, 128),
+                    s => {
+                        let base = source(s) & !3;
+                        (src[base + 1], src[base + 3])
+                    }
+                };
+                pair.copy_from_slice(&[luma(sources[0]), u, luma(sources[1]), v]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const K: [f64; 9] = [40.0, 0.0, 16.0, 0.0, 40.0, 12.0, 0.0, 0.0, 1.0];
+    const PINCUSHION: [f64; 5] = [0.3, 0.1, 0.0, 0.0, 0.0];
+
+    /// A 32x24 RGBA frame whose pixels all differ.
+    fn rgba_pattern() -> Vec<u8> {
+        (0..32 * 24u32)
+            .flat_map(|i| [i as u8, (i >> 8) as u8, (i * 7) as u8, 255])
+            .collect()
+    }
+
+    #[test]
+    fn zero_distortion_is_a_no_op() {
+        let table = RemapTable::undistort(32, 24, &K, &[0.0; 5]).unwrap();
+        assert!(table.is_identity());
+
+        let src = rgba_pattern();
+        let mut dst = vec![0; src.len()];
+        table.remap_rgba(&src, 32 * 4, &mut dst, 32 * 4);
+        assert_eq!(dst, src);
+
+        let yuyv: Vec<u8> = (0..32 * 24 * 2u32).map(|i| (i * 13) as u8).collect();
+        let mut dst = vec![0; yuyv.len()];
+        table.remap_yuyv(&yuyv, 32 * 2, &mut dst, 32 * 2);
+        assert_eq!(dst, yuyv);
+    }
+
+    #[test]
+    fn distortion_moves_pixels_but_not_the_centre() {
+        let table = RemapTable::undistort(32, 24, &K, &PINCUSHION).unwrap();
+        assert!(!table.is_identity());
+
+        let src = rgba_pattern();
+        let mut dst = vec![0; src.len()];
+        table.remap_rgba(&src, 32 * 4, &mut dst, 32 * 4);
+        assert_ne!(dst, src);
+        // The principal point stays put; the corners are pulled in from
+        // further out, beyond the frame.
+        let centre = (12 * 32 + 16) * 4;
+        assert_eq!(dst[centre..centre + 4], src[centre..centre + 4]);
+        assert_eq!(dst[..4], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn yuyv_keeps_pairs_of_chroma() {
+        let table = RemapTable::undistort(32, 24, &K, &PINCUSHION).unwrap();
+        let src: Vec<u8> = (0..32 * 24 * 2u32).map(|i| (i * 13) as u8).collect();
+        let mut dst = vec![0; src.len()];
+        table.remap_yuyv(&src, 32 * 2, &mut dst, 32 * 2);
+        assert_ne!(dst, src);
+        // Black outside the frame.
+        assert_eq!(dst[..4], [16, 128, 16, 128]);
+        // Each output pair carries the chroma of the pair its left pixel
+        // comes from.
+        for (pair, sources) in dst.chunks_exact(4).zip(table.map.chunks_exact(2)) {
+            if sources[0] != OUTSIDE {
+                let base = (sources[0] as usize * 2) & !3;
+                assert_eq!([pair[1], pair[3]], [src[base + 1], src[base + 3]]);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_bad_calibration() {
+        assert!(RemapTable::undistort(0, 24, &K, &PINCUSHION).is_err());
+        assert!(RemapTable::undistort(32, 24, &[0.0; 9], &PINCUSHION).is_err());
+        assert!(RemapTable::undistort(32, 24, &K, &[0.1; 8]).is_err());
+        assert!(RemapTable::undistort(32, 24, &K, &PINCUSHION[..4]).is_ok());
+    }
+}
@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Importing frames published by a camera node.
+//!
+//! The node shares each frame as an `edgefirst_msgs/CameraFrame` whose plane
+//! carries a DMA-BUF fd that is only valid in the publishing process.
+//! [`decode_dma_message`] turns such a message back into an [`Image`] in one
+//! call. Buffers from another process are imported with `pidfd_getfd(2)`,
+//! which needs Linux 5.6+ and ptrace access to the publisher: both processes
+//! running as the same user with `kernel.yama.ptrace_scope` at 0, or
+//! `CAP_SYS_PTRACE`.
+//!
+//! ```no_run
+//! use edgefirst_camera::remote::decode_dma_message;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let payload: Vec<u8> = Vec::new();
+//! // `payload` is a sample received on the `camera/frame` topic.
+//! let image = decode_dma_message(&payload, std::process::id())?;
+//! println!("received {image}");
+//! # Ok(())
+//! # }
+//! ```
+
+use edgefirst_schemas::{builtin_interfaces, edgefirst_msgs::CameraFrame};
+use std::{
+    error::Error,
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+use videostream::fourcc::FourCC;
+
+use crate::image::{check_dimensions, Image};
+
+/// The fields of a received `CameraFrame` needed to import its buffer.
+#[derive(Clone, Debug)]
+pub struct RemoteFrame {
+    pub stamp: builtin_interfaces::Time,
+    pub seq: u64,
+    pub pid: u32,
+    pub width: u32,
+    pub height: u32,
+    pub format: FourCC,
+    /// Plane 0 file descriptor, valid in the publisher's process.
+    pub fd: i32,
+    pub stride: u32,
+    pub size: u32,
+}
+
+/// Decode a CDR `CameraFrame`. Only single-plane frames, as published by
+/// the camera node, are supported.
+pub fn decode_camera_frame(bytes: &[u8]) -> Result<RemoteFrame, Box<dyn Error>> {
+    let msg = CameraFrame::from_cdr(bytes)?;
+    let planes = msg.planes();
+    let plane = match planes.first() {
+        Some(plane) => plane,
+        None => return Err("CameraFrame has no planes".into()),
+    };
+    if plane.fd < 0 {
+        return Err("CameraFrame plane carries no DMA-BUF fd".into());
+    }
+
+    let format: [u8; 4] = msg
+        .format()
+        .as_bytes()
+        .try_into()
+        .map_err(|_| format!("unsupported CameraFrame format '{}'", msg.format()))?;
+
+    Ok(RemoteFrame {
+        stamp: msg.stamp(),
+        seq: msg.seq(),
+        pid: msg.pid(),
+        width: msg.width(),
+        height: msg.height(),
+        format: FourCC(format),
+        fd: plane.fd,
+        stride: plane.stride,
+        size: plane.size,
+    })
+}
+
+/// Duplicate file descriptor `fd` of process `pid` into this process with
+/// `pidfd_getfd(2)`.
+pub fn import_fd(pid: u32, fd: i32) -> io::Result<OwnedFd> {
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if pidfd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let pidfd = unsafe { OwnedFd::from_raw_fd(pidfd as i32) };
+
+    let ret = unsafe { libc::syscall(libc::SYS_pidfd_getfd, pidfd.as_raw_fd(), fd, 0) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(ret as i32) })
+}
+
+/// Decode a CDR `CameraFrame` into an [`Image`] on its DMA-BUF, see
+/// [`import_camera_frame`].
+///
+/// # Errors
+///
+/// Returns an error if the message does not decode or
+/// [`import_camera_frame`] fails.
+pub fn decode_dma_message(bytes: &[u8], my_pid: u32) -> Result<Image, Box<dyn Error>> {
+    import_camera_frame(&decode_camera_frame(bytes)?, my_pid)
+}
+
+/// Import the buffer of a decoded `CameraFrame` as an [`Image`] with the
+/// frame's row stride, which the publisher may pad beyond the packed row.
+///
+/// `my_pid` is the caller's process id. Frames published by the same
+/// process duplicate the fd directly; anything else goes through
+/// [`import_fd`]. The returned image owns its own fd, so it stays valid after
+/// the publisher recycles the buffer number.
+///
+/// # Errors
+///
+/// Returns an error if the frame claims a zero size
+/// ([`CameraError::InvalidDimensions`](crate::image::CameraError)), names a
+/// format [`Image`] does not support, has a stride shorter than the packed
+/// row, or the fd cannot be imported.
+pub fn import_camera_frame(frame: &RemoteFrame, my_pid: u32) -> Result<Image, Box<dyn Error>> {
+    check_dimensions(frame.width, frame.height)?;
+    let fd = if frame.pid == my_pid {
+        dup_fd(frame.fd)
+    } else {
+        import_fd(frame.pid, frame.fd)
+    }
+    .map_err(|e| format!("Cannot import fd {} from pid {}: {e}", frame.fd, frame.pid))?;
+    Image::new_preallocated_with_stride(fd, frame.width, frame.height, frame.format, frame.stride)
+        .map_err(|e| format!("CameraFrame {e}").into())
+}
+
+/// Duplicate `fd` of this process. Uses `fcntl` rather than a `BorrowedFd`
+/// because a stale message may name an fd that is no longer open.
+fn dup_fd(fd: i32) -> io::Result<OwnedFd> {
+    let ret = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(ret) })
+}
@@ -26,6 +26,9 @@ fn zerr<E: std::fmt::Display>(e: E) -> Box<dyn Error> {
     e.to_string().into()
 }
 
+use edgefirst_camera::nal::{
+    find_start_code, is_keyframe, next_nal_unit_len, starts_with_start_code,
+};
 use tracing::{info, info_span, warn};
 use videostream::{
     decoder::{DecodeReturnCode, Decoder, DecoderCodec},
@@ -38,8 +41,11 @@ use zenoh::{
 };
 
 use crate::{
-    args::Args, build_camera_frame_msg, build_h264_msg, sidecar::Sidecar, timestamp,
-    zenoh_ts_from_ros_time, CameraInfoFields, TfStaticFields, SATURATED_TIME, SHUTDOWN,
+    args::{Args, Codec},
+    build_camera_frame_msg, build_h264_meta_msg, build_h264_msg, build_tf_static_msgs,
+    sidecar::Sidecar,
+    tf_static, timestamp, zenoh_ts_from_ros_time, CameraInfoFields, H264FrameMeta, SATURATED_TIME,
+    SHUTDOWN,
 };
 
 /// Read-chunk size for pulling Annex-B bytes off disk. Matches the
@@ -72,7 +78,6 @@ pub(crate) async fn run_replay(session: Session, args: Args) -> Result<(), Box<d
     // rather than CLI defaults; we build the CDR payloads once here and
     // reuse the bytes per publish, same pattern as the live path.
     let info_fields: CameraInfoFields = sidecar.camera_info.clone();
-    let tf_fields: TfStaticFields = sidecar.tf_static.clone();
 
     let publ_info = session
         .declare_publisher(args.info_topic.clone())
@@ -83,12 +88,14 @@ pub(crate) async fn run_replay(session: Session, args: Args) -> Result<(), Box<d
 
     // tf_static runs on its own loop exactly like the live path.
     let tf_session = session.clone();
-    let tf_bytes = ZBytes::from(tf_fields.build_msg()?.into_cdr());
+    let tf_msgs =
+        build_tf_static_msgs(std::iter::once(&sidecar.tf_static).chain(&sidecar.tf_static_links))?;
     let tf_enc = Encoding::APPLICATION_CDR.with_schema("geometry_msgs/msg/TransformStamped");
-    let tf_task = tokio::spawn(async move { tf_static_loop(tf_session, tf_bytes, tf_enc).await });
+    let tf_topic = args.tf_static_topic();
+    let tf_task =
+        tokio::spawn(async move { tf_static(tf_session, tf_topic, tf_msgs, tf_enc).await });
     std::mem::drop(tf_task);
 
-    let info_bytes = ZBytes::from(info_fields.build_msg()?.into_cdr());
     let info_enc = Encoding::APPLICATION_CDR.with_schema("sensor_msgs/msg/CameraInfo");
 
     // Replay always forwards the recorded Annex-B verbatim on
@@ -103,6 +110,12 @@ pub(crate) async fn run_replay(session: Session, args: Args) -> Result<(), Box<d
         .congestion_control(CongestionControl::Drop)
         .await
         .map_err(zerr)?;
+    let publ_h264_meta = session
+        .declare_publisher(args.h264_meta_topic.clone())
+        .priority(Priority::Data)
+        .congestion_control(CongestionControl::Drop)
+        .await
+        .map_err(zerr)?;
 
     // Decoder + file reader
     let fps = args.replay_fps.unwrap_or(sidecar.fps).max(1);
@@ -128,6 +141,8 @@ pub(crate) async fn run_replay(session: Session, args: Args) -> Result<(), Box<d
         tokio::time::interval(Duration::from_micros(1_000_000 / u64::from(interval_fps)));
     let mut carry = Carry::new();
     let mut last_data: Vec<u8> = Vec::with_capacity(READ_CHUNK);
+    // Reframed last_data for --h264-framing, reused across frames.
+    let mut framing = Vec::new();
     let mut seq: u64 = 0;
     let src_pid = std::process::id();
 
@@ -274,10 +289,12 @@ pub(crate) async fn run_replay(session: Session, args: Args) -> Result<(), Box<d
             &session,
             &publ_info,
             &publ_h264,
-            &info_bytes,
+            &publ_h264_meta,
+            &info_fields,
             &info_enc,
             &frame,
             &last_data,
+            &mut framing,
             stamp,
             src_pid,
             seq,
@@ -348,92 +365,17 @@ impl Carry {
     }
 }
 
-/// Length of the leading NAL unit in `buf`, terminated by the *next*
-/// NAL start code (Annex-B `00 00 01` or `00 00 00 01`), or `None` if
-/// either no terminating start code is present yet in `buf` or `buf`
-/// is not start-code-aligned. The caller distinguishes the two cases
-/// via [`starts_with_start_code`] / [`find_start_code`].
-///
-/// V4L2 decoders expect one access unit per queued OUTPUT buffer.
-/// Feeding a 256 KiB chunk that spans multiple frames is what caused
-/// the "no OUTPUT buffer available" deadlock observed on i.MX8MP —
-/// the hardware never produced any CAPTURE frames because it could
-/// not partition the input. This scanner lets the replay loop feed
-/// one NAL per `decode_frame` call.
-fn next_nal_unit_len(buf: &[u8]) -> Option<usize> {
-    // Every recorded frame starts with a 3- or 4-byte start code
-    // (00 00 01 or 00 00 00 01) at offset 0. Skip past that and the
-    // next NAL header byte before scanning, otherwise the scanner
-    // would trigger on the leading start code itself (its tail
-    // 00 00 01 matches the 3-byte form starting at offset 1).
-    let leading_sc_len = leading_start_code_len(buf)?;
-
-    let mut i = leading_sc_len + 1;
-    while i + 2 < buf.len() {
-        if buf[i] == 0 && buf[i + 1] == 0 {
-            // Accept both the 3-byte (00 00 01) and 4-byte
-            // (00 00 00 01) forms.
-            if buf[i + 2] == 1 {
-                return Some(i);
-            }
-            if buf[i + 2] == 0 && i + 3 < buf.len() && buf[i + 3] == 1 {
-                return Some(i);
-            }
-        }
-        i += 1;
-    }
-    None
-}
-
-/// Length of the Annex-B start code at the very front of `buf`, or
-/// `None` if there isn't one. Returns 3 for `00 00 01`, 4 for
-/// `00 00 00 01`. Used to keep [`next_nal_unit_len`] and the resync
-/// path agreeing on what counts as alignment.
-#[inline]
-fn leading_start_code_len(buf: &[u8]) -> Option<usize> {
-    if buf.len() >= 4 && buf[0] == 0 && buf[1] == 0 && buf[2] == 0 && buf[3] == 1 {
-        Some(4)
-    } else if buf.len() >= 3 && buf[0] == 0 && buf[1] == 0 && buf[2] == 1 {
-        Some(3)
-    } else {
-        None
-    }
-}
-
-#[inline]
-fn starts_with_start_code(buf: &[u8]) -> bool {
-    leading_start_code_len(buf).is_some()
-}
-
-/// Find the offset of the next Annex-B start code anywhere in `buf`
-/// (3- or 4-byte form). Returns `None` if the buffer contains no full
-/// start code. Used by the resync path to skip past garbage between
-/// NAL units after a decoder error or a corrupted prefix.
-fn find_start_code(buf: &[u8]) -> Option<usize> {
-    let mut i = 0;
-    while i + 2 < buf.len() {
-        if buf[i] == 0 && buf[i + 1] == 0 {
-            if buf[i + 2] == 1 {
-                return Some(i);
-            }
-            if buf[i + 2] == 0 && i + 3 < buf.len() && buf[i + 3] == 1 {
-                return Some(i);
-            }
-        }
-        i += 1;
-    }
-    None
-}
-
 #[allow(clippy::too_many_arguments)]
 async fn publish_replayed_frame(
     session: &Session,
     publ_info: &zenoh::pubsub::Publisher<'_>,
     publ_h264: &zenoh::pubsub::Publisher<'_>,
-    info_bytes: &ZBytes,
+    publ_h264_meta: &zenoh::pubsub::Publisher<'_>,
+    info_fields: &CameraInfoFields,
     info_enc: &Encoding,
     frame: &Frame,
     h264_bytes: &[u8],
+    framing: &mut Vec<u8>,
     stamp: edgefirst_schemas::builtin_interfaces::Time,
     src_pid: u32,
     seq: u64,
@@ -454,7 +396,7 @@ async fn publish_replayed_frame(
     // camera/frame
     let (frame_msg, frame_enc) = build_camera_frame_msg(
         stamp,
-        &args.camera_frame_id,
+        &args.message_frame_id(stamp.sec, stamp.nanosec, seq),
         seq,
         src_pid,
         width,
@@ -463,6 +405,7 @@ async fn publish_replayed_frame(
         fd,
         stride,
         length,
+        length,
         &sidecar.colorimetry,
     )?;
     session
@@ -474,25 +417,48 @@ async fn publish_replayed_frame(
         .await
         .map_err(zerr)?;
 
-    // rt/camera/info — same content every frame, same cadence as the live path.
+    // rt/camera/info — same content every frame, same cadence and stamp as
+    // the live path.
     publ_info
-        .put(info_bytes.clone())
+        .put(ZBytes::from(info_fields.build_msg(stamp)?.into_cdr()))
         .encoding(info_enc.clone())
-        .timestamp(session.new_timestamp())
+        .timestamp(sample_ts)
         .await
         .map_err(zerr)?;
 
-    // rt/camera/h264 — forward the Annex-B bytes verbatim. We have them
-    // in h264_bytes because the replay loop collected every byte the
-    // decoder consumed for this frame.
+    // rt/camera/h264 — forward the Annex-B bytes verbatim, or reframed
+    // for --h264-framing. We have them in h264_bytes because the replay
+    // loop collected every byte the decoder consumed for this frame.
     if !h264_bytes.is_empty() {
-        let (msg, enc) = build_h264_msg(h264_bytes, stamp, &args.camera_frame_id)?;
+        let frame_id = args.message_frame_id(stamp.sec, stamp.nanosec, seq);
+        let framed = args.h264_framing.apply(h264_bytes, framing);
+        let (msg, enc) = build_h264_msg(framed, Codec::H264, stamp, &frame_id)?;
         publ_h264
             .put(msg)
             .encoding(enc)
             .timestamp(sample_ts)
             .await
             .map_err(zerr)?;
+
+        let meta = H264FrameMeta {
+            sec: stamp.sec,
+            nanosec: stamp.nanosec,
+            frame_id: args.camera_frame_id.clone(),
+            sequence: seq,
+            width,
+            height,
+            is_keyframe: is_keyframe(h264_bytes),
+            size: framed.len(),
+            framing: args.h264_framing,
+            sensor: None,
+        };
+        let (msg, enc) = build_h264_meta_msg(&meta)?;
+        publ_h264_meta
+            .put(msg)
+            .encoding(enc)
+            .timestamp(sample_ts)
+            .await
+            .map_err(zerr)?;
     }
 
     Ok(())
@@ -517,6 +483,12 @@ fn warn_on_sidecar_overrides(args: &Args, sidecar: &Sidecar) {
             args.cam_info_path
         );
     }
+    if let Some(path) = args.tf_config.as_ref() {
+        warn!(
+            "--tf-config {:?} is ignored in replay mode; the sidecar's tf_static_links are used",
+            path
+        );
+    }
     if args.base_frame_id != sidecar.tf_static.base_frame_id {
         warn!(
             "--base-frame-id {:?} differs from sidecar tf_static.base_frame_id {:?}; using the sidecar value",
@@ -549,116 +521,3 @@ fn warn_on_sidecar_overrides(args: &Args, sidecar: &Sidecar) {
         );
     }
 }
-
-async fn tf_static_loop(
-    session: Session,
-    msg: ZBytes,
-    enc: Encoding,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let topic = "rt/tf_static".to_string();
-    let mut interval = tokio::time::interval(Duration::from_secs(1));
-    loop {
-        interval.tick().await;
-        session
-            .put(&topic, msg.clone())
-            .encoding(enc.clone())
-            .timestamp(session.new_timestamp())
-            .await?;
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::{find_start_code, next_nal_unit_len, starts_with_start_code};
-
-    #[test]
-    fn returns_none_for_short_input() {
-        assert_eq!(next_nal_unit_len(&[0, 0, 0]), None);
-        assert_eq!(next_nal_unit_len(&[]), None);
-    }
-
-    #[test]
-    fn returns_none_when_only_one_start_code_present() {
-        // Single 4-byte start code + NAL bytes, no terminator yet.
-        let buf = [0, 0, 0, 1, 0x67, 0xaa, 0xbb, 0xcc];
-        assert_eq!(next_nal_unit_len(&buf), None);
-    }
-
-    #[test]
-    fn finds_next_4byte_start_code() {
-        // First NAL starts at 0 (4-byte SC), second NAL starts at 7
-        // (4-byte SC).  Expected len = 7.
-        let mut buf = Vec::new();
-        buf.extend_from_slice(&[0, 0, 0, 1, 0x67, 0x42, 0xe0]); // NAL #1 (7 bytes incl SC)
-        buf.extend_from_slice(&[0, 0, 0, 1, 0x28, 0xce]); // NAL #2
-        assert_eq!(next_nal_unit_len(&buf), Some(7));
-    }
-
-    #[test]
-    fn finds_next_3byte_start_code() {
-        // First NAL starts with 4-byte SC, second with 3-byte SC.
-        let mut buf = Vec::new();
-        buf.extend_from_slice(&[0, 0, 0, 1, 0x67, 0x42]); // NAL #1
-        buf.extend_from_slice(&[0, 0, 1, 0x28, 0xce]); // NAL #2 (3-byte SC)
-        assert_eq!(next_nal_unit_len(&buf), Some(6));
-    }
-
-    #[test]
-    fn scan_skips_leading_zeros_inside_nal() {
-        // Valid NAL payload may contain 00 00 sequences that are not
-        // start codes (emulation-prevention bytes ensure they're
-        // never followed by 01 inside a NAL). Here we have
-        // 00 00 03 01 inside NAL #1 — scanner must treat the 03 as
-        // not-a-start-code and keep scanning to the real boundary.
-        let mut buf = Vec::new();
-        buf.extend_from_slice(&[0, 0, 0, 1, 0x65, 0x00, 0x00, 0x03, 0x01]); // NAL #1 (9 bytes)
-        buf.extend_from_slice(&[0, 0, 0, 1, 0x28]); // NAL #2
-        assert_eq!(next_nal_unit_len(&buf), Some(9));
-    }
-
-    #[test]
-    fn starts_with_start_code_recognizes_both_forms() {
-        assert!(starts_with_start_code(&[0, 0, 1, 0x67]));
-        assert!(starts_with_start_code(&[0, 0, 0, 1, 0x67]));
-        assert!(!starts_with_start_code(&[0, 0, 2, 0x67]));
-        assert!(!starts_with_start_code(&[0xff, 0, 0, 1]));
-        assert!(!starts_with_start_code(&[]));
-    }
-
-    #[test]
-    fn find_start_code_reports_offset_in_garbage() {
-        // 5 bytes of garbage, then a 4-byte SC.
-        let buf = [0xab, 0xcd, 0xef, 0x12, 0x34, 0, 0, 0, 1, 0x67];
-        assert_eq!(find_start_code(&buf), Some(5));
-    }
-
-    #[test]
-    fn find_start_code_returns_none_on_pure_garbage() {
-        let buf = [0xff; 32];
-        assert_eq!(find_start_code(&buf), None);
-    }
-
-    #[test]
-    fn find_start_code_handles_zero_runs_without_one() {
-        // Long run of zeroes without a terminating 0x01 must not be
-        // treated as a start code.
-        let buf = [0u8; 16];
-        assert_eq!(find_start_code(&buf), None);
-    }
-
-    #[test]
-    fn find_start_code_skips_to_3byte_form() {
-        // Garbage prefix + 3-byte SC.
-        let buf = [0xaa, 0xbb, 0, 0, 1, 0x67];
-        assert_eq!(find_start_code(&buf), Some(2));
-    }
-
-    #[test]
-    fn next_nal_unit_len_rejects_unaligned_input() {
-        // No leading start code → caller must resync.
-        let buf = [0xab, 0xcd, 0, 0, 0, 1, 0x67, 0, 0, 0, 1, 0x28];
-        assert_eq!(next_nal_unit_len(&buf), None);
-        // ...but find_start_code still locates the next SC for resync.
-        assert_eq!(find_start_code(&buf), Some(2));
-    }
-}
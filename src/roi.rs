@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Runtime crop control for the DMA output.
+//!
+//! With `--dma-crop-topic` the node subscribes to a control topic and crops
+//! the published `CameraFrame` buffers to the latest region received on it.
+//! Each message is a JSON object in full-frame pixels:
+//!
+//! ```json
+//! { "x": 640, "y": 360, "width": 640, "height": 360 }
+//! ```
+//!
+//! An empty payload or `null` goes back to the full frame. The region must
+//! fit in the camera frame and have even position and size; a rejected
+//! region is logged and the previous one stays in effect. The encoder
+//! outputs are not affected.
+
+use edgefirst_camera::image::Rect;
+use serde::Deserialize;
+use std::error::Error;
+use tracing::warn;
+use zenoh::{handlers::FifoChannelHandler, pubsub::Subscriber, sample::Sample, Session};
+
+/// A crop region as sent on the control topic.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CropRequest {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Decode a control message: `Some` region to crop to, or `None` to go back
+/// to full frames.
+pub fn parse_crop(payload: &[u8]) -> Result<Option<Rect>, Box<dyn Error>> {
+    if payload.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    let request: Option<CropRequest> = serde_json::from_slice(payload)?;
+    let Some(request) = request else {
+        return Ok(None);
+    };
+    let field = |v: u32| i32::try_from(v).map_err(|_| format!("crop value {v} is too large"));
+    Ok(Some(Rect {
+        x: field(request.x)?,
+        y: field(request.y)?,
+        width: field(request.width)?,
+        height: field(request.height)?,
+    }))
+}
+
+/// Subscription to the crop control topic.
+pub struct CropControl {
+    subscriber: Subscriber<FifoChannelHandler<Sample>>,
+}
+
+impl CropControl {
+    pub async fn subscribe(session: &Session, topic: &str) -> Result<Self, Box<dyn Error>> {
+        let subscriber = session
+            .declare_subscriber(topic.to_string())
+            .await
+            .map_err(|e| format!("Cannot subscribe to {topic}: {e}"))?;
+        Ok(Self { subscriber })
+    }
+
+    /// The newest valid request received since the last call, if any. Never
+    /// blocks; undecodable messages are logged and skipped.
+    pub fn take(&self) -> Option<Option<Rect>> {
+        let mut latest = None;
+        while let Ok(Some(sample)) = self.subscriber.try_recv() {
+            match parse_crop(&sample.payload().to_bytes()) {
+                Ok(crop) => latest = Some(crop),
+                Err(e) => warn!("Ignoring crop request on {}: {e}", sample.key_expr()),
+            }
+        }
+        latest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_region() {
+        let crop = parse_crop(br#"{"x": 640, "y": 360, "width": 320, "height": 240}"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            crop,
+            Rect {
+                x: 640,
+                y: 360,
+                width: 320,
+                height: 240
+            }
+        );
+    }
+
+    #[test]
+    fn empty_or_null_clears_crop() {
+        assert_eq!(parse_crop(b"").unwrap(), None);
+        assert_eq!(parse_crop(b" \n").unwrap(), None);
+        assert_eq!(parse_crop(b"null").unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        assert!(parse_crop(br#"{"x": 0, "y": 0, "width": 64}"#).is_err());
+        assert!(parse_crop(br#"{"x": -2, "y": 0, "width": 64, "height": 64}"#).is_err());
+        assert!(parse_crop(br#"{"x": 0, "y": 0, "w": 64, "h": 64}"#).is_err());
+        assert!(parse_crop(br#"{"x": 4294967294, "y": 0, "width": 64, "height": 64}"#).is_err());
+        assert!(parse_crop(b"crop please").is_err());
+    }
+}
@@ -0,0 +1,347 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! `--sensor-diagnostics`: watch the sensor's temperature and error
+//! counters.
+//!
+//! Some sensor drivers expose their die temperature and error counters
+//! (MIPI CRC or ECC errors, for example) as read-only V4L2 integer
+//! controls. The controls are found by name with `VIDIOC_QUERYCTRL` and
+//! read once a second with `VIDIOC_G_CTRL` on a second handle to the
+//! capture device. A rise in an error counter is logged as a warning, and
+//! the latest readings go out with every frame on `--h264-meta-topic`
+//! through [`DIAGNOSTICS`].
+
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io,
+    os::fd::AsRawFd,
+    path::Path,
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+use tracing::{debug, info, warn};
+
+/// `VIDIOC_QUERYCTRL`, i.e. `_IOWR('V', 36, struct v4l2_queryctrl)`.
+const VIDIOC_QUERYCTRL: u64 = 0xc044_5624;
+
+/// `VIDIOC_G_CTRL`, i.e. `_IOWR('V', 27, struct v4l2_control)`.
+const VIDIOC_G_CTRL: u64 = 0xc008_561b;
+
+/// `V4L2_CTRL_FLAG_NEXT_CTRL`
+const V4L2_CTRL_FLAG_NEXT_CTRL: u32 = 0x8000_0000;
+
+/// `V4L2_CTRL_FLAG_DISABLED`
+const V4L2_CTRL_FLAG_DISABLED: u32 = 0x0001;
+
+/// `V4L2_CTRL_TYPE_INTEGER`
+const V4L2_CTRL_TYPE_INTEGER: u32 = 1;
+
+/// Mirror of the kernel's `struct v4l2_queryctrl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct V4l2QueryCtrl {
+    id: u32,
+    type_: u32,
+    name: [u8; 32],
+    minimum: i32,
+    maximum: i32,
+    step: i32,
+    default_value: i32,
+    flags: u32,
+    reserved: [u32; 2],
+}
+
+/// Mirror of the kernel's `struct v4l2_control`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct V4l2Control {
+    id: u32,
+    value: i32,
+}
+
+/// The latest value of every diagnostic control, keyed by
+/// [`Control::key`].
+pub type SensorReadings = BTreeMap<String, i64>;
+
+/// What a diagnostic control reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlKind {
+    /// A temperature, in whatever unit the driver uses.
+    Temperature,
+    /// A counter of errors the sensor has seen.
+    ErrorCount,
+}
+
+impl ControlKind {
+    /// The kind of a control from its V4L2 name, or `None` for controls
+    /// that are not diagnostics.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if name.contains("temperature") || name.split(' ').any(|word| word == "temp") {
+            Some(Self::Temperature)
+        } else if ["error", "crc", "ecc"].iter().any(|w| name.contains(w)) {
+            Some(Self::ErrorCount)
+        } else {
+            None
+        }
+    }
+}
+
+/// A diagnostic control of the capture device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Control {
+    pub id: u32,
+    /// The control's V4L2 name, e.g. "MIPI CRC Errors".
+    pub name: String,
+    pub kind: ControlKind,
+}
+
+impl Control {
+    /// The control's name in snake case, e.g. `mipi_crc_errors`, as
+    /// published.
+    pub fn key(&self) -> String {
+        self.name
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_ascii_lowercase)
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+}
+
+/// Reads the current value of a control.
+pub trait ControlReader {
+    fn read(&mut self, id: u32) -> io::Result<i64>;
+}
+
+impl<F: FnMut(u32) -> io::Result<i64>> ControlReader for F {
+    fn read(&mut self, id: u32) -> io::Result<i64> {
+        self(id)
+    }
+}
+
+/// The controls of a V4L2 device, on a handle of their own.
+pub struct V4l2Controls {
+    file: File,
+}
+
+impl V4l2Controls {
+    pub fn open(device: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(device)?;
+        Ok(Self { file })
+    }
+
+    /// The enabled integer controls whose name marks them as a temperature
+    /// or an error counter.
+    pub fn diagnostics(&self) -> io::Result<Vec<Control>> {
+        let mut controls = Vec::new();
+        let mut id = 0;
+        loop {
+            let mut query = V4l2QueryCtrl {
+                id: id | V4L2_CTRL_FLAG_NEXT_CTRL,
+                ..Default::default()
+            };
+            let ret =
+                unsafe { libc::ioctl(self.file.as_raw_fd(), VIDIOC_QUERYCTRL as _, &mut query) };
+            if ret < 0 {
+                let err = io::Error::last_os_error();
+                // EINVAL past the last control; ENOTTY without any.
+                return match err.raw_os_error() {
+                    Some(libc::EINVAL) | Some(libc::ENOTTY) => Ok(controls),
+                    _ => Err(err),
+                };
+            }
+            id = query.id;
+            if query.type_ != V4L2_CTRL_TYPE_INTEGER || query.flags & V4L2_CTRL_FLAG_DISABLED != 0 {
+                continue;
+            }
+            let len = query.name.iter().position(|&b| b == 0).unwrap_or(32);
+            let name = String::from_utf8_lossy(&query.name[..len]).into_owned();
+            if let Some(kind) = ControlKind::from_name(&name) {
+                controls.push(Control { id, name, kind });
+            }
+        }
+    }
+}
+
+impl ControlReader for V4l2Controls {
+    fn read(&mut self, id: u32) -> io::Result<i64> {
+        let mut control = V4l2Control { id, value: 0 };
+        let ret = unsafe { libc::ioctl(self.file.as_raw_fd(), VIDIOC_G_CTRL as _, &mut control) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(control.value as i64)
+    }
+}
+
+/// Polls a set of diagnostic controls and spots rising error counters.
+pub struct Monitor {
+    controls: Vec<Control>,
+    errors: BTreeMap<u32, i64>,
+}
+
+impl Monitor {
+    pub fn new(controls: Vec<Control>) -> Self {
+        Self {
+            controls,
+            errors: BTreeMap::new(),
+        }
+    }
+
+    /// Read every control. Returns the readings and, for each error
+    /// counter that rose since the previous poll, its name and how far.
+    /// The first poll only sets the baseline. Controls that fail to read
+    /// are left out.
+    pub fn poll(&mut self, reader: &mut impl ControlReader) -> (SensorReadings, Vec<(&str, i64)>) {
+        let mut readings = SensorReadings::new();
+        let mut rises = Vec::new();
+        for control in &self.controls {
+            let value = match reader.read(control.id) {
+                Ok(value) => value,
+                Err(e) => {
+                    debug!("Cannot read sensor control {:?}: {e}", control.name);
+                    continue;
+                }
+            };
+            if control.kind == ControlKind::ErrorCount {
+                let last = self.errors.insert(control.id, value);
+                if let Some(rise) = last.map(|last| value - last).filter(|&rise| rise > 0) {
+                    rises.push((control.name.as_str(), rise));
+                }
+            }
+            readings.insert(control.key(), value);
+        }
+        (readings, rises)
+    }
+}
+
+/// The latest readings of the process's `--sensor-diagnostics` monitor,
+/// added to the H.264 frame metadata.
+pub struct Diagnostics {
+    latest: Mutex<Option<SensorReadings>>,
+}
+
+/// Empty when no monitor runs.
+pub static DIAGNOSTICS: Diagnostics = Diagnostics {
+    latest: Mutex::new(None),
+};
+
+impl Diagnostics {
+    pub fn latest(&self) -> Option<SensorReadings> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    fn set(&self, readings: SensorReadings) {
+        *self.latest.lock().unwrap() = Some(readings);
+    }
+}
+
+/// Poll the diagnostic controls of `device` once every `interval` on a
+/// thread of its own, publishing the readings in [`DIAGNOSTICS`] and
+/// warning when an error counter rises. Returns `None` when the device
+/// has no diagnostic controls.
+///
+/// # Errors
+///
+/// Returns an error if the controls cannot be listed or the thread
+/// cannot be spawned.
+pub fn spawn(device: &Path, interval: Duration) -> io::Result<Option<thread::JoinHandle<()>>> {
+    let mut reader = V4l2Controls::open(device)?;
+    let controls = reader.diagnostics()?;
+    if controls.is_empty() {
+        return Ok(None);
+    }
+    let names: Vec<_> = controls.iter().map(|c| c.name.as_str()).collect();
+    info!("Sensor diagnostics: {}", names.join(", "));
+    let mut monitor = Monitor::new(controls);
+    thread::Builder::new()
+        .name("sensor".to_string())
+        .spawn(move || loop {
+            let (readings, rises) = monitor.poll(&mut reader);
+            for (name, rise) in rises {
+                warn!("Sensor reports {rise} new {name}");
+            }
+            DIAGNOSTICS.set(readings);
+            thread::sleep(interval);
+        })
+        .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Reads the control values in `values` instead of a driver's.
+    fn mock(values: &HashMap<u32, i64>) -> impl FnMut(u32) -> io::Result<i64> + '_ {
+        |id| {
+            values
+                .get(&id)
+                .copied()
+                .ok_or_else(|| io::Error::from_raw_os_error(libc::EINVAL))
+        }
+    }
+
+    fn control(id: u32, name: &str) -> Control {
+        Control {
+            id,
+            name: name.to_string(),
+            kind: ControlKind::from_name(name).unwrap(),
+        }
+    }
+
+    #[test]
+    fn v4l2_control_structs_match_kernel_layout() {
+        assert_eq!(std::mem::size_of::<V4l2QueryCtrl>(), 68);
+        assert_eq!(std::mem::size_of::<V4l2Control>(), 8);
+    }
+
+    #[test]
+    fn controls_are_classified_by_name() {
+        let kind = ControlKind::from_name;
+        assert_eq!(kind("Temperature"), Some(ControlKind::Temperature));
+        assert_eq!(kind("Sensor Temp"), Some(ControlKind::Temperature));
+        assert_eq!(kind("MIPI CRC Errors"), Some(ControlKind::ErrorCount));
+        assert_eq!(kind("ECC Count"), Some(ControlKind::ErrorCount));
+        assert_eq!(kind("Exposure"), None);
+        assert_eq!(kind("Template Select"), None);
+        assert_eq!(control(1, "MIPI CRC Errors").key(), "mipi_crc_errors");
+        assert_eq!(
+            control(1, " Die Temperature (C)").key(),
+            "die_temperature_c"
+        );
+    }
+
+    #[test]
+    fn rising_error_counters_are_reported() {
+        let mut monitor = Monitor::new(vec![
+            control(1, "Temperature"),
+            control(2, "MIPI CRC Errors"),
+            control(3, "ECC Errors"),
+        ]);
+        let mut values = HashMap::from([(1, 45), (2, 7), (3, 0)]);
+
+        // Counts from before the first poll are not new errors.
+        let (readings, rises) = monitor.poll(&mut mock(&values));
+        assert!(rises.is_empty());
+        assert_eq!(readings["mipi_crc_errors"], 7);
+        assert_eq!(readings["temperature"], 45);
+
+        values.extend([(1, 60), (2, 10)]);
+        let (readings, rises) = monitor.poll(&mut mock(&values));
+        assert_eq!(rises, [("MIPI CRC Errors", 3)]);
+        assert_eq!(readings["mipi_crc_errors"], 10);
+
+        // A temperature rise is no error, and a control that stops
+        // reading is left out.
+        values.remove(&3);
+        values.insert(1, 70);
+        let (readings, rises) = monitor.poll(&mut mock(&values));
+        assert!(rises.is_empty());
+        assert!(!readings.contains_key("ecc_errors"));
+    }
+}
@@ -27,10 +27,14 @@
 //!   "height":      1080,
 //!   "colorimetry": { "color_space": "bt709", ... },
 //!   "camera_info": { ... CameraInfoFields ... },
-//!   "tf_static":   { ... TfStaticFields ... }
+//!   "tf_static":   { ... TfStaticFields ... },
+//!   "tf_static_links": [ { ... TfStaticFields ... } ]
 //! }
 //! ```
 //!
+//! `tf_static_links` holds the `--tf-config` transforms and is omitted when
+//! there are none, so sidecars without it still load.
+//!
 //! `fps` / `width` / `height` describe the encoded bitstream and are what
 //! the replay path uses to initialize its decoder and pacing. The schema
 //! deliberately omits a `format` field because the decoder always
@@ -63,6 +67,8 @@ pub(crate) struct Sidecar {
     pub colorimetry: Colorimetry,
     pub camera_info: CameraInfoFields,
     pub tf_static: TfStaticFields,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tf_static_links: Vec<TfStaticFields>,
 }
 
 impl Sidecar {
@@ -84,6 +90,7 @@ impl Sidecar {
         cam: &CameraReader,
         camera_info: CameraInfoFields,
         tf_static: TfStaticFields,
+        tf_static_links: Vec<TfStaticFields>,
     ) -> Self {
         Sidecar {
             version: SIDECAR_VERSION,
@@ -94,6 +101,7 @@ impl Sidecar {
             colorimetry: Colorimetry::from_camera(cam),
             camera_info,
             tf_static,
+            tf_static_links,
         }
     }
 
@@ -189,6 +197,7 @@ mod tests {
                 translation: [0.0, 0.1, 0.2],
                 rotation: [0.0, 0.0, 0.0, 1.0],
             },
+            tf_static_links: Vec::new(),
         }
     }
 
@@ -230,6 +239,25 @@ mod tests {
         assert_eq!(back.tf_static.base_frame_id, src.tf_static.base_frame_id);
         assert_eq!(back.tf_static.translation, src.tf_static.translation);
         assert_eq!(back.tf_static.rotation, src.tf_static.rotation);
+        assert!(back.tf_static_links.is_empty());
+    }
+
+    #[test]
+    fn tf_static_links_round_trip_and_default_to_empty() {
+        let mut src = sample_sidecar();
+        let json = serde_json::to_string(&src).unwrap();
+        assert!(!json.contains("tf_static_links"));
+
+        src.tf_static_links.push(TfStaticFields {
+            base_frame_id: "base_link".into(),
+            child_frame_id: "mast".into(),
+            translation: [0.0, 0.0, 1.0],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+        });
+        let bytes = serde_json::to_vec(&src).unwrap();
+        let back: Sidecar = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(back.tf_static_links.len(), 1);
+        assert_eq!(back.tf_static_links[0].child_frame_id, "mast");
     }
 
     #[test]
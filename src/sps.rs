@@ -0,0 +1,620 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! H.264 sequence parameter set (SPS) inspection and patching.
+//!
+//! The hardware encoder has no control over the VUI it writes, so the
+//! sample aspect ratio of anamorphic sensors is signalled by rewriting the
+//! SPS the encoder emits with every keyframe. Only the fields in front of
+//! the VUI are parsed; everything after the aspect ratio is copied bit for
+//! bit.
+//!
+//! ```
+//! use edgefirst_camera::sps::SampleAspectRatio;
+//!
+//! let sar: SampleAspectRatio = "24:22".parse().unwrap();
+//! assert_eq!(sar, SampleAspectRatio::new(12, 11).unwrap());
+//! assert_eq!(sar.to_string(), "12:11");
+//! ```
+
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::nal::{escape, iter_nal_units, nal_type, unescape, NAL_TYPE_SPS};
+
+/// `aspect_ratio_idc` value that is followed by an explicit width and
+/// height (`Extended_SAR`).
+const EXTENDED_SAR: u32 = 255;
+
+/// Sample aspect ratios with their own `aspect_ratio_idc` (Table E-1).
+const SAR_TABLE: [(u16, u16); 16] = [
+    (1, 1),
+    (12, 11),
+    (10, 11),
+    (16, 11),
+    (40, 33),
+    (24, 11),
+    (20, 11),
+    (32, 11),
+    (80, 33),
+    (18, 11),
+    (15, 11),
+    (64, 33),
+    (160, 99),
+    (4, 3),
+    (3, 2),
+    (2, 1),
+];
+
+/// Profiles whose SPS carries the chroma format and bit depth fields.
+const HIGH_PROFILES: [u32; 13] = [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135];
+
+/// Width:height of one pixel, as signalled in the H.264 VUI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SampleAspectRatio {
+    width: u16,
+    height: u16,
+}
+
+impl SampleAspectRatio {
+    /// Square pixels.
+    pub const SQUARE: Self = Self {
+        width: 1,
+        height: 1,
+    };
+
+    /// A ratio reduced to lowest terms, or `None` if either side is zero.
+    pub fn new(width: u16, height: u16) -> Option<Self> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let g = gcd(width, height);
+        Some(Self {
+            width: width / g,
+            height: height / g,
+        })
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// The `aspect_ratio_idc` that encodes this ratio.
+    fn idc(&self) -> u32 {
+        SAR_TABLE
+            .iter()
+            .position(|&r| r == (self.width, self.height))
+            .map_or(EXTENDED_SAR, |i| i as u32 + 1)
+    }
+
+    fn from_idc(idc: u32) -> Option<Self> {
+        let &(width, height) = SAR_TABLE.get((idc as usize).checked_sub(1)?)?;
+        Some(Self { width, height })
+    }
+}
+
+impl Default for SampleAspectRatio {
+    fn default() -> Self {
+        Self::SQUARE
+    }
+}
+
+impl FromStr for SampleAspectRatio {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (w, h) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected a pixel aspect of the form W:H, got '{s}'"))?;
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<u16>()
+                .map_err(|e| format!("invalid pixel aspect '{s}': {e}"))
+        };
+        Self::new(parse(w)?, parse(h)?)
+            .ok_or_else(|| format!("invalid pixel aspect '{s}': both sides must be non-zero"))
+    }
+}
+
+impl fmt::Display for SampleAspectRatio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.width, self.height)
+    }
+}
+
+fn gcd(mut a: u16, mut b: u16) -> u16 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Reads an RBSP most significant bit first.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bit(&mut self) -> Result<u32, Box<dyn Error>> {
+        let byte = self.data.get(self.pos / 8).ok_or("truncated RBSP")?;
+        let bit = (byte >> (7 - self.pos % 8)) & 1;
+        self.pos += 1;
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, Box<dyn Error>> {
+        (0..n).try_fold(0, |v, _| Ok((v << 1) | self.bit()?))
+    }
+
+    /// Unsigned Exp-Golomb, `ue(v)`.
+    pub(crate) fn ue(&mut self) -> Result<u32, Box<dyn Error>> {
+        let mut zeros = 0;
+        while self.bit()? == 0 {
+            zeros += 1;
+            if zeros > 31 {
+                return Err("invalid Exp-Golomb code".into());
+            }
+        }
+        Ok(((1u64 << zeros) - 1 + self.bits(zeros)? as u64) as u32)
+    }
+
+    /// Signed Exp-Golomb, `se(v)`.
+    fn se(&mut self) -> Result<i32, Box<dyn Error>> {
+        let v = self.ue()? as i64;
+        Ok(if v % 2 == 1 { (v + 1) / 2 } else { -(v / 2) } as i32)
+    }
+}
+
+/// Writes an RBSP most significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    data: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn bit(&mut self, bit: u32) {
+        if self.len.is_multiple_of(8) {
+            self.data.push(0);
+        }
+        if bit != 0 {
+            *self.data.last_mut().unwrap() |= 0x80 >> (self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    fn bits(&mut self, value: u32, n: u32) {
+        for i in (0..n).rev() {
+            self.bit((value >> i) & 1);
+        }
+    }
+
+    #[cfg(test)]
+    fn ue(&mut self, value: u32) {
+        let v = value as u64 + 1;
+        let len = 64 - v.leading_zeros();
+        self.bits(0, len - 1);
+        for i in (0..len).rev() {
+            self.bit(((v >> i) & 1) as u32);
+        }
+    }
+
+    /// Copy bits `start..end` of `data`.
+    fn copy(&mut self, data: &[u8], start: usize, end: usize) {
+        for pos in start..end {
+            self.bit(((data[pos / 8] >> (7 - pos % 8)) & 1) as u32);
+        }
+    }
+
+    /// Append the RBSP stop bit and alignment.
+    fn finish(mut self) -> Vec<u8> {
+        self.bit(1);
+        self.data
+    }
+}
+
+/// Bit offsets of the aspect ratio fields within an SPS RBSP, and the
+/// picture size it codes.
+struct VuiLayout {
+    /// Width and height in pixels after frame cropping.
+    size: (u32, u32),
+    /// Offset of `vui_parameters_present_flag`.
+    vui_flag: usize,
+    /// When the VUI is present, the offsets where the aspect ratio info
+    /// starts and where the field after it begins.
+    aspect: Option<(usize, usize)>,
+    sar: Option<SampleAspectRatio>,
+}
+
+/// Walk an SPS RBSP (header byte included) up to the end of the VUI
+/// aspect ratio info.
+fn parse_vui_layout(rbsp: &[u8]) -> Result<VuiLayout, Box<dyn Error>> {
+    let mut r = BitReader::new(rbsp);
+    r.bits(8)?; // NAL header
+    let profile_idc = r.bits(8)?;
+    r.bits(16)?; // constraint flags, level_idc
+    r.ue()?; // seq_parameter_set_id
+
+    // 4:2:0 unless a high profile says otherwise.
+    let mut chroma_format_idc = 1;
+    let mut separate_planes = false;
+    if HIGH_PROFILES.contains(&profile_idc) {
+        chroma_format_idc = r.ue()?;
+        if chroma_format_idc == 3 {
+            separate_planes = r.bit()? == 1;
+        }
+        r.ue()?; // bit_depth_luma_minus8
+        r.ue()?; // bit_depth_chroma_minus8
+        r.bit()?; // qpprime_y_zero_transform_bypass_flag
+        if r.bit()? == 1 {
+            let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+            for i in 0..lists {
+                if r.bit()? == 1 {
+                    skip_scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+
+    r.ue()?; // log2_max_frame_num_minus4
+    match r.ue()? {
+        0 => {
+            r.ue()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            r.bit()?; // delta_pic_order_always_zero_flag
+            r.se()?; // offset_for_non_ref_pic
+            r.se()?; // offset_for_top_to_bottom_field
+            for _ in 0..r.ue()? {
+                r.se()?; // offset_for_ref_frame
+            }
+        }
+        _ => {}
+    }
+    r.ue()?; // max_num_ref_frames
+    r.bit()?; // gaps_in_frame_num_value_allowed_flag
+    let width_mbs = r.ue()? + 1;
+    let height_map_units = r.ue()? + 1;
+    let frame_mbs_only = r.bit()?;
+    if frame_mbs_only == 0 {
+        r.bit()?; // mb_adaptive_frame_field_flag
+    }
+    r.bit()?; // direct_8x8_inference_flag
+    let mut crop = [0; 4];
+    if r.bit()? == 1 {
+        for offset in &mut crop {
+            *offset = r.ue()?; // frame_crop_{left,right,top,bottom}_offset
+        }
+    }
+    // Crop offsets count chroma samples (Table 6-1); fields double the
+    // vertical unit.
+    let (sub_width, sub_height) = match (chroma_format_idc, separate_planes) {
+        (0, _) | (3, true) => (1, 1),
+        (1, _) => (2, 2),
+        (2, _) => (2, 1),
+        _ => (1, 1),
+    };
+    let field_factor = 2 - frame_mbs_only;
+    let size = (
+        (width_mbs * 16).saturating_sub(sub_width * (crop[0] + crop[1])),
+        (height_map_units * field_factor * 16)
+            .saturating_sub(sub_height * field_factor * (crop[2] + crop[3])),
+    );
+
+    let vui_flag = r.pos;
+    if r.bit()? == 0 {
+        return Ok(VuiLayout {
+            size,
+            vui_flag,
+            aspect: None,
+            sar: None,
+        });
+    }
+
+    let start = r.pos;
+    let mut sar = None;
+    if r.bit()? == 1 {
+        let idc = r.bits(8)?;
+        sar = if idc == EXTENDED_SAR {
+            let (w, h) = (r.bits(16)?, r.bits(16)?);
+            SampleAspectRatio::new(w as u16, h as u16)
+        } else {
+            SampleAspectRatio::from_idc(idc)
+        };
+    }
+    Ok(VuiLayout {
+        size,
+        vui_flag,
+        aspect: Some((start, r.pos)),
+        sar,
+    })
+}
+
+fn skip_scaling_list(r: &mut BitReader, size: usize) -> Result<(), Box<dyn Error>> {
+    let (mut last, mut next) = (8i32, 8i32);
+    for _ in 0..size {
+        if next != 0 {
+            next = (last + r.se()? + 256) % 256;
+        }
+        if next != 0 {
+            last = next;
+        }
+    }
+    Ok(())
+}
+
+/// Offset of the RBSP stop bit, the last set bit of the payload.
+fn stop_bit(rbsp: &[u8]) -> Result<usize, Box<dyn Error>> {
+    let i = rbsp
+        .iter()
+        .rposition(|&b| b != 0)
+        .ok_or("SPS has no stop bit")?;
+    Ok(i * 8 + 7 - rbsp[i].trailing_zeros() as usize)
+}
+
+fn write_aspect_ratio_info(w: &mut BitWriter, sar: SampleAspectRatio) {
+    w.bit(1); // aspect_ratio_info_present_flag
+    let idc = sar.idc();
+    w.bits(idc, 8);
+    if idc == EXTENDED_SAR {
+        w.bits(sar.width as u32, 16);
+        w.bits(sar.height as u32, 16);
+    }
+}
+
+/// The sample aspect ratio signalled by an SPS NAL unit (without start
+/// code), or `None` if its VUI carries none.
+pub fn sample_aspect_ratio(sps: &[u8]) -> Result<Option<SampleAspectRatio>, Box<dyn Error>> {
+    Ok(parse_vui_layout(&unescape(sps))?.sar)
+}
+
+/// The width and height in pixels, after frame cropping, coded by an SPS
+/// NAL unit (without start code).
+pub fn picture_size(sps: &[u8]) -> Result<(u32, u32), Box<dyn Error>> {
+    if nal_type(sps) != NAL_TYPE_SPS {
+        return Err("not an SPS NAL unit".into());
+    }
+    Ok(parse_vui_layout(&unescape(sps))?.size)
+}
+
+/// Rewrite an SPS NAL unit (without start code) to signal `sar`, adding a
+/// minimal VUI when the SPS has none.
+pub fn set_sample_aspect_ratio(
+    sps: &[u8],
+    sar: SampleAspectRatio,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    if nal_type(sps) != NAL_TYPE_SPS {
+        return Err("not an SPS NAL unit".into());
+    }
+    let rbsp = unescape(sps);
+    let layout = parse_vui_layout(&rbsp)?;
+    let end = stop_bit(&rbsp)?;
+
+    let mut w = BitWriter::default();
+    match layout.aspect {
+        Some((start, rest)) => {
+            w.copy(&rbsp, 0, start);
+            write_aspect_ratio_info(&mut w, sar);
+            w.copy(&rbsp, rest, end);
+        }
+        None => {
+            w.copy(&rbsp, 0, layout.vui_flag);
+            w.bit(1); // vui_parameters_present_flag
+            write_aspect_ratio_info(&mut w, sar);
+            // overscan_info, video_signal_type, chroma_loc_info,
+            // timing_info, nal_hrd, vcl_hrd, pic_struct and
+            // bitstream_restriction all absent.
+            w.bits(0, 8);
+        }
+    }
+    Ok(escape(&w.finish()))
+}
+
+/// Rewrite every SPS in an Annex-B buffer to signal `sar`, leaving all
+/// other bytes untouched.
+pub fn rewrite_sample_aspect_ratio(
+    buf: &[u8],
+    sar: SampleAspectRatio,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut out = Vec::with_capacity(buf.len() + 8);
+    let mut copied = 0;
+    for unit in iter_nal_units(buf) {
+        if nal_type(unit) != NAL_TYPE_SPS {
+            continue;
+        }
+        let offset = unit.as_ptr() as usize - buf.as_ptr() as usize;
+        out.extend_from_slice(&buf[copied..offset]);
+        out.extend_from_slice(&set_sample_aspect_ratio(unit, sar)?);
+        copied = offset + unit.len();
+    }
+    out.extend_from_slice(&buf[copied..]);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(aspect_ratio_idc, sar_width, sar_height)`, absent when the VUI
+    /// carries no aspect ratio.
+    type AspectInfo = Option<(u32, u16, u16)>;
+
+    /// Build a 1920x1080 SPS the way an encoder would, optionally with a VUI
+    /// holding `(aspect ratio, timing info present)`.
+    fn build_sps(profile_idc: u32, vui: Option<(AspectInfo, bool)>) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(0x67, 8);
+        w.bits(profile_idc, 8);
+        w.bits(0xc0, 8); // constraint flags
+        w.bits(40, 8); // level 4.0
+        w.ue(0); // seq_parameter_set_id
+        if HIGH_PROFILES.contains(&profile_idc) {
+            w.ue(1); // 4:2:0
+            w.ue(0);
+            w.ue(0);
+            w.bit(0);
+            w.bit(1); // seq_scaling_matrix_present_flag
+            for i in 0..8 {
+                // One explicit flat 4x4 list to exercise the skip.
+                w.bit(u32::from(i == 0));
+                if i == 0 {
+                    for _ in 0..16 {
+                        w.ue(0); // se(0): delta_scale 0
+                    }
+                }
+            }
+        }
+        w.ue(0); // log2_max_frame_num_minus4
+        w.ue(2); // pic_order_cnt_type
+        w.ue(1); // max_num_ref_frames
+        w.bit(0);
+        w.ue(119); // 1920 / 16 - 1
+        w.ue(67); // 1088 / 16 - 1
+        w.bit(1); // frame_mbs_only_flag
+        w.bit(1);
+        w.bit(1); // frame_cropping_flag
+        w.ue(0);
+        w.ue(0);
+        w.ue(0);
+        w.ue(4); // crop 8 rows: 1088 -> 1080
+
+        match vui {
+            None => w.bit(0),
+            Some((aspect, timing)) => {
+                w.bit(1);
+                match aspect {
+                    None => w.bit(0),
+                    Some((idc, sar_w, sar_h)) => {
+                        w.bit(1);
+                        w.bits(idc, 8);
+                        if idc == EXTENDED_SAR {
+                            w.bits(sar_w as u32, 16);
+                            w.bits(sar_h as u32, 16);
+                        }
+                    }
+                }
+                w.bits(0, 3); // overscan, video_signal_type, chroma_loc
+                w.bit(u32::from(timing));
+                if timing {
+                    w.bits(1, 32); // num_units_in_tick
+                    w.bits(60, 32); // time_scale
+                    w.bit(1); // fixed_frame_rate_flag
+                }
+                w.bits(0, 4); // nal_hrd, vcl_hrd, pic_struct, bitstream_restriction
+            }
+        }
+        escape(&w.finish())
+    }
+
+    fn sar(w: u16, h: u16) -> SampleAspectRatio {
+        SampleAspectRatio::new(w, h).unwrap()
+    }
+
+    #[test]
+    fn parse_sample_aspect_ratio_spec() {
+        assert_eq!("1:1".parse(), Ok(SampleAspectRatio::SQUARE));
+        assert_eq!("4:3".parse(), Ok(sar(4, 3)));
+        assert_eq!(" 8 : 6 ".parse(), Ok(sar(4, 3)));
+        assert_eq!(
+            "59:54".parse::<SampleAspectRatio>().unwrap().to_string(),
+            "59:54"
+        );
+        assert!("4x3".parse::<SampleAspectRatio>().is_err());
+        assert!("0:1".parse::<SampleAspectRatio>().is_err());
+        assert!("70000:1".parse::<SampleAspectRatio>().is_err());
+        assert_eq!(SampleAspectRatio::default(), SampleAspectRatio::SQUARE);
+    }
+
+    #[test]
+    fn sar_idc_table() {
+        assert_eq!(SampleAspectRatio::SQUARE.idc(), 1);
+        assert_eq!(sar(12, 11).idc(), 2);
+        assert_eq!(sar(4, 3).idc(), 14);
+        assert_eq!(sar(2, 1).idc(), 16);
+        assert_eq!(sar(59, 54).idc(), EXTENDED_SAR);
+        for idc in 1..=16 {
+            assert_eq!(SampleAspectRatio::from_idc(idc).unwrap().idc(), idc);
+        }
+        assert_eq!(SampleAspectRatio::from_idc(0), None);
+        assert_eq!(SampleAspectRatio::from_idc(17), None);
+    }
+
+    #[test]
+    fn adds_vui_to_sps_without_one() {
+        for profile in [66, 100] {
+            let sps = build_sps(profile, None);
+            assert_eq!(sample_aspect_ratio(&sps).unwrap(), None);
+
+            let patched = set_sample_aspect_ratio(&sps, sar(4, 3)).unwrap();
+            assert_eq!(patched, build_sps(profile, Some((Some((14, 0, 0)), false))));
+            assert_eq!(sample_aspect_ratio(&patched).unwrap(), Some(sar(4, 3)));
+        }
+    }
+
+    #[test]
+    fn replaces_aspect_ratio_and_keeps_rest_of_vui() {
+        let sps = build_sps(100, Some((None, true)));
+        let patched = set_sample_aspect_ratio(&sps, sar(59, 54)).unwrap();
+        assert_eq!(
+            patched,
+            build_sps(100, Some((Some((EXTENDED_SAR, 59, 54)), true)))
+        );
+
+        let repatched = set_sample_aspect_ratio(&patched, SampleAspectRatio::SQUARE).unwrap();
+        assert_eq!(repatched, build_sps(100, Some((Some((1, 0, 0)), true))));
+        assert_eq!(
+            sample_aspect_ratio(&repatched).unwrap(),
+            Some(SampleAspectRatio::SQUARE)
+        );
+    }
+
+    #[test]
+    fn rewrites_only_sps_in_access_unit() {
+        let sps = build_sps(66, None);
+        let mut au = vec![0, 0, 0, 1, 0x09, 0xf0, 0, 0, 0, 1];
+        au.extend_from_slice(&sps);
+        au.extend_from_slice(&[
+            0, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80, 0, 0, 1, 0x65, 0x88, 0x84,
+        ]);
+
+        let out = rewrite_sample_aspect_ratio(&au, sar(16, 11)).unwrap();
+        let units: Vec<&[u8]> = iter_nal_units(&out).collect();
+        assert_eq!(units.len(), 4);
+        assert_eq!(units[0], [0x09, 0xf0]);
+        assert_eq!(sample_aspect_ratio(units[1]).unwrap(), Some(sar(16, 11)));
+        assert_eq!(units[2], [0x68, 0xce, 0x3c, 0x80]);
+        assert_eq!(units[3], [0x65, 0x88, 0x84]);
+        assert!(out.ends_with(&[0, 0, 1, 0x65, 0x88, 0x84]));
+
+        // No SPS, no change.
+        let p_frame = [0, 0, 0, 1, 0x41, 0x9a, 0x10];
+        assert_eq!(
+            rewrite_sample_aspect_ratio(&p_frame, sar(16, 11)).unwrap(),
+            p_frame
+        );
+    }
+
+    #[test]
+    fn picture_size_applies_frame_cropping() {
+        for profile in [66, 100] {
+            let sps = build_sps(profile, Some((Some((14, 0, 0)), true)));
+            assert_eq!(picture_size(&sps).unwrap(), (1920, 1080));
+        }
+        assert!(picture_size(&[0x68, 0xce]).is_err());
+    }
+
+    #[test]
+    fn rejects_non_sps_and_truncated_units() {
+        assert!(set_sample_aspect_ratio(&[0x68, 0xce], SampleAspectRatio::SQUARE).is_err());
+        let sps = build_sps(66, None);
+        assert!(set_sample_aspect_ratio(&sps[..4], SampleAspectRatio::SQUARE).is_err());
+    }
+}
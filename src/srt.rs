@@ -0,0 +1,367 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! `--srt`: send the H.264 stream to an SRT listener (`srt` feature).
+//!
+//! SRT retransmits lost packets within a fixed latency budget and drops
+//! what arrives too late, which suits cellular and long-haul links better
+//! than plain RTP. The node is the caller: it connects to
+//! `srt://host:port` and sends each encoded access unit as an Annex-B
+//! byte stream, split into live-mode messages of [`MESSAGE_SIZE`] bytes.
+//! Receivers read it as a raw H.264 elementary stream, for example
+//! `ffplay -f h264 "srt://:9000?mode=listener"`.
+//!
+//! Sub-options go in the query string:
+//!
+//! | Option          | Meaning                                                 |
+//! |-----------------|---------------------------------------------------------|
+//! | `latency=<ms>`  | Receiver buffer for retransmission (default 120)        |
+//! | `overhead=<%>`  | Bandwidth kept for retransmission above the stream rate, 5 to 100 (default 25) |
+//! | `streamid=<id>` | Stream ID the listener may use to route the connection |
+//!
+//! The connection is retried once a second while the listener is away.
+//! After each connect the encoder is asked for a keyframe and frames are
+//! held back until it arrives, led by the last parameter sets if the
+//! keyframe does not carry its own, so the receiver can start decoding at
+//! once.
+
+use std::{
+    error::Error,
+    fmt,
+    net::ToSocketAddrs,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use edgefirst_camera::{config::H264Bitrate, nal};
+use futures::SinkExt;
+use kanal::{AsyncReceiver, Sender};
+use srt_tokio::{
+    options::{DataRate, LiveBandwidthMode, Percent},
+    SrtSocket,
+};
+use tracing::{debug, info, warn};
+
+/// Payload of one live-mode message, libsrt's default of seven 188-byte
+/// MPEG-TS packets.
+pub const MESSAGE_SIZE: usize = 1316;
+
+/// Access units queued for the connection before further ones are dropped.
+const QUEUE: usize = 8;
+
+/// Wait between connection attempts.
+const RECONNECT: Duration = Duration::from_secs(1);
+
+/// A parsed `--srt` URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SrtConfig {
+    /// `host:port` of the listener.
+    pub addr: String,
+    pub latency: Duration,
+    /// Percent of the stream rate kept for retransmission.
+    pub overhead: u64,
+    pub stream_id: Option<String>,
+}
+
+impl FromStr for SrtConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix("srt://")
+            .ok_or_else(|| format!("SRT URL {s:?} must start with srt://"))?;
+        let (addr, query) = rest.split_once('?').unwrap_or((rest, ""));
+        match addr.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+            _ => return Err(format!("SRT URL {s:?} needs a host:port to call")),
+        }
+        let mut config = SrtConfig {
+            addr: addr.to_string(),
+            latency: Duration::from_millis(120),
+            overhead: 25,
+            stream_id: None,
+        };
+        for option in query.split('&').filter(|o| !o.is_empty()) {
+            let (key, value) = option
+                .split_once('=')
+                .ok_or_else(|| format!("SRT option {option:?} needs a value"))?;
+            let number = || {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("SRT option {key}={value:?} is not a whole number"))
+            };
+            match key {
+                "latency" => config.latency = Duration::from_millis(number()?),
+                "overhead" => {
+                    config.overhead = number()?;
+                    if !(5..=100).contains(&config.overhead) {
+                        return Err(format!("SRT overhead {value}% is outside 5 to 100 percent"));
+                    }
+                }
+                "streamid" => config.stream_id = Some(value.to_string()),
+                // Accepted so URLs written for other tools carry over, but
+                // the node can only call.
+                "mode" if value == "caller" => {}
+                "mode" => return Err(format!("SRT mode {value:?} is not supported, only caller")),
+                _ => {
+                    return Err(format!(
+                        "unknown SRT option {key:?}, expected latency, overhead or streamid"
+                    ))
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl fmt::Display for SrtConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "srt://{}?latency={}&overhead={}",
+            self.addr,
+            self.latency.as_millis(),
+            self.overhead
+        )?;
+        match &self.stream_id {
+            Some(id) => write!(f, "&streamid={id}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Stream rate in bytes per second that SRT starts its bandwidth estimate
+/// from, before it has measured the encoder output.
+pub fn expected_rate(bitrate: H264Bitrate) -> u64 {
+    let mbps = match bitrate {
+        H264Bitrate::Mbps5 => 5,
+        H264Bitrate::Auto | H264Bitrate::Mbps25 => 25,
+        H264Bitrate::Mbps50 => 50,
+        H264Bitrate::Mbps100 => 100,
+    };
+    mbps * 1_000_000 / 8
+}
+
+/// Messages to send for one access unit: the parameter sets `init` first
+/// when given, then the access unit, each cut into [`MESSAGE_SIZE`] pieces.
+pub fn messages<'a>(init: Option<&'a [u8]>, access_unit: &'a [u8]) -> Vec<&'a [u8]> {
+    init.into_iter()
+        .chain([access_unit])
+        .flat_map(|data| data.chunks(MESSAGE_SIZE))
+        .collect()
+}
+
+/// One encoded access unit and whether it is a keyframe.
+type Frame = (Arc<[u8]>, bool);
+
+/// Handle of the task that sends to the SRT listener.
+pub struct SrtOutput {
+    tx: Sender<Frame>,
+}
+
+impl SrtOutput {
+    /// Connect to `config` on a task of the current Tokio runtime, raising
+    /// `keyframe` whenever a connection opens.
+    pub fn spawn(config: SrtConfig, bitrate: H264Bitrate, keyframe: Arc<AtomicBool>) -> Self {
+        let (tx, rx) = kanal::bounded(QUEUE);
+        tokio::spawn(run(config, expected_rate(bitrate), rx.to_async(), keyframe));
+        Self { tx }
+    }
+
+    /// Queue an access unit, dropping it when the connection is behind or
+    /// down.
+    pub fn send(&self, access_unit: &[u8], is_key: bool) {
+        if !matches!(self.tx.try_send((access_unit.into(), is_key)), Ok(true)) {
+            debug!("SRT queue full, dropping an access unit");
+        }
+    }
+}
+
+async fn connect(config: &SrtConfig, rate: u64) -> Result<SrtSocket, Box<dyn Error>> {
+    // Resolve off the runtime so a slow DNS server does not stall the
+    // encoder sharing it.
+    let host = config.addr.clone();
+    let addr = tokio::task::spawn_blocking(move || host.to_socket_addrs())
+        .await??
+        .next()
+        .ok_or_else(|| format!("{} has no address", config.addr))?;
+    let overhead = config.overhead;
+    let socket = SrtSocket::builder()
+        .latency(config.latency)
+        .set(|options| {
+            options.sender.bandwidth = LiveBandwidthMode::Estimated {
+                expected: DataRate(rate),
+                overhead: Percent(overhead),
+            };
+        })
+        .call(addr, config.stream_id.as_deref())
+        .await?;
+    Ok(socket)
+}
+
+/// Keep a connection to `config` open and send it every access unit from
+/// `rx`, until the encoder drops its end.
+async fn run(config: SrtConfig, rate: u64, rx: AsyncReceiver<Frame>, keyframe: Arc<AtomicBool>) {
+    // The parameter sets of the last keyframe that carried them.
+    let mut init: Option<Vec<u8>> = None;
+    let mut failing = false;
+    loop {
+        let mut socket = match connect(&config, rate).await {
+            Ok(socket) => socket,
+            Err(e) => {
+                if !failing {
+                    warn!("Cannot connect to SRT listener {config}, retrying: {e}");
+                    failing = true;
+                }
+                tokio::time::sleep(RECONNECT).await;
+                continue;
+            }
+        };
+        failing = false;
+        info!("Sending H264 to SRT listener {config}");
+        keyframe.store(true, Ordering::Relaxed);
+        let mut started = false;
+        loop {
+            let Ok((data, is_key)) = rx.recv().await else {
+                let _ = socket.close().await;
+                return;
+            };
+            if is_key {
+                if let Some(sets) = nal::parameter_sets(&data) {
+                    init = Some(sets);
+                }
+            }
+            // Deltas before the first keyframe cannot be decoded.
+            if !started && !is_key {
+                continue;
+            }
+            let lead = if !started && nal::parameter_sets(&data).is_none() {
+                init.as_deref()
+            } else {
+                None
+            };
+            started = true;
+            let mut sent = Ok(());
+            for message in messages(lead, &data) {
+                sent = socket
+                    .send((Instant::now(), Bytes::copy_from_slice(message)))
+                    .await;
+                if sent.is_err() {
+                    break;
+                }
+            }
+            if let Err(e) = sent {
+                warn!("SRT connection to {config} lost, reconnecting: {e}");
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+
+    #[test]
+    fn parses_url_and_options() {
+        let config: SrtConfig = "srt://relay.example.com:9000".parse().unwrap();
+        assert_eq!(config.addr, "relay.example.com:9000");
+        assert_eq!(config.latency, Duration::from_millis(120));
+        assert_eq!(config.overhead, 25);
+        assert_eq!(config.stream_id, None);
+
+        let config: SrtConfig =
+            "srt://10.0.0.2:9000?latency=400&overhead=50&streamid=cam1&mode=caller"
+                .parse()
+                .unwrap();
+        assert_eq!(config.latency, Duration::from_millis(400));
+        assert_eq!(config.overhead, 50);
+        assert_eq!(config.stream_id.as_deref(), Some("cam1"));
+        assert_eq!(
+            config.to_string(),
+            "srt://10.0.0.2:9000?latency=400&overhead=50&streamid=cam1"
+        );
+        assert_eq!(config.to_string().parse::<SrtConfig>(), Ok(config));
+    }
+
+    #[test]
+    fn rejects_bad_urls() {
+        for (url, error) in [
+            ("udp://10.0.0.2:9000", "srt://"),
+            ("srt://10.0.0.2", "host:port"),
+            ("srt://:9000", "host:port"),
+            ("srt://10.0.0.2:9000?latency=fast", "whole number"),
+            ("srt://10.0.0.2:9000?overhead=200", "5 to 100"),
+            ("srt://10.0.0.2:9000?mode=listener", "only caller"),
+            (
+                "srt://10.0.0.2:9000?passphrase=secret",
+                "unknown SRT option",
+            ),
+            ("srt://10.0.0.2:9000?latency", "needs a value"),
+        ] {
+            let err = url.parse::<SrtConfig>().unwrap_err();
+            assert!(err.contains(error), "{url}: {err}");
+        }
+    }
+
+    #[test]
+    fn splits_access_units_into_messages() {
+        let au = vec![7u8; MESSAGE_SIZE * 2 + 10];
+        let sizes: Vec<usize> = messages(None, &au).iter().map(|m| m.len()).collect();
+        assert_eq!(sizes, [MESSAGE_SIZE, MESSAGE_SIZE, 10]);
+
+        let init = [0, 0, 0, 1, 0x67, 0, 0, 0, 1, 0x68];
+        let all = messages(Some(&init), &au[..20]);
+        assert_eq!(all, [&init[..], &au[..20]]);
+    }
+
+    #[test]
+    fn rate_follows_bitrate_preset() {
+        assert_eq!(expected_rate(H264Bitrate::Mbps5), 625_000);
+        assert_eq!(expected_rate(H264Bitrate::Auto), 3_125_000);
+    }
+
+    #[test]
+    fn listener_receives_an_encoded_frame() {
+        // An IDR access unit with its parameter sets, as the encoder emits
+        // at the start of the stream.
+        let mut au = vec![
+            0, 0, 0, 1, 0x67, 0x42, 0, 0x1f, 0, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80,
+        ];
+        au.extend([0, 0, 0, 1, 0x65]);
+        au.extend((0..3000u32).map(|i| (i % 251) as u8 | 1));
+
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let listener = tokio::spawn(SrtSocket::builder().listen_on(":39001"));
+                let keyframe = Arc::new(AtomicBool::new(false));
+                let config = "srt://127.0.0.1:39001?latency=50".parse().unwrap();
+                let output = SrtOutput::spawn(config, H264Bitrate::Mbps5, keyframe.clone());
+                let mut socket = listener.await.unwrap().unwrap();
+
+                // A delta before the first keyframe is held back.
+                output.send(&[0, 0, 0, 1, 0x41, 0x9a], false);
+                output.send(&au, true);
+                let mut received = Vec::new();
+                while received.len() < au.len() {
+                    let next = tokio::time::timeout(Duration::from_secs(5), socket.try_next());
+                    let (_, message) = next.await.unwrap().unwrap().unwrap();
+                    assert!(message.len() <= MESSAGE_SIZE);
+                    received.extend_from_slice(&message);
+                }
+                assert_eq!(received, au);
+                // The connection asked the encoder for a keyframe.
+                assert!(keyframe.load(Ordering::Relaxed));
+            });
+    }
+}
@@ -0,0 +1,197 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Runtime counters shared between the capture loop and the encoder
+//! threads.
+//!
+//! Everything is a relaxed atomic so updating a counter costs the hot path
+//! nothing more than an uncontended add. Readers (the optional Prometheus
+//! endpoint) take a consistent-enough snapshot field by field.
+
+use std::{
+    sync::atomic::{AtomicI32, AtomicU64, AtomicU8, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Process-wide counters, see [`STATS`].
+pub struct Stats {
+    frames: AtomicU64,
+    dropped_frames: AtomicU64,
+    duplicate_frames: AtomicU64,
+    /// Capture rate in millihertz, so it fits an integer atomic.
+    fps_milli: AtomicU64,
+    encode_latency_us: AtomicU64,
+    h264_bytes: AtomicU64,
+    h264_bitrate: AtomicU64,
+    /// `--thermal-zone` reading in millidegrees Celsius.
+    temperature_milli: AtomicI32,
+    thermal_level: AtomicU8,
+}
+
+pub static STATS: Stats = Stats::new();
+
+impl Stats {
+    pub const fn new() -> Self {
+        Self {
+            frames: AtomicU64::new(0),
+            dropped_frames: AtomicU64::new(0),
+            duplicate_frames: AtomicU64::new(0),
+            fps_milli: AtomicU64::new(0),
+            encode_latency_us: AtomicU64::new(0),
+            h264_bytes: AtomicU64::new(0),
+            h264_bitrate: AtomicU64::new(0),
+            temperature_milli: AtomicI32::new(0),
+            thermal_level: AtomicU8::new(0),
+        }
+    }
+
+    /// Count a captured frame and record the current capture rate.
+    pub fn record_frame(&self, fps: f64) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.fps_milli
+            .store((fps.max(0.0) * 1000.0) as u64, Ordering::Relaxed);
+    }
+
+    /// Count a frame an output dropped because its encoder was busy.
+    pub fn record_drop(&self) {
+        self.dropped_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a frame that repeated the previous one (`--dedup-frames`).
+    pub fn record_duplicate(&self) {
+        self.duplicate_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record one H.264 encode of `bytes` bytes that took `latency`.
+    pub fn record_encode(&self, latency: Duration, bytes: usize) {
+        self.encode_latency_us
+            .store(latency.as_micros() as u64, Ordering::Relaxed);
+        self.h264_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_h264_bitrate(&self, bits_per_second: u64) {
+        self.h264_bitrate.store(bits_per_second, Ordering::Relaxed);
+    }
+
+    /// Record a `--thermal-zone` reading and the level the governor chose.
+    pub fn set_thermal(&self, milli_celsius: i32, level: u8) {
+        self.temperature_milli
+            .store(milli_celsius, Ordering::Relaxed);
+        self.thermal_level.store(level, Ordering::Relaxed);
+    }
+}
+
+// Read back only by the `metrics` endpoint.
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+impl Stats {
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames.load(Ordering::Relaxed)
+    }
+
+    pub fn duplicate_frames(&self) -> u64 {
+        self.duplicate_frames.load(Ordering::Relaxed)
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.fps_milli.load(Ordering::Relaxed) as f64 / 1000.0
+    }
+
+    /// Duration of the most recent H.264 encode.
+    pub fn encode_latency(&self) -> Duration {
+        Duration::from_micros(self.encode_latency_us.load(Ordering::Relaxed))
+    }
+
+    /// Total H.264 bytes produced.
+    pub fn h264_bytes(&self) -> u64 {
+        self.h264_bytes.load(Ordering::Relaxed)
+    }
+
+    /// H.264 output rate over the last measurement window.
+    pub fn h264_bitrate(&self) -> u64 {
+        self.h264_bitrate.load(Ordering::Relaxed)
+    }
+
+    /// Last `--thermal-zone` reading in degrees Celsius.
+    pub fn temperature(&self) -> f64 {
+        self.temperature_milli.load(Ordering::Relaxed) as f64 / 1000.0
+    }
+
+    /// Outputs shed by the thermal governor, 0 for none.
+    pub fn thermal_level(&self) -> u8 {
+        self.thermal_level.load(Ordering::Relaxed)
+    }
+}
+
+/// Turns a stream of encoded frame sizes into a bitrate, reported once per
+/// `window`.
+pub struct BitrateWindow {
+    window: Duration,
+    start: Instant,
+    bytes: u64,
+}
+
+impl BitrateWindow {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    /// Add `bytes` produced at `now`. Returns the bitrate in bits per second
+    /// when a window completes.
+    pub fn add(&mut self, bytes: usize, now: Instant) -> Option<u64> {
+        self.bytes += bytes as u64;
+        let elapsed = now.duration_since(self.start);
+        if elapsed < self.window {
+            return None;
+        }
+        let bitrate = (self.bytes * 8) as f64 / elapsed.as_secs_f64();
+        self.start = now;
+        self.bytes = 0;
+        Some(bitrate as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_accumulate() {
+        let stats = Stats::new();
+        stats.record_frame(29.97);
+        stats.record_frame(30.0);
+        stats.record_drop();
+        stats.record_encode(Duration::from_micros(4500), 1000);
+        stats.record_encode(Duration::from_micros(5200), 500);
+
+        assert_eq!(stats.frames(), 2);
+        assert_eq!(stats.dropped_frames(), 1);
+        assert_eq!(stats.fps(), 30.0);
+        assert_eq!(stats.encode_latency(), Duration::from_micros(5200));
+        assert_eq!(stats.h264_bytes(), 1500);
+    }
+
+    #[test]
+    fn bitrate_window_reports_once_per_window() {
+        let start = Instant::now();
+        let mut window = BitrateWindow {
+            window: Duration::from_secs(1),
+            start,
+            bytes: 0,
+        };
+        assert_eq!(window.add(50_000, start + Duration::from_millis(500)), None);
+        assert_eq!(
+            window.add(75_000, start + Duration::from_secs(1)),
+            Some(1_000_000)
+        );
+        // The next window starts empty.
+        assert_eq!(window.add(10, start + Duration::from_millis(1500)), None);
+    }
+}
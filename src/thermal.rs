@@ -0,0 +1,274 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! `--thermal-zone`: shed the heaviest outputs while the SoC runs hot.
+//!
+//! A governor thread reads `/sys/class/thermal/thermal_zoneN/temp` once a
+//! second and maps the temperature to a [`ThermalLevel`]. Each step of
+//! `--thermal-step` above `--thermal-threshold` sheds one more output, the
+//! heaviest first: the 4K tiles, then the `--hires-interval` JPEGs, then
+//! the H.264 bitrate drops one preset. The level falls back only once the
+//! temperature is `--thermal-hysteresis` below where it was entered, so a
+//! zone hovering at a threshold does not toggle outputs every second.
+//!
+//! The encoder threads read the level from [`THERMAL`] before each frame.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU8, Ordering},
+    thread,
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::{args::H264Bitrate, stats::STATS};
+
+/// How far the governor has throttled the outputs, each level including
+/// the ones below it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ThermalLevel {
+    /// Every output runs.
+    #[default]
+    Normal,
+    /// The `--h264-tiles` outputs are paused.
+    NoTiles,
+    /// The `--hires-interval` JPEGs are paused as well.
+    NoHires,
+    /// The H.264 outputs also encode one bitrate preset lower.
+    LowBitrate,
+}
+
+impl ThermalLevel {
+    const ALL: [Self; 4] = [Self::Normal, Self::NoTiles, Self::NoHires, Self::LowBitrate];
+
+    fn from_u8(level: u8) -> Self {
+        Self::ALL[(level as usize).min(Self::ALL.len() - 1)]
+    }
+
+    pub fn tiles(self) -> bool {
+        self < Self::NoTiles
+    }
+
+    pub fn hires(self) -> bool {
+        self < Self::NoHires
+    }
+
+    /// The bitrate an H.264 output configured with `bitrate` encodes at.
+    pub fn h264_bitrate(self, bitrate: H264Bitrate) -> H264Bitrate {
+        if self < Self::LowBitrate {
+            return bitrate;
+        }
+        match bitrate {
+            H264Bitrate::Mbps100 => H264Bitrate::Mbps50,
+            H264Bitrate::Mbps50 => H264Bitrate::Mbps25,
+            H264Bitrate::Auto | H264Bitrate::Mbps25 | H264Bitrate::Mbps5 => H264Bitrate::Mbps5,
+        }
+    }
+}
+
+/// Maps zone temperatures to a [`ThermalLevel`] with hysteresis. All
+/// temperatures are in millidegrees Celsius, as sysfs reports them.
+#[derive(Clone, Debug)]
+pub struct ThermalGovernor {
+    threshold: i32,
+    step: i32,
+    hysteresis: i32,
+    level: ThermalLevel,
+}
+
+impl ThermalGovernor {
+    /// Level [`ThermalLevel::NoTiles`] starts at `threshold` and each
+    /// further level `step` above the previous one. A level is left once
+    /// the temperature falls `hysteresis` below its start.
+    pub fn new(threshold: i32, step: i32, hysteresis: i32) -> Self {
+        Self {
+            threshold,
+            step: step.max(1),
+            hysteresis: hysteresis.max(0),
+            level: ThermalLevel::Normal,
+        }
+    }
+
+    pub fn level(&self) -> ThermalLevel {
+        self.level
+    }
+
+    /// The temperature `level` starts at.
+    fn start(&self, level: ThermalLevel) -> i32 {
+        self.threshold + (level as i32 - 1) * self.step
+    }
+
+    /// Feed a new temperature reading and return the resulting level.
+    pub fn update(&mut self, temp: i32) -> ThermalLevel {
+        let reached = ThermalLevel::ALL
+            .into_iter()
+            .skip(1)
+            .take_while(|&level| temp >= self.start(level))
+            .last()
+            .unwrap_or_default();
+        if reached > self.level {
+            self.level = reached;
+        }
+        while self.level > ThermalLevel::Normal && temp < self.start(self.level) - self.hysteresis {
+            self.level = ThermalLevel::from_u8(self.level as u8 - 1);
+        }
+        self.level
+    }
+}
+
+/// The governor's current level, read by the encoder threads.
+pub struct Thermal {
+    level: AtomicU8,
+}
+
+/// The level of the process's `--thermal-zone` governor, `Normal` when
+/// none runs.
+pub static THERMAL: Thermal = Thermal {
+    level: AtomicU8::new(ThermalLevel::Normal as u8),
+};
+
+impl Thermal {
+    pub fn level(&self) -> ThermalLevel {
+        ThermalLevel::from_u8(self.level.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, level: ThermalLevel) {
+        self.level.store(level as u8, Ordering::Relaxed);
+    }
+}
+
+/// The sysfs file of thermal zone `zone`.
+pub fn zone_path(zone: u32) -> PathBuf {
+    PathBuf::from(format!("/sys/class/thermal/thermal_zone{zone}/temp"))
+}
+
+/// Read a sysfs zone temperature, in millidegrees Celsius.
+pub fn read_temp(path: &Path) -> io::Result<i32> {
+    let text = fs::read_to_string(path)?;
+    text.trim().parse().map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {text:?} is not a temperature: {e}", path.display()),
+        )
+    })
+}
+
+/// Run `governor` on the temperature at `path` once every `interval` on a
+/// thread of its own, publishing the level in [`THERMAL`] and the stats.
+///
+/// # Errors
+///
+/// Returns an error if the zone cannot be read at startup or the thread
+/// cannot be spawned.
+pub fn spawn(
+    path: PathBuf,
+    mut governor: ThermalGovernor,
+    interval: Duration,
+) -> io::Result<thread::JoinHandle<()>> {
+    read_temp(&path)?;
+    info!("Thermal governor watching {}", path.display());
+    thread::Builder::new()
+        .name("thermal".to_string())
+        .spawn(move || {
+            let mut failing = false;
+            loop {
+                match read_temp(&path) {
+                    Ok(temp) => {
+                        failing = false;
+                        let before = governor.level();
+                        let level = governor.update(temp);
+                        if level != before {
+                            let celsius = temp as f64 / 1000.0;
+                            match level > before {
+                                true => warn!("{celsius:.1} °C, thermal level {level:?}"),
+                                false => info!("{celsius:.1} °C, thermal level {level:?}"),
+                            }
+                        }
+                        THERMAL.set(level);
+                        STATS.set_thermal(temp, level as u8);
+                    }
+                    Err(e) if !failing => {
+                        warn!("Cannot read the thermal zone, keeping the last level: {e}");
+                        failing = true;
+                    }
+                    Err(_) => {}
+                }
+                thread::sleep(interval);
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 80 °C threshold with 5 °C steps and hysteresis.
+    fn governor() -> ThermalGovernor {
+        ThermalGovernor::new(80_000, 5_000, 5_000)
+    }
+
+    #[test]
+    fn sheds_outputs_heaviest_first_and_restores_with_hysteresis() {
+        let mut governor = governor();
+        let outputs = |level: ThermalLevel| {
+            (
+                level.tiles(),
+                level.hires(),
+                level.h264_bitrate(H264Bitrate::Mbps50),
+            )
+        };
+        // (temperature, tiles, hires, H.264 bitrate)
+        let readings = [
+            (70_000, true, true, H264Bitrate::Mbps50),
+            (80_000, false, true, H264Bitrate::Mbps50),
+            // Hovering around the threshold keeps the tiles off.
+            (79_000, false, true, H264Bitrate::Mbps50),
+            (81_000, false, true, H264Bitrate::Mbps50),
+            (86_000, false, false, H264Bitrate::Mbps50),
+            (91_000, false, false, H264Bitrate::Mbps25),
+            (99_000, false, false, H264Bitrate::Mbps25),
+            // Cooling restores one output per step once past hysteresis.
+            (86_500, false, false, H264Bitrate::Mbps25),
+            (84_000, false, false, H264Bitrate::Mbps50),
+            (79_500, false, true, H264Bitrate::Mbps50),
+            (75_500, false, true, H264Bitrate::Mbps50),
+            (74_900, true, true, H264Bitrate::Mbps50),
+        ];
+        for (temp, tiles, hires, bitrate) in readings {
+            let level = governor.update(temp);
+            assert_eq!(outputs(level), (tiles, hires, bitrate), "{temp} m°C");
+        }
+
+        // A sudden spike goes straight to the top level, and a sudden drop
+        // straight back.
+        assert_eq!(governor.update(120_000), ThermalLevel::LowBitrate);
+        assert_eq!(governor.update(30_000), ThermalLevel::Normal);
+    }
+
+    #[test]
+    fn bitrate_drops_one_preset() {
+        let level = ThermalLevel::LowBitrate;
+        assert_eq!(
+            level.h264_bitrate(H264Bitrate::Mbps100),
+            H264Bitrate::Mbps50
+        );
+        assert_eq!(level.h264_bitrate(H264Bitrate::Auto), H264Bitrate::Mbps5);
+        assert_eq!(level.h264_bitrate(H264Bitrate::Mbps5), H264Bitrate::Mbps5);
+        let cool = ThermalLevel::NoHires;
+        assert_eq!(cool.h264_bitrate(H264Bitrate::Auto), H264Bitrate::Auto);
+    }
+
+    #[test]
+    fn reads_sysfs_millidegrees() {
+        let dir = std::env::temp_dir().join(format!("thermal-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("temp");
+        fs::write(&path, "48250\n").unwrap();
+        assert_eq!(read_temp(&path).unwrap(), 48_250);
+        fs::write(&path, "hot\n").unwrap();
+        assert!(read_temp(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(read_temp(&path).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! `--timing-log`: per-stage timings from the tracing spans, without Tracy.
+//!
+//! The hot paths are already wrapped in `info_span!` for Tracy. A
+//! [`TimingLayer`] times the spans of the stages in [`TIMED_SPANS`] from
+//! enter to exit, and a reporter thread logs their average and p99 once
+//! per period, then starts a fresh period:
+//!
+//! ```text
+//! camera_read: 300 spans, avg 1.21 ms, p99 2.87 ms
+//! h264_encode: 300 spans, avg 9.84 ms, p99 12.40 ms
+//! ```
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{info, span, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+/// Spans whose durations are aggregated.
+pub const TIMED_SPANS: [&str; 5] = [
+    "camera_read",
+    "jpeg_convert",
+    "jpeg_encode",
+    "h264_resize",
+    "h264_encode",
+];
+
+/// Aggregate of one stage over a period.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StageTiming {
+    pub name: &'static str,
+    pub count: usize,
+    pub avg: Duration,
+    pub p99: Duration,
+}
+
+/// Span durations collected since the last [`SpanTimings::take`].
+#[derive(Default)]
+pub struct SpanTimings {
+    samples: Mutex<HashMap<&'static str, Vec<Duration>>>,
+}
+
+impl SpanTimings {
+    pub fn record(&self, name: &'static str, duration: Duration) {
+        self.samples
+            .lock()
+            .unwrap()
+            .entry(name)
+            .or_default()
+            .push(duration);
+    }
+
+    /// Summarise the stages that ran since the last call, in
+    /// [`TIMED_SPANS`] order, and start a new period.
+    pub fn take(&self) -> Vec<StageTiming> {
+        let mut samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        TIMED_SPANS
+            .iter()
+            .filter_map(|name| {
+                let mut durations = samples.remove(name)?;
+                durations.sort_unstable();
+                let count = durations.len();
+                let total: Duration = durations.iter().sum();
+                // Nearest-rank percentile.
+                let p99 = durations[(count * 99).div_ceil(100) - 1];
+                Some(StageTiming {
+                    name,
+                    count,
+                    avg: total / count as u32,
+                    p99,
+                })
+            })
+            .collect()
+    }
+}
+
+/// When the current entry of a timed span started.
+struct Entered(Instant);
+
+/// Records the time between entering and exiting each span in
+/// [`TIMED_SPANS`] into a [`SpanTimings`].
+pub struct TimingLayer {
+    timings: Arc<SpanTimings>,
+}
+
+impl TimingLayer {
+    pub fn new(timings: Arc<SpanTimings>) -> Self {
+        Self { timings }
+    }
+}
+
+impl<S> Layer<S> for TimingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        if TIMED_SPANS.contains(&span.name()) {
+            span.extensions_mut().replace(Entered(Instant::now()));
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let entered = span.extensions_mut().remove::<Entered>();
+        if let Some(Entered(start)) = entered {
+            self.timings.record(span.name(), start.elapsed());
+        }
+    }
+}
+
+/// Log the stage timings collected by a [`TimingLayer`] every `period`
+/// from a background thread. Stages that did not run in a period are left
+/// out of it.
+pub fn spawn_reporter(timings: Arc<SpanTimings>, period: Duration) -> Result<(), Box<dyn Error>> {
+    thread::Builder::new()
+        .name("timing-log".to_string())
+        .spawn(move || loop {
+            thread::sleep(period);
+            for stage in timings.take() {
+                info!(
+                    "{}: {} spans, avg {:.2} ms, p99 {:.2} ms",
+                    stage.name,
+                    stage.count,
+                    stage.avg.as_secs_f64() * 1e3,
+                    stage.p99.as_secs_f64() * 1e3
+                );
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::info_span;
+    use tracing_subscriber::{layer::SubscriberExt as _, Registry};
+
+    #[test]
+    fn layer_captures_span_duration() {
+        let timings = Arc::new(SpanTimings::default());
+        let subscriber = Registry::default().with(TimingLayer::new(timings.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            info_span!("h264_encode").in_scope(|| thread::sleep(Duration::from_millis(20)));
+            // Spans outside the timed stages are ignored.
+            info_span!("h264_publish").in_scope(|| thread::sleep(Duration::from_millis(1)));
+        });
+
+        let stages = timings.take();
+        assert_eq!(stages.len(), 1);
+        assert_eq!(stages[0].name, "h264_encode");
+        assert_eq!(stages[0].count, 1);
+        assert!(stages[0].avg >= Duration::from_millis(20));
+        assert_eq!(stages[0].p99, stages[0].avg);
+        // The next period starts empty.
+        assert!(timings.take().is_empty());
+    }
+
+    #[test]
+    fn aggregate_reports_avg_and_p99() {
+        let timings = SpanTimings::default();
+        for ms in 1..=100 {
+            timings.record("camera_read", Duration::from_millis(ms));
+        }
+        timings.record("jpeg_encode", Duration::from_millis(7));
+
+        let stages = timings.take();
+        assert_eq!(
+            stages,
+            [
+                StageTiming {
+                    name: "camera_read",
+                    count: 100,
+                    avg: Duration::from_micros(50_500),
+                    p99: Duration::from_millis(99),
+                },
+                StageTiming {
+                    name: "jpeg_encode",
+                    count: 1,
+                    avg: Duration::from_millis(7),
+                    p99: Duration::from_millis(7),
+                },
+            ]
+        );
+    }
+}
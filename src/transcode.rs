@@ -0,0 +1,548 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Transcoder mode: encode frames published by another camera node.
+//!
+//! With `--source dma:<topic>` the node does not open a V4L2 device.
+//! Instead it subscribes to the `edgefirst_msgs/CameraFrame` messages a
+//! capturing node publishes on `<topic>` (normally `camera/frame`), imports
+//! the advertised DMA-BUF from the publishing process, and feeds it through
+//! the same pipeline sinks and H.264 / JPEG encoder threads as a live
+//! capture. Nothing is republished on the frame, info, or TF topics; the
+//! capturing node already owns those.
+//!
+//! The buffer is imported with `pidfd_getfd(2)`, which needs Linux 5.6+
+//! and ptrace access to the publisher: both nodes running as the same user
+//! with `kernel.yama.ptrace_scope` at 0, or `CAP_SYS_PTRACE`.
+//!
+//! `--test-pattern` drives the same outputs from a synthetic
+//! [`PatternSource`] instead, for bring-up without any camera.
+
+use std::{
+    error::Error,
+    fmt, io,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use edgefirst_camera::{
+    config::TestPattern,
+    pattern::PatternSource,
+    pipeline::{is_interrupted, CameraSource, CapturedFrame, Pipeline},
+    remote::{decode_camera_frame, import_camera_frame, RemoteFrame},
+};
+use tracing::{info, info_span, warn};
+use zenoh::{handlers::FifoChannelHandler, pubsub::Subscriber, sample::Sample, Session};
+
+use crate::{
+    add_encoder_output, add_h264_variants, args::Args, bind_unix_socket, encode_channel,
+    spawn_h264_thread, spawn_jpeg_thread, ClockOffset, OutputThreads, ENCODE_QUEUE_DEPTH, SHUTDOWN,
+};
+
+/// How long a read waits for a frame before giving the caller a chance to
+/// notice a shutdown request.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Where the node gets its frames from (`--source`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameSource {
+    /// Capture from the V4L2 device given by `--camera`.
+    Camera,
+    /// Import the DMA buffers advertised on a `CameraFrame` topic.
+    Dma(String),
+}
+
+impl FromStr for FrameSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "camera" {
+            return Ok(FrameSource::Camera);
+        }
+        match s.strip_prefix("dma:") {
+            Some("") => Err("dma source requires a topic, e.g. dma:camera/frame".to_string()),
+            Some(topic) => Ok(FrameSource::Dma(topic.to_string())),
+            None => Err(format!(
+                "unknown source '{s}', expected 'camera' or 'dma:<topic>'"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FrameSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameSource::Camera => write!(f, "camera"),
+            FrameSource::Dma(topic) => write!(f, "dma:{topic}"),
+        }
+    }
+}
+
+/// A [`CameraSource`] fed by `CameraFrame` messages from another process.
+pub(crate) struct DmaSource {
+    subscriber: Subscriber<FifoChannelHandler<Sample>>,
+    clock_offset: ClockOffset,
+}
+
+impl DmaSource {
+    pub async fn subscribe(
+        session: &Session,
+        topic: &str,
+        clock_offset: ClockOffset,
+    ) -> Result<Self, Box<dyn Error>> {
+        let subscriber = session
+            .declare_subscriber(topic.to_string())
+            .await
+            .map_err(|e| format!("Cannot subscribe to {topic}: {e}"))?;
+        Ok(Self {
+            subscriber,
+            clock_offset,
+        })
+    }
+
+    fn import(&self, frame: &RemoteFrame) -> Result<CapturedFrame, Box<dyn Error>> {
+        let image = import_camera_frame(frame, std::process::id())?;
+        Ok(CapturedFrame {
+            fd: image.raw_fd(),
+            image,
+            timestamp: self.clock_offset.to_monotonic(&frame.stamp),
+            sequence: frame.seq,
+            stride: frame.stride,
+            length: frame.size,
+        })
+    }
+}
+
+impl CameraSource for DmaSource {
+    /// Wait for the next decodable frame. Returns an interrupted error when
+    /// nothing arrives within [`POLL_INTERVAL`] so the caller can check for
+    /// shutdown; undecodable messages are logged and skipped.
+    fn read_frame(&mut self) -> Result<CapturedFrame, Box<dyn Error>> {
+        self.read_frame_timeout(POLL_INTERVAL)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Interrupted, "no frame within poll interval").into()
+        })
+    }
+
+    fn read_frame_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<CapturedFrame>, Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let sample = match self.subscriber.recv_timeout(remaining) {
+                Ok(Some(sample)) => sample,
+                Ok(None) => return Ok(None),
+                Err(e) => return Err(format!("frame subscriber closed: {e}").into()),
+            };
+
+            let frame = match decode_camera_frame(&sample.payload().to_bytes()) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    warn!("Dropping undecodable CameraFrame: {e}");
+                    continue;
+                }
+            };
+            return self.import(&frame).map(Some);
+        }
+    }
+
+    fn restart(&mut self) -> Result<(), Box<dyn Error>> {
+        // Nothing to reopen: the publisher owns the device. Keep waiting.
+        Ok(())
+    }
+}
+
+/// Reject options that need a local camera or are not wired up for
+/// transcoding or `--test-pattern`.
+pub(crate) fn validate_transcode_args(args: &Args) -> Result<(), Box<dyn Error>> {
+    let mode = match (&args.source, args.test_pattern) {
+        (FrameSource::Camera, None) => return Ok(()),
+        (FrameSource::Camera, Some(_)) => "--test-pattern",
+        (FrameSource::Dma(_), None) => "--source dma:",
+        (FrameSource::Dma(_), Some(_)) => {
+            return Err("--test-pattern cannot be combined with --source dma:".into())
+        }
+    };
+    if args.replay.is_some() {
+        return Err(format!("--replay cannot be combined with {mode}").into());
+    }
+    if args.record.is_some() {
+        return Err(format!("--record is not supported with {mode}").into());
+    }
+    if args.h264_tiles {
+        return Err(format!("--h264-tiles is not supported with {mode}").into());
+    }
+    if args.v4l2_output.is_some() {
+        return Err(format!("--v4l2-output is not supported with {mode}").into());
+    }
+    if !args.h264 && !args.jpeg && args.h264_variant.is_empty() {
+        return Err(
+            format!("{mode} needs at least one of --h264, --h264-variant or --jpeg").into(),
+        );
+    }
+    Ok(())
+}
+
+/// Run the node as a transcoder on `topic` until shutdown.
+pub async fn run_transcode(
+    session: Session,
+    args: Args,
+    topic: String,
+) -> Result<(), Box<dyn Error>> {
+    let clock_offset = ClockOffset::new()?;
+    let source = DmaSource::subscribe(&session, &topic, clock_offset).await?;
+    info!(
+        "Transcoding frames from {}, stream: {}x{}",
+        topic, args.stream_size[0], args.stream_size[1]
+    );
+    run_encoders(&session, &args, source, clock_offset)
+}
+
+/// Run the node on `--test-pattern` frames at `--camera-size` and
+/// `--target-fps` until shutdown.
+pub async fn run_test_pattern(
+    session: Session,
+    args: Args,
+    pattern: TestPattern,
+) -> Result<(), Box<dyn Error>> {
+    let clock_offset = ClockOffset::new()?;
+    let [width, height] = args.camera_size;
+    let source = PatternSource::new(pattern, width, height, args.target_fps)?;
+    info!(
+        "Test pattern {pattern:?} at {width}x{height}, {} fps, stream: {}x{}",
+        args.target_fps, args.stream_size[0], args.stream_size[1]
+    );
+    run_encoders(&session, &args, source, clock_offset)
+}
+
+/// Feed the frames of `source` to the H.264, JPEG and variant outputs until
+/// shutdown.
+fn run_encoders<S: CameraSource>(
+    session: &Session,
+    args: &Args,
+    source: S,
+    clock_offset: ClockOffset,
+) -> Result<(), Box<dyn Error>> {
+    let mut pipeline = Pipeline::new(source);
+    let shared = args.shared_convert_target();
+    let socket = bind_unix_socket(args)?;
+    let mut threads = OutputThreads::default();
+    if args.h264 {
+        let (tx, rx) = encode_channel(args, ENCODE_QUEUE_DEPTH);
+        threads.push(spawn_h264_thread(
+            session,
+            args,
+            rx,
+            clock_offset,
+            None,
+            socket.clone(),
+        )?);
+        add_encoder_output(&mut pipeline, shared, tx, "H264")?;
+    }
+    if args.jpeg {
+        let (tx, rx) = encode_channel(args, ENCODE_QUEUE_DEPTH);
+        threads.push(spawn_jpeg_thread(session, args, rx, clock_offset, socket)?);
+        add_encoder_output(&mut pipeline, shared, tx, "JPEG")?;
+    }
+    add_h264_variants(&mut pipeline, &mut threads, session, args, clock_offset)?;
+
+    while !SHUTDOWN.load(std::sync::atomic::Ordering::SeqCst) {
+        match info_span!("transcode_read").in_scope(|| pipeline.step()) {
+            Ok(_) => {}
+            Err(e) if is_interrupted(e.as_ref()) => continue,
+            Err(e) => warn!("Skipping frame: {e}"),
+        }
+    }
+
+    drop(pipeline);
+    threads.join();
+    info!("Shutdown complete");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_camera_frame_msg, Colorimetry};
+    use edgefirst_camera::{
+        image::{CameraError, Image, YUYV},
+        remote::{decode_dma_message, import_fd},
+    };
+    use edgefirst_schemas::builtin_interfaces;
+    use std::{
+        fs::File,
+        os::{fd::AsRawFd, unix::fs::MetadataExt},
+        process,
+    };
+    use videostream::fourcc::FourCC;
+
+    #[test]
+    fn parse_frame_source() {
+        assert_eq!("camera".parse(), Ok(FrameSource::Camera));
+        assert_eq!(
+            "dma:camera/frame".parse(),
+            Ok(FrameSource::Dma("camera/frame".to_string()))
+        );
+        assert_eq!(
+            "dma:rt/camera/dma".parse(),
+            Ok(FrameSource::Dma("rt/camera/dma".to_string()))
+        );
+        assert!("dma:".parse::<FrameSource>().is_err());
+        assert!("v4l2:/dev/video0".parse::<FrameSource>().is_err());
+    }
+
+    #[test]
+    fn test_pattern_needs_an_encoder_and_no_camera_options() {
+        use clap::Parser;
+
+        let parse = |extra: &[&str]| {
+            let base = ["edgefirst-camera", "--test-pattern", "colorbars"];
+            validate_transcode_args(&Args::parse_from(base.iter().chain(extra)))
+        };
+        assert!(parse(&["--h264"]).is_ok());
+        assert!(parse(&["--jpeg"]).is_ok());
+        let err = parse(&[]).unwrap_err().to_string();
+        assert!(err.contains("--test-pattern needs"), "{err}");
+        assert!(parse(&["--h264", "--h264-tiles"]).is_err());
+        assert!(parse(&["--h264", "--source", "dma:camera/frame"]).is_err());
+    }
+
+    #[test]
+    fn frame_source_display_round_trips() {
+        for s in ["camera", "dma:camera/frame"] {
+            assert_eq!(s.parse::<FrameSource>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn decode_published_camera_frame() {
+        let stamp = builtin_interfaces::Time {
+            sec: 1_700_000_000,
+            nanosec: 123_456_789,
+        };
+        let (msg, _) = build_camera_frame_msg(
+            stamp,
+            "camera",
+            42,
+            1234,
+            1920,
+            1080,
+            "YUYV",
+            17,
+            3840,
+            3840 * 1080,
+            3840 * 1080,
+            &Colorimetry::default(),
+        )
+        .unwrap();
+
+        let frame = decode_camera_frame(&msg.to_bytes()).unwrap();
+        assert_eq!(
+            (frame.stamp.sec, frame.stamp.nanosec),
+            (1_700_000_000, 123_456_789)
+        );
+        assert_eq!(frame.seq, 42);
+        assert_eq!(frame.pid, 1234);
+        assert_eq!((frame.width, frame.height), (1920, 1080));
+        assert_eq!(frame.format, FourCC(*b"YUYV"));
+        assert_eq!(frame.fd, 17);
+        assert_eq!(frame.stride, 3840);
+        assert_eq!(frame.size, 3840 * 1080);
+    }
+
+    #[test]
+    fn decode_rejects_frame_without_fd() {
+        let (msg, _) = build_camera_frame_msg(
+            builtin_interfaces::Time { sec: 0, nanosec: 0 },
+            "camera",
+            0,
+            1234,
+            640,
+            480,
+            "YUYV",
+            -1,
+            1280,
+            1280 * 480,
+            1280 * 480,
+            &Colorimetry::default(),
+        )
+        .unwrap();
+        assert!(decode_camera_frame(&msg.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn decode_dma_message_round_trips_published_frame() {
+        // Any open file stands in for the DMA-BUF: the same-process path
+        // only duplicates the fd.
+        let file = File::open("/proc/self/stat").unwrap();
+        let (msg, _) = build_camera_frame_msg(
+            builtin_interfaces::Time { sec: 0, nanosec: 0 },
+            "camera",
+            7,
+            process::id(),
+            640,
+            480,
+            "YUYV",
+            file.as_raw_fd(),
+            1280,
+            1280 * 480,
+            1280 * 480,
+            &Colorimetry::default(),
+        )
+        .unwrap();
+
+        let image = decode_dma_message(&msg.to_bytes(), process::id()).unwrap();
+        assert_eq!((image.width(), image.height()), (640, 480));
+        assert_eq!(image.format(), YUYV);
+        assert_ne!(image.raw_fd(), file.as_raw_fd());
+        let imported = File::from(image.fd().try_clone_to_owned().unwrap());
+        assert_eq!(
+            imported.metadata().unwrap().ino(),
+            file.metadata().unwrap().ino()
+        );
+    }
+
+    #[test]
+    fn decode_dma_message_keeps_padded_rows() {
+        // 640-pixel YUYV rows padded from 1280 to 1344 bytes, as the i.MX8
+        // ISP pads them to 64 bytes.
+        let file = File::open("/proc/self/stat").unwrap();
+        let frame = |stride: u32| {
+            build_camera_frame_msg(
+                builtin_interfaces::Time { sec: 0, nanosec: 0 },
+                "camera",
+                0,
+                process::id(),
+                640,
+                480,
+                "YUYV",
+                file.as_raw_fd(),
+                stride,
+                stride * 480,
+                stride * 480,
+                &Colorimetry::default(),
+            )
+            .unwrap()
+            .0
+            .to_bytes()
+            .into_owned()
+        };
+
+        let image = decode_dma_message(&frame(1344), process::id()).unwrap();
+        assert_eq!((image.width(), image.height()), (640, 480));
+        assert_eq!(image.stride(), 1344);
+        assert_eq!(image.size(), 1344 * 480);
+
+        let err = decode_dma_message(&frame(1000), process::id())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("stride is 1000"), "{err}");
+    }
+
+    #[test]
+    fn decode_dma_message_rejects_empty_frame() {
+        let (msg, _) = build_camera_frame_msg(
+            builtin_interfaces::Time { sec: 0, nanosec: 0 },
+            "camera",
+            0,
+            process::id(),
+            0,
+            0,
+            "YUYV",
+            0,
+            0,
+            0,
+            0,
+            &Colorimetry::default(),
+        )
+        .unwrap();
+        let err = decode_dma_message(&msg.to_bytes(), process::id()).unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<CameraError>(),
+                Some(CameraError::InvalidDimensions {
+                    width: 0,
+                    height: 0
+                })
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn import_fd_from_own_process() {
+        let file = std::fs::File::open("/proc/self/stat").unwrap();
+        let fd = file.as_raw_fd();
+        match import_fd(process::id(), fd) {
+            Ok(imported) => assert_ne!(imported.as_raw_fd(), fd),
+            // Kernels before 5.6 or a restrictive seccomp/yama policy.
+            Err(e) => println!("pidfd_getfd unavailable: {e}"),
+        }
+    }
+
+    #[test]
+    #[ignore = "requires a CMA heap, G2D and the Hantro H.264 encoder"]
+    fn dma_frame_drives_h264_output() {
+        use edgefirst_camera::{
+            config::H264Bitrate,
+            image::{ImageManager, Rotation},
+            video::VideoManager,
+        };
+
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let session = zenoh::open(zenoh::Config::default()).await.unwrap();
+                let topic = "test/transcode/frame";
+                let clock_offset = ClockOffset::new().unwrap();
+                let mut source = DmaSource::subscribe(&session, topic, clock_offset)
+                    .await
+                    .unwrap();
+
+                let (width, height) = (640, 480);
+                let image = Image::new(width, height, YUYV).unwrap();
+                let (msg, enc) = build_camera_frame_msg(
+                    builtin_interfaces::Time { sec: 1, nanosec: 0 },
+                    "camera",
+                    1,
+                    process::id(),
+                    width,
+                    height,
+                    "YUYV",
+                    image.raw_fd(),
+                    width * 2,
+                    image.size() as u32,
+                    image.size() as u32,
+                    &Colorimetry::default(),
+                )
+                .unwrap();
+                session.put(topic, msg).encoding(enc).await.unwrap();
+
+                let frame = tokio::task::block_in_place(|| loop {
+                    match source.read_frame() {
+                        Ok(frame) => break frame,
+                        Err(e) if is_interrupted(e.as_ref()) => continue,
+                        Err(e) => panic!("read_frame failed: {e}"),
+                    }
+                });
+                assert_eq!(frame.sequence, 1);
+                assert_eq!(frame.image.format(), YUYV);
+
+                let imgmgr = ImageManager::new().unwrap();
+                let mut vidmgr =
+                    VideoManager::builder(FourCC(*b"H264"), width as i32, height as i32)
+                        .bitrate(H264Bitrate::Auto)
+                        .build()
+                        .unwrap();
+                let img_h264 = vidmgr.new_input_image(width, height).unwrap();
+                let (data, is_key, _) = vidmgr
+                    .resize_and_encode(&frame.image, &imgmgr, &img_h264, Rotation::Rotation0)
+                    .unwrap();
+                assert!(!data.is_empty());
+                assert!(is_key, "first encoded frame must be a keyframe");
+            });
+    }
+}
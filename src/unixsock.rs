@@ -0,0 +1,254 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Local sink that serves the encoded JPEG and H.264 frames over a Unix
+//! domain socket, for consumers on the same host that do not want a Zenoh
+//! session.
+//!
+//! Every client connected to `--unix-socket` receives each frame encoded
+//! after it connected, as a 16-byte header followed by the payload:
+//!
+//! | Bytes   | Field                                                    |
+//! |---------|----------------------------------------------------------|
+//! | `0..4`  | Payload length, `u32` little-endian                      |
+//! | `4`     | Kind: `b'H'` for an H.264 Annex-B access unit, `b'J'` for a JPEG image, `b'P'` for a 16-bit PNG depth image |
+//! | `5..8`  | Zero                                                     |
+//! | `8..16` | Capture time in nanoseconds since the Unix epoch, `u64` little-endian |
+//!
+//! Each client has a queue of [`CLIENT_QUEUE`] frames. A client that falls
+//! that far behind loses the frames that do not fit rather than slowing
+//! the encoders down; an H.264 client should wait for the next keyframe
+//! after a loss.
+
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use kanal::Sender;
+use tracing::{info, warn};
+
+/// Frames queued per client before further frames are dropped for it.
+pub const CLIENT_QUEUE: usize = 8;
+
+/// Size of the header in front of every payload.
+pub const HEADER_LEN: usize = 16;
+
+/// What a frame on the socket carries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameKind {
+    H264,
+    Jpeg,
+    Png,
+}
+
+impl FrameKind {
+    fn tag(self) -> u8 {
+        match self {
+            FrameKind::H264 => b'H',
+            FrameKind::Jpeg => b'J',
+            FrameKind::Png => b'P',
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            b'H' => Some(FrameKind::H264),
+            b'J' => Some(FrameKind::Jpeg),
+            b'P' => Some(FrameKind::Png),
+            _ => None,
+        }
+    }
+}
+
+/// Header plus payload of one frame as written to the socket.
+pub fn encode_frame(kind: FrameKind, stamp_ns: u64, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&[kind.tag(), 0, 0, 0]);
+    frame.extend_from_slice(&stamp_ns.to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Parse a header written by [`encode_frame`] into the frame kind, capture
+/// time and payload length.
+pub fn decode_header(header: &[u8; HEADER_LEN]) -> Result<(FrameKind, u64, usize), Box<dyn Error>> {
+    let len = u32::from_le_bytes(header[0..4].try_into()?) as usize;
+    let kind = FrameKind::from_tag(header[4])
+        .ok_or_else(|| format!("unknown frame kind {:#04x}", header[4]))?;
+    let stamp_ns = u64::from_le_bytes(header[8..16].try_into()?);
+    Ok((kind, stamp_ns, len))
+}
+
+/// Read one frame from a client connection.
+pub fn read_frame(stream: &mut impl io::Read) -> Result<(FrameKind, u64, Vec<u8>), Box<dyn Error>> {
+    let mut header = [0u8; HEADER_LEN];
+    stream.read_exact(&mut header)?;
+    let (kind, stamp_ns, len) = decode_header(&header)?;
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    Ok((kind, stamp_ns, payload))
+}
+
+/// Queues of the connected clients, shared with the accept thread.
+type Clients = Arc<Mutex<Vec<Sender<Arc<[u8]>>>>>;
+
+/// The listening socket and its connected clients. Clones share both.
+#[derive(Clone)]
+pub struct UnixSocketOutput {
+    clients: Clients,
+}
+
+impl UnixSocketOutput {
+    /// Listen on `path`, replacing a socket left behind by an earlier run.
+    /// Any other kind of file at `path` is an error.
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        if let Ok(meta) = fs::symlink_metadata(path) {
+            if !meta.file_type().is_socket() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ));
+            }
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let output = Self {
+            clients: Arc::default(),
+        };
+        let clients = output.clients.clone();
+        thread::Builder::new()
+            .name("unix-socket".to_string())
+            .spawn(move || accept_loop(listener, clients))?;
+        info!("Serving encoded frames on {}", path.display());
+        Ok(output)
+    }
+
+    /// Queue `payload` for every connected client, dropping it for clients
+    /// whose queue is full and forgetting clients that disconnected.
+    pub fn send(&self, kind: FrameKind, stamp_ns: u64, payload: &[u8]) {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.is_empty() {
+            return;
+        }
+        let frame: Arc<[u8]> = encode_frame(kind, stamp_ns, payload).into();
+        clients.retain(|client| client.try_send(frame.clone()).is_ok());
+    }
+}
+
+fn accept_loop(listener: UnixListener, clients: Clients) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Unix socket accept failed: {e}");
+                continue;
+            }
+        };
+        let (tx, rx) = kanal::bounded(CLIENT_QUEUE);
+        let spawned = thread::Builder::new()
+            .name("unix-client".to_string())
+            .spawn(move || client_loop(stream, rx));
+        match spawned {
+            Ok(_) => clients.lock().unwrap().push(tx),
+            Err(e) => warn!("Cannot start Unix socket client thread: {e}"),
+        }
+    }
+}
+
+/// Write queued frames to one client until it disconnects. Returning drops
+/// the receiver, so the next [`UnixSocketOutput::send`] forgets the client.
+fn client_loop(mut stream: UnixStream, rx: kanal::Receiver<Arc<[u8]>>) {
+    while let Ok(frame) = rx.recv() {
+        if let Err(e) = stream.write_all(&frame) {
+            if e.kind() != io::ErrorKind::BrokenPipe {
+                warn!("Unix socket client write failed: {e}");
+            }
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn frame_round_trips() {
+        let frame = encode_frame(FrameKind::Jpeg, 1_700_000_000_123_456_789, b"\xff\xd8jpeg");
+        assert_eq!(frame.len(), HEADER_LEN + 6);
+        let (kind, stamp, payload) = read_frame(&mut frame.as_slice()).unwrap();
+        assert_eq!(kind, FrameKind::Jpeg);
+        assert_eq!(stamp, 1_700_000_000_123_456_789);
+        assert_eq!(payload, b"\xff\xd8jpeg");
+
+        let mut bad = frame.clone();
+        bad[4] = b'X';
+        assert!(read_frame(&mut bad.as_slice()).is_err());
+        // A truncated payload is an error, not a short frame.
+        assert!(read_frame(&mut &frame[..frame.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn client_receives_length_prefixed_frame() {
+        let path =
+            std::env::temp_dir().join(format!("edgefirst-camera-{}.sock", std::process::id()));
+        let output = UnixSocketOutput::bind(&path).unwrap();
+        let mut client = UnixStream::connect(&path).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // The accept thread registers the client asynchronously; frames
+        // sent before then are not queued for it.
+        let start = Instant::now();
+        while output.clients.lock().unwrap().is_empty() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "client not accepted"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+        output.send(FrameKind::H264, 42, &[0, 0, 0, 1, 0x65, 0x88]);
+
+        let (kind, stamp, payload) = read_frame(&mut client).unwrap();
+        assert_eq!(kind, FrameKind::H264);
+        assert_eq!(stamp, 42);
+        assert_eq!(payload, [0, 0, 0, 1, 0x65, 0x88]);
+
+        // A disconnected client is forgotten once a write to it fails.
+        drop(client);
+        let start = Instant::now();
+        while !output.clients.lock().unwrap().is_empty() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "client not dropped"
+            );
+            output.send(FrameKind::Jpeg, 43, b"jpeg");
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // Rebinding replaces the stale socket.
+        UnixSocketOutput::bind(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bind_refuses_to_replace_regular_file() {
+        let path =
+            std::env::temp_dir().join(format!("edgefirst-camera-{}.file", std::process::id()));
+        fs::write(&path, b"not a socket").unwrap();
+        assert!(UnixSocketOutput::bind(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
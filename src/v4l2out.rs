@@ -0,0 +1,297 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+//! Local interop sink that writes converted frames to a V4L2 output device,
+//! typically a `v4l2loopback` node such as `/dev/video10`.
+//!
+//! The device is configured once with `VIDIOC_S_FMT` on the
+//! `V4L2_BUF_TYPE_VIDEO_OUTPUT` queue and frames are then pushed with plain
+//! `write(2)`, which v4l2loopback accepts without any buffer negotiation.
+//! The driver is free to adjust the requested format, so the reply is
+//! checked by [`negotiate`] before any frame is written.
+
+use std::{
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    os::fd::AsRawFd,
+    path::Path,
+};
+use videostream::fourcc::FourCC;
+
+use edgefirst_camera::image::{RGBA, YUYV};
+
+/// `V4L2_BUF_TYPE_VIDEO_OUTPUT`
+const V4L2_BUF_TYPE_VIDEO_OUTPUT: u32 = 2;
+
+/// `V4L2_FIELD_NONE`
+const V4L2_FIELD_NONE: u32 = 1;
+
+/// `VIDIOC_S_FMT`, i.e. `_IOWR('V', 5, struct v4l2_format)`.
+const VIDIOC_S_FMT: u64 = 0xc0d0_5605;
+
+/// Pixel formats the output sink can feed from the G2D convert path.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Copy)]
+pub enum V4l2OutputFormat {
+    /// YUYV 4:2:2 packed, the most widely accepted format for webcam
+    /// consumers such as browsers and conferencing tools.
+    Yuyv,
+    /// RGBA 8-bit per channel.
+    Rgba,
+}
+
+impl V4l2OutputFormat {
+    pub fn fourcc(self) -> FourCC {
+        match self {
+            V4l2OutputFormat::Yuyv => YUYV,
+            V4l2OutputFormat::Rgba => RGBA,
+        }
+    }
+
+    fn bytes_per_pixel(self) -> u32 {
+        match self {
+            V4l2OutputFormat::Yuyv => 2,
+            V4l2OutputFormat::Rgba => 4,
+        }
+    }
+}
+
+/// Mirror of the kernel's `struct v4l2_pix_format`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct V4l2PixFormat {
+    width: u32,
+    height: u32,
+    pixelformat: u32,
+    field: u32,
+    bytesperline: u32,
+    sizeimage: u32,
+    colorspace: u32,
+    priv_: u32,
+    flags: u32,
+    ycbcr_enc: u32,
+    quantization: u32,
+    xfer_func: u32,
+}
+
+/// The `fmt` union of `struct v4l2_format`. Only `pix` is used; `raw` pads
+/// the union to the kernel's 200 bytes and 8-byte alignment.
+#[repr(C)]
+union V4l2FormatUnion {
+    pix: V4l2PixFormat,
+    raw: [u64; 25],
+}
+
+/// Mirror of the kernel's `struct v4l2_format`.
+#[repr(C)]
+struct V4l2Format {
+    type_: u32,
+    fmt: V4l2FormatUnion,
+}
+
+/// Output geometry agreed with the device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Negotiated {
+    /// Row pitch the device expects, at least `width * bpp`.
+    bytesperline: usize,
+    /// Bytes per `write(2)`; one complete frame.
+    sizeimage: usize,
+}
+
+/// Validate the format the driver returned from `VIDIOC_S_FMT` against what
+/// was requested. The driver may pad rows but must keep the pixel format and
+/// resolution, otherwise the converted frames cannot be written as-is.
+fn negotiate(
+    format: V4l2OutputFormat,
+    width: u32,
+    height: u32,
+    reply: &V4l2PixFormat,
+) -> Result<Negotiated, String> {
+    let fourcc = u32::from(format.fourcc());
+    if reply.pixelformat != fourcc {
+        return Err(format!(
+            "device does not accept {} (it proposed {})",
+            format.fourcc(),
+            FourCC::from(reply.pixelformat)
+        ));
+    }
+    if reply.width != width || reply.height != height {
+        return Err(format!(
+            "device changed the resolution from {}x{} to {}x{}",
+            width, height, reply.width, reply.height
+        ));
+    }
+
+    let row = width * format.bytes_per_pixel();
+    let bytesperline = reply.bytesperline.max(row);
+    let sizeimage = (bytesperline * height).max(reply.sizeimage);
+    Ok(Negotiated {
+        bytesperline: bytesperline as usize,
+        sizeimage: sizeimage as usize,
+    })
+}
+
+/// An open V4L2 output device configured for one fixed format.
+pub struct V4l2Output {
+    file: File,
+    format: V4l2OutputFormat,
+    width: u32,
+    height: u32,
+    negotiated: Negotiated,
+    /// Staging buffer used when the device pads rows beyond the tight pitch.
+    scratch: Vec<u8>,
+}
+
+impl V4l2Output {
+    /// Open `path` and set its output format to `format` at `width`x`height`.
+    pub fn open(
+        path: &Path,
+        format: V4l2OutputFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .map_err(|e| format!("Cannot open V4L2 output {:?}: {e}", path))?;
+
+        let mut fmt = V4l2Format {
+            type_: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+            fmt: V4l2FormatUnion { raw: [0; 25] },
+        };
+        fmt.fmt.pix = V4l2PixFormat {
+            width,
+            height,
+            pixelformat: u32::from(format.fourcc()),
+            field: V4L2_FIELD_NONE,
+            bytesperline: width * format.bytes_per_pixel(),
+            sizeimage: width * height * format.bytes_per_pixel(),
+            ..Default::default()
+        };
+
+        let ret = unsafe { libc::ioctl(file.as_raw_fd(), VIDIOC_S_FMT as _, &mut fmt) };
+        if ret < 0 {
+            return Err(format!(
+                "VIDIOC_S_FMT on {:?} failed (is it a v4l2loopback device?): {}",
+                path,
+                io::Error::last_os_error()
+            )
+            .into());
+        }
+
+        let reply = unsafe { fmt.fmt.pix };
+        let negotiated = negotiate(format, width, height, &reply)
+            .map_err(|e| format!("V4L2 output {:?}: {e}", path))?;
+
+        Ok(Self {
+            file,
+            format,
+            width,
+            height,
+            negotiated,
+            scratch: Vec::new(),
+        })
+    }
+
+    /// Write one tightly packed frame. Rows are re-pitched when the device
+    /// negotiated a padded `bytesperline`.
+    pub fn write_frame(&mut self, pix: &[u8]) -> io::Result<()> {
+        let row = (self.width * self.format.bytes_per_pixel()) as usize;
+        let height = self.height as usize;
+        if pix.len() < row * height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("frame is {} bytes, expected {}", pix.len(), row * height),
+            ));
+        }
+
+        if self.negotiated.bytesperline == row && self.negotiated.sizeimage == row * height {
+            return self.file.write_all(&pix[..row * height]);
+        }
+
+        self.scratch.clear();
+        self.scratch.resize(self.negotiated.sizeimage, 0);
+        for (y, src) in pix.chunks_exact(row).take(height).enumerate() {
+            let offset = y * self.negotiated.bytesperline;
+            self.scratch[offset..offset + row].copy_from_slice(src);
+        }
+        self.file.write_all(&self.scratch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(format: V4l2OutputFormat, width: u32, height: u32) -> V4l2PixFormat {
+        V4l2PixFormat {
+            width,
+            height,
+            pixelformat: u32::from(format.fourcc()),
+            field: V4L2_FIELD_NONE,
+            bytesperline: width * format.bytes_per_pixel(),
+            sizeimage: width * height * format.bytes_per_pixel(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn v4l2_format_matches_kernel_layout() {
+        assert_eq!(std::mem::size_of::<V4l2PixFormat>(), 48);
+        assert_eq!(std::mem::size_of::<V4l2Format>(), 208);
+    }
+
+    #[test]
+    fn negotiate_accepts_exact_reply() {
+        let r = reply(V4l2OutputFormat::Yuyv, 1280, 720);
+        let n = negotiate(V4l2OutputFormat::Yuyv, 1280, 720, &r).unwrap();
+        assert_eq!(n.bytesperline, 2560);
+        assert_eq!(n.sizeimage, 2560 * 720);
+    }
+
+    #[test]
+    fn negotiate_keeps_padded_pitch() {
+        let mut r = reply(V4l2OutputFormat::Rgba, 1000, 10);
+        r.bytesperline = 4096;
+        r.sizeimage = 4096 * 10;
+        let n = negotiate(V4l2OutputFormat::Rgba, 1000, 10, &r).unwrap();
+        assert_eq!(n.bytesperline, 4096);
+        assert_eq!(n.sizeimage, 40960);
+    }
+
+    #[test]
+    fn negotiate_ignores_undersized_driver_pitch() {
+        // Some drivers leave bytesperline at 0; fall back to the tight pitch.
+        let mut r = reply(V4l2OutputFormat::Rgba, 64, 4);
+        r.bytesperline = 0;
+        r.sizeimage = 0;
+        let n = negotiate(V4l2OutputFormat::Rgba, 64, 4, &r).unwrap();
+        assert_eq!(n.bytesperline, 256);
+        assert_eq!(n.sizeimage, 1024);
+    }
+
+    #[test]
+    fn negotiate_rejects_format_change() {
+        let r = reply(V4l2OutputFormat::Yuyv, 640, 480);
+        let err = negotiate(V4l2OutputFormat::Rgba, 640, 480, &r).unwrap_err();
+        assert!(err.contains("RGBA"), "unexpected error: {err}");
+    }
+
+    #[test]
+    fn negotiate_rejects_resolution_change() {
+        let r = reply(V4l2OutputFormat::Yuyv, 640, 480);
+        let err = negotiate(V4l2OutputFormat::Yuyv, 1920, 1080, &r).unwrap_err();
+        assert!(err.contains("640x480"), "unexpected error: {err}");
+    }
+
+    #[test]
+    #[ignore = "requires a v4l2loopback device at /dev/video10"]
+    fn write_frames_to_loopback() {
+        let mut out =
+            V4l2Output::open(Path::new("/dev/video10"), V4l2OutputFormat::Yuyv, 640, 480).unwrap();
+        let frame = vec![0x80u8; 640 * 480 * 2];
+        for _ in 0..10 {
+            out.write_frame(&frame).unwrap();
+        }
+    }
+}
@@ -1,9 +1,22 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright (c) 2025 Au-Zone Technologies. All Rights Reserved.
 
-use edgefirst_camera::image::{Image, ImageManager, Rotation};
-use std::{error::Error, os::raw::c_int};
-use tracing::{debug, info_span};
+//! Hardware H.264 and H.265 video encoding.
+//!
+//! ```no_run
+//! use edgefirst_camera::{config::H264Bitrate, video::VideoManager};
+//! use videostream::fourcc::FourCC;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let video_mgr = VideoManager::builder(FourCC(*b"H264"), 1280, 720)
+//!     .bitrate(H264Bitrate::Auto)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{error::Error, os::raw::c_int, time::Duration};
+use tracing::{debug, info_span, warn};
 use tracy_client::plot;
 use videostream::{
     encoder::{Encoder, VSLEncoderProfileEnum, VSLRect},
@@ -11,132 +24,208 @@ use videostream::{
     frame::Frame,
 };
 
-use crate::{args::H264Bitrate, TARGET_FPS};
+use crate::{
+    config::{Codec, H264Bitrate},
+    image::{Image, ImageManager, Rotation, RGBA},
+    nal,
+    sps::{self, SampleAspectRatio},
+};
+
+/// Frame rate the camera node captures and encodes at unless told otherwise.
+pub const TARGET_FPS: i32 = 30;
+
+/// Encoded width must be a multiple of this. The encoder reads the input
+/// with a row stride equal to the width and needs it 8-pixel aligned.
+pub const WIDTH_ALIGNMENT: i32 = 8;
 
-/// Manager for hardware H.264 video encoding operations.
+/// Encoded height must be a multiple of this (whole 4:2:0 chroma rows).
+/// Heights that are not a whole number of 16-line macroblocks, such as
+/// 1080, are padded by the encoder and cropped again in the SPS.
+pub const HEIGHT_ALIGNMENT: i32 = 2;
+
+/// Most B-frames the encoder can place between reference frames. The
+/// Hantro H1 only produces I and P frames, so its output never needs
+/// reordering.
+pub const MAX_B_FRAMES: u32 = 0;
+
+/// Staging format [`VideoManager::resize_and_encode`] converts into for the
+/// Hantro H1, which takes RGBA and does the colour conversion itself.
+pub const H264_INPUT_FORMAT: FourCC = RGBA;
+
+/// Manager for hardware H.264 and H.265 video encoding operations.
 ///
-/// `VideoManager` provides an interface to the NXP hardware H.264 encoder
-/// (Hantro H1) for real-time video compression. It supports configurable
+/// `VideoManager` provides an interface to the NXP hardware encoder
+/// (Hantro) for real-time video compression. It supports configurable
 /// bitrates, cropping, and tiling for high-resolution cameras.
 ///
 /// # Example
 ///
 /// ```no_run
-/// use edgefirst_camera::{
-///     image::{Image, NV12},
-///     video::VideoManager,
-/// };
+/// use edgefirst_camera::video::VideoManager;
 /// use videostream::fourcc::FourCC;
-/// # use edgefirst_camera::args::H264Bitrate;
+/// # use edgefirst_camera::config::H264Bitrate;
 ///
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-/// let mut video_mgr = VideoManager::new(FourCC(*b"H264"), 1920, 1080, H264Bitrate::Mbps25)?;
+/// let mut video_mgr = VideoManager::builder(FourCC(*b"H264"), 1920, 1080)
+///     .bitrate(H264Bitrate::Mbps25)
+///     .build()?;
 ///
-/// // Encode a frame (must be in NV12 format)
-/// let nv12_image = Image::new(1920, 1080, NV12)?;
-/// let (h264_data, is_keyframe) = video_mgr.encode_direct(&nv12_image)?;
+/// // Encode a frame (must be in the encoder's input format)
+/// let image = video_mgr.new_input_image(1920, 1080)?;
+/// let (h264_data, is_keyframe, _pts) = video_mgr.encode_direct(&image)?;
 /// # Ok(())
 /// # }
 /// ```
 pub struct VideoManager {
     encoder: Encoder,
+    codec: Codec,
     crop: VSLRect,
     output_frame: Frame,
+    /// Format of the staging image `resize_and_encode` converts into.
+    input_format: FourCC,
+    /// Encoded width and height.
+    size: (u32, u32),
     /// Accumulated bits since last keyframe (for bitrate estimation)
     pub bits: usize,
+    /// Sample aspect ratio written into each SPS, see
+    /// [`set_sample_aspect_ratio`](Self::set_sample_aspect_ratio). Always
+    /// `None` for H.265.
+    sar: Option<SampleAspectRatio>,
+    /// Set once a B slice has been reported, see [`MAX_B_FRAMES`].
+    reorder_warned: bool,
+    /// Settings the encoder is reopened with to force a keyframe.
+    settings: VideoManagerBuilder,
+    keyframes: KeyframeSchedule,
+}
+
+/// When [`VideoManager`] forces the next frame to be a keyframe.
+///
+/// The encoder has no control to request an IDR frame, but a freshly
+/// opened encoder always starts with one, so a keyframe is forced by
+/// reopening the encoder before the frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct KeyframeSchedule {
+    /// Set by [`VideoManager::request_keyframe`].
+    requested: bool,
+    /// Most frames from one keyframe to the next, 0 to leave it to the
+    /// encoder.
+    interval: u32,
+    /// Frames encoded since the last keyframe, counting it.
+    since: u32,
+}
+
+impl KeyframeSchedule {
+    /// Returns `true` if the next frame must be a keyframe.
+    fn due(&self) -> bool {
+        self.requested || (self.interval > 0 && self.since >= self.interval)
+    }
+
+    /// Record an encoded frame.
+    fn encoded(&mut self, is_key: bool) {
+        if is_key {
+            self.requested = false;
+            self.since = 1;
+        } else {
+            self.since = self.since.saturating_add(1);
+        }
+    }
+}
+
+/// Picks the frames that start each period of wall-clock time, so cameras
+/// whose clocks agree start their GOPs together and recordings can be cut
+/// into segments on the same boundaries. Pass each frame's capture time to
+/// [`crossed`](Self::crossed) and
+/// [`request_keyframe`](VideoManager::request_keyframe) when it returns
+/// `true`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WallclockKeyframes {
+    period_ns: u64,
+    /// Period the previous frame fell in, counted from the epoch.
+    last: Option<u64>,
+}
+
+impl WallclockKeyframes {
+    /// Keyframes on every multiple of `period` since the Unix epoch, e.g.
+    /// on each whole second for one second. `period` is rounded up to at
+    /// least a nanosecond.
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period_ns: (period.as_nanos() as u64).max(1),
+            last: None,
+        }
+    }
+
+    /// Returns `true` if the frame captured `stamp_ns` nanoseconds after
+    /// the epoch is the first past a boundary. The first frame never is,
+    /// as a new encoder starts on a keyframe anyway, and neither is a
+    /// frame after the clock stepped back.
+    pub fn crossed(&mut self, stamp_ns: u64) -> bool {
+        let period = stamp_ns / self.period_ns;
+        let crossed = self.last.is_some_and(|last| period > last);
+        self.last = Some(period);
+        crossed
+    }
 }
 
 impl VideoManager {
-    /// Creates a new `VideoManager` for H.264 encoding.
+    /// Starts a [`VideoManagerBuilder`] for `width`x`height` output in
+    /// `video_fmt`, the [`Codec::fourcc`] of the codec to encode.
     ///
-    /// Initializes the hardware H.264 encoder with the specified parameters.
-    ///
-    /// # Arguments
-    ///
-    /// * `video_fmt` - Output encoder format (use `FourCC(*b"H264")` for H.264)
-    /// * `width` - Video width in pixels (max 1920)
-    /// * `height` - Video height in pixels (max 1080)
-    /// * `bitrate` - Target encoding bitrate
-    ///
-    /// # Returns
+    /// # Example
     ///
-    /// A new `VideoManager` ready for encoding frames.
+    /// ```no_run
+    /// # use edgefirst_camera::video::VideoManager;
+    /// # use edgefirst_camera::config::H264Bitrate;
+    /// # use videostream::fourcc::FourCC;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// // Encode the top-left 1080p tile of a 4K image at 15 fps
+    /// let video_mgr = VideoManager::builder(FourCC(*b"H264"), 1920, 1080)
+    ///     .crop((0, 0, 1920, 1080))
+    ///     .bitrate(H264Bitrate::Mbps25)
+    ///     .fps(15)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder(video_fmt: FourCC, width: i32, height: i32) -> VideoManagerBuilder {
+        VideoManagerBuilder {
+            video_fmt,
+            width,
+            height,
+            crop: None,
+            bitrate: H264Bitrate::Auto,
+            fps: TARGET_FPS,
+            sar: None,
+            keyframe_interval: 0,
+        }
+    }
+
+    /// Creates a new `VideoManager` for H.264 encoding at [`TARGET_FPS`]
+    /// without cropping.
     ///
     /// # Errors
     ///
-    /// Returns an error if:
-    /// - Hardware encoder cannot be initialized
-    /// - Dimensions exceed hardware limits (1920×1080)
-    /// - Invalid format specified
-    ///
-    /// # Platform Requirements
-    ///
-    /// Requires NXP i.MX8M Plus with Hantro encoder support.
+    /// See [`VideoManagerBuilder::build`].
+    #[deprecated(note = "use VideoManager::builder")]
     pub fn new(
         video_fmt: FourCC,
         width: i32,
         height: i32,
         bitrate: H264Bitrate,
     ) -> Result<VideoManager, Box<dyn Error>> {
-        let profile = match bitrate {
-            H264Bitrate::Auto => VSLEncoderProfileEnum::Auto,
-            H264Bitrate::Mbps5 => VSLEncoderProfileEnum::Kbps5000,
-            H264Bitrate::Mbps25 => VSLEncoderProfileEnum::Kbps25000,
-            H264Bitrate::Mbps50 => VSLEncoderProfileEnum::Kbps50000,
-            H264Bitrate::Mbps100 => VSLEncoderProfileEnum::Kbps100000,
-        };
-        let encoder = Encoder::create(profile as u32, u32::from(video_fmt), TARGET_FPS)?;
-        let crop = VSLRect::new(0, 0, width, height);
-        let output_frame = encoder.new_output_frame(width, height, 30i64, 0, 0)?;
-        Ok(Self {
-            encoder,
-            crop,
-            output_frame,
-            bits: 0,
-        })
+        Self::builder(video_fmt, width, height)
+            .bitrate(bitrate)
+            .build()
     }
 
-    /// Creates a new `VideoManager` with custom cropping and FPS settings.
-    ///
-    /// This constructor is used for 4K tiling where each tile is a cropped
-    /// region of the source image, encoded as a separate 1080p H.264 stream.
-    ///
-    /// # Arguments
-    ///
-    /// * `video_fmt` - Output encoder format (use `FourCC(*b"H264")` for H.264)
-    /// * `output_width` - Output width in pixels (max 1920)
-    /// * `output_height` - Output height in pixels (max 1080)
-    /// * `crop_rect` - Source crop region as `(x, y, width, height)`
-    /// * `bitrate` - Target encoding bitrate
-    /// * `target_fps` - Optional FPS limit (useful for tiles)
-    ///
-    /// # Returns
-    ///
-    /// A new `VideoManager` configured for tiled encoding.
+    /// Creates a new `VideoManager` encoding the `crop_rect` region
+    /// `(x, y, width, height)` of each source frame, at `target_fps` or
+    /// [`TARGET_FPS`].
     ///
     /// # Errors
     ///
-    /// Returns an error if hardware encoder initialization fails.
-    ///
-    /// # Example
-    ///
-    /// ```no_run
-    /// # use edgefirst_camera::video::VideoManager;
-    /// # use edgefirst_camera::args::H264Bitrate;
-    /// # use videostream::fourcc::FourCC;
-    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// // Encode top-left tile of a 4K image
-    /// let video_mgr = VideoManager::new_with_crop(
-    ///     FourCC(*b"H264"),
-    ///     1920, // output size
-    ///     1080,
-    ///     (0, 0, 1920, 1080), // crop from top-left
-    ///     H264Bitrate::Mbps25,
-    ///     Some(15), // 15 FPS to reduce artifacts
-    /// )?;
-    /// # Ok(())
-    /// # }
-    /// ```
+    /// See [`VideoManagerBuilder::build`].
+    #[deprecated(note = "use VideoManager::builder")]
     pub fn new_with_crop(
         video_fmt: FourCC,
         output_width: i32,
@@ -145,97 +234,243 @@ impl VideoManager {
         bitrate: H264Bitrate,
         target_fps: Option<i32>,
     ) -> Result<VideoManager, Box<dyn Error>> {
-        let profile = match bitrate {
-            H264Bitrate::Auto => VSLEncoderProfileEnum::Auto,
-            H264Bitrate::Mbps5 => VSLEncoderProfileEnum::Kbps5000,
-            H264Bitrate::Mbps25 => VSLEncoderProfileEnum::Kbps25000,
-            H264Bitrate::Mbps50 => VSLEncoderProfileEnum::Kbps50000,
-            H264Bitrate::Mbps100 => VSLEncoderProfileEnum::Kbps100000,
-        };
+        Self::builder(video_fmt, output_width, output_height)
+            .crop(crop_rect)
+            .bitrate(bitrate)
+            .fps(target_fps.unwrap_or(TARGET_FPS))
+            .build()
+    }
 
-        let fps = target_fps.unwrap_or(TARGET_FPS);
-        let encoder = Encoder::create(profile as u32, u32::from(video_fmt), fps)?;
+    /// Checks that `width`x`height` can be encoded, see [`WIDTH_ALIGNMENT`]
+    /// and [`HEIGHT_ALIGNMENT`]. Misaligned sizes would otherwise be
+    /// silently cropped or produce a corrupted stream, so the constructors
+    /// reject them; call this up front to fail before any encoder exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the nearest valid sizes.
+    pub fn check_size(width: i32, height: i32) -> Result<(), Box<dyn Error>> {
+        let misaligned = |value: i32, align: i32| value <= 0 || value % align != 0;
+        if misaligned(width, WIDTH_ALIGNMENT) || misaligned(height, HEIGHT_ALIGNMENT) {
+            let round_up = |value: i32, align: i32| (value.max(1) + align - 1) / align * align;
+            return Err(format!(
+                "H.264 size {width}x{height} is not supported: width must be a multiple of \
+                 {WIDTH_ALIGNMENT} and height a multiple of {HEIGHT_ALIGNMENT} \
+                 (nearest supported size: {}x{})",
+                round_up(width, WIDTH_ALIGNMENT),
+                round_up(height, HEIGHT_ALIGNMENT)
+            )
+            .into());
+        }
+        Ok(())
+    }
 
-        let (crop_x, crop_y, crop_width, crop_height) = crop_rect;
-        let crop = VSLRect::new(crop_x, crop_y, crop_width, crop_height);
+    /// Codec of the encoded stream.
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
 
-        let output_frame =
-            encoder.new_output_frame(output_width, output_height, fps as i64, 0, 0)?;
-        Ok(Self {
-            encoder,
-            crop,
-            output_frame,
-            bits: 0,
-        })
+    /// Pixel format the encoder expects from
+    /// [`resize_and_encode`](Self::resize_and_encode)'s staging image.
+    pub fn input_format(&self) -> FourCC {
+        self.input_format
+    }
+
+    /// Allocates a `width`x`height` staging image in
+    /// [`input_format`](Self::input_format) for
+    /// [`resize_and_encode`](Self::resize_and_encode).
+    pub fn new_input_image(&self, width: u32, height: u32) -> Result<Image, Box<dyn Error>> {
+        Image::new(width, height, self.input_format)
+    }
+
+    /// Checks that the encoder can produce `bframes` B-frames between
+    /// reference frames, see [`MAX_B_FRAMES`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for any count above [`MAX_B_FRAMES`].
+    pub fn check_b_frames(bframes: u32) -> Result<(), Box<dyn Error>> {
+        if bframes > MAX_B_FRAMES {
+            return Err(format!(
+                "{bframes} H.264 B-frames requested but the encoder supports at most \
+                 {MAX_B_FRAMES} (I and P frames only)"
+            )
+            .into());
+        }
+        Ok(())
     }
 
     /// Resizes an image and encodes it to H.264.
     ///
     /// Performs G2D hardware-accelerated resize followed by H.264 encoding.
     /// This is used when the camera resolution differs from the output
-    /// resolution. The source image is converted to NV12 format before
-    /// encoding.
+    /// resolution. The source image is converted to the encoder's
+    /// [`input_format`](Self::input_format) before encoding.
     ///
     /// # Arguments
     ///
-    /// * `source` - Source image (typically RGBA from camera)
+    /// * `source` - Source image (typically YUYV from camera)
     /// * `imgmgr` - ImageManager for G2D operations
-    /// * `img` - Pre-allocated destination image (will be converted to NV12)
+    /// * `img` - Pre-allocated staging image in the encoder's input format
+    ///   (see [`new_input_image`](Self::new_input_image)), already sized for
+    ///   `rot` (see [`Rotation::rotated_size`])
+    /// * `rot` - Rotation applied during the resize
     ///
     /// # Returns
     ///
-    /// A tuple of `(h264_data, is_keyframe)` where:
+    /// A tuple of `(h264_data, is_keyframe, pts)` where:
     /// - `h264_data` - Encoded H.264 NAL units
     /// - `is_keyframe` - `true` if this is an I-frame
+    /// - `pts` - Presentation timestamp the encoder assigned to the output
+    ///   frame in nanoseconds, `None` if the encoder does not report one
     ///
     /// # Errors
     ///
-    /// Returns an error if G2D conversion or H.264 encoding fails.
+    /// Returns an error if `img` is not in the encoder's input format, or if
+    /// G2D conversion or H.264 encoding fails.
     pub fn resize_and_encode(
         &mut self,
         source: &Image,
         imgmgr: &ImageManager,
         img: &Image,
-    ) -> Result<(Vec<u8>, bool), Box<dyn Error>> {
-        info_span!("h264_resize")
-            .in_scope(|| imgmgr.convert(source, img, None, Rotation::Rotation0))?;
-        let frame: Frame = match img.try_into() {
-            Ok(f) => f,
-            Err(e) => {
-                return Err(e);
-            }
-        };
+        rot: Rotation,
+    ) -> Result<(Vec<u8>, bool, Option<i64>), Box<dyn Error>> {
+        let mut data = Vec::new();
+        let (is_key, pts) = self.resize_and_encode_into(source, imgmgr, img, rot, &mut data)?;
+        Ok((data, is_key, pts))
+    }
+
+    /// [`resize_and_encode`](Self::resize_and_encode), writing the H.264
+    /// data into `out` as [`encode_into`](Self::encode_into) does. Returns
+    /// `(is_keyframe, pts)`.
+    ///
+    /// # Errors
+    ///
+    /// As [`resize_and_encode`](Self::resize_and_encode).
+    pub fn resize_and_encode_into(
+        &mut self,
+        source: &Image,
+        imgmgr: &ImageManager,
+        img: &Image,
+        rot: Rotation,
+        out: &mut Vec<u8>,
+    ) -> Result<(bool, Option<i64>), Box<dyn Error>> {
+        out.clear();
+        check_input_format(self.input_format, img.format())?;
+        info_span!("h264_resize").in_scope(|| imgmgr.convert(source, img, None, rot))?;
+        let frame: Frame = img.try_into()?;
+        info_span!("h264_encode").in_scope(|| self.encode_from_vsl(&frame, out))
+    }
 
-        info_span!("h264_encode").in_scope(|| self.encode_from_vsl(&frame))
+    /// Encodes `source`, converting it only when it has to be.
+    ///
+    /// A frame that is already in the encoder's
+    /// [`input_format`](Self::input_format) at the encoded size, with no
+    /// rotation left to apply, is imported into the encoder as is through
+    /// [`encode_direct`](Self::encode_direct), without any G2D work. Any
+    /// other frame goes through [`resize_and_encode`](Self::resize_and_encode)
+    /// into `staging`, which is allocated on first use and kept for later
+    /// frames. Returns the same tuple as
+    /// [`resize_and_encode`](Self::resize_and_encode).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the staging image cannot be allocated, or if the
+    /// conversion or encoding fails.
+    pub fn encode(
+        &mut self,
+        source: &Image,
+        imgmgr: &ImageManager,
+        staging: &mut Option<Image>,
+        rot: Rotation,
+    ) -> Result<(Vec<u8>, bool, Option<i64>), Box<dyn Error>> {
+        let mut data = Vec::new();
+        let (is_key, pts) = self.encode_into(source, imgmgr, staging, rot, &mut data)?;
+        Ok((data, is_key, pts))
+    }
+
+    /// [`encode`](Self::encode), writing the H.264 data into `out` instead
+    /// of a new `Vec`. `out` is cleared first and keeps its capacity, so an
+    /// encode loop that passes the same buffer each frame stops allocating
+    /// once it has grown to the largest frame. Returns `(is_keyframe, pts)`.
+    ///
+    /// # Errors
+    ///
+    /// As [`encode`](Self::encode). `out` is left empty when the encode
+    /// fails.
+    pub fn encode_into(
+        &mut self,
+        source: &Image,
+        imgmgr: &ImageManager,
+        staging: &mut Option<Image>,
+        rot: Rotation,
+        out: &mut Vec<u8>,
+    ) -> Result<(bool, Option<i64>), Box<dyn Error>> {
+        out.clear();
+        let source_size = (source.width(), source.height());
+        if is_direct_input(
+            self.input_format,
+            self.size,
+            source.format(),
+            source_size,
+            rot,
+        ) {
+            return self.encode_direct_into(source, out);
+        }
+        let img = match staging {
+            Some(img) => img,
+            None => staging.insert(self.new_input_image(self.size.0, self.size.1)?),
+        };
+        self.resize_and_encode_into(source, imgmgr, img, rot, out)
     }
 
     /// Encodes an image directly to H.264 without resizing.
     ///
     /// Use this when the source image is already in the correct resolution
-    /// and format for encoding. The image must be in NV12 format.
+    /// and format for encoding. The image must be in
+    /// [`input_format`](Self::input_format), see
+    /// [`new_input_image`](Self::new_input_image).
     ///
     /// # Arguments
     ///
-    /// * `source_img` - Source image (must be in NV12 format)
+    /// * `source_img` - Source image (must be in
+    ///   [`input_format`](Self::input_format))
     ///
     /// # Returns
     ///
-    /// A tuple of `(h264_data, is_keyframe)` where:
+    /// A tuple of `(h264_data, is_keyframe, pts)` where:
     /// - `h264_data` - Encoded H.264 NAL units
     /// - `is_keyframe` - `true` if this is an I-frame
+    /// - `pts` - Presentation timestamp the encoder assigned to the output
+    ///   frame in nanoseconds, `None` if the encoder does not report one
     ///
     /// # Errors
     ///
     /// Returns an error if H.264 encoding fails.
-    pub fn encode_direct(&mut self, source_img: &Image) -> Result<(Vec<u8>, bool), Box<dyn Error>> {
-        let frame: Frame = match source_img.try_into() {
-            Ok(f) => f,
-            Err(e) => {
-                return Err(e);
-            }
-        };
+    pub fn encode_direct(
+        &mut self,
+        source_img: &Image,
+    ) -> Result<(Vec<u8>, bool, Option<i64>), Box<dyn Error>> {
+        let mut data = Vec::new();
+        let (is_key, pts) = self.encode_direct_into(source_img, &mut data)?;
+        Ok((data, is_key, pts))
+    }
 
-        info_span!("h264_encode_direct").in_scope(|| self.encode_from_vsl(&frame))
+    /// [`encode_direct`](Self::encode_direct), writing the H.264 data into
+    /// `out` as [`encode_into`](Self::encode_into) does. Returns
+    /// `(is_keyframe, pts)`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if H.264 encoding fails.
+    pub fn encode_direct_into(
+        &mut self,
+        source_img: &Image,
+        out: &mut Vec<u8>,
+    ) -> Result<(bool, Option<i64>), Box<dyn Error>> {
+        out.clear();
+        let frame: Frame = source_img.try_into()?;
+        info_span!("h264_encode_direct").in_scope(|| self.encode_from_vsl(&frame, out))
     }
 
     /// Updates the crop region for subsequent encoding operations.
@@ -259,14 +494,83 @@ impl VideoManager {
         self.crop = VSLRect::new(crop_x, crop_y, crop_width, crop_height);
     }
 
-    fn encode_from_vsl(&mut self, source: &Frame) -> Result<(Vec<u8>, bool), Box<dyn Error>> {
+    /// Signals `sar` as the sample (pixel) aspect ratio of the encoded
+    /// stream.
+    ///
+    /// The hardware encoder has no VUI controls, so the SPS of every
+    /// keyframe is rewritten with the aspect ratio before it is returned.
+    /// Players use it to stretch anamorphic video to its display shape.
+    /// Only H.264 parameter sets are rewritten; H.265 streams ignore this.
+    pub fn set_sample_aspect_ratio(&mut self, sar: SampleAspectRatio) {
+        if self.codec == Codec::H264 {
+            self.sar = Some(sar);
+        }
+    }
+
+    /// Make the next encoded frame a keyframe, so a decoder joining the
+    /// stream need not wait for the encoder's next one.
+    ///
+    /// The encoder is reopened before that frame, which costs a few
+    /// milliseconds.
+    pub fn request_keyframe(&mut self) {
+        self.keyframes.requested = true;
+    }
+
+    /// Force a keyframe at least every `frames` frames, shortening the
+    /// encoder's own keyframe interval; 0 leaves it to the encoder. Each
+    /// forced keyframe reopens the encoder, see
+    /// [`request_keyframe`](Self::request_keyframe).
+    pub fn set_keyframe_interval(&mut self, frames: u32) {
+        self.keyframes.interval = frames;
+    }
+
+    /// Reopen the encoder with the same settings, so it starts over with a
+    /// keyframe. On failure the current encoder is kept.
+    fn reopen(&mut self) -> Result<(), Box<dyn Error>> {
+        let (encoder, output_frame) = self.settings.open()?;
+        self.output_frame = output_frame;
+        self.encoder = encoder;
+        Ok(())
+    }
+
+    /// Encode `source` and append the output frame's bytes to the empty
+    /// `out`, reusing its allocation.
+    fn encode_from_vsl(
+        &mut self,
+        source: &Frame,
+        out: &mut Vec<u8>,
+    ) -> Result<(bool, Option<i64>), Box<dyn Error>> {
+        if self.keyframes.due() {
+            if let Err(e) = self.reopen() {
+                warn!("could not reopen the encoder to force a keyframe: {e}");
+                // Wait for the encoder's own keyframe instead of retrying
+                // every frame.
+                self.keyframes.requested = false;
+                self.keyframes.since = 0;
+            }
+        }
         let mut key_frame: c_int = 0;
         let _ret = unsafe {
             self.encoder
                 .frame(source, &self.output_frame, &self.crop, &mut key_frame)
         };
         let is_key = key_frame != 0;
-        let ret = self.output_frame.mmap().unwrap().to_vec();
+        self.keyframes.encoded(is_key);
+        // Zero when the encoder has no timestamp for the frame.
+        let pts = self.output_frame.pts().ok().filter(|&pts| pts > 0);
+        out.extend_from_slice(&self.output_frame.mmap().unwrap()[..]);
+        // Decoders handed this stream assume no frame reordering. The slice
+        // parsing is H.264 only.
+        if !self.reorder_warned && self.codec == Codec::H264 && nal::has_b_slices(out) {
+            warn!("encoder produced B-frames although none were configured");
+            self.reorder_warned = true;
+        }
+        if let (true, Some(sar)) = (is_key, self.sar) {
+            match sps::rewrite_sample_aspect_ratio(out, sar) {
+                Ok(patched) => *out = patched,
+                Err(e) => warn!("could not set the SPS sample aspect ratio: {e}"),
+            }
+        }
 
         if is_key && self.bits > 1000 {
             let bps = self.bits as f64 * 8.0 / 1000000.0;
@@ -274,8 +578,345 @@ impl VideoManager {
             debug!("estimated h264 bitrate: {:.2} mbps", bps);
             self.bits = 0;
         }
-        self.bits += ret.len();
+        self.bits += out.len();
+
+        Ok((is_key, pts))
+    }
+}
+
+/// Encoder settings collected before the hardware encoder is created, see
+/// [`VideoManager::builder`].
+#[derive(Clone, Debug)]
+pub struct VideoManagerBuilder {
+    video_fmt: FourCC,
+    width: i32,
+    height: i32,
+    crop: Option<(i32, i32, i32, i32)>,
+    bitrate: H264Bitrate,
+    fps: i32,
+    sar: Option<SampleAspectRatio>,
+    keyframe_interval: u32,
+}
+
+impl VideoManagerBuilder {
+    /// Encode only the `(x, y, width, height)` region of each source frame,
+    /// as for 4K tiling. Defaults to the whole output size.
+    pub fn crop(mut self, crop: (i32, i32, i32, i32)) -> Self {
+        self.crop = Some(crop);
+        self
+    }
+
+    /// Frame rate the encoder's rate control targets. Defaults to
+    /// [`TARGET_FPS`].
+    pub fn fps(mut self, fps: i32) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    /// Target bitrate preset. Defaults to [`H264Bitrate::Auto`].
+    pub fn bitrate(mut self, bitrate: H264Bitrate) -> Self {
+        self.bitrate = bitrate;
+        self
+    }
+
+    /// Signal `sar` in the stream, see
+    /// [`VideoManager::set_sample_aspect_ratio`].
+    pub fn sample_aspect_ratio(mut self, sar: SampleAspectRatio) -> Self {
+        self.sar = Some(sar);
+        self
+    }
+
+    /// Force a keyframe at least every `frames` frames, see
+    /// [`VideoManager::set_keyframe_interval`]. Defaults to 0, the
+    /// encoder's own interval.
+    pub fn keyframe_interval(mut self, frames: u32) -> Self {
+        self.keyframe_interval = frames;
+        self
+    }
+
+    /// Checks the settings, then opens the hardware encoder.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The output size is rejected by [`VideoManager::check_size`]
+    /// - The frame rate is not positive
+    /// - `video_fmt` is not the FourCC of a [`Codec`]
+    /// - A non-square sample aspect ratio is set for H.265
+    /// - The hardware encoder cannot be initialized
+    ///
+    /// # Platform Requirements
+    ///
+    /// Requires NXP i.MX8M Plus with Hantro encoder support.
+    pub fn build(self) -> Result<VideoManager, Box<dyn Error>> {
+        let codec = self.check()?;
+        let (encoder, output_frame) = self.open()?;
+
+        let (crop_x, crop_y, crop_width, crop_height) = self.crop_rect();
+        let crop = VSLRect::new(crop_x, crop_y, crop_width, crop_height);
+        let keyframes = KeyframeSchedule {
+            interval: self.keyframe_interval,
+            ..Default::default()
+        };
+        Ok(VideoManager {
+            encoder,
+            codec,
+            crop,
+            output_frame,
+            input_format: H264_INPUT_FORMAT,
+            size: (self.width as u32, self.height as u32),
+            bits: 0,
+            sar: self.sar.filter(|_| codec == Codec::H264),
+            reorder_warned: false,
+            settings: self,
+            keyframes,
+        })
+    }
+
+    /// Open the hardware encoder and its output frame.
+    fn open(&self) -> Result<(Encoder, Frame), Box<dyn Error>> {
+        let profile = match self.bitrate {
+            H264Bitrate::Auto => VSLEncoderProfileEnum::Auto,
+            H264Bitrate::Mbps5 => VSLEncoderProfileEnum::Kbps5000,
+            H264Bitrate::Mbps25 => VSLEncoderProfileEnum::Kbps25000,
+            H264Bitrate::Mbps50 => VSLEncoderProfileEnum::Kbps50000,
+            H264Bitrate::Mbps100 => VSLEncoderProfileEnum::Kbps100000,
+        };
+        let encoder = Encoder::create(profile as u32, u32::from(self.video_fmt), self.fps)?;
+        let output_frame =
+            encoder.new_output_frame(self.width, self.height, self.fps as i64, 0, 0)?;
+        Ok((encoder, output_frame))
+    }
+
+    /// The settings checks of [`build`](Self::build) that need no hardware,
+    /// returning the codec to encode.
+    fn check(&self) -> Result<Codec, Box<dyn Error>> {
+        VideoManager::check_size(self.width, self.height)?;
+        if self.fps <= 0 {
+            return Err(format!("encoder frame rate must be positive, got {}", self.fps).into());
+        }
+        let codec = Codec::from_fourcc(self.video_fmt)
+            .ok_or_else(|| format!("cannot encode {}: expected H264 or HEVC", self.video_fmt))?;
+        if let (Codec::H265, Some(sar)) = (codec, self.sar) {
+            if sar != SampleAspectRatio::SQUARE {
+                return Err(format!(
+                    "sample aspect ratio {sar} is only signalled in H.264 streams"
+                )
+                .into());
+            }
+        }
+        Ok(codec)
+    }
+
+    fn crop_rect(&self) -> (i32, i32, i32, i32) {
+        self.crop.unwrap_or((0, 0, self.width, self.height))
+    }
+}
+
+/// Returns `true` if a `format` frame of `source_size` needs no conversion
+/// before an encoder reading `input_format` at `encoded_size`.
+fn is_direct_input(
+    input_format: FourCC,
+    encoded_size: (u32, u32),
+    format: FourCC,
+    source_size: (u32, u32),
+    rot: Rotation,
+) -> bool {
+    format == input_format && source_size == encoded_size && rot == Rotation::Rotation0
+}
+
+/// Rejects a staging image whose format is not the one the encoder reads.
+fn check_input_format(expected: FourCC, staging: FourCC) -> Result<(), Box<dyn Error>> {
+    if staging != expected {
+        return Err(format!(
+            "encoder staging image is {staging} but the encoder expects {expected} input; \
+             allocate it with VideoManager::new_input_image"
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::NV12;
+
+    #[test]
+    fn builder_applies_options() {
+        let sar = SampleAspectRatio::new(4, 3).unwrap();
+        let builder = VideoManager::builder(FourCC(*b"H264"), 1920, 1080)
+            .crop((1920, 1080, 1920, 1080))
+            .fps(15)
+            .bitrate(H264Bitrate::Mbps25)
+            .sample_aspect_ratio(sar);
+        assert_eq!(builder.crop_rect(), (1920, 1080, 1920, 1080));
+        assert_eq!(builder.fps, 15);
+        assert_eq!(builder.bitrate, H264Bitrate::Mbps25);
+        assert_eq!(builder.sar, Some(sar));
+        assert!(builder.check().is_ok());
+        assert_eq!(builder.keyframe_interval(60).keyframe_interval, 60);
+
+        let defaults = VideoManager::builder(FourCC(*b"H264"), 1280, 720);
+        assert_eq!(defaults.crop_rect(), (0, 0, 1280, 720));
+        assert_eq!(defaults.fps, TARGET_FPS);
+        assert_eq!(defaults.bitrate, H264Bitrate::Auto);
+        assert_eq!(defaults.sar, None);
+        assert_eq!(defaults.keyframe_interval, 0);
+    }
+
+    #[test]
+    fn keyframes_are_forced_on_request_and_every_interval() {
+        // Which of `frames` the schedule forces, given the encoder's own
+        // keyframes.
+        let forced = |schedule: &mut KeyframeSchedule, own: &[bool]| -> Vec<bool> {
+            own.iter()
+                .map(|&own| {
+                    let due = schedule.due();
+                    schedule.encoded(due || own);
+                    due
+                })
+                .collect()
+        };
+
+        // Left to the encoder, nothing is forced.
+        let mut schedule = KeyframeSchedule::default();
+        assert_eq!(forced(&mut schedule, &[true, false, false]), [false; 3]);
+
+        // A request forces the next frame only.
+        schedule.requested = true;
+        assert_eq!(forced(&mut schedule, &[false; 3]), [true, false, false]);
+
+        // An interval of 3 forces every third frame, counting from the
+        // encoder's own keyframes too.
+        let mut schedule = KeyframeSchedule {
+            interval: 3,
+            ..Default::default()
+        };
+        let own = [true, false, false, false, false, true, false, false, false];
+        let want = [false, false, false, true, false, false, false, false, true];
+        assert_eq!(forced(&mut schedule, &own), want);
+
+        // A request already answered by the encoder's own keyframe is
+        // not forced again.
+        schedule.requested = true;
+        schedule.encoded(true);
+        assert!(!schedule.due());
+    }
+
+    #[test]
+    fn wallclock_keyframes_fall_on_the_first_frame_past_each_boundary() {
+        const SECOND: u64 = 1_000_000_000;
+        let mut align = WallclockKeyframes::new(Duration::from_secs(2));
+        // 30 fps frames from 1.9 s to 4.1 s: boundaries at 2 and 4 s.
+        let stamps = (0..67).map(|i| 1_900_000_000 + i * SECOND / 30);
+        let forced: Vec<u64> = stamps.filter(|&ns| align.crossed(ns)).collect();
+        assert_eq!(forced.len(), 2, "{forced:?}");
+        assert!((2 * SECOND..2 * SECOND + SECOND / 30).contains(&forced[0]));
+        assert!((4 * SECOND..4 * SECOND + SECOND / 30).contains(&forced[1]));
+
+        // A frame exactly on the boundary is the first in its period.
+        let mut align = WallclockKeyframes::new(Duration::from_secs(1));
+        assert!(!align.crossed(SECOND - 1));
+        assert!(align.crossed(SECOND));
+        assert!(!align.crossed(SECOND + 1));
+
+        // The clock stepping back does not force one; the next boundary
+        // after it does.
+        assert!(!align.crossed(SECOND / 2));
+        assert!(align.crossed(SECOND));
+    }
+
+    #[test]
+    fn builder_rejects_bad_settings_before_opening_encoder() {
+        let err = VideoManager::builder(FourCC(*b"H264"), 1918, 1080)
+            .build()
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("1918x1080"), "{err}");
+
+        let err = VideoManager::builder(FourCC(*b"H264"), 1920, 1080)
+            .fps(0)
+            .build()
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("frame rate"), "{err}");
+
+        let err = VideoManager::builder(FourCC(*b"MJPG"), 1920, 1080)
+            .build()
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("MJPG"), "{err}");
+    }
+
+    #[test]
+    fn h265_takes_square_pixels_only() {
+        let hevc = |sar| {
+            VideoManager::builder(Codec::H265.fourcc(), 1920, 1080)
+                .sample_aspect_ratio(sar)
+                .check()
+        };
+        assert_eq!(hevc(SampleAspectRatio::SQUARE).unwrap(), Codec::H265);
+        let err = hevc(SampleAspectRatio::new(4, 3).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("4:3"), "{err}");
+        let h264 = VideoManager::builder(Codec::H264.fourcc(), 1920, 1080)
+            .sample_aspect_ratio(SampleAspectRatio::new(4, 3).unwrap());
+        assert_eq!(h264.check().unwrap(), Codec::H264);
+    }
+
+    #[test]
+    fn only_zero_b_frames_are_supported() {
+        assert!(VideoManager::check_b_frames(0).is_ok());
+        let err = VideoManager::check_b_frames(2).unwrap_err().to_string();
+        assert!(err.contains("2 H.264 B-frames"), "{err}");
+    }
+
+    #[test]
+    fn staging_image_must_match_encoder_input_format() {
+        assert!(check_input_format(H264_INPUT_FORMAT, RGBA).is_ok());
+
+        let err = check_input_format(H264_INPUT_FORMAT, NV12)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("staging image is NV12"), "{err}");
+        assert!(err.contains("expects RGBA"), "{err}");
+    }
+
+    #[test]
+    fn direct_input_needs_matching_format_size_and_no_rotation() {
+        let direct =
+            |format, size, rot| is_direct_input(H264_INPUT_FORMAT, (1920, 1080), format, size, rot);
+        assert!(direct(RGBA, (1920, 1080), Rotation::Rotation0));
+        assert!(!direct(NV12, (1920, 1080), Rotation::Rotation0));
+        assert!(!direct(RGBA, (1280, 720), Rotation::Rotation0));
+        assert!(!direct(RGBA, (1920, 1080), Rotation::Rotation180));
+    }
+
+    #[test]
+    fn check_size_accepts_encoder_aligned_sizes() {
+        for (width, height) in [(1920, 1080), (1080, 1920), (1280, 720), (640, 480)] {
+            assert!(
+                VideoManager::check_size(width, height).is_ok(),
+                "{width}x{height}"
+            );
+        }
+    }
+
+    #[test]
+    fn check_size_rejects_misaligned_sizes() {
+        let err = VideoManager::check_size(1918, 1080)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("1918x1080"), "{err}");
+        assert!(err.contains("nearest supported size: 1920x1080"), "{err}");
+
+        let err = VideoManager::check_size(1280, 719).unwrap_err().to_string();
+        assert!(err.contains("nearest supported size: 1280x720"), "{err}");
 
-        Ok((ret, is_key))
+        assert!(VideoManager::check_size(0, 1080).is_err());
+        assert!(VideoManager::check_size(1920, -2).is_err());
     }
 }
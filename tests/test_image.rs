@@ -2,11 +2,18 @@
 // Copyright (c) 2025 Au-Zone Technologies. All Rights Reserved.
 
 use edgefirst_camera::{
-    image,
-    image::{encode_jpeg, Image, ImageManager, Rotation},
+    cpu, image,
+    image::{
+        encode_jpeg, BlitPriority, CompositeOptions, ConvertOptions, Flip, Image, ImageManager,
+        Rect, Rotation,
+    },
 };
 use serial_test::serial;
-use std::{error::Error, time::Instant};
+use std::{
+    error::Error,
+    os::fd::{FromRawFd, OwnedFd},
+    time::Instant,
+};
 use videostream::{
     camera::{create_camera, Mirror},
     fourcc::FourCC,
@@ -19,10 +26,12 @@ fn test_formats() -> Result<(), Box<dyn Error>> {
 
     println!("{}", img);
     assert_eq!(img.size(), 3110400);
+    assert_eq!(img.stride(), 1920);
 
     img = Image::new(1920, 1080, image::YUYV)?;
     println!("{}", img);
     assert_eq!(img.size(), 4147200);
+    assert_eq!(img.stride(), 3840);
 
     img = Image::new(1920, 1080, image::RGB3)?;
     println!("{}", img);
@@ -115,6 +124,66 @@ fn test_cleanup() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Dropping the manager while G2D buffers are still outstanding (here
+/// leaked with `mem::forget`) must free them before the G2D handle closes.
+#[test]
+#[serial]
+fn test_manager_drop_frees_outstanding_buffers() -> Result<(), Box<dyn Error>> {
+    let mgr = ImageManager::new()?;
+    let kept = mgr.alloc(640, 480, 4)?;
+    let leaked = mgr.alloc(640, 480, 4)?;
+    assert_eq!(mgr.outstanding_allocations(), 2);
+
+    drop(kept);
+    assert_eq!(mgr.outstanding_allocations(), 1);
+
+    std::mem::forget(leaked);
+    assert_eq!(mgr.outstanding_allocations(), 1);
+    drop(mgr);
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_free_twice_is_noop() -> Result<(), Box<dyn Error>> {
+    let mgr = ImageManager::new()?;
+    let mut buf = mgr.alloc(64, 64, 4)?;
+    mgr.free(&mut buf);
+    assert_eq!(mgr.outstanding_allocations(), 0);
+    // The buffer's own Drop frees it again.
+    drop(buf);
+    assert_eq!(mgr.outstanding_allocations(), 0);
+
+    Ok(())
+}
+
+fn open_fd_count() -> usize {
+    std::fs::read_dir("/proc/self/fd").unwrap().count()
+}
+
+#[test]
+#[serial]
+fn test_with_dma_buf_does_not_leak_fds() -> Result<(), Box<dyn Error>> {
+    let img = Image::new(640, 480, image::RGBA)?;
+    let before = open_fd_count();
+
+    for _ in 0..256 {
+        let jpeg = img.with_dma_buf(|dma| {
+            // The DmaBuf borrows the image fd: no fd is open beyond those
+            // there were before, not even while it is in use.
+            assert_eq!(open_fd_count(), before);
+            let buf = dma.memory_map()?.read(encode_jpeg, Some(&img))?;
+            Ok::<_, Box<dyn Error>>(buf)
+        })?;
+        assert!(!jpeg.is_empty());
+    }
+
+    assert_eq!(open_fd_count(), before);
+
+    Ok(())
+}
+
 #[test]
 #[serial]
 fn test_resize() -> Result<(), Box<dyn Error>> {
@@ -137,6 +206,865 @@ fn test_convert() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Convert a magenta YUYV image to NV12 and back to RGBA, with images from
+/// `new`, and check the colour survives. Chroma read from or written to the
+/// wrong plane comes back green.
+fn check_nv12_round_trip(
+    mgr: &ImageManager,
+    new: impl Fn(u32, u32, FourCC) -> Result<Image, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let (width, height) = (64, 32);
+    let [y, u, v] = cpu::rgb_to_yuv(200, 40, 160);
+    let mut src = new(width, height, image::YUYV)?;
+    src.mmap()?
+        .as_slice_mut()
+        .chunks_exact_mut(4)
+        .for_each(|px| px.copy_from_slice(&[y, u, y, v]));
+    let nv12 = new(width, height, image::NV12)?;
+    let mut dst = new(width, height, image::RGBA)?;
+    mgr.convert(&src, &nv12, None, Rotation::Rotation0)?;
+    mgr.convert(&nv12, &dst, None, Rotation::Rotation0)?;
+
+    let mapped = dst.mmap()?;
+    for (i, px) in mapped.as_slice().chunks_exact(4).enumerate() {
+        assert!(
+            px[0] > 160 && px[1] < 80 && px[2] > 120,
+            "pixel {i} is {px:?}, not magenta"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_convert_nv12_round_trip() -> Result<(), Box<dyn Error>> {
+    check_nv12_round_trip(&ImageManager::new()?, Image::new)
+}
+
+#[test]
+#[serial]
+fn test_managers_share_one_handle_across_threads() -> Result<(), Box<dyn Error>> {
+    let mgr = ImageManager::new()?;
+    let mut low = ImageManager::new()?;
+    low.set_priority(BlitPriority::Low);
+    assert!(mgr.shares_handle_with(&low));
+
+    // Both managers convert from their own threads at once, each source a
+    // different grey so crossed results show.
+    let grey = |mgr: &ImageManager, level: u8| {
+        let mut src = Image::new(640, 480, image::RGBA).unwrap();
+        let mut dst = Image::new(640, 480, image::RGBA).unwrap();
+        for _ in 0..50 {
+            src.mmap().unwrap().as_slice_mut().fill(level);
+            mgr.convert(&src, &dst, None, Rotation::Rotation0).unwrap();
+            assert!(dst.mmap().unwrap().as_slice().iter().all(|&px| px == level));
+        }
+    };
+    std::thread::scope(|s| {
+        s.spawn(|| grey(&mgr, 0x40));
+        s.spawn(|| grey(&low, 0xc0));
+    });
+
+    // The handle stays open while any manager is alive.
+    drop(low);
+    assert!(ImageManager::new()?.shares_handle_with(&mgr));
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_g2d_surface_address_is_stable() -> Result<(), Box<dyn Error>> {
+    let img = Image::new(640, 480, image::RGBA)?;
+    let first = img.as_g2d_surface()?;
+    assert_eq!(img.as_g2d_surface()?.planes, first.planes);
+
+    // A fresh handle to the same buffer looks the address up again and
+    // finds the same one.
+    let fd = img.fd().try_clone_to_owned()?;
+    let other = Image::new_preallocated(fd, 640, 480, image::RGBA);
+    assert_eq!(other.as_g2d_surface()?.planes, first.planes);
+    Ok(())
+}
+
+/// Open file descriptors of this process.
+fn open_fds() -> Result<usize, Box<dyn Error>> {
+    Ok(std::fs::read_dir("/proc/self/fd")?.count())
+}
+
+#[test]
+#[serial]
+fn test_repeated_converts_do_not_leak_fds() -> Result<(), Box<dyn Error>> {
+    let src = Image::new(640, 480, image::YUYV)?;
+    let dst = Image::new(320, 240, image::RGBA)?;
+    let mgr = ImageManager::new()?;
+    mgr.convert(&src, &dst, None, Rotation::Rotation0)?;
+
+    let before = open_fds()?;
+    for _ in 0..200 {
+        mgr.convert(&src, &dst, None, Rotation::Rotation0)?;
+    }
+    // Tests running alongside may hold a few fds of their own; a leak
+    // would be at least one per convert.
+    let after = open_fds()?;
+    assert!(after < before + 16, "{before} fds before, {after} after");
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_pyramid() -> Result<(), Box<dyn Error>> {
+    let src = Image::new(1920, 1080, image::YUYV)?;
+    let mgr = ImageManager::new()?;
+    let levels = mgr.pyramid(&src, &[1, 2, 4])?;
+
+    let sizes: Vec<_> = levels.iter().map(|l| (l.width(), l.height())).collect();
+    assert_eq!(sizes, [(1920, 1080), (960, 540), (480, 270)]);
+    assert!(levels.iter().all(|l| l.format() == image::YUYV));
+
+    // 1080 / 8 is odd.
+    assert!(mgr.pyramid(&src, &[2, 8]).is_err());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_composite() -> Result<(), Box<dyn Error>> {
+    let solid = |width, height, color: [u8; 4]| -> Result<Image, Box<dyn Error>> {
+        let mut img = Image::new(width, height, image::RGBA)?;
+        img.mmap()?
+            .as_slice_mut()
+            .chunks_exact_mut(4)
+            .for_each(|px| px.copy_from_slice(&color));
+        Ok(img)
+    };
+    // The main source is half the output size and the inset twice the
+    // inset region, so both blits scale.
+    let main = solid(64, 32, [255, 0, 0, 255])?;
+    let inset = solid(64, 64, [0, 0, 255, 255])?;
+    let mut out = Image::new(128, 64, image::RGBA)?;
+    let options = CompositeOptions {
+        inset: Rect {
+            x: 80,
+            y: 16,
+            width: 32,
+            height: 32,
+        },
+    };
+
+    let mgr = ImageManager::new()?;
+    mgr.composite(&main, &inset, &out, &options)?;
+
+    let map = out.mmap()?;
+    let pixel = |x: usize, y: usize| {
+        let i = (y * 128 + x) * 4;
+        map.as_slice()[i..i + 3].to_vec()
+    };
+    for (x, y) in [(80, 16), (111, 47), (96, 32)] {
+        assert_eq!(pixel(x, y), [0, 0, 255], "inset pixel ({x}, {y})");
+    }
+    for (x, y) in [(0, 0), (127, 63), (79, 32), (112, 32), (96, 15), (96, 48)] {
+        assert_eq!(pixel(x, y), [255, 0, 0], "main pixel ({x}, {y})");
+    }
+
+    // An inset that does not fit is rejected before anything is drawn.
+    let outside = CompositeOptions::bottom_right(128, 64, 2, 100);
+    assert!(mgr.composite(&main, &inset, &out, &outside).is_err());
+
+    Ok(())
+}
+
+/// Blend an 8x4 overlay, opaque white on the left half and transparent on
+/// the right, into the region at (8, 4) of a 32x16 blue frame, with images
+/// from `new`, and check the pixels either side of each edge.
+fn check_blend(
+    mgr: &ImageManager,
+    new: impl Fn(u32, u32, FourCC) -> Result<Image, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut base = new(32, 16, image::RGBA)?;
+    base.mmap()?
+        .as_slice_mut()
+        .chunks_exact_mut(4)
+        .for_each(|px| px.copy_from_slice(&[0, 0, 200, 255]));
+    let mut overlay = new(8, 4, image::RGBA)?;
+    overlay
+        .mmap()?
+        .as_slice_mut()
+        .chunks_exact_mut(4)
+        .enumerate()
+        .for_each(|(i, px)| {
+            let alpha = if i % 8 < 4 { 255 } else { 0 };
+            px.copy_from_slice(&[255, 255, 255, alpha]);
+        });
+    let mut dst = new(32, 16, image::RGBA)?;
+    let region = Rect {
+        x: 8,
+        y: 4,
+        width: 8,
+        height: 4,
+    };
+    mgr.blend(&base, &overlay, &dst, region, 255)?;
+
+    let mapped = dst.mmap()?;
+    let pixel = |x: usize, y: usize| &mapped.as_slice()[(y * 32 + x) * 4..][..4];
+    for (x, y) in [(8, 4), (11, 7)] {
+        assert_eq!(
+            pixel(x, y),
+            [255, 255, 255, 255],
+            "overlay pixel ({x}, {y})"
+        );
+    }
+    for (x, y) in [(12, 4), (15, 7), (7, 4), (16, 4), (8, 3), (8, 8), (0, 0)] {
+        assert_eq!(pixel(x, y), [0, 0, 200, 255], "base pixel ({x}, {y})");
+    }
+    drop(mapped);
+
+    // Only RGBA overlays carry the alpha the blend needs.
+    let rgbx = new(4, 2, image::RGBX)?;
+    let err = mgr.blend(&base, &rgbx, &dst, region, 255).unwrap_err();
+    assert!(err.to_string().contains("RGBA"), "{err}");
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_blend() -> Result<(), Box<dyn Error>> {
+    check_blend(&ImageManager::new()?, Image::new)
+}
+
+/// Fill a 32x16 image from `new` with blue, then a grey 8x4 region at
+/// (8, 4), and check the pixels either side of its edges.
+fn check_fill(
+    mgr: &ImageManager,
+    new: impl Fn(u32, u32, FourCC) -> Result<Image, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut dst = new(32, 16, image::RGBA)?;
+    dst.mmap()?.as_slice_mut().fill(0x55);
+    mgr.fill(&dst, None, 0x0000c8ff)?;
+    let region = Rect {
+        x: 8,
+        y: 4,
+        width: 8,
+        height: 4,
+    };
+    mgr.fill(&dst, Some(region), 0x808080ff)?;
+
+    let mapped = dst.mmap()?;
+    let pixel = |x: usize, y: usize| &mapped.as_slice()[(y * 32 + x) * 4..][..4];
+    for (x, y) in [(8, 4), (15, 7)] {
+        assert_eq!(pixel(x, y), [128, 128, 128, 255], "filled pixel ({x}, {y})");
+    }
+    for (x, y) in [(7, 4), (16, 7), (8, 3), (15, 8), (0, 0), (31, 15)] {
+        assert_eq!(pixel(x, y), [0, 0, 200, 255], "background pixel ({x}, {y})");
+    }
+    drop(mapped);
+
+    let odd = Rect { x: 1, ..region };
+    assert!(mgr.fill(&dst, Some(odd), 0).is_err());
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_fill() -> Result<(), Box<dyn Error>> {
+    check_fill(&ImageManager::new()?, Image::new)
+}
+
+#[test]
+#[serial]
+fn test_convert_into_dst_rect() -> Result<(), Box<dyn Error>> {
+    let mut src = Image::new(64, 32, image::RGBA)?;
+    src.mmap()?
+        .as_slice_mut()
+        .chunks_exact_mut(4)
+        .for_each(|px| px.copy_from_slice(&[255, 0, 0, 255]));
+    let mut dst = Image::new(128, 64, image::RGBA)?;
+    dst.mmap()?.as_slice_mut().fill(0);
+
+    // The source is scaled down into the bottom-right quadrant only.
+    let options = ConvertOptions {
+        dst_rect: Some(Rect {
+            x: 64,
+            y: 32,
+            width: 64,
+            height: 32,
+        }),
+        ..Default::default()
+    };
+    let mgr = ImageManager::new()?;
+    mgr.convert_with(&src, &dst, &options)?;
+
+    let map = dst.mmap()?;
+    let pixel = |x: usize, y: usize| {
+        let i = (y * 128 + x) * 4;
+        map.as_slice()[i..i + 4].to_vec()
+    };
+    for (x, y) in [(64, 32), (127, 63), (96, 48)] {
+        assert_eq!(pixel(x, y), [255, 0, 0, 255], "quadrant pixel ({x}, {y})");
+    }
+    for (x, y) in [(0, 0), (63, 63), (127, 31), (63, 32), (96, 31)] {
+        assert_eq!(pixel(x, y), [0, 0, 0, 0], "cleared pixel ({x}, {y})");
+    }
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_convert_per_output_rotation() -> Result<(), Box<dyn Error>> {
+    // Left half red, right half blue.
+    let mut src = Image::new(64, 32, image::RGBA)?;
+    {
+        let mut map = src.mmap()?;
+        for (i, px) in map.as_slice_mut().chunks_exact_mut(4).enumerate() {
+            let color = if i % 64 < 32 {
+                [255, 0, 0, 255]
+            } else {
+                [0, 0, 255, 255]
+            };
+            px.copy_from_slice(&color);
+        }
+    }
+
+    let mgr = ImageManager::new()?;
+    let mut upright = Image::new(64, 32, image::RGBA)?;
+    let mut flipped = Image::new(64, 32, image::RGBA)?;
+    mgr.convert(&src, &upright, None, Rotation::Rotation0)?;
+    mgr.convert(&src, &flipped, None, Rotation::Rotation180)?;
+
+    let upright = upright.mmap()?;
+    let flipped = flipped.mmap()?;
+    let pixel = |map: &image::MappedImage, x: usize, y: usize| {
+        let i = (y * 64 + x) * 4;
+        map.as_slice()[i..i + 3].to_vec()
+    };
+    assert_eq!(pixel(&upright, 0, 16), [255, 0, 0]);
+    assert_eq!(pixel(&upright, 63, 16), [0, 0, 255]);
+    assert_eq!(pixel(&flipped, 0, 16), [0, 0, 255]);
+    assert_eq!(pixel(&flipped, 63, 16), [255, 0, 0]);
+
+    let (width, height) = Rotation::Rotation90.rotated_size(64, 32);
+    let portrait = Image::new(width, height, image::RGBA)?;
+    mgr.convert(&src, &portrait, None, Rotation::Rotation90)?;
+    assert_eq!((portrait.width(), portrait.height()), (32, 64));
+
+    Ok(())
+}
+
+/// Convert an 8x4 RGBA image of distinct pixels, with images from `new`,
+/// under every rotation and flip, and check each output pixel came from
+/// the right source pixel. Combinations the manager cannot flip must fail.
+fn check_flip(
+    mgr: &ImageManager,
+    new: impl Fn(u32, u32, FourCC) -> Result<Image, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let (width, height) = (8, 4);
+    let mut src = new(width as u32, height as u32, image::RGBA)?;
+    src.mmap()?
+        .as_slice_mut()
+        .chunks_exact_mut(4)
+        .enumerate()
+        .for_each(|(i, px)| px.copy_from_slice(&[i as u8, 0, 0, 255]));
+
+    // The source pixel at output (x, y): undo the flip, then the rotation.
+    let source = |x: usize, y: usize, rotation: Rotation, flip: Flip| {
+        let (w, h) = rotation.rotated_size(width as u32, height as u32);
+        let (w, h) = (w as usize, h as usize);
+        let x = if matches!(flip, Flip::Horizontal | Flip::Both) {
+            w - 1 - x
+        } else {
+            x
+        };
+        let y = if matches!(flip, Flip::Vertical | Flip::Both) {
+            h - 1 - y
+        } else {
+            y
+        };
+        let (sx, sy) = match rotation {
+            Rotation::Rotation0 => (x, y),
+            Rotation::Rotation90 => (y, height - 1 - x),
+            Rotation::Rotation180 => (width - 1 - x, height - 1 - y),
+            Rotation::Rotation270 => (width - 1 - y, x),
+        };
+        (sy * width + sx) as u8
+    };
+
+    for rotation in [
+        Rotation::Rotation0,
+        Rotation::Rotation90,
+        Rotation::Rotation180,
+        Rotation::Rotation270,
+    ] {
+        for flip in [Flip::None, Flip::Horizontal, Flip::Vertical, Flip::Both] {
+            let (w, h) = rotation.rotated_size(width as u32, height as u32);
+            let mut dst = new(w, h, image::RGBA)?;
+            let options = ConvertOptions {
+                rotation,
+                flip,
+                ..Default::default()
+            };
+            let converted = mgr.convert_with(&src, &dst, &options);
+            if !mgr.can_flip(rotation, flip) {
+                assert!(converted.is_err(), "{rotation:?} {flip:?}");
+                continue;
+            }
+            converted?;
+            let mapped = dst.mmap()?;
+            for (i, px) in mapped.as_slice().chunks_exact(4).enumerate() {
+                let (x, y) = (i % w as usize, i / w as usize);
+                assert_eq!(
+                    px[0],
+                    source(x, y, rotation, flip),
+                    "{rotation:?} {flip:?} ({x}, {y})"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_convert_flips() -> Result<(), Box<dyn Error>> {
+    check_flip(&ImageManager::new()?, Image::new)
+}
+
+#[test]
+#[serial]
+fn test_convert_snaps_odd_nv12_crop() -> Result<(), Box<dyn Error>> {
+    // A 64x32 NV12 image whose first chroma sample column (pixels 0 and 1)
+    // is blue and the rest red, in BT.601 limited range.
+    let mut src = Image::new(64, 32, image::NV12)?;
+    {
+        let (width, height) = (64, 32);
+        let mut map = src.mmap()?;
+        let (luma, chroma) = map.as_slice_mut().split_at_mut(width * height);
+        for row in luma.chunks_exact_mut(width) {
+            row[..2].fill(41);
+            row[2..].fill(81);
+        }
+        for row in chroma[..width * height / 2].chunks_exact_mut(width) {
+            row[..2].copy_from_slice(&[240, 110]);
+            for uv in row[2..].chunks_exact_mut(2) {
+                uv.copy_from_slice(&[90, 240]);
+            }
+        }
+    }
+
+    // Starting at x = 1 would split the blue chroma sample; the crop is
+    // snapped back to x = 0 and the 1:1 copy keeps both blue columns.
+    let crop = Rect {
+        x: 1,
+        y: 1,
+        width: 32,
+        height: 16,
+    };
+    assert_eq!(
+        image::align_crop(crop, image::NV12),
+        Rect {
+            x: 0,
+            y: 0,
+            width: 32,
+            height: 16,
+        }
+    );
+    let mut dst = Image::new(32, 16, image::RGBA)?;
+    let mgr = ImageManager::new()?;
+    mgr.convert(&src, &dst, Some(crop), Rotation::Rotation0)?;
+
+    let map = dst.mmap()?;
+    let pixel = |x: usize, y: usize| {
+        let i = (y * 32 + x) * 4;
+        map.as_slice()[i..i + 3].to_vec()
+    };
+    for y in [0, 8, 15] {
+        for x in [0, 1] {
+            let px = pixel(x, y);
+            assert!(px[2] > 200 && px[0] < 40, "blue pixel ({x}, {y}): {px:?}");
+        }
+        for x in [2, 16, 31] {
+            let px = pixel(x, y);
+            assert!(px[0] > 200 && px[2] < 40, "red pixel ({x}, {y}): {px:?}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Source bytes for readback iteration `iteration`: every byte differs
+/// from the same byte of the previous iteration, so a stale read of any
+/// cache line shows up as a mismatch.
+fn readback_pattern(iteration: u8, len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i as u8).wrapping_mul(31) ^ iteration.wrapping_mul(97).wrapping_add(1))
+        .collect()
+}
+
+/// Cache-coherency harness: for each iteration, fill a source from the CPU
+/// with a fresh pattern, let `convert` run the backend and return the
+/// destination as the CPU reads it straight afterwards, and compare that
+/// with `expected` for the same source. A backend whose destination is
+/// read through stale cache lines returns an earlier iteration's pixels
+/// and fails here.
+fn check_readback(
+    iterations: u8,
+    src_len: usize,
+    expected: impl Fn(&[u8]) -> Vec<u8>,
+    mut convert: impl FnMut(&[u8]) -> Result<Vec<u8>, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    for iteration in 0..iterations {
+        let src = readback_pattern(iteration, src_len);
+        let want = expected(&src);
+        let got = convert(&src)?;
+        if got.len() != want.len() {
+            return Err(format!(
+                "iteration {iteration}: read back {} bytes, expected {}",
+                got.len(),
+                want.len()
+            )
+            .into());
+        }
+        if let Some(at) = got.iter().zip(&want).position(|(g, w)| g != w) {
+            return Err(format!(
+                "iteration {iteration}: byte {at} reads {:#04x}, expected {:#04x}; \
+                 the destination was read through stale cache lines",
+                got[at], want[at]
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_g2d_readback_is_coherent() -> Result<(), Box<dyn Error>> {
+    // Both buffers are reused across iterations, so the CPU caches hold
+    // the previous iteration's lines when the next blit lands.
+    let (width, height) = (256, 64);
+    let mut src = Image::new(width, height, image::RGBA)?;
+    let mut dst = Image::new(width, height, image::RGBA)?;
+    let mgr = ImageManager::new()?;
+
+    check_readback(
+        16,
+        src.size(),
+        |pixels| pixels.to_vec(),
+        |pixels| {
+            src.mmap()?.as_slice_mut().copy_from_slice(pixels);
+            mgr.convert(&src, &dst, None, Rotation::Rotation0)?;
+            Ok(dst.mmap()?.as_slice().to_vec())
+        },
+    )
+}
+
+#[test]
+#[serial]
+fn test_g2d_readback_is_coherent_without_mmap() -> Result<(), Box<dyn Error>> {
+    // Read the destination through its own mapping rather than
+    // `Image::mmap`, which syncs by itself: only the invalidation done by
+    // the conversion keeps this coherent.
+    let (width, height) = (256, 64);
+    let mut src = Image::new(width, height, image::RGBA)?;
+    let dst = Image::new(width, height, image::RGBA)?;
+    let mgr = ImageManager::new()?;
+
+    check_readback(
+        16,
+        src.size(),
+        |pixels| pixels.to_vec(),
+        |pixels| {
+            src.mmap()?.as_slice_mut().copy_from_slice(pixels);
+            mgr.convert(&src, &dst, None, Rotation::Rotation0)?;
+            let len = dst.size();
+            let map = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_SHARED,
+                    dst.raw_fd(),
+                    0,
+                )
+            };
+            if map == libc::MAP_FAILED {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            let pixels = unsafe { std::slice::from_raw_parts(map as *const u8, len) }.to_vec();
+            unsafe { libc::munmap(map, len) };
+            Ok(pixels)
+        },
+    )
+}
+
+#[test]
+#[serial]
+fn test_buffer_length_covers_padding() -> Result<(), Box<dyn Error>> {
+    // A 640x240 YUYV image in a buffer allocated for 640x480, as a driver
+    // that pads its buffers hands out.
+    let buffer = Image::new(640, 480, image::YUYV)?;
+    let padded = Image::new_preallocated(buffer.fd().try_clone_to_owned()?, 640, 240, image::YUYV);
+    assert_eq!(padded.size(), 640 * 2 * 240);
+    assert_eq!(padded.buffer_length()?, buffer.buffer_length()?);
+    assert!(padded.buffer_length()? >= 640 * 2 * 480);
+    // Reading the length leaves the buffer mappable.
+    assert_eq!(buffer.try_clone()?.mmap()?.as_slice().len(), buffer.size());
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_sync_for_cpu() -> Result<(), Box<dyn Error>> {
+    let img = Image::new(64, 64, image::RGBA)?;
+    img.sync_for_cpu()?;
+    Ok(())
+}
+
+#[test]
+fn test_software_readback_is_coherent() -> Result<(), Box<dyn Error>> {
+    // The CPU conversion path works on ordinary memory, so it is coherent
+    // by construction; this keeps the harness running without G2D.
+    let (width, height) = (256, 64);
+    let to_rgba = |pixels: &[u8]| {
+        let mut rgba = vec![0; width * height * 4];
+        cpu::yuyv_to_rgba_scalar(pixels, width * 2, &mut rgba, width * 4, width, height);
+        rgba
+    };
+    let mut dst = vec![0; width * height * 4];
+
+    check_readback(16, width * height * 2, to_rgba, |pixels| {
+        cpu::yuyv_to_rgba(pixels, width * 2, &mut dst, width * 4, width, height);
+        Ok(dst.clone())
+    })
+}
+
+/// A `width`x`height` image in `format` backed by a memfd instead of the
+/// CMA heap, so the software manager runs on hosts with neither G2D nor
+/// CMA.
+fn memfd_image(width: u32, height: u32, format: FourCC) -> Result<Image, Box<dyn Error>> {
+    let fd = unsafe { libc::memfd_create(b"image\0".as_ptr().cast(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let img = Image::new_preallocated(unsafe { OwnedFd::from_raw_fd(fd) }, width, height, format);
+    if unsafe { libc::ftruncate(img.raw_fd(), img.size() as libc::off_t) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(img)
+}
+
+#[test]
+fn test_software_manager_converts_yuyv_to_rgba() -> Result<(), Box<dyn Error>> {
+    let (width, height) = (64, 32);
+    let mut src = memfd_image(width as u32, height as u32, image::YUYV)?;
+    let mut dst = memfd_image(width as u32, height as u32, image::RGBA)?;
+    let pixels = readback_pattern(3, src.size());
+    src.mmap()?.as_slice_mut().copy_from_slice(&pixels);
+
+    let mgr = ImageManager::software();
+    assert!(!mgr.is_hardware());
+    mgr.convert(&src, &dst, None, Rotation::Rotation0)?;
+
+    let mut want = vec![0; width * height * 4];
+    cpu::yuyv_to_rgba_scalar(&pixels, width * 2, &mut want, width * 4, width, height);
+    assert_eq!(dst.mmap()?.as_slice(), want);
+    assert_eq!(mgr.conversion_count(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_software_manager_nv12_round_trip() -> Result<(), Box<dyn Error>> {
+    check_nv12_round_trip(&ImageManager::software(), memfd_image)
+}
+
+#[test]
+fn test_software_manager_scales_rgba_to_nv12() -> Result<(), Box<dyn Error>> {
+    let mut src = memfd_image(64, 32, image::RGBA)?;
+    let mut dst = memfd_image(32, 16, image::NV12)?;
+    src.mmap()?
+        .as_slice_mut()
+        .chunks_exact_mut(4)
+        .for_each(|px| px.copy_from_slice(&[90, 160, 30, 255]));
+
+    ImageManager::software().convert(&src, &dst, None, Rotation::Rotation0)?;
+
+    let [y, u, v] = cpu::rgb_to_yuv(90, 160, 30);
+    let mapped = dst.mmap()?;
+    let (luma, chroma) = mapped.as_slice().split_at(32 * 16);
+    assert!(luma.iter().all(|&b| b == y));
+    assert!(chroma.chunks_exact(2).all(|uv| uv == [u, v]));
+    Ok(())
+}
+
+/// Encode a gradient as JPEG and decode it into an RGBA image from `new`,
+/// returning the PSNR of the decoded pixels in dB.
+#[cfg(feature = "turbojpeg")]
+fn jpeg_round_trip(
+    new: impl Fn(u32, u32, FourCC) -> Result<Image, Box<dyn Error>>,
+) -> Result<f64, Box<dyn Error>> {
+    let (width, height) = (64, 48);
+    let mut src = new(width, height, image::RGBA)?;
+    src.mmap()?
+        .as_slice_mut()
+        .chunks_exact_mut(4)
+        .enumerate()
+        .for_each(|(i, px)| {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            px.copy_from_slice(&[(x * 4) as u8, (y * 5) as u8, (x + y) as u8, 255]);
+        });
+    let jpeg = encode_jpeg(src.mmap()?.as_slice(), Some(&src))?;
+
+    let mut dst = new(width, height, image::RGBA)?;
+    image::decode_jpeg(&jpeg, &dst)?;
+    let (a, b) = (src.mmap()?, dst.mmap()?);
+    // Alpha does not survive JPEG; the decoder writes it opaque.
+    let mse = a
+        .as_slice()
+        .iter()
+        .zip(b.as_slice())
+        .map(|(&a, &b)| (a as f64 - b as f64).powi(2))
+        .sum::<f64>()
+        / a.as_slice().len() as f64;
+    Ok(10.0 * (255.0 * 255.0 / mse).log10())
+}
+
+#[cfg(feature = "turbojpeg")]
+#[test]
+fn test_decode_jpeg_round_trip() -> Result<(), Box<dyn Error>> {
+    let psnr = jpeg_round_trip(memfd_image)?;
+    assert!(psnr > 30.0, "PSNR {psnr:.1} dB");
+
+    let jpeg = encode_jpeg(
+        &[128; 16 * 16 * 4],
+        Some(&memfd_image(16, 16, image::RGBA)?),
+    )?;
+    let small = memfd_image(8, 16, image::RGBA)?;
+    assert!(image::decode_jpeg(&jpeg, &small).is_err());
+    let yuyv = memfd_image(16, 16, image::YUYV)?;
+    assert!(image::decode_jpeg(&jpeg, &yuyv).is_err());
+    let rgb = memfd_image(16, 16, image::RGB3)?;
+    image::decode_jpeg(&jpeg, &rgb)?;
+    Ok(())
+}
+
+#[cfg(feature = "turbojpeg")]
+#[test]
+#[serial]
+fn test_decode_jpeg_to_image() -> Result<(), Box<dyn Error>> {
+    let psnr = jpeg_round_trip(Image::new)?;
+    assert!(psnr > 30.0, "PSNR {psnr:.1} dB");
+
+    let jpeg = encode_jpeg(&[128; 32 * 16 * 4], Some(&Image::new(32, 16, image::RGBA)?))?;
+    let img = image::decode_jpeg_to_image(&jpeg)?;
+    assert_eq!(
+        (img.width(), img.height(), img.format()),
+        (32, 16, image::RGBA)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_software_manager_rotates_into_dst_rect() -> Result<(), Box<dyn Error>> {
+    // A 4x2 source of distinct pixels, turned a quarter clockwise into the
+    // 2x4 region at (2, 2) of an 8x8 destination.
+    let mut src = memfd_image(4, 2, image::RGBA)?;
+    let mut dst = memfd_image(8, 8, image::RGBA)?;
+    src.mmap()?
+        .as_slice_mut()
+        .chunks_exact_mut(4)
+        .enumerate()
+        .for_each(|(i, px)| px.copy_from_slice(&[i as u8, 0, 0, 255]));
+    dst.mmap()?.as_slice_mut().fill(0xee);
+
+    let options = ConvertOptions {
+        rotation: Rotation::Rotation90,
+        dst_rect: Some(Rect {
+            x: 2,
+            y: 2,
+            width: 2,
+            height: 4,
+        }),
+        ..Default::default()
+    };
+    ImageManager::software().convert_with(&src, &dst, &options)?;
+
+    let mapped = dst.mmap()?;
+    let pixel = |x: usize, y: usize| &mapped.as_slice()[(y * 8 + x) * 4..][..4];
+    for (y, row) in [[4, 0], [5, 1], [6, 2], [7, 3]].iter().enumerate() {
+        for (x, &value) in row.iter().enumerate() {
+            assert_eq!(pixel(2 + x, 2 + y), [value, 0, 0, 255], "({x}, {y})");
+        }
+    }
+    assert_eq!(pixel(0, 0), [0xee; 4]);
+    assert_eq!(pixel(4, 2), [0xee; 4]);
+    assert_eq!(pixel(2, 6), [0xee; 4]);
+    Ok(())
+}
+
+#[test]
+fn test_software_manager_flips() -> Result<(), Box<dyn Error>> {
+    check_flip(&ImageManager::software(), memfd_image)
+}
+
+#[test]
+fn test_software_manager_blends_overlay() -> Result<(), Box<dyn Error>> {
+    check_blend(&ImageManager::software(), memfd_image)
+}
+
+#[test]
+fn test_software_manager_fills_region() -> Result<(), Box<dyn Error>> {
+    check_fill(&ImageManager::software(), memfd_image)
+}
+
+#[test]
+fn test_mmap_failure_is_an_error() -> Result<(), Box<dyn Error>> {
+    // A pipe cannot be mapped: the errno comes back rather than
+    // MAP_FAILED as a pointer.
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let _write = unsafe { OwnedFd::from_raw_fd(fds[1]) };
+    let mut img =
+        Image::new_preallocated(unsafe { OwnedFd::from_raw_fd(fds[0]) }, 16, 16, image::RGBA);
+    let err = img.mmap().err().expect("mapping a pipe succeeded");
+    assert!(err.raw_os_error().is_some(), "{err}");
+    Ok(())
+}
+
+#[test]
+fn test_software_manager_needs_g2d_for_g2d_buffers() -> Result<(), Box<dyn Error>> {
+    let mgr = ImageManager::software();
+    assert!(mgr.alloc(64, 64, 4).is_err());
+    assert_eq!(mgr.outstanding_allocations(), 0);
+    assert!(!mgr.shares_handle_with(&ImageManager::software()));
+
+    // Formats the CPU path does not read are refused, not misread.
+    let src = memfd_image(64, 32, image::GREY)?;
+    let dst = memfd_image(64, 32, image::RGBA)?;
+    let err = mgr
+        .convert(&src, &dst, None, Rotation::Rotation0)
+        .unwrap_err();
+    assert!(err.to_string().contains("not supported"), "{err}");
+    Ok(())
+}
+
+#[test]
+fn test_readback_harness_detects_stale_reads() {
+    // A backend that hands back the previous result, as a missing cache
+    // invalidation would, must not pass.
+    let mut previous = readback_pattern(0, 64);
+    let result = check_readback(
+        2,
+        64,
+        |pixels| pixels.to_vec(),
+        |pixels| Ok(std::mem::replace(&mut previous, pixels.to_vec())),
+    );
+    let err = result.unwrap_err().to_string();
+    assert!(err.starts_with("iteration 1: byte 0"), "{err}");
+}
+
 #[test]
 #[serial]
 #[ignore = "camera test is disabled by default (run with --include-ignored to enable)"]
@@ -160,6 +1088,8 @@ fn test_capture() -> Result<(), Box<dyn Error>> {
 
     let buf = cam.read()?;
     let src = Image::from_camera(&buf)?;
+    // The driver's bytesperline, which may pad the packed YUYV row.
+    assert_eq!(src.stride(), buf.bytes_per_line()?.max(src.width() * 2));
     let dst = Image::new(1920, 1080, image::RGBA)?;
 
     let mgr = ImageManager::new()?;
@@ -0,0 +1,932 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+use edgefirst_camera::{
+    color::{self, ColorCorrection},
+    config::{BayerPattern, ErrorFramePolicy, FrameFormat, TestPattern},
+    image::{self, encode_jpeg, Image, ImageManager, Rect, Rotation},
+    pattern::PatternSource,
+    pipeline::{
+        check_crop, frame_length, CameraSource, CapturedFrame, ColorCorrect, ConvertTarget,
+        Debayer, ErrorFrames, FormatConverter, Liveness, Pipeline, RateLimiter, Rectify, Watchdog,
+    },
+    rectify::RemapTable,
+    video::VideoManager,
+};
+use serial_test::serial;
+use std::{
+    error::Error,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use unix_ts::Timestamp;
+use videostream::fourcc::FourCC;
+
+/// Produces `count` small RGBA frames with increasing sequence numbers.
+struct MockSource {
+    sequence: u64,
+    count: u64,
+}
+
+impl CameraSource for MockSource {
+    fn read_frame(&mut self) -> Result<CapturedFrame, Box<dyn Error>> {
+        if self.sequence >= self.count {
+            return Err("mock source exhausted".into());
+        }
+        let image = Image::new(64, 48, image::RGBA)?;
+        let fd = image.raw_fd();
+        let frame = CapturedFrame {
+            image,
+            timestamp: Timestamp::new(self.sequence as i64, 0),
+            sequence: self.sequence,
+            fd,
+            stride: 64 * 4,
+            length: 64 * 48 * 4,
+        };
+        self.sequence += 1;
+        Ok(frame)
+    }
+}
+
+/// Delivers the frames sent on its channel, waiting for them with a
+/// timeout the way a source backed by a queue or a pollable fd would.
+struct ChannelSource {
+    frames: mpsc::Receiver<CapturedFrame>,
+}
+
+impl CameraSource for ChannelSource {
+    fn read_frame(&mut self) -> Result<CapturedFrame, Box<dyn Error>> {
+        Ok(self.frames.recv()?)
+    }
+
+    fn read_frame_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<CapturedFrame>, Box<dyn Error>> {
+        match self.frames.recv_timeout(timeout) {
+            Ok(frame) => Ok(Some(frame)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Delivers one frame, then blocks until `interrupt` is raised, noting the
+/// pipeline's liveness when it is restarted, then delivers frames again.
+struct DroppingSource {
+    inner: MockSource,
+    interrupt: Arc<AtomicBool>,
+    liveness: Option<Liveness>,
+    connected_at_restart: Option<bool>,
+}
+
+impl CameraSource for DroppingSource {
+    fn read_frame(&mut self) -> Result<CapturedFrame, Box<dyn Error>> {
+        if self.inner.sequence == 1 && self.connected_at_restart.is_none() {
+            while !self.interrupt.swap(false, Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            return Err(io::Error::from(io::ErrorKind::Interrupted).into());
+        }
+        self.inner.read_frame()
+    }
+
+    fn restart(&mut self) -> Result<(), Box<dyn Error>> {
+        self.connected_at_restart = self.liveness.as_ref().map(Liveness::is_connected);
+        Ok(())
+    }
+}
+
+/// Blocks in its first read until `interrupt` is raised, the way a wedged
+/// V4L2 driver blocks until a signal arrives, then delivers frames from
+/// `inner` once restarted.
+struct StallingSource {
+    inner: MockSource,
+    interrupt: Arc<AtomicBool>,
+    stalled: bool,
+    restarts: u32,
+}
+
+impl CameraSource for StallingSource {
+    fn read_frame(&mut self) -> Result<CapturedFrame, Box<dyn Error>> {
+        if self.stalled {
+            let start = Instant::now();
+            while !self.interrupt.swap(false, Ordering::SeqCst) {
+                assert!(
+                    start.elapsed() < Duration::from_secs(5),
+                    "watchdog never fired"
+                );
+                thread::sleep(Duration::from_millis(1));
+            }
+            return Err(io::Error::from(io::ErrorKind::Interrupted).into());
+        }
+        self.inner.read_frame()
+    }
+
+    fn restart(&mut self) -> Result<(), Box<dyn Error>> {
+        self.restarts += 1;
+        self.stalled = false;
+        Ok(())
+    }
+}
+
+#[test]
+#[serial]
+fn test_pipeline_sink_collects_frames() -> Result<(), Box<dyn Error>> {
+    let collected = Arc::new(Mutex::new(Vec::new()));
+    let sink = collected.clone();
+
+    let mut pipeline = Pipeline::new(MockSource {
+        sequence: 0,
+        count: 3,
+    })
+    .with_sink(move |frame| {
+        sink.lock()
+            .unwrap()
+            .push((frame.sequence, frame.image.try_clone().unwrap()));
+    });
+    assert_eq!(pipeline.sink_count(), 1);
+
+    for _ in 0..3 {
+        pipeline.step()?;
+    }
+    assert!(pipeline.step().is_err());
+
+    let collected = collected.lock().unwrap();
+    assert_eq!(collected.len(), 3);
+    for (i, (sequence, img)) in collected.iter().enumerate() {
+        assert_eq!(*sequence, i as u64);
+        assert_eq!(img.width(), 64);
+        assert_eq!(img.height(), 48);
+        assert_eq!(img.format(), image::RGBA);
+    }
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_pipeline_dispatches_to_every_sink() -> Result<(), Box<dyn Error>> {
+    let counts = Arc::new(Mutex::new([0u32; 2]));
+    let (a, b) = (counts.clone(), counts.clone());
+
+    let mut pipeline = Pipeline::new(MockSource {
+        sequence: 0,
+        count: 2,
+    });
+    pipeline.add_sink(move |_| a.lock().unwrap()[0] += 1);
+    pipeline.add_sink(move |_| b.lock().unwrap()[1] += 1);
+
+    let frame = pipeline.next_frame()?;
+    assert_eq!(*counts.lock().unwrap(), [0, 0]);
+    pipeline.dispatch(&frame);
+    pipeline.step()?;
+    assert_eq!(*counts.lock().unwrap(), [2, 2]);
+
+    Ok(())
+}
+
+/// Two outputs with the same target share one conversion per frame and see
+/// the same converted buffer; an output with another target gets its own.
+#[test]
+#[serial]
+fn test_pipeline_shares_conversion_between_same_target_sinks() -> Result<(), Box<dyn Error>> {
+    let target = ConvertTarget {
+        format: image::RGBA,
+        width: 32,
+        height: 24,
+        rotation: Rotation::Rotation0,
+        crop: None,
+    };
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let (a, b) = (received.clone(), received.clone());
+
+    let mut pipeline = Pipeline::new(MockSource {
+        sequence: 0,
+        count: 6,
+    });
+    pipeline.add_converted_sink(target, move |frame| {
+        a.lock().unwrap().push(("a", frame.sequence, frame.fd));
+        assert_eq!(frame.image.width(), 32);
+        assert_eq!(frame.image.height(), 24);
+        assert_eq!(frame.stride, 32 * 4);
+    })?;
+    pipeline.add_converted_sink(target, move |frame| {
+        b.lock().unwrap().push(("b", frame.sequence, frame.fd));
+    })?;
+    assert_eq!(pipeline.sink_count(), 2);
+
+    for frames in 1..=3 {
+        pipeline.step()?;
+        assert_eq!(pipeline.conversion_count(), frames);
+    }
+    {
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 6);
+        for pair in received.chunks(2) {
+            let [(_, seq_a, fd_a), (_, seq_b, fd_b)] = pair else {
+                unreachable!()
+            };
+            assert_eq!(seq_a, seq_b);
+            assert_eq!(fd_a, fd_b, "both sinks must see the same buffer");
+        }
+    }
+
+    // A different rotation needs its own conversion.
+    pipeline.add_converted_sink(
+        ConvertTarget {
+            rotation: Rotation::Rotation180,
+            ..target
+        },
+        |_| {},
+    )?;
+    pipeline.step()?;
+    assert_eq!(pipeline.conversion_count(), 5);
+
+    // So does a crop.
+    pipeline.add_converted_sink(
+        ConvertTarget {
+            crop: Some(Rect {
+                x: 16,
+                y: 12,
+                width: 32,
+                height: 24,
+            }),
+            ..target
+        },
+        |_| {},
+    )?;
+    pipeline.step()?;
+    assert_eq!(pipeline.conversion_count(), 8);
+
+    Ok(())
+}
+
+/// A conversion the capture loop already made of a frame is reused by the
+/// converted sinks with the same target, for that frame only.
+#[test]
+#[serial]
+fn test_pipeline_reuses_shared_conversion() -> Result<(), Box<dyn Error>> {
+    let mut converter = FormatConverter::new(FrameFormat::Yuyv, 64, 48)?;
+    let target = converter.target();
+    assert_eq!(
+        target,
+        ConvertTarget {
+            format: image::YUYV,
+            width: 64,
+            height: 48,
+            rotation: Rotation::Rotation0,
+            crop: None,
+        }
+    );
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = received.clone();
+    let mut pipeline = Pipeline::new(MockSource {
+        sequence: 0,
+        count: 2,
+    });
+    pipeline.add_converted_sink(target, move |frame| {
+        sink.lock().unwrap().push(frame.fd);
+    })?;
+
+    let frame = pipeline.next_frame()?;
+    let converted = converter.convert(&frame)?.expect("RGBA must be converted");
+    pipeline.share_conversion(target, &converted)?;
+    pipeline.dispatch(&frame);
+    assert_eq!(pipeline.conversion_count(), 0);
+    assert_eq!(*received.lock().unwrap(), [converted.fd]);
+
+    // The offer does not outlive its frame.
+    let frame = pipeline.next_frame()?;
+    pipeline.dispatch(&frame);
+    assert_eq!(pipeline.conversion_count(), 1);
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    assert_ne!(received[1], converted.fd);
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_pipeline_watchdog_restarts_stalled_source() -> Result<(), Box<dyn Error>> {
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let mut pipeline = Pipeline::new(StallingSource {
+        inner: MockSource {
+            sequence: 0,
+            count: 2,
+        },
+        interrupt: interrupt.clone(),
+        stalled: true,
+        restarts: 0,
+    });
+    pipeline.set_watchdog(Watchdog::spawn(Duration::from_millis(20), move || {
+        interrupt.store(true, Ordering::SeqCst)
+    })?);
+
+    let frame = pipeline.next_frame()?;
+    assert_eq!(frame.sequence, 0);
+    assert_eq!(pipeline.source().restarts, 1);
+
+    pipeline.next_frame()?;
+    assert_eq!(pipeline.source().restarts, 1);
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_pipeline_liveness_follows_stall_and_recovery() -> Result<(), Box<dyn Error>> {
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let mut pipeline = Pipeline::new(DroppingSource {
+        inner: MockSource {
+            sequence: 0,
+            count: 3,
+        },
+        interrupt: interrupt.clone(),
+        liveness: None,
+        connected_at_restart: None,
+    });
+    pipeline.set_watchdog(Watchdog::spawn(Duration::from_millis(20), move || {
+        interrupt.store(true, Ordering::SeqCst)
+    })?);
+    let liveness = pipeline.liveness();
+    pipeline.source_mut().liveness = Some(liveness.clone());
+    assert!(!liveness.is_connected());
+
+    pipeline.next_frame()?;
+    assert!(liveness.is_connected());
+
+    // The second read stalls; the flag is down while the source restarts
+    // and back up once frames flow again.
+    let frame = pipeline.next_frame()?;
+    assert_eq!(frame.sequence, 1);
+    assert_eq!(pipeline.source().connected_at_restart, Some(false));
+    assert!(liveness.is_connected());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_read_timeout_returns_none_without_frame() -> Result<(), Box<dyn Error>> {
+    let (tx, rx) = mpsc::channel();
+    let mut pipeline = Pipeline::new(ChannelSource { frames: rx });
+    let liveness = pipeline.liveness();
+
+    let start = Instant::now();
+    assert!(pipeline.read_timeout(Duration::from_millis(50))?.is_none());
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(!liveness.is_connected());
+
+    let mut source = MockSource {
+        sequence: 5,
+        count: 6,
+    };
+    tx.send(source.read_frame()?)?;
+    let frame = pipeline.read_timeout(Duration::from_secs(5))?;
+    assert_eq!(frame.map(|f| f.sequence), Some(5));
+    assert!(liveness.is_connected());
+
+    // A source without timed reads reports it instead of blocking.
+    let mut blocking = Pipeline::new(MockSource {
+        sequence: 0,
+        count: 1,
+    });
+    assert!(blocking.read_timeout(Duration::from_millis(1)).is_err());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_watchdog_fires_only_without_feed() -> Result<(), Box<dyn Error>> {
+    let watchdog = Watchdog::spawn(Duration::from_millis(100), || {})?;
+    for _ in 0..10 {
+        thread::sleep(Duration::from_millis(10));
+        watchdog.feed();
+    }
+    assert_eq!(watchdog.fire_count(), 0);
+    assert!(!watchdog.take_fired());
+
+    thread::sleep(Duration::from_millis(300));
+    assert!(watchdog.fire_count() >= 1);
+    assert!(watchdog.take_fired());
+    assert!(!watchdog.take_fired());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_interrupt_without_watchdog_is_returned() {
+    let mut pipeline = Pipeline::new(StallingSource {
+        inner: MockSource {
+            sequence: 0,
+            count: 1,
+        },
+        interrupt: Arc::new(AtomicBool::new(true)),
+        stalled: true,
+        restarts: 0,
+    });
+    assert!(pipeline.next_frame().is_err());
+    assert_eq!(pipeline.source().restarts, 0);
+}
+
+#[test]
+#[serial]
+fn test_format_converter_rgba() -> Result<(), Box<dyn Error>> {
+    let image = Image::new(640, 480, image::YUYV)?;
+    let fd = image.raw_fd();
+    let frame = CapturedFrame {
+        image,
+        timestamp: Timestamp::new(7, 0),
+        sequence: 7,
+        fd,
+        stride: 640 * 2,
+        length: 640 * 480 * 2,
+    };
+
+    let mut converter = FormatConverter::new(FrameFormat::Rgba, 640, 480)?;
+    let rgba = converter.convert(&frame)?.expect("YUYV must be converted");
+    assert_eq!(rgba.image.format(), image::RGBA);
+    assert_eq!(rgba.stride, 640 * 4);
+    assert_eq!(rgba.length, 640 * 480 * 4);
+    assert_eq!(rgba.sequence, 7);
+    assert_ne!(rgba.fd, frame.fd);
+
+    // Successive frames rotate through the ring.
+    let fds: Vec<i32> = (0..FormatConverter::BUFFERS)
+        .map(|_| converter.convert(&frame).map(|f| f.unwrap().fd))
+        .collect::<Result<_, _>>()?;
+    assert!(fds[1..].iter().all(|&fd| fd != fds[0]));
+
+    // Frames already in the target format pass through.
+    let mut passthrough = FormatConverter::new(FrameFormat::Yuyv, 640, 480)?;
+    assert!(passthrough.convert(&frame)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_format_converter_crop() -> Result<(), Box<dyn Error>> {
+    let image = Image::new(640, 480, image::YUYV)?;
+    let fd = image.raw_fd();
+    let frame = CapturedFrame {
+        image,
+        timestamp: Timestamp::new(7, 0),
+        sequence: 7,
+        fd,
+        stride: 640 * 2,
+        length: 640 * 480 * 2,
+    };
+
+    // A crop converts even when the format already matches.
+    let mut converter = FormatConverter::new(FrameFormat::Yuyv, 640, 480)?;
+    let crop = Rect {
+        x: 160,
+        y: 120,
+        width: 320,
+        height: 240,
+    };
+    converter.set_crop(Some(crop))?;
+    assert_eq!(converter.crop(), Some(crop));
+    let cropped = converter.convert(&frame)?.expect("cropped frame");
+    assert_eq!(cropped.image.width(), 320);
+    assert_eq!(cropped.image.height(), 240);
+    assert_eq!(cropped.stride, 320 * 2);
+    assert_eq!(cropped.length, 320 * 240 * 2);
+    assert_eq!(cropped.sequence, 7);
+
+    // A rejected region keeps the previous one.
+    let outside = Rect {
+        x: 480,
+        y: 0,
+        width: 320,
+        height: 240,
+    };
+    assert!(converter.set_crop(Some(outside)).is_err());
+    assert_eq!(converter.crop(), Some(crop));
+
+    // Clearing the crop restores pass-through.
+    converter.set_crop(None)?;
+    assert!(converter.convert(&frame)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_check_crop() {
+    let rect = |x, y, width, height| Rect {
+        x,
+        y,
+        width,
+        height,
+    };
+    assert!(check_crop(&rect(0, 0, 1920, 1080), 1920, 1080).is_ok());
+    assert!(check_crop(&rect(640, 360, 640, 360), 1920, 1080).is_ok());
+    // Empty.
+    assert!(check_crop(&rect(0, 0, 0, 1080), 1920, 1080).is_err());
+    // Outside the frame.
+    assert!(check_crop(&rect(-2, 0, 64, 64), 1920, 1080).is_err());
+    assert!(check_crop(&rect(1900, 0, 64, 64), 1920, 1080).is_err());
+    assert!(check_crop(&rect(0, 1080, 64, 64), 1920, 1080).is_err());
+    // Odd position or size splits chroma pairs.
+    assert!(check_crop(&rect(1, 0, 64, 64), 1920, 1080).is_err());
+    assert!(check_crop(&rect(0, 0, 63, 64), 1920, 1080).is_err());
+}
+
+#[test]
+fn test_frame_length() {
+    let size = 1280 * 2 * 720;
+    let unused = || -> io::Result<usize> { panic!("buffer length read needlessly") };
+    // The driver's length, padding included.
+    assert_eq!(
+        frame_length(size + 4096, size, unused).unwrap(),
+        size as u32 + 4096
+    );
+    assert_eq!(frame_length(size, size, unused).unwrap(), size as u32);
+
+    // A driver reporting only the bytes used: the buffer's own length.
+    assert_eq!(
+        frame_length(size - 100, size, || Ok(size + 1024)).unwrap(),
+        size as u32 + 1024
+    );
+    // A buffer too small for its stride and height, or of unknown length.
+    let err = frame_length(size - 100, size, || Ok(size - 100)).unwrap_err();
+    assert!(err.to_string().contains("smaller than"), "{err}");
+    assert!(frame_length(0, size, || Err(io::ErrorKind::Unsupported.into())).is_err());
+    assert!(frame_length(1 << 33, 16, unused).is_err());
+}
+
+/// Frames a limiter emits from `seconds` of a `source_fps` stream whose
+/// timestamps wobble by up to `jitter_us`.
+fn limited_count(limiter: &mut RateLimiter, source_fps: u32, seconds: u32, jitter_us: u64) -> u32 {
+    let period = Duration::from_secs(1) / source_fps;
+    (0..source_fps * seconds)
+        .filter(|&i| {
+            let jitter = Duration::from_micros((i as u64 * 7919) % (jitter_us + 1));
+            limiter.ready(period * i + jitter)
+        })
+        .count() as u32
+}
+
+#[test]
+fn test_rate_limiter_jpeg_5fps_from_30fps() {
+    let mut limiter = RateLimiter::new(5);
+    let emitted = limited_count(&mut limiter, 30, 10, 0);
+    assert!(
+        (49..=51).contains(&emitted),
+        "emitted {emitted} frames in 10s"
+    );
+
+    let mut limiter = RateLimiter::new(5);
+    let emitted = limited_count(&mut limiter, 30, 10, 2000);
+    assert!(
+        (49..=51).contains(&emitted),
+        "emitted {emitted} frames in 10s"
+    );
+}
+
+#[test]
+fn test_rate_limiter_non_divisible_rates() {
+    // 15 from 30 must not degrade to 10 when frames land just short of
+    // the interval.
+    let mut limiter = RateLimiter::new(15);
+    let emitted = limited_count(&mut limiter, 30, 10, 0);
+    assert!(
+        (149..=151).contains(&emitted),
+        "emitted {emitted} frames in 10s"
+    );
+
+    let mut limiter = RateLimiter::new(12);
+    let emitted = limited_count(&mut limiter, 30, 10, 0);
+    assert!(
+        (119..=121).contains(&emitted),
+        "emitted {emitted} frames in 10s"
+    );
+}
+
+#[test]
+fn test_rate_limiter_passes_everything_when_source_is_slower() {
+    let mut limiter = RateLimiter::new(30);
+    assert_eq!(limited_count(&mut limiter, 10, 5, 0), 50);
+
+    let mut limiter = RateLimiter::new(0);
+    assert_eq!(limited_count(&mut limiter, 30, 1, 0), 30);
+}
+
+#[test]
+fn test_rate_limiter_resyncs_after_gap() {
+    let mut limiter = RateLimiter::new(5);
+    assert!(limiter.ready(Duration::ZERO));
+    // A ten second stall must not be followed by a catch-up burst.
+    assert!(limiter.ready(Duration::from_secs(10)));
+    assert!(!limiter.ready(Duration::from_millis(10_033)));
+    assert!(!limiter.ready(Duration::from_millis(10_100)));
+    assert!(limiter.ready(Duration::from_millis(10_200)));
+}
+
+#[test]
+fn test_rate_limiter_hires_interval_interleaves_with_stream() {
+    // --jpeg-fps 5 with --hires-interval 2 over ten seconds of 30 fps.
+    let mut stream = RateLimiter::new(5);
+    let mut hires = RateLimiter::every(Duration::from_secs(2));
+    let period = Duration::from_secs(1) / 30;
+    let mut hires_at = Vec::new();
+    let mut stream_between = Vec::new();
+    let mut stream_count = 0;
+    for i in 0..300 {
+        let time = period * i;
+        if hires.ready(time) {
+            hires_at.push(time);
+            stream_between.push(stream_count);
+            stream_count = 0;
+        }
+        if stream.ready(time) {
+            stream_count += 1;
+        }
+    }
+
+    // The first frame, then one every two seconds; the last lands at
+    // 9.83 s since frames are accepted up to a tenth of the interval early.
+    assert_eq!(hires_at.len(), 6, "{hires_at:?}");
+    for pair in hires_at.windows(2) {
+        let gap = pair[1] - pair[0];
+        assert!(
+            gap >= Duration::from_millis(1800) && gap <= Duration::from_millis(2200),
+            "{hires_at:?}"
+        );
+    }
+    // About ten stream frames between consecutive full-size frames.
+    for &count in &stream_between[1..] {
+        assert!((9..=11).contains(&count), "{stream_between:?}");
+    }
+}
+
+#[test]
+#[serial]
+fn test_pattern_source_drives_jpeg_and_h264() -> Result<(), Box<dyn Error>> {
+    // No camera device: colour bars stand in for it.
+    let (width, height) = (640, 480);
+    let source = PatternSource::new(TestPattern::Colorbars, width, height, 30)?;
+    let mut pipeline = Pipeline::new(source);
+    let imgmgr = ImageManager::new()?;
+    let mut vidmgr =
+        VideoManager::builder(FourCC(*b"H264"), width as i32, height as i32).build()?;
+    let mut staging = None;
+    let mut rgba = Image::new(width, height, image::RGBA)?;
+
+    let start = Instant::now();
+    for sequence in 1..=5 {
+        let frame = pipeline.step()?;
+        assert_eq!(frame.sequence, sequence);
+        assert_eq!(frame.image.format(), image::YUYV);
+
+        imgmgr.convert(&frame.image, &rgba, None, Rotation::Rotation0)?;
+        let map = rgba.mmap()?;
+        let jpeg = encode_jpeg(map.as_slice(), Some(&rgba))?;
+        assert_eq!(jpeg[..2], [0xff, 0xd8]);
+        assert_eq!(jpeg[jpeg.len() - 2..], [0xff, 0xd9]);
+        // The middle of the first (75% white) and sixth (red) bars.
+        let pixel = |x: usize| map.as_slice()[x * 4..x * 4 + 3].to_vec();
+        let grey = pixel(40);
+        assert!(grey.iter().all(|&c| c.abs_diff(180) < 24), "{grey:?}");
+        let red = pixel(440);
+        assert!(red[0] > 150 && red[1] < 60 && red[2] < 60, "{red:?}");
+        drop(map);
+
+        let (data, is_key, _) =
+            vidmgr.encode(&frame.image, &imgmgr, &mut staging, Rotation::Rotation0)?;
+        assert!(
+            data.starts_with(&[0, 0, 1]) || data.starts_with(&[0, 0, 0, 1]),
+            "not an Annex B stream"
+        );
+        if sequence == 1 {
+            assert!(is_key, "first encoded frame must be a keyframe");
+        }
+    }
+    // Paced to 30 fps rather than as fast as frames are read.
+    assert!(start.elapsed() >= Duration::from_millis(4 * 33));
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_debayer() -> Result<(), Box<dyn Error>> {
+    // A flat orange scene seen through an RGGB filter.
+    let (width, height) = (64u32, 48u32);
+    let mut image = Image::new(width, height, image::SRGGB8)?;
+    {
+        let mut mapped = image.mmap()?;
+        for (i, sample) in mapped.as_slice_mut().iter_mut().enumerate() {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            *sample = match (x % 2, y % 2) {
+                (0, 0) => 240,
+                (1, 1) => 16,
+                _ => 128,
+            };
+        }
+    }
+    let fd = image.raw_fd();
+    let frame = CapturedFrame {
+        image,
+        timestamp: Timestamp::new(3, 0),
+        sequence: 3,
+        fd,
+        stride: width,
+        length: width * height,
+    };
+
+    let mut debayer = Debayer::new(BayerPattern::Rggb, width, height)?;
+    let mut rgba = debayer.convert(&frame)?.expect("demosaiced frame");
+    assert_eq!(rgba.image.format(), image::RGBA);
+    assert_eq!(rgba.stride, width * 4);
+    assert_eq!(rgba.sequence, 3);
+    let mapped = rgba.image.mmap()?;
+    for pixel in mapped.as_slice().chunks_exact(4) {
+        assert_eq!(pixel, [240, 128, 16, 255]);
+    }
+
+    // Frames that are not Bayer pass through.
+    let yuyv = Image::new(width, height, image::YUYV)?;
+    let fd = yuyv.raw_fd();
+    let frame = CapturedFrame {
+        image: yuyv,
+        timestamp: Timestamp::new(4, 0),
+        sequence: 4,
+        fd,
+        stride: width * 2,
+        length: width * height * 2,
+    };
+    assert!(debayer.convert(&frame)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_rectify() -> Result<(), Box<dyn Error>> {
+    let (width, height) = (64u32, 48u32);
+    let mut image = Image::new(width, height, image::YUYV)?;
+    {
+        let mut mapped = image.mmap()?;
+        for (i, byte) in mapped.as_slice_mut().iter_mut().enumerate() {
+            *byte = (i * 13) as u8;
+        }
+    }
+    let fd = image.raw_fd();
+    let frame = CapturedFrame {
+        image,
+        timestamp: Timestamp::new(5, 0),
+        sequence: 5,
+        fd,
+        stride: width * 2,
+        length: width * height * 2,
+    };
+    let k = [60.0, 0.0, 32.0, 0.0, 60.0, 24.0, 0.0, 0.0, 1.0];
+
+    // Without distortion the frame comes out unchanged.
+    let table = RemapTable::undistort(width, height, &k, &[0.0; 5])?;
+    let mut rectify = Rectify::new(table, image::YUYV)?;
+    let mut out = rectify.convert(&frame)?;
+    assert_eq!(out.sequence, 5);
+    assert_eq!(out.stride, width * 2);
+    let mut src = frame.image.try_clone()?;
+    assert_eq!(out.image.mmap()?.as_slice(), src.mmap()?.as_slice());
+
+    // With it, pixels move.
+    let table = RemapTable::undistort(width, height, &k, &[0.3, 0.1, 0.0, 0.0, 0.0])?;
+    let mut rectify = Rectify::new(table, image::YUYV)?;
+    let mut out = rectify.convert(&frame)?;
+    assert_ne!(out.image.mmap()?.as_slice(), src.mmap()?.as_slice());
+
+    // The stage only takes frames of its own format and size.
+    assert!(Rectify::new(
+        RemapTable::undistort(width, height, &k, &[0.0; 5])?,
+        image::NV12
+    )
+    .is_err());
+    let table = RemapTable::undistort(32, 24, &k, &[0.0; 5])?;
+    assert!(Rectify::new(table, image::YUYV)?.convert(&frame).is_err());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_color_correct() -> Result<(), Box<dyn Error>> {
+    let (width, height) = (64u32, 48u32);
+    let mut image = Image::new(width, height, image::RGBA)?;
+    {
+        let mut mapped = image.mmap()?;
+        for pixel in mapped.as_slice_mut().chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[200, 100, 50, 255]);
+        }
+    }
+    let fd = image.raw_fd();
+    let frame = CapturedFrame {
+        image,
+        timestamp: Timestamp::new(6, 0),
+        sequence: 6,
+        fd,
+        stride: width * 4,
+        length: width * height * 4,
+    };
+
+    // The identity leaves the frame unchanged.
+    let identity = ColorCorrection::new(color::IDENTITY, [1.0; 3])?;
+    let mut correct = ColorCorrect::new(identity, width, height)?;
+    let mut out = correct.convert(&frame)?;
+    assert_eq!(out.sequence, 6);
+    assert_eq!(out.stride, width * 4);
+    let mut src = frame.image.try_clone()?;
+    assert_eq!(out.image.mmap()?.as_slice(), src.mmap()?.as_slice());
+
+    // Swapping red and blue.
+    let swap = [0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0];
+    let mut correct = ColorCorrect::new(ColorCorrection::new(swap, [1.0; 3])?, width, height)?;
+    let mut out = correct.convert(&frame)?;
+    for pixel in out.image.mmap()?.as_slice().chunks_exact(4) {
+        assert_eq!(pixel, [50, 100, 200, 255]);
+    }
+
+    // Only RGBA frames of the stage's size are taken.
+    let identity = ColorCorrection::new(color::IDENTITY, [1.0; 3])?;
+    assert!(ColorCorrect::new(identity, 32, 24)?
+        .convert(&frame)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_error_frames_gap_never_fills() -> Result<(), Box<dyn Error>> {
+    let mut missed = ErrorFrames::new(ErrorFramePolicy::Gap, 30);
+    assert_eq!(missed.timeout(Instant::now()), None);
+    assert!(missed.fill()?.is_none());
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_error_frames_freeze_repeats_last_frame() -> Result<(), Box<dyn Error>> {
+    let imgmgr = ImageManager::new()?;
+    let mut frame = Image::new(64, 48, image::RGBA)?;
+    frame.mmap()?.as_slice_mut().fill(0x40);
+
+    let mut missed = ErrorFrames::new(ErrorFramePolicy::Freeze, 25);
+    // Nothing to repeat before the first frame.
+    assert_eq!(missed.timeout(Instant::now()), None);
+    assert!(missed.fill()?.is_none());
+
+    let start = Instant::now();
+    missed.received(&imgmgr, &frame, Timestamp::new(10, 0), start)?;
+    // The next frame is missed half an interval after it was due.
+    assert_eq!(missed.timeout(start), Some(Duration::from_millis(60)));
+
+    // The driver reuses the camera buffer for a frame that never arrives.
+    frame.mmap()?.as_slice_mut().fill(0x99);
+
+    let (mut repeat, ts) = missed.fill()?.expect("frozen frame");
+    assert_eq!(missed.missed(), 1);
+    assert_eq!((ts.seconds(), ts.subsec(9)), (10, 40_000_000));
+    assert!(repeat.mmap()?.as_slice().iter().all(|&b| b == 0x40));
+    let (_, ts) = missed.fill()?.expect("frozen frame");
+    assert_eq!((ts.seconds(), ts.subsec(9)), (10, 80_000_000));
+    // Filled frames follow at the frame rate.
+    assert_eq!(missed.timeout(start), Some(Duration::from_millis(140)));
+
+    missed.received(&imgmgr, &frame, Timestamp::new(11, 0), Instant::now())?;
+    assert_eq!(missed.missed(), 0);
+    let (mut repeat, _) = missed.fill()?.expect("frozen frame");
+    assert!(repeat.mmap()?.as_slice().iter().all(|&b| b == 0x99));
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_error_frames_black() -> Result<(), Box<dyn Error>> {
+    let imgmgr = ImageManager::new()?;
+    let mut frame = Image::new(64, 48, image::YUYV)?;
+    frame.mmap()?.as_slice_mut().fill(0xff);
+
+    let mut missed = ErrorFrames::new(ErrorFramePolicy::Black, 30);
+    missed.received(&imgmgr, &frame, Timestamp::new(1, 0), Instant::now())?;
+    let (mut black, _) = missed.fill()?.expect("black frame");
+    assert_eq!(black.format(), image::YUYV);
+    for px in black.mmap()?.as_slice().chunks_exact(4) {
+        assert_eq!(px, [16, 128, 16, 128]);
+    }
+
+    Ok(())
+}
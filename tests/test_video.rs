@@ -0,0 +1,100 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright (c) 2026 Au-Zone Technologies. All Rights Reserved.
+
+use edgefirst_camera::{
+    image::{self, Image, ImageManager, Rotation},
+    video::{VideoManager, H264_INPUT_FORMAT},
+};
+use serial_test::serial;
+use std::error::Error;
+use videostream::fourcc::FourCC;
+
+#[test]
+#[serial]
+fn test_encode_skips_g2d_for_matching_frames() -> Result<(), Box<dyn Error>> {
+    let imgmgr = ImageManager::new()?;
+    let mut vidmgr = VideoManager::builder(FourCC(*b"H264"), 640, 480).build()?;
+    let mut staging = None;
+
+    // Already the encoder's input format at the encoded size: imported as is.
+    let frame = Image::new(640, 480, H264_INPUT_FORMAT)?;
+    let (data, ..) = vidmgr.encode(&frame, &imgmgr, &mut staging, Rotation::Rotation0)?;
+    assert!(!data.is_empty());
+    assert_eq!(imgmgr.conversion_count(), 0);
+    assert!(staging.is_none());
+
+    // A camera YUYV frame, a different size, or a pending rotation goes
+    // through the staging image.
+    let yuyv = Image::new(640, 480, image::YUYV)?;
+    vidmgr.encode(&yuyv, &imgmgr, &mut staging, Rotation::Rotation0)?;
+    assert_eq!(imgmgr.conversion_count(), 1);
+    let large = Image::new(1280, 960, H264_INPUT_FORMAT)?;
+    vidmgr.encode(&large, &imgmgr, &mut staging, Rotation::Rotation0)?;
+    assert_eq!(imgmgr.conversion_count(), 2);
+    vidmgr.encode(&frame, &imgmgr, &mut staging, Rotation::Rotation180)?;
+    assert_eq!(imgmgr.conversion_count(), 3);
+    assert_eq!(staging.as_ref().map(Image::format), Some(H264_INPUT_FORMAT));
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_encoder_pts_increases() -> Result<(), Box<dyn Error>> {
+    let imgmgr = ImageManager::new()?;
+    let mut vidmgr = VideoManager::builder(FourCC(*b"H264"), 640, 480).build()?;
+    let mut staging = None;
+    let frame = Image::new(640, 480, H264_INPUT_FORMAT)?;
+
+    let mut stamps = Vec::new();
+    for _ in 0..10 {
+        let (_, _, pts) = vidmgr.encode(&frame, &imgmgr, &mut staging, Rotation::Rotation0)?;
+        stamps.push(pts);
+    }
+    // An encoder either stamps every frame or none of them.
+    if stamps.iter().all(Option::is_none) {
+        return Ok(());
+    }
+    let stamps: Vec<i64> = stamps
+        .into_iter()
+        .collect::<Option<_>>()
+        .expect("encoder stamped only some frames");
+    assert!(
+        stamps.windows(2).all(|pair| pair[0] < pair[1]),
+        "encoder pts not increasing: {stamps:?}"
+    );
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_encode_into_matches_encode() -> Result<(), Box<dyn Error>> {
+    let imgmgr = ImageManager::new()?;
+    let mut fresh = VideoManager::builder(FourCC(*b"H264"), 640, 480).build()?;
+    let mut reused = VideoManager::builder(FourCC(*b"H264"), 640, 480).build()?;
+    let (mut fresh_staging, mut reused_staging) = (None, None);
+    let frame = Image::new(640, 480, image::YUYV)?;
+
+    let mut data = Vec::new();
+    let mut first = None;
+    for i in 0..10 {
+        let (expected, is_key, _) =
+            fresh.encode(&frame, &imgmgr, &mut fresh_staging, Rotation::Rotation0)?;
+        let (reused_key, _) = reused.encode_into(
+            &frame,
+            &imgmgr,
+            &mut reused_staging,
+            Rotation::Rotation0,
+            &mut data,
+        )?;
+        assert_eq!(data, expected, "frame {i}");
+        assert_eq!(reused_key, is_key, "frame {i}");
+        // The static scene's later frames are smaller than the first
+        // keyframe, so they fit the buffer it grew.
+        let ptr = *first.get_or_insert(data.as_ptr());
+        assert_eq!(data.as_ptr(), ptr, "frame {i} reallocated");
+    }
+
+    Ok(())
+}